@@ -17,5 +17,9 @@ fuzz_target!(|data: &[u8]| {
             Err(e) => panic!("{}: {:?} {:?}", e, d2, pkt2),
         };
         assert_eq!(pkt2, pkt3);
+        // Check every record can be displayed in presentation format.
+        for rr in pkt3.answer.iter().chain(&pkt3.nameserver).chain(&pkt3.additional) {
+            let _ = rr.to_string();
+        }
     }
 });
@@ -15,4 +15,8 @@ fuzz_target!(|pkt: erbium::dns::dnspkt::DNSPkt| {
         Err(e) => panic!("Second: {}: {:?} {:?}", e, d1, pkt),
     };
     assert_eq!(pkt2, pkt3);
+    // Check every record can be displayed in presentation format.
+    for rr in pkt3.answer.iter().chain(&pkt3.nameserver).chain(&pkt3.additional) {
+        let _ = rr.to_string();
+    }
 });
@@ -14,6 +14,7 @@ fuzz_target!(|data: &[u8]| {
                 ),
                 ..Default::default()
             }],
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -23,11 +24,12 @@ fuzz_target!(|data: &[u8]| {
             pkt,
             serverip: "192.168.0.1".parse().unwrap(),
             ifindex: 1,
+            if_name: None,
             if_mtu: Some(1500),
             if_router: None,
         };
 
-        if let Ok(reply) = erbium::dhcp::handle_pkt(&mut pools, &request, serverids, &cfg).await {
+        if let Ok(Some(reply)) = erbium::dhcp::handle_pkt(&mut pools, &request, serverids, &cfg).await {
             let _ = reply.serialise();
         }
     }
@@ -35,10 +35,14 @@ pub struct Permission {
     pub allow_http: bool,
     pub allow_http_metrics: bool,
     pub allow_http_leases: bool,
+    pub allow_http_rw: bool,
+    pub allow_tftp: bool,
 }
 
 pub struct Attributes {
     pub addr: NetAddr,
+    /// How a DNS query reached us, if this is a DNS query.
+    pub dns_protocol: Option<crate::dns::Protocol>,
 }
 
 impl std::fmt::Display for Attributes {
@@ -53,6 +57,7 @@ impl Default for Attributes {
     fn default() -> Self {
         Self {
             addr: UNSPECIFIED6.with_port(0),
+            dns_protocol: None,
         }
     }
 }
@@ -61,6 +66,7 @@ impl Default for Attributes {
 pub struct Acl {
     pub subnet: Option<Vec<Prefix>>,
     pub unix: Option<bool>,
+    pub dns_protocols: Option<Vec<crate::dns::Protocol>>,
     pub permission: Permission,
 }
 
@@ -86,6 +92,14 @@ impl Acl {
             use erbium_net::addr::NetAddrExt as _;
             ok = ok && attr.addr.to_unix_addr().is_some() == unix;
         }
+        /* Check that this is a DNS query, arriving over one of the protocols */
+        if let Some(protocols) = &self.dns_protocols {
+            ok = ok
+                && attr
+                    .dns_protocol
+                    .map(|p| protocols.contains(&p))
+                    .unwrap_or(false);
+        }
 
         if ok {
             Some(&self.permission)
@@ -100,6 +114,8 @@ pub enum PermissionType {
     Http,
     HttpLeases,
     HttpMetrics,
+    HttpRw,
+    Tftp,
 }
 
 impl std::fmt::Display for PermissionType {
@@ -110,6 +126,8 @@ impl std::fmt::Display for PermissionType {
             Http => write!(f, "HTTP"),
             HttpLeases => write!(f, "HTTP Leases"),
             HttpMetrics => write!(f, "HTTP Metrics"),
+            HttpRw => write!(f, "HTTP Read/Write"),
+            Tftp => write!(f, "TFTP"),
         }
     }
 }
@@ -161,6 +179,8 @@ pub fn require_permission(
         (Ok(perms), Http) => check_permission(perms.allow_http, "http"),
         (Ok(perms), HttpLeases) => check_permission(perms.allow_http_leases, "http-leases"),
         (Ok(perms), HttpMetrics) => check_permission(perms.allow_http_metrics, "http-metrics"),
+        (Ok(perms), HttpRw) => check_permission(perms.allow_http_rw, "http-rw"),
+        (Ok(perms), Tftp) => check_permission(perms.allow_tftp, "tftp"),
         (Err(err), perm) => {
             log::warn!("{}: {}: {}", client, perm, err);
             Err(err)
@@ -174,11 +194,14 @@ pub fn default_acls(addresses: &[Prefix]) -> Vec<Acl> {
             /* Any address we hand out by DHCP we should also accept DNS requests from */
             subnet: Some(addresses.to_vec()),
             unix: None,
+            dns_protocols: None,
             permission: Permission {
                 allow_dns_recursion: true,
                 allow_http_leases: true,
                 allow_http_metrics: true,
                 allow_http: true,
+                allow_http_rw: false,
+                allow_tftp: true,
             },
         },
         Acl {
@@ -194,32 +217,48 @@ pub fn default_acls(addresses: &[Prefix]) -> Vec<Acl> {
                 }),
             ]),
             unix: None,
+            dns_protocols: None,
             permission: Permission {
                 allow_dns_recursion: true,
                 allow_http_leases: true,
                 allow_http_metrics: true,
                 allow_http: true,
+                allow_http_rw: false,
+                allow_tftp: true,
             },
         },
         Acl {
             /* Allow API access over the unix domain socket */
             subnet: None,
             unix: Some(true),
+            dns_protocols: None,
             permission: Permission {
                 allow_dns_recursion: false,
                 allow_http_leases: true,
                 allow_http_metrics: true,
                 allow_http: true,
+                allow_http_rw: true,
+                allow_tftp: false,
             },
         },
     ]
 }
 
+fn parse_dns_protocol(
+    name: &str,
+    fragment: &yaml::Yaml,
+) -> Result<Option<crate::dns::Protocol>, Error> {
+    parse_string(name, fragment)?
+        .map(|s| s.parse().map_err(Error::InvalidConfig))
+        .transpose()
+}
+
 pub(crate) fn parse_acl(name: &str, fragment: &yaml::Yaml) -> Result<Option<Acl>, Error> {
     match fragment {
         yaml::Yaml::Hash(h) => {
             let mut subnet = None;
             let mut unix = None;
+            let mut dns_protocols = None;
             let mut accesses = vec![];
             for (k, v) in h {
                 match (k.as_str(), v) {
@@ -229,6 +268,9 @@ pub(crate) fn parse_acl(name: &str, fragment: &yaml::Yaml) -> Result<Option<Acl>
                     (Some("match-unix"), s) => {
                         unix = parse_boolean("match-unix", s)?;
                     }
+                    (Some("match-dns-protocols"), s) => {
+                        dns_protocols = parse_array("match-dns-protocols", s, parse_dns_protocol)?;
+                    }
                     (Some("apply-access"), a) => {
                         accesses =
                             parse_array("apply-access", a, parse_string)?.ok_or_else(|| {
@@ -250,31 +292,44 @@ pub(crate) fn parse_acl(name: &str, fragment: &yaml::Yaml) -> Result<Option<Acl>
             let mut allow_http = false;
             let mut allow_http_metrics = false;
             let mut allow_http_leases = false;
+            let mut allow_http_rw = false;
+            let mut allow_tftp = false;
             for access in accesses {
                 match access.as_str() {
                     "dhcp-client" => {
                         allow_dns_recursion = true;
+                        allow_tftp = true;
                     }
                     "dns-recursion" => allow_dns_recursion = true,
                     "http" => allow_http = true,
                     "http-metrics" => allow_http_metrics = true,
                     "http-leases" => allow_http_leases = true,
+                    "tftp" => allow_tftp = true,
                     "http-ro" => {
                         allow_http = true;
                         allow_http_metrics = true;
                         allow_http_leases = true;
                     }
+                    "http-rw" => {
+                        allow_http = true;
+                        allow_http_metrics = true;
+                        allow_http_leases = true;
+                        allow_http_rw = true;
+                    }
                     e => return Err(Error::InvalidConfig(format!("Unknown access {}", e))),
                 }
             }
             Ok(Some(Acl {
                 subnet,
                 unix,
+                dns_protocols,
                 permission: Permission {
                     allow_dns_recursion,
                     allow_http,
                     allow_http_metrics,
                     allow_http_leases,
+                    allow_http_rw,
+                    allow_tftp,
                 },
             }))
         }
@@ -295,11 +350,14 @@ fn acl_not_authenticated() {
             prefixlen: 24,
         })]),
         unix: None,
+        dns_protocols: None,
         permission: Permission {
             allow_dns_recursion: true,
             allow_http: false,
             allow_http_leases: false,
             allow_http_metrics: false,
+            allow_http_rw: false,
+            allow_tftp: false,
         },
     }];
 
@@ -307,6 +365,7 @@ fn acl_not_authenticated() {
 
     let client = Attributes {
         addr: ip.to_net_addr(),
+        ..Default::default()
     };
 
     assert_eq!(
@@ -326,11 +385,14 @@ fn acl_not_authorized() {
             prefixlen: 24,
         })]),
         unix: None,
+        dns_protocols: None,
         permission: Permission {
             allow_dns_recursion: false,
             allow_http: false,
             allow_http_leases: false,
             allow_http_metrics: false,
+            allow_http_rw: false,
+            allow_tftp: false,
         },
     }];
 
@@ -338,6 +400,7 @@ fn acl_not_authorized() {
 
     let client = Attributes {
         addr: ip.to_net_addr(),
+        ..Default::default()
     };
 
     assert_eq!(
@@ -357,11 +420,14 @@ fn acl_allowed() {
             prefixlen: 24,
         })]),
         unix: None,
+        dns_protocols: None,
         permission: Permission {
             allow_dns_recursion: true,
             allow_http: false,
             allow_http_leases: false,
             allow_http_metrics: false,
+            allow_http_rw: false,
+            allow_tftp: false,
         },
     }];
 
@@ -369,6 +435,7 @@ fn acl_allowed() {
 
     let client = Attributes {
         addr: ip.to_net_addr(),
+        ..Default::default()
     };
 
     assert_eq!(
@@ -429,3 +496,96 @@ fn acl_parse_fail() {
         "Invalid Configuration: apply-access cannot be null"
     );
 }
+
+#[tokio::test]
+async fn acl_parse_http_rw() {
+    use erbium_net::addr::{Ipv4Addr, ToNetAddr as _, WithPort as _};
+    let conf = load_config_from_string_for_test(
+        "---
+      acls:
+       - match-subnets: [192.0.2.0/24]
+         apply-access: ['http-ro']
+       - match-subnets: [198.51.100.0/24]
+         apply-access: ['http-rw']
+    ",
+    )
+    .expect("Failed to parse ACL configuration");
+    let acls = &conf.read().await.acls;
+    let client = |ip: &str| Attributes {
+        addr: ip.parse::<Ipv4Addr>().unwrap().with_port(0).to_net_addr(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        require_permission(acls, &client("192.0.2.1"), PermissionType::HttpRw),
+        Err(AclError::NotAuthorised("http-rw".into()))
+    );
+    assert_eq!(
+        require_permission(acls, &client("198.51.100.1"), PermissionType::HttpRw),
+        Ok(())
+    );
+    assert_eq!(
+        require_permission(acls, &client("198.51.100.1"), PermissionType::HttpLeases),
+        Ok(())
+    );
+}
+
+#[tokio::test]
+async fn acl_dns_protocols() {
+    use crate::dns::Protocol;
+    use erbium_net::addr::{Ipv4Addr, ToNetAddr as _, WithPort as _};
+    let conf = load_config_from_string_for_test(
+        "---
+      acls:
+       - match-subnets: [192.0.2.0/24]
+         match-dns-protocols: [tls, https]
+         apply-access: ['dns-recursion']
+       - match-subnets: [192.0.2.0/24]
+         apply-access: ['http']
+    ",
+    )
+    .expect("Failed to parse ACL configuration");
+    let acls = &conf.read().await.acls;
+    let client = |dns_protocol| Attributes {
+        addr: "192.0.2.1"
+            .parse::<Ipv4Addr>()
+            .unwrap()
+            .with_port(0)
+            .to_net_addr(),
+        dns_protocol,
+    };
+
+    assert_eq!(
+        require_permission(
+            acls,
+            &client(Some(Protocol::Tls)),
+            PermissionType::DnsRecursion
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        require_permission(
+            acls,
+            &client(Some(Protocol::Udp)),
+            PermissionType::DnsRecursion
+        ),
+        Err(AclError::NotAuthorised("dns-recursion".into()))
+    );
+    assert_eq!(
+        require_permission(acls, &client(None), PermissionType::Http),
+        Ok(())
+    );
+
+    assert_eq!(
+        load_config_from_string_for_test(
+            "---
+      acls:
+       - match-dns-protocols: [quic]
+         apply-access: ['dns-recursion']
+    ",
+        )
+        .expect_err("Bad config unexpectedly successfully parsed")
+        .to_string(),
+        "Invalid Configuration: Unknown DNS protocol quic, expected udp, tcp, tls or https"
+    );
+}
@@ -43,28 +43,48 @@ impl std::fmt::Display for Error {
 async fn go() -> Result<(), Error> {
     use futures::StreamExt as _;
     let args: Vec<_> = std::env::args_os().collect();
-    let config_file = match args.len() {
-        1 => std::path::Path::new("erbium.conf"),
-        2 => std::path::Path::new(&args[1]),
-        _ => {
-            println!("Usage: {} <configfile>", args[0].to_string_lossy());
+    let usage = || {
+        println!(
+            "Usage: {} [--state-dir <dir>] [<configfile>]",
+            args[0].to_string_lossy()
+        );
+    };
+    let mut state_dir = None;
+    let mut config_file = None;
+    let mut remaining = args.iter().skip(1);
+    while let Some(arg) = remaining.next() {
+        if arg == "--state-dir" {
+            match remaining.next() {
+                Some(dir) => state_dir = Some(std::path::PathBuf::from(dir)),
+                None => {
+                    usage();
+                    return Ok(());
+                }
+            }
+        } else if arg.to_string_lossy().starts_with('-') || config_file.is_some() {
+            usage();
             return Ok(());
+        } else {
+            config_file = Some(std::path::Path::new(arg));
         }
-    };
+    }
+    let config_file = config_file.unwrap_or_else(|| std::path::Path::new("erbium.conf"));
     let mut services: futures::stream::FuturesUnordered<
         tokio::task::JoinHandle<std::result::Result<(), String>>,
     > = futures::stream::FuturesUnordered::new();
 
     let netinfo = erbium_net::netinfo::SharedNetInfo::new().await;
 
-    let dns = dns::DnsService::new(
-        erbium::config::load_config_from_path(config_file)
-            .await
-            .map_err(Error::Config)?,
-        &netinfo,
-    )
-    .await
-    .map_err(Error::Dns)?;
+    let conf = erbium::config::load_config_from_path(config_file)
+        .await
+        .map_err(Error::Config)?;
+    /* The command line overrides the configuration file */
+    if let Some(state_dir) = state_dir {
+        conf.write().await.state_dir = state_dir;
+    }
+    let dns = dns::DnsService::new(conf, &netinfo)
+        .await
+        .map_err(Error::Dns)?;
 
     services.push(tokio::spawn(async move {
         dns.run().await.map_err(|err| err.to_string())
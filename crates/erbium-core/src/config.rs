@@ -225,6 +225,9 @@ where
     }
 }
 
+/* Where the lease database is kept, unless configured otherwise. */
+pub const DEFAULT_STATE_DIR: &str = "/var/lib/erbium";
+
 pub const INTERFACE4: std::net::IpAddr = std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0));
 pub const INTERFACE6: std::net::IpAddr =
     std::net::IpAddr::V6(std::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0));
@@ -454,6 +457,27 @@ pub fn parse_string_hwaddr(name: &str, fragment: &yaml::Yaml) -> Result<Option<V
     parse_string(name, fragment).and_then(str_hwaddr)
 }
 
+/// Parses either a string, which is used as is, or {hex: 01:02:ab} for binary values.
+pub fn parse_string_bytes(name: &str, fragment: &yaml::Yaml) -> Result<Option<Vec<u8>>, Error> {
+    match fragment {
+        yaml::Yaml::String(s) => Ok(Some(s.as_bytes().to_vec())),
+        yaml::Yaml::Hash(h) if h.len() == 1 => match h.iter().next() {
+            Some((k, v)) if k.as_str() == Some("hex") => parse_string_hwaddr("hex", v)?
+                .ok_or_else(|| Error::InvalidConfig("hex cannot be null".into()))
+                .map(Some),
+            _ => Err(Error::InvalidConfig(format!(
+                "{} should be a String or {{hex: ...}}",
+                name
+            ))),
+        },
+        e => Err(Error::InvalidConfig(format!(
+            "{} should be a String or {{hex: ...}}, not {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
+
 pub fn parse_string_ip(
     name: &str,
     fragment: &yaml::Yaml,
@@ -782,6 +806,8 @@ impl Default for AddressType {
 pub struct Config {
     #[cfg(feature = "dhcp")]
     pub dhcp: crate::dhcp::config::Config,
+    #[cfg(feature = "dhcp")]
+    pub dhcp6: crate::dhcp6::config::Config,
     pub ra: crate::radv::config::Config,
     pub dns_servers: Vec<std::net::IpAddr>,
     pub dns_search: Vec<String>,
@@ -790,7 +816,13 @@ pub struct Config {
     pub listeners: Vec<NetAddr>,
     pub dns_listeners: AddressType,
     pub dns_routes: Vec<crate::dns::config::Route>,
+    pub dns_tls: Option<crate::dns::config::TlsListeners>,
+    pub dns_cache: crate::dns::config::CacheLimits,
     pub acls: Vec<crate::acl::Acl>,
+    #[cfg(feature = "tftp")]
+    pub tftp: Option<crate::tftp::config::Config>,
+    /// Where state that persists across restarts, such as the lease database, is kept.
+    pub state_dir: std::path::PathBuf,
 }
 
 pub type SharedConfig = std::sync::Arc<tokio::sync::RwLock<Config>>;
@@ -822,6 +854,17 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
         let mut ra = None;
         #[cfg(feature = "dhcp")]
         let mut dhcp = None;
+        #[cfg(feature = "dhcp")]
+        let mut dhcp6 = None;
+        #[cfg(feature = "dhcp")]
+        let mut conflict_detection = None;
+        #[cfg(feature = "dhcp")]
+        let mut failover = None;
+        #[cfg(feature = "dhcp")]
+        let mut lease_storage = Default::default();
+        #[cfg(feature = "dhcp")]
+        let mut lease_hooks = vec![];
+        let mut state_dir = None;
         #[cfg(feature = "dns")]
         let mut dns_servers = vec![INTERFACE4, INTERFACE6];
         #[cfg(not(feature = "dns"))]
@@ -832,8 +875,12 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
         let mut listeners = None;
         let mut dns_listeners = None;
         let mut dns_routes = None;
+        let mut dns_tls = None;
+        let mut dns_cache = None;
         let mut default_listen_style = DefaultAddressType::Unspecified;
         let mut acls = None;
+        #[cfg(feature = "tftp")]
+        let mut tftp = None;
         for (k, v) in fragment {
             match (k.as_str(), v) {
                 (Some("dhcp"), _) => return Err(Error::InvalidConfig("The dhcp section has been replaced with dhcp-policies section, please see the manpage for more details".into())),
@@ -842,6 +889,34 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
                     .map_err(|e| e.annotate("while parsing dhcp-policies"))?,
                 #[cfg(not(feature = "dhcp"))]
                 (Some("dhcp-policies"), _) => (),
+                #[cfg(feature = "dhcp")]
+                (Some("dhcp-conflict-detection"), d) => conflict_detection =
+                    crate::dhcp::config::parse_conflict_detection("dhcp-conflict-detection", d)?,
+                #[cfg(not(feature = "dhcp"))]
+                (Some("dhcp-conflict-detection"), _) => (),
+                #[cfg(feature = "dhcp")]
+                (Some("dhcp-failover"), d) => failover =
+                    crate::dhcp::config::parse_failover("dhcp-failover", d)?,
+                #[cfg(not(feature = "dhcp"))]
+                (Some("dhcp-failover"), _) => (),
+                #[cfg(feature = "dhcp")]
+                (Some("lease-storage"), s) => lease_storage =
+                    crate::dhcp::config::parse_lease_storage("lease-storage", s)?,
+                #[cfg(not(feature = "dhcp"))]
+                (Some("lease-storage"), _) => (),
+                #[cfg(feature = "dhcp")]
+                (Some("dhcp-lease-hooks"), h) => lease_hooks =
+                    crate::dhcp::config::parse_lease_hooks("dhcp-lease-hooks", h)?,
+                #[cfg(not(feature = "dhcp"))]
+                (Some("dhcp-lease-hooks"), _) => (),
+                (Some("state-dir"), s) => {
+                    state_dir = parse_string("state-dir", s)?.map(std::path::PathBuf::from);
+                }
+                #[cfg(feature = "dhcp")]
+                (Some("prefix-delegation"), d) => dhcp6 = crate::dhcp6::config::parse(d)
+                    .map_err(|e| e.annotate("while parsing prefix-delegation"))?,
+                #[cfg(not(feature = "dhcp"))]
+                (Some("prefix-delegation"), _) => (),
                 (Some("router-advertisements"), r) => ra = crate::radv::config::parse(r)
                     .map_err(|e| e.annotate("while parsing router-advertisements"))?,
                 (Some("dns-servers"), s) => {
@@ -883,6 +958,18 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
                 (Some("dns-routes"), s) => {
                     dns_routes = crate::dns::config::parse_dns_routes("dns-routes", s)?;
                 }
+                (Some("dns-tls"), s) => {
+                    dns_tls = crate::dns::config::parse_dns_tls("dns-tls", s)?;
+                }
+                (Some("dns-cache"), s) => {
+                    dns_cache = crate::dns::config::parse_dns_cache("dns-cache", s)?;
+                }
+                #[cfg(feature = "tftp")]
+                (Some("tftp"), s) => {
+                    tftp = crate::tftp::config::parse("tftp", s)?;
+                }
+                #[cfg(not(feature = "tftp"))]
+                (Some("tftp"), _) => (),
                 (Some(x), _) => {
                     return Err(Error::InvalidConfig(format!(
                         "Unknown configuration option {}",
@@ -898,9 +985,35 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
             }
         }
         let addresses = addresses.unwrap_or_default();
+        #[cfg(feature = "dhcp")]
+        let dhcp6 = dhcp6.unwrap_or_default();
+        #[cfg(feature = "dhcp")]
+        for delegation in &dhcp6.delegations {
+            if !addresses.iter().any(|addr| match addr {
+                Prefix::V6(p6) => {
+                    p6.prefixlen <= delegation.prefix.prefixlen
+                        && Prefix6::new(p6.network(), p6.prefixlen)
+                            .contains(delegation.prefix.network())
+                }
+                Prefix::V4(_) => false,
+            }) {
+                return Err(Error::InvalidConfig(format!(
+                    "prefix-delegation prefix {}/{} is not inside any of the addresses",
+                    delegation.prefix.addr, delegation.prefix.prefixlen
+                )));
+            }
+        }
         let conf = Config {
             #[cfg(feature = "dhcp")]
-            dhcp: dhcp.unwrap_or_default(),
+            dhcp: crate::dhcp::config::Config {
+                conflict_detection,
+                failover,
+                lease_storage,
+                lease_hooks,
+                ..dhcp.unwrap_or_default()
+            },
+            #[cfg(feature = "dhcp")]
+            dhcp6,
             ra: ra.unwrap_or_default(),
             dns_servers,
             dns_search,
@@ -917,6 +1030,8 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
                 DefaultAddressType::Interface => AddressType::BindInterface,
             }),
             dns_routes: dns_routes.unwrap_or_default(),
+            dns_tls,
+            dns_cache: dns_cache.unwrap_or_default(),
             captive_portal,
             listeners: listeners.unwrap_or_else(|| {
                 vec![UnixAddr::new("/var/lib/erbium/control")
@@ -925,7 +1040,18 @@ fn load_config_from_string(cfg: &str) -> Result<SharedConfig, Error> {
             }),
             acls: acls.unwrap_or_else(|| crate::acl::default_acls(&addresses)),
             addresses,
+            #[cfg(feature = "tftp")]
+            tftp,
+            state_dir: state_dir.unwrap_or_else(|| DEFAULT_STATE_DIR.into()),
         };
+        #[cfg(feature = "dhcp")]
+        if conf.dhcp.lease_storage == crate::dhcp::config::LeaseStorage::Journal
+            && crate::dhcp6::is_configured(&conf)
+        {
+            return Err(Error::InvalidConfig(
+                "lease-storage journal can't be used with DHCPv6, use sqlite or memory".into(),
+            ));
+        }
         Ok(std::sync::Arc::new(tokio::sync::RwLock::new(conf)))
     } else {
         Err(Error::InvalidConfig(
@@ -1046,6 +1172,174 @@ dns-listeners: [192.0.2.0:53]
     Ok(())
 }
 
+#[cfg(feature = "dhcp")]
+#[test]
+fn test_prefix_delegation_outside_addresses() {
+    assert!(load_config_from_string(
+        "---
+addresses: [2001:db8::/32]
+prefix-delegation:
+  - prefix: 2001:db8:100::/48
+"
+    )
+    .is_ok());
+    assert!(load_config_from_string(
+        "---
+addresses: [2001:db8::/64]
+prefix-delegation:
+  - prefix: 2001:db8:100::/48
+"
+    )
+    .is_err());
+}
+
+#[cfg(feature = "dhcp")]
+#[tokio::test]
+async fn test_conflict_detection_parse() {
+    use crate::dhcp::config::{ConflictDetection, ProbeMethod};
+    let conf = load_config_from_string(
+        "---
+dhcp-conflict-detection:
+  method: icmp
+  hold-down: 2h
+",
+    )
+    .unwrap();
+    assert_eq!(
+        conf.read().await.dhcp.conflict_detection,
+        Some(ConflictDetection {
+            method: ProbeMethod::Icmp,
+            timeout: std::time::Duration::from_secs(1),
+            holddown: std::time::Duration::from_secs(7200),
+        })
+    );
+    let conf = load_config_from_string("---\ndhcp-policies: []\n").unwrap();
+    assert_eq!(conf.read().await.dhcp.conflict_detection, None);
+    assert!(load_config_from_string("---\ndhcp-conflict-detection: {method: dns}\n").is_err());
+}
+
+#[cfg(feature = "dhcp")]
+#[tokio::test]
+async fn test_failover_parse() {
+    use crate::dhcp::config::{FailoverMode, FailoverRole};
+    let conf = load_config_from_string(
+        "---
+dhcp-failover:
+  mode: split-scope
+  role: secondary
+  peer: 192.0.2.1:9967
+  secret: correct horse battery staple
+  split: 80
+  partner-down-after: null
+",
+    )
+    .unwrap();
+    let lockedconf = conf.read().await;
+    let failover = lockedconf.dhcp.failover.as_ref().unwrap();
+    assert_eq!(failover.mode, FailoverMode::SplitScope);
+    assert_eq!(failover.role, FailoverRole::Secondary);
+    assert_eq!(failover.peer, "192.0.2.1:9967".parse().unwrap());
+    assert_eq!(failover.listener, "[::]:9967".parse().unwrap());
+    assert_eq!(failover.split, 80);
+    assert_eq!(
+        failover.max_client_lead_time,
+        std::time::Duration::from_secs(3600)
+    );
+    assert_eq!(failover.partner_down_after, None);
+    assert!(!format!("{:?}", failover).contains("staple"));
+    assert!(
+        load_config_from_string("---\ndhcp-failover: {role: primary, peer: 192.0.2.2:9967}\n")
+            .is_err()
+    );
+    assert!(load_config_from_string(
+        "---\ndhcp-failover: {role: primary, peer: 192.0.2.2:9967, secret: x, split: 101}\n"
+    )
+    .is_err());
+}
+
+#[cfg(feature = "dhcp")]
+#[tokio::test]
+async fn test_state_dir_parse() {
+    use crate::dhcp::config::LeaseStorage;
+    let conf = load_config_from_string("---\naddresses: []\n").unwrap();
+    let lockedconf = conf.read().await;
+    assert_eq!(
+        lockedconf.state_dir,
+        std::path::Path::new(DEFAULT_STATE_DIR)
+    );
+    assert_eq!(lockedconf.dhcp.lease_storage, LeaseStorage::Sqlite);
+
+    let conf =
+        load_config_from_string("---\nstate-dir: /tmp/erbium\nlease-storage: journal\n").unwrap();
+    let lockedconf = conf.read().await;
+    assert_eq!(lockedconf.state_dir, std::path::Path::new("/tmp/erbium"));
+    assert_eq!(lockedconf.dhcp.lease_storage, LeaseStorage::Journal);
+
+    assert!(load_config_from_string("---\nlease-storage: postgres\n").is_err());
+    /* DHCPv6 leases can't be kept in the journal */
+    assert!(load_config_from_string(
+        "---
+addresses: [2001:db8::/32]
+lease-storage: journal
+prefix-delegation:
+  - prefix: 2001:db8:100::/48
+    delegated-length: 56
+"
+    )
+    .is_err());
+}
+
+#[cfg(feature = "dhcp")]
+#[tokio::test]
+async fn test_lease_hooks_parse() {
+    use crate::dhcp::config::{HookAction, LeaseEvent, ALL_LEASE_EVENTS};
+    let conf = load_config_from_string(
+        "---
+dhcp-lease-hooks:
+ - exec: /usr/local/sbin/lease-changed
+ - post: http://localhost/api/leases
+   events: [add, expire]
+ - post: http://[::1]:8080
+",
+    )
+    .unwrap();
+    let lockedconf = conf.read().await;
+    let hooks = &lockedconf.dhcp.lease_hooks;
+    assert_eq!(
+        hooks[0].action,
+        HookAction::Exec("/usr/local/sbin/lease-changed".into())
+    );
+    assert_eq!(hooks[0].events, ALL_LEASE_EVENTS);
+    assert_eq!(
+        hooks[1].action,
+        HookAction::Post {
+            host: "localhost:80".into(),
+            path: "/api/leases".into()
+        }
+    );
+    assert_eq!(hooks[1].events, vec![LeaseEvent::Add, LeaseEvent::Expire]);
+    assert_eq!(
+        hooks[2].action,
+        HookAction::Post {
+            host: "[::1]:8080".into(),
+            path: "/".into()
+        }
+    );
+
+    assert!(load_config_from_string("---\ndhcp-lease-hooks: [{events: [add]}]\n").is_err());
+    assert!(load_config_from_string(
+        "---\ndhcp-lease-hooks: [{exec: /bin/true, post: http://localhost/}]\n"
+    )
+    .is_err());
+    assert!(
+        load_config_from_string("---\ndhcp-lease-hooks: [{post: https://example.com/}]\n").is_err()
+    );
+    assert!(load_config_from_string(
+        "---\ndhcp-lease-hooks: [{exec: /bin/true, events: [commit]}]\n"
+    )
+    .is_err());
+}
+
 #[test]
 fn test_duration() {
     assert_eq!(
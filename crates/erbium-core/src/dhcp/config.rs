@@ -17,8 +17,8 @@
  *  DHCP Configuration parsing.
  */
 use super::dhcppkt;
+use super::pool::PoolAddresses;
 use std::convert::TryFrom as _;
-use std::ops::Sub;
 use yaml_rust::yaml;
 
 pub use crate::config::*;
@@ -28,17 +28,26 @@ pub struct Policy {
     pub match_all: bool,
     pub match_interface: Option<Option<String>>,
     pub match_chaddr: Option<Vec<u8>>,
+    pub match_circuit_id: Option<Vec<u8>>,
+    pub match_remote_id: Option<Vec<u8>>,
     pub match_subnet: Option<erbium_net::Ipv4Subnet>,
+    pub match_client_arch: Option<Vec<u16>>,
+    pub match_pxe_client: Option<bool>,
+    pub match_ipxe: Option<bool>,
     pub match_other:
         std::collections::HashMap<dhcppkt::DhcpOption, Option<dhcppkt::DhcpOptionTypeValue>>,
-    pub apply_address: Option<super::pool::PoolAddresses>,
+    pub apply_address: Option<PoolAddresses>,
     pub apply_default_lease: Option<std::time::Duration>,
+    pub apply_min_lease: Option<std::time::Duration>,
     pub apply_max_lease: Option<std::time::Duration>,
+    pub apply_renewal_time: Option<std::time::Duration>,
+    pub apply_rebind_time: Option<std::time::Duration>,
+    pub apply_next_server: Option<std::net::Ipv4Addr>,
+    pub apply_boot_file: Option<String>,
     pub apply_other:
         std::collections::HashMap<dhcppkt::DhcpOption, Option<dhcppkt::DhcpOptionTypeValue>>,
     pub policies: Vec<Policy>,
-    pub(super) address_cache:
-        std::sync::Mutex<std::cell::RefCell<Option<super::pool::PoolAddresses>>>,
+    pub(super) address_cache: std::sync::Mutex<std::cell::RefCell<Option<PoolAddresses>>>,
 }
 
 impl Clone for Policy {
@@ -47,8 +56,12 @@ impl Clone for Policy {
             address_cache: Default::default(),
             match_interface: self.match_interface.clone(),
             match_chaddr: self.match_chaddr.clone(),
+            match_circuit_id: self.match_circuit_id.clone(),
+            match_remote_id: self.match_remote_id.clone(),
+            match_client_arch: self.match_client_arch.clone(),
             match_other: self.match_other.clone(),
             apply_address: self.apply_address.clone(),
+            apply_boot_file: self.apply_boot_file.clone(),
             apply_other: self.apply_other.clone(),
             policies: self.policies.clone(),
             ..*self
@@ -57,16 +70,16 @@ impl Clone for Policy {
 }
 
 impl Policy {
-    fn get_all_used_addresses(&self) -> std::collections::HashSet<std::net::Ipv4Addr> {
+    fn get_all_used_addresses(&self) -> PoolAddresses {
         /* We cache the result of this. */
         if self.address_cache.lock().unwrap().borrow().is_none() {
             /* Cache is cold, heat it. */
-            let mut addrset: std::collections::HashSet<std::net::Ipv4Addr> = Default::default();
+            let mut addrset = PoolAddresses::new();
             if let Some(address) = &self.apply_address {
-                addrset.extend(address.iter());
+                addrset.extend(address);
             }
             for p in &self.policies {
-                addrset.extend(p.get_all_used_addresses().iter());
+                addrset.extend(&p.get_all_used_addresses());
             }
             self.address_cache.lock().unwrap().replace(Some(addrset));
         }
@@ -75,23 +88,456 @@ impl Policy {
     }
 }
 
+/// The addresses in a subnet that can be handed out to clients.
+pub fn subnet_addresses(subnet: &erbium_net::Ipv4Subnet) -> PoolAddresses {
+    let base = u64::from(u32::from(subnet.network()));
+    let size = 1_u64 << (32 - subnet.prefixlen);
+    if size < 4 {
+        return PoolAddresses::new();
+    }
+    PoolAddresses::from_range(
+        ((base + 1) as u32).into(),
+        ((base + size - 3) as u32).into(),
+    )
+}
+
+/* How long to wait for something to answer a conflict detection probe. */
+const DEFAULT_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeMethod {
+    Arp,
+    Icmp,
+}
+
+/// Configuration for checking that nothing is already using an address before it is offered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictDetection {
+    pub method: ProbeMethod,
+    pub timeout: std::time::Duration,
+    pub holddown: std::time::Duration,
+}
+
+impl Default for ConflictDetection {
+    fn default() -> Self {
+        ConflictDetection {
+            method: ProbeMethod::Arp,
+            timeout: DEFAULT_PROBE_TIMEOUT,
+            holddown: super::pool::DEFAULT_DECLINE_HOLDDOWN,
+        }
+    }
+}
+
+pub fn parse_conflict_detection(
+    name: &str,
+    fragment: &yaml::Yaml,
+) -> Result<Option<ConflictDetection>, Error> {
+    match fragment {
+        yaml::Yaml::Null => Ok(None),
+        yaml::Yaml::Hash(h) => {
+            let mut ret = ConflictDetection::default();
+            for (k, v) in h {
+                match (k.as_str(), v) {
+                    (Some("method"), m) => {
+                        ret.method = match parse_string("method", m)?.as_deref() {
+                            Some("arp") => ProbeMethod::Arp,
+                            Some("icmp") => ProbeMethod::Icmp,
+                            Some(m) => {
+                                return Err(Error::InvalidConfig(format!(
+                                    "Unknown conflict detection method {}, expected arp or icmp",
+                                    m
+                                )))
+                            }
+                            None => {
+                                return Err(Error::InvalidConfig("method cannot be null".into()))
+                            }
+                        }
+                    }
+                    (Some("timeout"), t) => {
+                        ret.timeout = parse_duration("timeout", t)?
+                            .ok_or_else(|| Error::InvalidConfig("timeout cannot be null".into()))?
+                    }
+                    (Some("hold-down"), t) => {
+                        ret.holddown = parse_duration("hold-down", t)?.ok_or_else(|| {
+                            Error::InvalidConfig("hold-down cannot be null".into())
+                        })?
+                    }
+                    (Some(x), _) => {
+                        return Err(Error::InvalidConfig(format!("Unknown {} key {}", name, x)))
+                    }
+                    (None, _) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} keys should be strings, not {}",
+                            name,
+                            type_to_name(k)
+                        )))
+                    }
+                }
+            }
+            Ok(Some(ret))
+        }
+        e => Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
+
+/* Port that the secondary listens on for the primary to connect to. */
+pub const DEFAULT_FAILOVER_PORT: u16 = 9967;
+/* How long leases are limited to while the peer can't be told about them, and how long after
+ * losing contact with the peer before its addresses can be used.
+ */
+const DEFAULT_MAX_CLIENT_LEAD_TIME: std::time::Duration = std::time::Duration::from_secs(3600);
+const DEFAULT_PARTNER_DOWN_AFTER: std::time::Duration = std::time::Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverMode {
+    /// The primary serves all clients, the secondary only serves clients once the primary is down.
+    ActiveStandby,
+    /// Both servers serve clients, each handing out new addresses from their own part of the pool.
+    SplitScope,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverRole {
+    Primary,
+    Secondary,
+}
+
+/// Configuration for sharing leases with another erbium instance.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FailoverConfig {
+    pub mode: FailoverMode,
+    pub role: FailoverRole,
+    /// The primary connects to this address.  The secondary only accepts connections from it.
+    pub peer: std::net::SocketAddr,
+    /// The address the secondary listens on.
+    pub listener: std::net::SocketAddr,
+    pub secret: String,
+    /// The percentage of addresses that belong to the primary in split-scope mode.
+    pub split: u8,
+    pub max_client_lead_time: std::time::Duration,
+    pub partner_down_after: Option<std::time::Duration>,
+}
+
+/* Don't leak the secret into logs. */
+impl std::fmt::Debug for FailoverConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FailoverConfig")
+            .field("mode", &self.mode)
+            .field("role", &self.role)
+            .field("peer", &self.peer)
+            .field("listener", &self.listener)
+            .field("split", &self.split)
+            .field("max_client_lead_time", &self.max_client_lead_time)
+            .field("partner_down_after", &self.partner_down_after)
+            .finish()
+    }
+}
+
+fn parse_std_sockaddr(name: &str, fragment: &yaml::Yaml) -> Result<std::net::SocketAddr, Error> {
+    use erbium_net::addr::NetAddrExt as _;
+    parse_string_sockaddr(name, fragment)?
+        .ok_or_else(|| Error::InvalidConfig(format!("{} cannot be null", name)))?
+        .to_std_socket_addr()
+        .ok_or_else(|| Error::InvalidConfig(format!("{} must be an IP address and port", name)))
+}
+
+pub fn parse_failover(name: &str, fragment: &yaml::Yaml) -> Result<Option<FailoverConfig>, Error> {
+    match fragment {
+        yaml::Yaml::Null => Ok(None),
+        yaml::Yaml::Hash(h) => {
+            let mut mode = FailoverMode::ActiveStandby;
+            let mut role = None;
+            let mut peer = None;
+            let mut listener = std::net::SocketAddr::new(
+                std::net::Ipv6Addr::UNSPECIFIED.into(),
+                DEFAULT_FAILOVER_PORT,
+            );
+            let mut secret = None;
+            let mut split = 50;
+            let mut max_client_lead_time = DEFAULT_MAX_CLIENT_LEAD_TIME;
+            let mut partner_down_after = Some(DEFAULT_PARTNER_DOWN_AFTER);
+            for (k, v) in h {
+                match (k.as_str(), v) {
+                    (Some("mode"), m) => {
+                        mode = match parse_string("mode", m)?.as_deref() {
+                            Some("active-standby") => FailoverMode::ActiveStandby,
+                            Some("split-scope") => FailoverMode::SplitScope,
+                            Some(m) => return Err(Error::InvalidConfig(format!(
+                                "Unknown failover mode {}, expected active-standby or split-scope",
+                                m
+                            ))),
+                            None => return Err(Error::InvalidConfig("mode cannot be null".into())),
+                        }
+                    }
+                    (Some("role"), r) => {
+                        role = match parse_string("role", r)?.as_deref() {
+                            Some("primary") => Some(FailoverRole::Primary),
+                            Some("secondary") => Some(FailoverRole::Secondary),
+                            Some(r) => {
+                                return Err(Error::InvalidConfig(format!(
+                                    "Unknown failover role {}, expected primary or secondary",
+                                    r
+                                )))
+                            }
+                            None => None,
+                        }
+                    }
+                    (Some("peer"), p) => peer = Some(parse_std_sockaddr("peer", p)?),
+                    (Some("listener"), l) => listener = parse_std_sockaddr("listener", l)?,
+                    (Some("secret"), s) => secret = parse_string("secret", s)?,
+                    (Some("split"), s) => {
+                        split = parse_num("split", s)?
+                            .ok_or_else(|| Error::InvalidConfig("split cannot be null".into()))?;
+                        if split > 100 {
+                            return Err(Error::InvalidConfig(format!(
+                                "split is a percentage, so cannot be {}",
+                                split
+                            )));
+                        }
+                    }
+                    (Some("max-client-lead-time"), t) => {
+                        max_client_lead_time = parse_duration("max-client-lead-time", t)?
+                            .ok_or_else(|| {
+                                Error::InvalidConfig("max-client-lead-time cannot be null".into())
+                            })?
+                    }
+                    (Some("partner-down-after"), t) => {
+                        partner_down_after = parse_duration("partner-down-after", t)?
+                    }
+                    (Some(x), _) => {
+                        return Err(Error::InvalidConfig(format!("Unknown {} key {}", name, x)))
+                    }
+                    (None, _) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} keys should be strings, not {}",
+                            name,
+                            type_to_name(k)
+                        )))
+                    }
+                }
+            }
+            Ok(Some(FailoverConfig {
+                mode,
+                role: role.ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "{} requires a role of primary or secondary",
+                        name
+                    ))
+                })?,
+                peer: peer
+                    .ok_or_else(|| Error::InvalidConfig(format!("{} requires a peer", name)))?,
+                listener,
+                secret: secret
+                    .filter(|s| !s.is_empty())
+                    .ok_or_else(|| Error::InvalidConfig(format!("{} requires a secret", name)))?,
+                split,
+                max_client_lead_time,
+                partner_down_after,
+            }))
+        }
+        e => Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
+
+/// Where DHCP leases are kept.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseStorage {
+    #[default]
+    Sqlite,
+    /// An append-only file of lease changes.
+    Journal,
+    /// Leases are forgotten on restart.
+    Memory,
+}
+
+pub fn parse_lease_storage(name: &str, fragment: &yaml::Yaml) -> Result<LeaseStorage, Error> {
+    match parse_string(name, fragment)?.as_deref() {
+        Some("sqlite") | None => Ok(LeaseStorage::Sqlite),
+        Some("journal") => Ok(LeaseStorage::Journal),
+        Some("memory") => Ok(LeaseStorage::Memory),
+        Some(s) => Err(Error::InvalidConfig(format!(
+            "Unknown {} {}, expected sqlite, journal or memory",
+            name, s
+        ))),
+    }
+}
+
+/// Something that happens to a lease, which hooks can be run for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaseEvent {
+    /// A client was acknowledged an address it didn't already have.
+    Add,
+    /// A client extended the lease it already had.
+    Renew,
+    /// A client told us it has finished with its address.
+    Release,
+    /// A lease ran out without the client renewing it.
+    Expire,
+}
+
+pub const ALL_LEASE_EVENTS: [LeaseEvent; 4] = [
+    LeaseEvent::Add,
+    LeaseEvent::Renew,
+    LeaseEvent::Release,
+    LeaseEvent::Expire,
+];
+
+impl std::fmt::Display for LeaseEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LeaseEvent::Add => write!(f, "add"),
+            LeaseEvent::Renew => write!(f, "renew"),
+            LeaseEvent::Release => write!(f, "release"),
+            LeaseEvent::Expire => write!(f, "expire"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookAction {
+    /// Runs a program, with the details of the lease in its environment.
+    Exec(std::path::PathBuf),
+    /// POSTs the details of the lease as JSON to an http:// URL.
+    Post { host: String, path: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaseHook {
+    pub action: HookAction,
+    pub events: Vec<LeaseEvent>,
+}
+
+fn parse_lease_event(name: &str, fragment: &yaml::Yaml) -> Result<Option<LeaseEvent>, Error> {
+    match parse_string(name, fragment)?.as_deref() {
+        None => Ok(None),
+        Some(event) => ALL_LEASE_EVENTS
+            .iter()
+            .copied()
+            .find(|e| e.to_string() == event)
+            .map(Some)
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "Unknown {} {}, expected add, renew, release or expire",
+                    name, event
+                ))
+            }),
+    }
+}
+
+fn parse_post_url(name: &str, fragment: &yaml::Yaml) -> Result<HookAction, Error> {
+    let url = parse_string(name, fragment)?
+        .ok_or_else(|| Error::InvalidConfig(format!("{} cannot be null", name)))?;
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        Error::InvalidConfig(format!("{} should be an http:// URL, not {}", name, url))
+    })?;
+    let (host, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(Error::InvalidConfig(format!(
+            "{} has no host: {}",
+            name, url
+        )));
+    }
+    /* Add the default port, so the host can be connected to directly */
+    let host = if host.rsplit(':').next().unwrap().parse::<u16>().is_ok() && !host.ends_with(']') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Ok(HookAction::Post {
+        host,
+        path: path.into(),
+    })
+}
+
+fn parse_lease_hook(name: &str, fragment: &yaml::Yaml) -> Result<Option<LeaseHook>, Error> {
+    match fragment {
+        yaml::Yaml::Null => Ok(None),
+        yaml::Yaml::Hash(h) => {
+            let mut action = None;
+            let mut events = ALL_LEASE_EVENTS.to_vec();
+            for (k, v) in h {
+                match (k.as_str(), v) {
+                    (Some("exec"), _) | (Some("post"), _) if action.is_some() => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} can only have one of exec or post",
+                            name
+                        )))
+                    }
+                    (Some("exec"), e) => {
+                        action = parse_string("exec", e)?
+                            .map(|path| HookAction::Exec(std::path::PathBuf::from(path)))
+                    }
+                    (Some("post"), p) => action = Some(parse_post_url("post", p)?),
+                    (Some("events"), e) => {
+                        events = parse_array("events", e, parse_lease_event)?.unwrap_or_default()
+                    }
+                    (Some(x), _) => {
+                        return Err(Error::InvalidConfig(format!("Unknown {} key {}", name, x)))
+                    }
+                    (None, _) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} keys should be strings, not {}",
+                            name,
+                            type_to_name(k)
+                        )))
+                    }
+                }
+            }
+            Ok(Some(LeaseHook {
+                action: action.ok_or_else(|| {
+                    Error::InvalidConfig(format!("{} requires either exec or post", name))
+                })?,
+                events,
+            }))
+        }
+        e => Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
+
+pub fn parse_lease_hooks(name: &str, fragment: &yaml::Yaml) -> Result<Vec<LeaseHook>, Error> {
+    Ok(parse_array(name, fragment, parse_lease_hook)?.unwrap_or_default())
+}
+
 #[derive(Debug, Default)]
 pub struct Config {
     pub policies: Vec<Policy>,
+    pub conflict_detection: Option<ConflictDetection>,
+    pub failover: Option<FailoverConfig>,
+    pub lease_storage: LeaseStorage,
+    pub lease_hooks: Vec<LeaseHook>,
+    /* The addresses used by any policy, which is built once for each config. */
+    pub(crate) address_cache: std::sync::Mutex<Option<PoolAddresses>>,
 }
 
 impl Config {
-    pub fn get_all_used_addresses(&self) -> std::collections::HashSet<std::net::Ipv4Addr> {
-        self.policies
-            .iter()
-            .map(Policy::get_all_used_addresses)
-            .fold(
-                std::collections::HashSet::<std::net::Ipv4Addr>::new(),
-                |mut acc, e| {
-                    acc.extend(e);
-                    acc
-                },
-            )
+    pub fn get_all_used_addresses(&self) -> PoolAddresses {
+        self.address_cache
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                self.policies
+                    .iter()
+                    .fold(PoolAddresses::new(), |mut acc, policy| {
+                        acc.extend(&policy.get_all_used_addresses());
+                        acc
+                    })
+            })
+            .clone()
     }
     fn parse_routes(fragment: &yaml::Yaml) -> Result<Option<Vec<dhcppkt::Route>>, Error> {
         match fragment {
@@ -225,6 +671,23 @@ impl Config {
         }
     }
 
+    /* Client architectures can be given as a single number, or a list of them. */
+    fn parse_client_arch(name: &str, value: &yaml::Yaml) -> Result<Option<Vec<u16>>, Error> {
+        let parse_arch = |_: &str, v: &yaml::Yaml| {
+            Config::parse_number(v)?
+                .map(|i| {
+                    u16::try_from(i).map_err(|_| {
+                        Error::InvalidConfig(format!("Architecture {} out of range", i))
+                    })
+                })
+                .transpose()
+        };
+        match value {
+            yaml::Yaml::Array(_) => parse_array(name, value, parse_arch),
+            _ => Ok(parse_arch(name, value)?.map(|arch| vec![arch])),
+        }
+    }
+
     fn parse_generic(
         name: &str,
         value: &yaml::Yaml,
@@ -333,7 +796,7 @@ impl Config {
     fn parse_policy(fragment: &yaml::Yaml) -> Result<Policy, Error> {
         if let Some(h) = fragment.as_hash() {
             let mut policy: Policy = Default::default();
-            let mut addresses: Option<Vec<std::net::Ipv4Addr>> = None;
+            let mut addresses: Option<PoolAddresses> = None;
             for (k, v) in h {
                 match k.as_str() {
                     Some("match-interface") => {
@@ -351,6 +814,24 @@ impl Config {
                         policy.match_chaddr = parse_string_hwaddr("match-hardware-address", v)
                             .map_err(|x| x.annotate("Failed to parse match-hardware-address"))?;
                     }
+                    Some("match-circuit-id") => {
+                        if policy.match_circuit_id.is_some() {
+                            return Err(Error::InvalidConfig(
+                                "match-circuit-id specified twice".into(),
+                            ));
+                        }
+                        policy.match_circuit_id = parse_string_bytes("match-circuit-id", v)
+                            .map_err(|x| x.annotate("Failed to parse match-circuit-id"))?;
+                    }
+                    Some("match-remote-id") => {
+                        if policy.match_remote_id.is_some() {
+                            return Err(Error::InvalidConfig(
+                                "match-remote-id specified twice".into(),
+                            ));
+                        }
+                        policy.match_remote_id = parse_string_bytes("match-remote-id", v)
+                            .map_err(|x| x.annotate("Failed to parse match-remote-id"))?;
+                    }
                     Some("match-subnet") => {
                         if policy.match_subnet.is_some() {
                             return Err(Error::InvalidConfig(
@@ -365,6 +846,38 @@ impl Config {
                                 })?,
                         );
                     }
+                    Some("match-client-arch") => {
+                        if policy.match_client_arch.is_some() {
+                            return Err(Error::InvalidConfig(
+                                "match-client-arch specified twice".into(),
+                            ));
+                        }
+                        policy.match_client_arch = Some(
+                            Config::parse_client_arch("match-client-arch", v)
+                                .map_err(|x| x.annotate("Failed to parse match-client-arch"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("match-client-arch cannot be nil".into())
+                                })?,
+                        );
+                    }
+                    Some("match-pxe-client") => {
+                        policy.match_pxe_client = Some(
+                            parse_boolean("match-pxe-client", v)
+                                .map_err(|x| x.annotate("Failed to parse match-pxe-client"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("match-pxe-client cannot be nil".into())
+                                })?,
+                        );
+                    }
+                    Some("match-ipxe") => {
+                        policy.match_ipxe = Some(
+                            parse_boolean("match-ipxe", v)
+                                .map_err(|x| x.annotate("Failed to parse match-ipxe"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("match-ipxe cannot be nil".into())
+                                })?,
+                        );
+                    }
                     Some(x) if x.starts_with("match-") => {
                         let name = &x[6..];
                         let (opt, value) = Config::parse_generic(name, v)
@@ -372,8 +885,7 @@ impl Config {
                         policy.match_other.insert(opt, value);
                     }
                     Some("apply-address") => {
-                        let addresses = addresses.get_or_insert_with(Vec::new);
-                        addresses.push(
+                        addresses.get_or_insert_with(PoolAddresses::new).insert(
                             parse_string_ip4("apply-address", v)
                                 .map_err(|x| x.annotate("Failed to parse apply-address"))?
                                 .ok_or_else(|| {
@@ -390,8 +902,17 @@ impl Config {
                                 })?,
                         );
                     }
+                    Some("apply-min-lease") => {
+                        policy.apply_min_lease = Some(
+                            parse_duration("apply-min-lease", v)
+                                .map_err(|x| x.annotate("Failed to parse apply-min-lease"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("apply-min-lease cannot be nil".into())
+                                })?,
+                        );
+                    }
                     Some("apply-max-lease") => {
-                        policy.apply_default_lease = Some(
+                        policy.apply_max_lease = Some(
                             parse_duration("apply-max-lease", v)
                                 .map_err(|x| x.annotate("Failed to parse apply-max-lease"))?
                                 .ok_or_else(|| {
@@ -399,6 +920,42 @@ impl Config {
                                 })?,
                         );
                     }
+                    Some("apply-renewal-time") => {
+                        policy.apply_renewal_time = Some(
+                            parse_duration("apply-renewal-time", v)
+                                .map_err(|x| x.annotate("Failed to parse apply-renewal-time"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("apply-renewal-time cannot be nil".into())
+                                })?,
+                        );
+                    }
+                    Some("apply-rebind-time") => {
+                        policy.apply_rebind_time = Some(
+                            parse_duration("apply-rebind-time", v)
+                                .map_err(|x| x.annotate("Failed to parse apply-rebind-time"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("apply-rebind-time cannot be nil".into())
+                                })?,
+                        );
+                    }
+                    Some("apply-next-server") => {
+                        policy.apply_next_server = Some(
+                            parse_string_ip4("apply-next-server", v)
+                                .map_err(|x| x.annotate("Failed to parse apply-next-server"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("apply-next-server cannot be nil".into())
+                                })?,
+                        );
+                    }
+                    Some("apply-boot-file") => {
+                        policy.apply_boot_file = Some(
+                            parse_string("apply-boot-file", v)
+                                .map_err(|x| x.annotate("Failed to parse apply-boot-file"))?
+                                .ok_or_else(|| {
+                                    Error::InvalidConfig("apply-boot-file cannot be nil".into())
+                                })?,
+                        );
+                    }
                     Some("apply-range") => {
                         if let Some(range) = v.as_hash() {
                             let mut start: Option<std::net::Ipv4Addr> = None;
@@ -451,10 +1008,9 @@ impl Config {
                             let end = end.ok_or_else(|| {
                                 Error::InvalidConfig("Missing end in range".into())
                             })?;
-                            let addresses = addresses.get_or_insert_with(Vec::new);
-                            for i in u32::from(start)..=u32::from(end) {
-                                addresses.push(i.into());
-                            }
+                            addresses
+                                .get_or_insert_with(PoolAddresses::new)
+                                .insert_range(start, end);
                         } else {
                             return Err(Error::InvalidConfig(format!(
                                 "Range should be a hash, not '{:?}'",
@@ -468,11 +1024,9 @@ impl Config {
                             .ok_or_else(|| {
                                 Error::InvalidConfig("apply-subnet cannot be nil".into())
                             })?;
-                        let base: u32 = subnet.network().into();
-                        let addresses = addresses.get_or_insert_with(Vec::new);
-                        for i in 1..(((1 << (32 - subnet.prefixlen)) - 1) - 1) {
-                            addresses.push((base + i).into())
-                        }
+                        addresses
+                            .get_or_insert_with(PoolAddresses::new)
+                            .extend(&subnet_addresses(&subnet));
                     }
                     Some(x) if x.starts_with("apply-") => {
                         let name = &x[6..];
@@ -504,11 +1058,9 @@ impl Config {
             }
             /* If this Policy overrides addresses, then remove any addresses that are reserved for
              * sub policies */
-            if let Some(mut addrvec) = addresses {
-                let mut addrset: std::collections::HashSet<std::net::Ipv4Addr> = Default::default();
-                addrset.extend(addrvec.drain(..));
+            if let Some(mut addrset) = addresses {
                 for p in &policy.policies {
-                    addrset = addrset.sub(&p.get_all_used_addresses());
+                    addrset = addrset.difference(&p.get_all_used_addresses());
                 }
                 policy.apply_address = Some(addrset);
             }
@@ -535,6 +1087,7 @@ impl Config {
     pub fn new(y: &yaml::Yaml) -> Result<Option<Self>, Error> {
         Ok(Some(Config {
             policies: Config::parse_policies(y)?,
+            ..Default::default()
         }))
     }
 }
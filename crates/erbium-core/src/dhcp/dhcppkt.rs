@@ -96,6 +96,9 @@ impl fmt::Debug for HwType {
     }
 }
 
+/* RFC2131 Figure 2: The leftmost bit of the flags field is the BROADCAST flag. */
+pub const FLAG_BROADCAST: u16 = 0x8000;
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct MessageType(u8);
 pub const DHCPDISCOVER: MessageType = MessageType(1);
@@ -184,6 +187,7 @@ pub const OPTION_XWFONTSRVS: DhcpOption = DhcpOption(48);
 pub const OPTION_XWDISPLAY: DhcpOption = DhcpOption(49);
 pub const OPTION_ADDRESSREQUEST: DhcpOption = DhcpOption(50);
 pub const OPTION_LEASETIME: DhcpOption = DhcpOption(51);
+pub const OPTION_OVERLOAD: DhcpOption = DhcpOption(52);
 pub const OPTION_MSGTYPE: DhcpOption = DhcpOption(53);
 pub const OPTION_SERVERID: DhcpOption = DhcpOption(54);
 pub const OPTION_PARAMLIST: DhcpOption = DhcpOption(55);
@@ -195,6 +199,8 @@ pub const OPTION_VENDOR_CLASS: DhcpOption = DhcpOption(60);
 pub const OPTION_CLIENTID: DhcpOption = DhcpOption(61);
 pub const OPTION_NIS3DOMAIN: DhcpOption = DhcpOption(64);
 pub const OPTION_NIS3SERVERS: DhcpOption = DhcpOption(65);
+pub const OPTION_TFTPSERVER: DhcpOption = DhcpOption(66);
+pub const OPTION_BOOTFILE: DhcpOption = DhcpOption(67);
 pub const OPTION_HOMEAGENT: DhcpOption = DhcpOption(68);
 pub const OPTION_SMTP: DhcpOption = DhcpOption(69);
 pub const OPTION_POP3: DhcpOption = DhcpOption(70);
@@ -206,11 +212,13 @@ pub const OPTION_STREETTALK: DhcpOption = DhcpOption(75);
 pub const OPTION_STDA: DhcpOption = DhcpOption(76);
 pub const OPTION_USERCLASS: DhcpOption = DhcpOption(77); /* RFC3004 */
 pub const OPTION_FQDN: DhcpOption = DhcpOption(81); /* RFC4702 */
+pub const OPTION_RELAYINFO: DhcpOption = DhcpOption(82); /* RFC3046 */
+pub const OPTION_CLIENTARCH: DhcpOption = DhcpOption(93); /* RFC4578 */
 pub const OPTION_UUID: DhcpOption = DhcpOption(97); /* RFC4578 */
 pub const OPTION_PCODE: DhcpOption = DhcpOption(100); /* RFC4833 */
 pub const OPTION_TCODE: DhcpOption = DhcpOption(101); /* RFC4833 */
 pub const OPTION_AUTOCONF: DhcpOption = DhcpOption(103);
-pub const OPTION_SUBNETSELECT: DhcpOption = DhcpOption(104);
+pub const OPTION_SUBNETSELECT: DhcpOption = DhcpOption(118); /* RFC3011 */
 pub const OPTION_DOMAINSEARCH: DhcpOption = DhcpOption(119);
 pub const OPTION_SIPSERVERS: DhcpOption = DhcpOption(120);
 pub const OPTION_CIDRROUTE: DhcpOption = DhcpOption(121);
@@ -319,9 +327,9 @@ const OPT_INFO: &[(&str, DhcpOption, DhcpOptionType)] = &[
     (
         "renewal-time",
         OPTION_RENEWALTIME,
-        DhcpOptionType::Seconds16,
-    ), // seconds
-    ("rebind-time", OPTION_REBINDTIME, DhcpOptionType::Seconds16), // seconds
+        DhcpOptionType::Seconds32,
+    ),
+    ("rebind-time", OPTION_REBINDTIME, DhcpOptionType::Seconds32),
     // 60
     ("class-id", OPTION_VENDOR_CLASS, DhcpOptionType::String),
     ("client-id", OPTION_CLIENTID, DhcpOptionType::HwAddr),
@@ -333,8 +341,12 @@ const OPT_INFO: &[(&str, DhcpOption, DhcpOptionType)] = &[
         OPTION_NIS3SERVERS,
         DhcpOptionType::IpList,
     ),
-    //("tftp-server",  OPTION_TFTPSERVER, DhcpOptionType::String), handled specially.
-    //bootfile name, handled specially.
+    (
+        "tftp-server-name",
+        OPTION_TFTPSERVER,
+        DhcpOptionType::String,
+    ),
+    ("bootfile-name", OPTION_BOOTFILE, DhcpOptionType::String),
     (
         "home-agent-servers",
         OPTION_HOMEAGENT,
@@ -359,7 +371,7 @@ const OPT_INFO: &[(&str, DhcpOption, DhcpOptionType)] = &[
     // 80
     //("rapid-commit", OPTION_RAPID_COMMIT
     ("fqdn", OPTION_FQDN, DhcpOptionType::String),
-    // option 82 (relay agent information) needs special handling.
+    // option 82 (relay agent information) is parsed by RelayAgentInformation.
     // iSNS
     // NDS Servers
     // NDS Tree
@@ -370,7 +382,7 @@ const OPT_INFO: &[(&str, DhcpOption, DhcpOptionType)] = &[
     // Authentication, needs special handling
     // client-last-transaction-time, RFC4388
     // associated-ip, RFC4388
-    // client-system, RFC4578
+    ("client-arch", OPTION_CLIENTARCH, DhcpOptionType::U16), // RFC4578
     // client-ndi, RFC4578
     // ldap, RFC3679
     //
@@ -382,7 +394,7 @@ const OPT_INFO: &[(&str, DhcpOption, DhcpOptionType)] = &[
     ("tz-name", OPTION_TCODE, DhcpOptionType::String),
     // uuid/guid
     ("autoconfig", OPTION_AUTOCONF, DhcpOptionType::Bool),
-    ("subnet-selection", OPTION_SUBNETSELECT, DhcpOptionType::Ip), // RFC3011 -- needs better support
+    ("subnet-selection", OPTION_SUBNETSELECT, DhcpOptionType::Ip), // RFC3011
     (
         "dns-searches",
         OPTION_DOMAINSEARCH,
@@ -753,6 +765,49 @@ impl DhcpParse for String {
     }
 }
 
+/* RFC3046 Section 2.0: Relay Agent Information sub-options */
+pub const RELAYINFO_CIRCUITID: u8 = 1;
+pub const RELAYINFO_REMOTEID: u8 = 2;
+pub const RELAYINFO_LINKSELECTION: u8 = 5; /* RFC3527 */
+pub const RELAYINFO_SUBSCRIBERID: u8 = 6; /* RFC3993 */
+
+/// The sub-options that a relay agent adds in option 82.
+#[derive(Debug, Clone, PartialEq, Default, Eq)]
+pub struct RelayAgentInformation {
+    pub circuit_id: Option<Vec<u8>>,
+    pub remote_id: Option<Vec<u8>>,
+    pub link_selection: Option<std::net::Ipv4Addr>,
+    pub subscriber_id: Option<String>,
+    pub other: collections::HashMap<u8, Vec<u8>>,
+}
+
+impl DhcpParse for RelayAgentInformation {
+    type Item = Self;
+    fn parse_into(v: &[u8]) -> Option<Self> {
+        let mut ret = Self::default();
+        let mut it = v.iter().copied();
+        while let Some(subopt) = it.next() {
+            let len = it.next()? as usize;
+            let value = it.by_ref().take(len).collect::<Vec<u8>>();
+            if value.len() != len {
+                return None;
+            }
+            match subopt {
+                RELAYINFO_CIRCUITID => ret.circuit_id = Some(value),
+                RELAYINFO_REMOTEID => ret.remote_id = Some(value),
+                RELAYINFO_LINKSELECTION => {
+                    ret.link_selection = Some(std::net::Ipv4Addr::parse_into(&value)?)
+                }
+                RELAYINFO_SUBSCRIBERID => ret.subscriber_id = String::parse_into(&value),
+                _ => {
+                    ret.other.insert(subopt, value);
+                }
+            }
+        }
+        Some(ret)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default, Eq)]
 pub struct DhcpOptions {
     pub other: collections::HashMap<DhcpOption, Vec<u8>>,
@@ -787,6 +842,22 @@ impl DhcpOptions {
         self.get_option::<String>(&OPTION_HOSTNAME)
     }
 
+    pub fn get_relay_agent_info(&self) -> Option<RelayAgentInformation> {
+        self.get_option::<RelayAgentInformation>(&OPTION_RELAYINFO)
+    }
+
+    /// Returns the largest DHCP message that the client will accept.
+    ///
+    /// RFC2132 Section 9.10: The maximum message size includes the IP and UDP headers, and the
+    /// minimum legal value is 576 octets.
+    pub fn get_max_message_size(&self) -> usize {
+        let size = self
+            .get_option::<u16>(&OPTION_MAXMSGSIZE)
+            .map(usize::from)
+            .unwrap_or(0);
+        std::cmp::max(size, 576) - 28
+    }
+
     #[must_use]
     pub fn set_raw_option(mut self, option: &DhcpOption, value: &[u8]) -> Self {
         self.other.insert(*option, value.to_vec());
@@ -898,8 +969,15 @@ fn null_terminated(mut v: Vec<u8>) -> Vec<u8> {
     v
 }
 
-pub fn parse_options(mut buf: pktparser::Buffer) -> Result<DhcpOptions, ParseError> {
-    let mut raw_options: collections::HashMap<DhcpOption, Vec<u8>> = collections::HashMap::new();
+/* RFC2132 Section 9.3: Values for the Option Overload option */
+const OVERLOAD_FILE: u8 = 1;
+const OVERLOAD_SNAME: u8 = 2;
+
+fn parse_options_into(
+    mut buf: pktparser::Buffer,
+    raw_options: &mut collections::HashMap<DhcpOption, Vec<u8>>,
+    require_end: bool,
+) -> Result<(), ParseError> {
     loop {
         match buf.get_u8() {
             Some(0) => (),      /* Pad byte */
@@ -914,10 +992,17 @@ pub fn parse_options(mut buf: pktparser::Buffer) -> Result<DhcpOptions, ParseErr
                             .ok_or(ParseError::UnexpectedEndOfInput)?,
                     );
             }
+            /* Overloaded sname and file fields may be filled with options right to the end */
+            None if !require_end => break,
             None => return Err(ParseError::UnexpectedEndOfInput),
         }
     }
+    Ok(())
+}
 
+pub fn parse_options(buf: pktparser::Buffer) -> Result<DhcpOptions, ParseError> {
+    let mut raw_options: collections::HashMap<DhcpOption, Vec<u8>> = collections::HashMap::new();
+    parse_options_into(buf, &mut raw_options, true)?;
     Ok(DhcpOptions { other: raw_options })
 }
 
@@ -938,13 +1023,31 @@ pub fn parse(pkt: &[u8]) -> Result<Dhcp, ParseError> {
     if hlen as usize > chaddr.len() {
         return Err(ParseError::InvalidPacket);
     }
-    let sname = null_terminated(buf.get_vec(64).ok_or(ParseError::UnexpectedEndOfInput)?);
-    let file = null_terminated(buf.get_vec(128).ok_or(ParseError::UnexpectedEndOfInput)?);
+    let mut sname = buf.get_vec(64).ok_or(ParseError::UnexpectedEndOfInput)?;
+    let mut file = buf.get_vec(128).ok_or(ParseError::UnexpectedEndOfInput)?;
     let magic = buf.get_be32().ok_or(ParseError::UnexpectedEndOfInput)?;
     if magic != 0x6382_5363 {
         return Err(ParseError::WrongMagic);
     }
-    let options = parse_options(buf)?;
+    let mut options = parse_options(buf)?;
+
+    /* RFC2131 Section 4.1: If the options in a DHCP message extend into the 'sname' and 'file'
+     * fields, the 'option overload' option MUST appear in the 'options' field.  RFC3396 says the
+     * options field is followed by file, and then sname when concatenating.
+     */
+    if let Some(overload) = options.other.remove(&OPTION_OVERLOAD) {
+        let overload = overload.first().copied().unwrap_or(0);
+        if overload & OVERLOAD_FILE != 0 {
+            parse_options_into(pktparser::Buffer::new(&file), &mut options.other, false)?;
+            file.clear();
+        }
+        if overload & OVERLOAD_SNAME != 0 {
+            parse_options_into(pktparser::Buffer::new(&sname), &mut options.other, false)?;
+            sname.clear();
+        }
+    }
+    let sname = null_terminated(sname);
+    let file = null_terminated(file);
 
     Ok(Dhcp {
         op: DhcpOp(op),
@@ -1045,14 +1148,16 @@ impl Serialise for DhcpOptionTypeValue {
     }
 }
 
-fn serialise_option<T>(option: DhcpOption, bytes: &[T], v: &mut Vec<u8>)
-where
-    T: Serialise,
-{
-    option.serialise(v);
-    (bytes.len() as u8).serialise(v);
-    for i in bytes.iter() {
-        i.serialise(v);
+fn serialise_option(option: DhcpOption, bytes: &[u8], v: &mut Vec<u8>) {
+    if bytes.is_empty() {
+        option.serialise(v);
+        0_u8.serialise(v);
+    }
+    /* RFC3396: Options longer than 255 bytes are split into multiple options */
+    for chunk in bytes.chunks(255) {
+        option.serialise(v);
+        (chunk.len() as u8).serialise(v);
+        chunk.serialise(v);
     }
 }
 
@@ -1076,8 +1181,113 @@ fn serialise_fixed(out: &[u8], l: usize, v: &mut Vec<u8>) {
     }
 }
 
+/* The fixed header, including the magic cookie, before the options start. */
+const DHCP_HEADER_SIZE: usize = 240;
+const SNAME_SIZE: usize = 64;
+const FILE_SIZE: usize = 128;
+
+/* Where each option ends up in a serialised packet. */
+#[derive(Default)]
+struct OptionAreas {
+    options: Vec<u8>,
+    file: Option<Vec<u8>>,
+    sname: Option<Vec<u8>>,
+}
+
+impl OptionAreas {
+    /* RFC2131 Section 4.1: The server MUST NOT send messages larger than the client is willing
+     * to accept.  When the options don't fit, then the 'file' and 'sname' fields are used to
+     * carry options too, if they're not being used for anything else.
+     */
+    fn new(options: &DhcpOptions, file_free: bool, sname_free: bool, max_size: usize) -> Self {
+        let mut encoded = options
+            .other
+            .iter()
+            .map(|(o, p)| {
+                let mut v = vec![];
+                serialise_option(*o, p, &mut v);
+                (*o, v)
+            })
+            .collect::<Vec<_>>();
+        /* The message type and server id must always be in the options field, so put them first.
+         * After that, placing the largest options first packs them in best.
+         */
+        encoded.sort_by_key(|(o, v)| {
+            (
+                *o != OPTION_MSGTYPE && *o != OPTION_SERVERID,
+                std::cmp::Reverse(v.len()),
+                o.0,
+            )
+        });
+
+        let mut ret = Self::default();
+        let total: usize = encoded.iter().map(|(_, v)| v.len()).sum();
+        let space = max_size.saturating_sub(DHCP_HEADER_SIZE);
+        if total < space || !(file_free || sname_free) {
+            for (_, v) in encoded {
+                ret.options.extend(v);
+            }
+            return ret;
+        }
+
+        /* Leave room for the overload option and the end marker */
+        let space = space.saturating_sub(3 + 1);
+        let mut file = vec![];
+        let mut sname = vec![];
+        for (_, v) in encoded {
+            if ret.options.len() + v.len() <= space {
+                ret.options.extend(v);
+            } else if file_free && file.len() + v.len() < FILE_SIZE {
+                file.extend(v);
+            } else if sname_free && sname.len() + v.len() < SNAME_SIZE {
+                sname.extend(v);
+            } else {
+                /* Send it anyway, and hope that the client copes */
+                ret.options.extend(v);
+            }
+        }
+        let mut overload = 0;
+        if !file.is_empty() {
+            overload |= OVERLOAD_FILE;
+            file.push(255);
+            ret.file = Some(file);
+        }
+        if !sname.is_empty() {
+            overload |= OVERLOAD_SNAME;
+            sname.push(255);
+            ret.sname = Some(sname);
+        }
+        if overload != 0 {
+            serialise_option(OPTION_OVERLOAD, &[overload], &mut ret.options);
+        }
+        ret
+    }
+}
+
 impl Dhcp {
     pub fn serialise(&self) -> Vec<u8> {
+        self.serialise_max(usize::MAX)
+    }
+
+    /// Serialises the packet so that it fits in `max_size` bytes if possible.
+    ///
+    /// Names that are too long for the 'sname' and 'file' fields are sent as options instead,
+    /// and options that don't fit are moved into the 'sname' and 'file' fields using option
+    /// overload.
+    pub fn serialise_max(&self, max_size: usize) -> Vec<u8> {
+        let mut options = self.options.clone();
+        let mut sname = self.sname.as_slice();
+        let mut file = self.file.as_slice();
+        if sname.len() > SNAME_SIZE {
+            options = options.set_raw_option(&OPTION_TFTPSERVER, sname);
+            sname = &[];
+        }
+        if file.len() > FILE_SIZE {
+            options = options.set_raw_option(&OPTION_BOOTFILE, file);
+            file = &[];
+        }
+        let areas = OptionAreas::new(&options, file.is_empty(), sname.is_empty(), max_size);
+
         let mut v: Vec<u8> = Vec::new();
         self.op.0.serialise(&mut v);
         self.htype.0.serialise(&mut v);
@@ -1092,13 +1302,15 @@ impl Dhcp {
         self.giaddr.serialise(&mut v);
 
         serialise_fixed(&self.chaddr, 16, &mut v);
-        serialise_fixed(&self.sname, 64, &mut v);
-        serialise_fixed(&self.file, 128, &mut v);
+        serialise_fixed(areas.sname.as_deref().unwrap_or(sname), SNAME_SIZE, &mut v);
+        serialise_fixed(areas.file.as_deref().unwrap_or(file), FILE_SIZE, &mut v);
 
         /* DHCP Magic */
         0x6382_5363_u32.serialise(&mut v);
 
-        self.options.serialise(&mut v);
+        v.extend(areas.options);
+        /* Add end of options marker */
+        (255_u8).serialise(&mut v);
 
         v
     }
@@ -1252,3 +1464,23 @@ fn test_parse() {
         "192.0.2.0/24->192.0.2.254,198.51.100.0/24->192.0.2.254"
     );
 }
+
+#[test]
+fn test_relay_agent_info() {
+    let info = RelayAgentInformation::parse_into(&[
+        1, 7, b'G', b'i', b'1', b'/', b'0', b'/', b'1', /* Circuit ID */
+        2, 3, 0x01, 0x02, 0x03, /* Remote ID */
+        5, 4, 192, 0, 2, 0, /* Link Selection */
+        6, 3, b's', b'u', b'b', /* Subscriber ID */
+        9, 1, 0xff, /* Vendor Specific, not parsed */
+    ])
+    .expect("Failed to parse relay agent information");
+    assert_eq!(info.circuit_id, Some(b"Gi1/0/1".to_vec()));
+    assert_eq!(info.remote_id, Some(vec![0x01, 0x02, 0x03]));
+    assert_eq!(info.link_selection, Some("192.0.2.0".parse().unwrap()));
+    assert_eq!(info.subscriber_id, Some("sub".into()));
+    assert_eq!(info.other.get(&9), Some(&vec![0xff]));
+
+    /* Truncated sub-option */
+    assert_eq!(RelayAgentInformation::parse_into(&[1, 7, b'G']), None);
+}
@@ -0,0 +1,938 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  DHCP failover between a pair of erbium servers.
+ *
+ *  The primary connects to the secondary over TCP.  Each message is a length delimited frame,
+ *  followed by a HMAC-SHA256 over the message.  The first message in each direction is a hello
+ *  containing a random nonce, authenticated with the shared secret.  After that, messages are
+ *  authenticated with a key derived from the secret and both nonces, and include a sequence
+ *  number, so they can't be replayed.
+ *
+ *  After the hellos, each side sends every lease it knows about, then a sync done message.  After
+ *  that, each change to a lease is sent as it happens.  Heartbeats are sent so that a dead peer is
+ *  noticed.
+ *
+ *  Each address is owned by one of the servers: in active-standby mode the primary owns them all,
+ *  and in split-scope mode they are divided up based on the split.  A server only hands out new
+ *  addresses that it owns, so the two servers can never hand out the same address, even if they
+ *  can't talk to each other.  While a server can't tell its peer about leases, it keeps them to at
+ *  most max-client-lead-time.  If the peer has been unreachable for partner-down-after, it is
+ *  assumed to be down, and once max-client-lead-time has passed (so any leases that the peer
+ *  handed out that we don't know about have expired) its addresses can be used too.
+ */
+
+use super::config::{FailoverConfig, FailoverMode, FailoverRole};
+use super::pool;
+use super::worker::PoolWorker;
+use crate::pktparser::Buffer;
+use crypto::mac::Mac as _;
+use futures::{SinkExt as _, StreamExt as _};
+use tokio::sync;
+
+const PROTOCOL_VERSION: u8 = 1;
+const NONCE_SIZE: usize = 16;
+const MAC_SIZE: usize = 32;
+const MAX_MESSAGE_SIZE: usize = 65536;
+
+const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const HEARTBEAT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+const RECONNECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+const MSG_HELLO: u8 = 1;
+const MSG_UPDATE: u8 = 2;
+const MSG_SYNC: u8 = 3;
+const MSG_SYNC_DONE: u8 = 4;
+const MSG_HEARTBEAT: u8 = 5;
+
+lazy_static::lazy_static! {
+    static ref DHCP_FAILOVER_STATE: prometheus::IntGaugeVec =
+        prometheus::register_int_gauge_vec!("dhcp_failover_state",
+            "The current DHCP failover state",
+            &["state"])
+        .unwrap();
+    static ref DHCP_FAILOVER_UPDATES: prometheus::IntCounterVec =
+        prometheus::register_int_counter_vec!("dhcp_failover_updates",
+            "Number of leases exchanged with the DHCP failover peer",
+            &["direction"])
+        .unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// We've just started, and don't know what the peer handed out while we weren't running, so
+    /// no new addresses can be handed out until we've synchronised or max-client-lead-time has
+    /// passed.
+    Recover,
+    /// We have all of the peer's leases, and the peer is being told about all of ours.
+    Normal,
+    /// We can't talk to the peer, but it may still be handing out addresses.
+    CommunicationsInterrupted,
+    /// The peer is assumed to be down, so we can take over its addresses.
+    PartnerDown,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Recover => write!(f, "recover"),
+            State::Normal => write!(f, "normal"),
+            State::CommunicationsInterrupted => write!(f, "communications-interrupted"),
+            State::PartnerDown => write!(f, "partner-down"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ListenError(std::io::Error),
+    Io(std::io::Error),
+    Closed,
+    Timeout,
+    BadMac,
+    ProtocolError(String),
+    ConfigMismatch(String),
+    PoolError(pool::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Error::*;
+        match self {
+            ListenError(e) => write!(f, "Failed to listen for DHCP failover peer: {}", e),
+            Io(e) => write!(f, "{}", e),
+            Closed => write!(f, "Connection closed by peer"),
+            Timeout => write!(f, "Timed out waiting for peer"),
+            BadMac => write!(f, "Message failed authentication, check the secret matches"),
+            ProtocolError(msg) => write!(f, "Protocol error: {}", msg),
+            ConfigMismatch(msg) => write!(f, "Peer configuration doesn't match ours: {}", msg),
+            PoolError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Message {
+    Hello {
+        role: FailoverRole,
+        mode: FailoverMode,
+        nonce: [u8; NONCE_SIZE],
+    },
+    /// A lease that has changed.
+    Update(pool::LeaseInfo),
+    /// One of our leases, sent when first connecting.
+    Sync(pool::LeaseInfo),
+    SyncDone,
+    Heartbeat,
+}
+
+fn role_to_u8(role: FailoverRole) -> u8 {
+    match role {
+        FailoverRole::Primary => 0,
+        FailoverRole::Secondary => 1,
+    }
+}
+
+fn mode_to_u8(mode: FailoverMode) -> u8 {
+    match mode {
+        FailoverMode::ActiveStandby => 0,
+        FailoverMode::SplitScope => 1,
+    }
+}
+
+fn put_lease(v: &mut Vec<u8>, lease: &pool::LeaseInfo) {
+    v.extend(lease.ip.octets());
+    v.extend(lease.start.to_be_bytes());
+    v.extend(lease.expire.to_be_bytes());
+    v.extend((lease.client_id.len() as u16).to_be_bytes());
+    v.extend(&lease.client_id);
+    v.extend((lease.options.len() as u16).to_be_bytes());
+    v.extend(&lease.options);
+}
+
+fn get_lease(buf: &mut Buffer) -> Option<pool::LeaseInfo> {
+    let ip = buf.get_ipv4()?;
+    let start = buf.get_be32()?;
+    let expire = buf.get_be32()?;
+    let len = buf.get_be16()?.into();
+    let client_id = buf.get_vec(len)?;
+    let len = buf.get_be16()?.into();
+    let options = buf.get_vec(len)?;
+    Some(pool::LeaseInfo {
+        ip,
+        client_id,
+        start,
+        expire,
+        options,
+    })
+}
+
+impl Message {
+    fn serialise(&self) -> Vec<u8> {
+        let mut v = vec![];
+        match self {
+            Message::Hello { role, mode, nonce } => {
+                v.push(MSG_HELLO);
+                v.push(PROTOCOL_VERSION);
+                v.push(role_to_u8(*role));
+                v.push(mode_to_u8(*mode));
+                v.extend(nonce);
+            }
+            Message::Update(lease) => {
+                v.push(MSG_UPDATE);
+                put_lease(&mut v, lease);
+            }
+            Message::Sync(lease) => {
+                v.push(MSG_SYNC);
+                put_lease(&mut v, lease);
+            }
+            Message::SyncDone => v.push(MSG_SYNC_DONE),
+            Message::Heartbeat => v.push(MSG_HEARTBEAT),
+        }
+        v
+    }
+
+    fn parse(pkt: &[u8]) -> Result<Message, Error> {
+        let truncated = || Error::ProtocolError("Truncated message".into());
+        let mut buf = Buffer::new(pkt);
+        let msg = match buf.get_u8().ok_or_else(truncated)? {
+            MSG_HELLO => {
+                let version = buf.get_u8().ok_or_else(truncated)?;
+                if version != PROTOCOL_VERSION {
+                    return Err(Error::ProtocolError(format!(
+                        "Unsupported protocol version {}",
+                        version
+                    )));
+                }
+                let role = match buf.get_u8().ok_or_else(truncated)? {
+                    0 => FailoverRole::Primary,
+                    1 => FailoverRole::Secondary,
+                    r => return Err(Error::ProtocolError(format!("Unknown role {}", r))),
+                };
+                let mode = match buf.get_u8().ok_or_else(truncated)? {
+                    0 => FailoverMode::ActiveStandby,
+                    1 => FailoverMode::SplitScope,
+                    m => return Err(Error::ProtocolError(format!("Unknown mode {}", m))),
+                };
+                let mut nonce = [0; NONCE_SIZE];
+                nonce.copy_from_slice(buf.get_bytes(NONCE_SIZE).ok_or_else(truncated)?);
+                Message::Hello { role, mode, nonce }
+            }
+            MSG_UPDATE => Message::Update(get_lease(&mut buf).ok_or_else(truncated)?),
+            MSG_SYNC => Message::Sync(get_lease(&mut buf).ok_or_else(truncated)?),
+            MSG_SYNC_DONE => Message::SyncDone,
+            MSG_HEARTBEAT => Message::Heartbeat,
+            t => return Err(Error::ProtocolError(format!("Unknown message type {}", t))),
+        };
+        if buf.remaining() != 0 {
+            return Err(Error::ProtocolError("Trailing data after message".into()));
+        }
+        Ok(msg)
+    }
+}
+
+fn calculate_mac(
+    key: &[u8],
+    sender: FailoverRole,
+    seq: u64,
+    payload: &[u8],
+) -> crypto::mac::MacResult {
+    let mut hmac = crypto::hmac::Hmac::new(crypto::sha2::Sha256::new(), key);
+    hmac.input(&[role_to_u8(sender)]);
+    hmac.input(&seq.to_be_bytes());
+    hmac.input(payload);
+    hmac.result()
+}
+
+/// Authenticates messages sent and received on one connection.
+struct Session {
+    key: Vec<u8>,
+    role: FailoverRole,
+    sent: u64,
+    received: u64,
+}
+
+impl Session {
+    fn new(key: &[u8], role: FailoverRole) -> Self {
+        Session {
+            key: key.to_vec(),
+            role,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    fn seal(&mut self, msg: &Message) -> bytes::Bytes {
+        self.sent += 1;
+        let mut frame = msg.serialise();
+        let mac = calculate_mac(&self.key, self.role, self.sent, &frame);
+        frame.extend(mac.code());
+        frame.into()
+    }
+
+    fn open(&mut self, frame: &[u8]) -> Result<Message, Error> {
+        if frame.len() < MAC_SIZE {
+            return Err(Error::ProtocolError("Truncated message".into()));
+        }
+        let (payload, mac) = frame.split_at(frame.len() - MAC_SIZE);
+        let peer = match self.role {
+            FailoverRole::Primary => FailoverRole::Secondary,
+            FailoverRole::Secondary => FailoverRole::Primary,
+        };
+        self.received += 1;
+        /* MacResult compares in constant time */
+        if calculate_mac(&self.key, peer, self.received, payload)
+            != crypto::mac::MacResult::new(mac)
+        {
+            return Err(Error::BadMac);
+        }
+        Message::parse(payload)
+    }
+}
+
+type Connection =
+    tokio_util::codec::Framed<tokio::net::TcpStream, tokio_util::codec::LengthDelimitedCodec>;
+
+struct Status {
+    /* True if we have received all of the peer's leases on the current connection */
+    synced: bool,
+    /* True once we have received all of the peer's leases at least once since starting */
+    recovered: bool,
+    started: std::time::Instant,
+    last_contact: std::time::Instant,
+}
+
+pub struct Failover {
+    conf: FailoverConfig,
+    status: std::sync::Mutex<Status>,
+    updates: sync::mpsc::UnboundedSender<pool::LeaseInfo>,
+    updates_rx: sync::Mutex<sync::mpsc::UnboundedReceiver<pool::LeaseInfo>>,
+}
+
+impl Failover {
+    pub fn new(conf: FailoverConfig) -> Self {
+        let (updates, updates_rx) = sync::mpsc::unbounded_channel();
+        Failover {
+            conf,
+            /* We don't know what the peer did while we weren't running, so start off as if we've
+             * just lost contact with it.
+             */
+            status: std::sync::Mutex::new(Status {
+                synced: false,
+                recovered: false,
+                started: std::time::Instant::now(),
+                last_contact: std::time::Instant::now(),
+            }),
+            updates,
+            updates_rx: sync::Mutex::new(updates_rx),
+        }
+    }
+
+    fn state_at(&self, now: std::time::Instant) -> State {
+        let status = self.status.lock().unwrap();
+        if status.synced {
+            State::Normal
+        } else if !status.recovered
+            && now.saturating_duration_since(status.started) < self.conf.max_client_lead_time
+        {
+            /* The peer may have leased any address while we were down, including our own */
+            State::Recover
+        } else if matches!(self.conf.partner_down_after,
+            Some(after) if now.saturating_duration_since(status.last_contact) >= after)
+        {
+            State::PartnerDown
+        } else {
+            State::CommunicationsInterrupted
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state_at(std::time::Instant::now())
+    }
+
+    fn owner(&self, addr: std::net::Ipv4Addr) -> FailoverRole {
+        match self.conf.mode {
+            FailoverMode::ActiveStandby => FailoverRole::Primary,
+            FailoverMode::SplitScope if u32::from(addr) % 100 < self.conf.split.into() => {
+                FailoverRole::Primary
+            }
+            FailoverMode::SplitScope => FailoverRole::Secondary,
+        }
+    }
+
+    fn may_allocate_at(&self, addr: std::net::Ipv4Addr, now: std::time::Instant) -> bool {
+        match self.state_at(now) {
+            State::Recover => false,
+            state => {
+                self.owner(addr) == self.conf.role
+                    || (state == State::PartnerDown
+                        && now.saturating_duration_since(self.status.lock().unwrap().last_contact)
+                            >= self.conf.max_client_lead_time)
+            }
+        }
+    }
+
+    /// Returns true if we can hand out `addr` to a client that doesn't already have a lease on it.
+    pub fn may_allocate(&self, addr: std::net::Ipv4Addr) -> bool {
+        self.may_allocate_at(addr, std::time::Instant::now())
+    }
+
+    /// Returns false if we should ignore DHCP clients, and leave them to the peer.
+    pub fn is_serving(&self) -> bool {
+        match (self.conf.mode, self.conf.role) {
+            (FailoverMode::ActiveStandby, FailoverRole::Secondary) => {
+                self.state() == State::PartnerDown
+            }
+            _ => true,
+        }
+    }
+
+    /// The longest lease that can currently be handed out.
+    pub fn max_lease(&self) -> Option<std::time::Duration> {
+        match self.state() {
+            State::Normal => None,
+            _ => Some(self.conf.max_client_lead_time),
+        }
+    }
+
+    /// Queues a lease that has changed to be sent to the peer.
+    pub fn lease_updated(&self, lease: pool::LeaseInfo) {
+        /* If the receiver has gone away, then there's nobody to tell anyway */
+        let _ = self.updates.send(lease);
+    }
+
+    fn contact(&self) {
+        self.status.lock().unwrap().last_contact = std::time::Instant::now();
+    }
+
+    async fn send(
+        conn: &mut Connection,
+        session: &mut Session,
+        msg: &Message,
+    ) -> Result<(), Error> {
+        conn.send(session.seal(msg)).await.map_err(Error::Io)
+    }
+
+    async fn recv(conn: &mut Connection, session: &mut Session) -> Result<Message, Error> {
+        match conn.next().await {
+            Some(Ok(frame)) => session.open(&frame),
+            Some(Err(e)) => Err(Error::Io(e)),
+            None => Err(Error::Closed),
+        }
+    }
+
+    async fn handshake(&self, conn: &mut Connection) -> Result<Session, Error> {
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let mut hello = Session::new(self.conf.secret.as_bytes(), self.conf.role);
+        Self::send(
+            conn,
+            &mut hello,
+            &Message::Hello {
+                role: self.conf.role,
+                mode: self.conf.mode,
+                nonce,
+            },
+        )
+        .await?;
+        let peer_nonce = match tokio::time::timeout(HEARTBEAT_TIMEOUT, Self::recv(conn, &mut hello))
+            .await
+            .map_err(|_| Error::Timeout)??
+        {
+            Message::Hello { role, .. } if role == self.conf.role => {
+                return Err(Error::ConfigMismatch(format!(
+                    "Both servers are {:?}",
+                    role
+                )))
+            }
+            Message::Hello { mode, .. } if mode != self.conf.mode => {
+                return Err(Error::ConfigMismatch(format!(
+                    "Peer is using {:?}, but we are using {:?}",
+                    mode, self.conf.mode
+                )))
+            }
+            Message::Hello { nonce, .. } => nonce,
+            msg => {
+                return Err(Error::ProtocolError(format!(
+                    "Expected hello, got {:?}",
+                    msg
+                )))
+            }
+        };
+        let (primary_nonce, secondary_nonce) = match self.conf.role {
+            FailoverRole::Primary => (nonce, peer_nonce),
+            FailoverRole::Secondary => (peer_nonce, nonce),
+        };
+        let mut hmac =
+            crypto::hmac::Hmac::new(crypto::sha2::Sha256::new(), self.conf.secret.as_bytes());
+        hmac.input(&primary_nonce);
+        hmac.input(&secondary_nonce);
+        Ok(Session::new(hmac.result().code(), self.conf.role))
+    }
+
+    async fn handle_message(&self, pool: &PoolWorker, msg: Message) -> Result<(), Error> {
+        self.contact();
+        match msg {
+            Message::Update(lease) => {
+                DHCP_FAILOVER_UPDATES.with_label_values(&["received"]).inc();
+                pool.call(move |pool| pool.update_lease(&lease))
+                    .await
+                    .map_err(Error::PoolError)
+            }
+            Message::Sync(lease) => {
+                DHCP_FAILOVER_UPDATES.with_label_values(&["received"]).inc();
+                pool.call(move |pool| pool.merge_lease(&lease))
+                    .await
+                    .map_err(Error::PoolError)
+            }
+            Message::SyncDone => {
+                log::info!("Synchronised leases from DHCP failover peer");
+                let mut status = self.status.lock().unwrap();
+                status.synced = true;
+                status.recovered = true;
+                Ok(())
+            }
+            Message::Heartbeat => Ok(()),
+            Message::Hello { .. } => Err(Error::ProtocolError("Unexpected hello".into())),
+        }
+    }
+
+    async fn run_session(
+        &self,
+        stream: tokio::net::TcpStream,
+        pool: &PoolWorker,
+    ) -> Result<(), Error> {
+        let mut conn = tokio_util::codec::LengthDelimitedCodec::builder()
+            .max_frame_length(MAX_MESSAGE_SIZE)
+            .new_framed(stream);
+        let mut session = self.handshake(&mut conn).await?;
+        self.contact();
+
+        /* Anything that was queued while we were disconnected is included in the sync below */
+        let mut updates = self.updates_rx.lock().await;
+        while updates.try_recv().is_ok() {}
+        let leases = pool
+            .call(|pool| pool.get_leases())
+            .await
+            .map_err(Error::PoolError)?;
+        for lease in leases {
+            Self::send(&mut conn, &mut session, &Message::Sync(lease)).await?;
+            DHCP_FAILOVER_UPDATES.with_label_values(&["sent"]).inc();
+        }
+        Self::send(&mut conn, &mut session, &Message::SyncDone).await?;
+
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut last_received = tokio::time::Instant::now();
+        loop {
+            tokio::select! {
+                msg = Self::recv(&mut conn, &mut session) => {
+                    last_received = tokio::time::Instant::now();
+                    self.handle_message(pool, msg?).await?;
+                }
+                Some(lease) = updates.recv() => {
+                    Self::send(&mut conn, &mut session, &Message::Update(lease)).await?;
+                    DHCP_FAILOVER_UPDATES.with_label_values(&["sent"]).inc();
+                }
+                _ = heartbeat.tick() => {
+                    Self::send(&mut conn, &mut session, &Message::Heartbeat).await?;
+                }
+                _ = tokio::time::sleep_until(last_received + HEARTBEAT_TIMEOUT) => {
+                    return Err(Error::Timeout);
+                }
+            }
+        }
+    }
+
+    async fn session(&self, stream: tokio::net::TcpStream, pool: &PoolWorker) {
+        let peer = stream
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "<unknown>".into());
+        log::info!("Connected to DHCP failover peer {}", peer);
+        let ret = self.run_session(stream, pool).await;
+        {
+            let mut status = self.status.lock().unwrap();
+            if status.synced {
+                status.synced = false;
+                status.last_contact = std::time::Instant::now();
+            }
+        }
+        if let Err(e) = ret {
+            log::warn!("Lost connection to DHCP failover peer {}: {}", peer, e);
+        }
+    }
+
+    async fn connect(&self, pool: &PoolWorker) -> Result<(), Error> {
+        loop {
+            match tokio::net::TcpStream::connect(self.conf.peer).await {
+                Ok(stream) => self.session(stream, pool).await,
+                Err(e) => log::debug!(
+                    "Failed to connect to DHCP failover peer {}: {}",
+                    self.conf.peer,
+                    e
+                ),
+            }
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    }
+
+    async fn serve(
+        &self,
+        listener: tokio::net::TcpListener,
+        pool: &PoolWorker,
+    ) -> Result<(), Error> {
+        loop {
+            let (stream, remote) = match listener.accept().await {
+                Ok(x) => x,
+                Err(e) => {
+                    log::warn!("Failed to accept DHCP failover connection: {}", e);
+                    tokio::time::sleep(RECONNECT_INTERVAL).await;
+                    continue;
+                }
+            };
+            if remote.ip() != self.conf.peer.ip() {
+                log::warn!(
+                    "Refusing DHCP failover connection from {}, as it is not our peer",
+                    remote
+                );
+                continue;
+            }
+            self.session(stream, pool).await;
+        }
+    }
+
+    fn update_metrics(&self, state: State) {
+        for s in &[
+            State::Recover,
+            State::Normal,
+            State::CommunicationsInterrupted,
+            State::PartnerDown,
+        ] {
+            DHCP_FAILOVER_STATE
+                .with_label_values(&[&s.to_string()])
+                .set(if *s == state { 1 } else { 0 });
+        }
+    }
+
+    async fn watch_state(&self) {
+        let mut previous = None;
+        loop {
+            let state = self.state();
+            if previous != Some(state) {
+                log::info!("DHCP failover state is now {}", state);
+                self.update_metrics(state);
+                previous = Some(state);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    }
+
+    pub async fn run(&self, pool: &PoolWorker) -> Result<(), Error> {
+        let peer = async {
+            match self.conf.role {
+                FailoverRole::Primary => self.connect(pool).await,
+                FailoverRole::Secondary => {
+                    let listener = tokio::net::TcpListener::bind(self.conf.listener)
+                        .await
+                        .map_err(Error::ListenError)?;
+                    log::info!("Listening for DHCP failover peer on {}", self.conf.listener);
+                    self.serve(listener, pool).await
+                }
+            }
+        };
+        tokio::select! {
+            ret = peer => ret,
+            () = self.watch_state() => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_config(role: FailoverRole, mode: FailoverMode) -> FailoverConfig {
+    FailoverConfig {
+        mode,
+        role,
+        peer: "127.0.0.1:9967".parse().unwrap(),
+        listener: "127.0.0.1:9967".parse().unwrap(),
+        secret: "secret".into(),
+        split: 50,
+        max_client_lead_time: std::time::Duration::from_secs(3600),
+        partner_down_after: Some(std::time::Duration::from_secs(300)),
+    }
+}
+
+#[test]
+fn test_message_roundtrip() {
+    let lease = pool::LeaseInfo {
+        ip: "192.0.2.10".parse().unwrap(),
+        client_id: vec![1, 2, 3],
+        start: 1000,
+        expire: 2000,
+        options: vec![],
+    };
+    for msg in [
+        Message::Hello {
+            role: FailoverRole::Secondary,
+            mode: FailoverMode::SplitScope,
+            nonce: [7; NONCE_SIZE],
+        },
+        Message::Update(lease.clone()),
+        Message::Sync(lease),
+        Message::SyncDone,
+        Message::Heartbeat,
+    ] {
+        let mut primary = Session::new(b"key", FailoverRole::Primary);
+        let mut secondary = Session::new(b"key", FailoverRole::Secondary);
+        assert_eq!(secondary.open(&primary.seal(&msg)).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_message_authentication() {
+    let mut primary = Session::new(b"key", FailoverRole::Primary);
+    let mut secondary = Session::new(b"key", FailoverRole::Secondary);
+    let first = primary.seal(&Message::Heartbeat);
+    assert!(secondary.open(&first).is_ok());
+    /* Replaying a message fails, as the sequence number has moved on */
+    assert!(matches!(secondary.open(&first), Err(Error::BadMac)));
+    /* Reflecting a message back to the sender fails */
+    let mut primary = Session::new(b"key", FailoverRole::Primary);
+    let frame = Session::new(b"key", FailoverRole::Primary).seal(&Message::Heartbeat);
+    assert!(matches!(primary.open(&frame), Err(Error::BadMac)));
+    /* The wrong key fails */
+    let mut secondary = Session::new(b"key", FailoverRole::Secondary);
+    let frame = Session::new(b"other", FailoverRole::Primary).seal(&Message::Heartbeat);
+    assert!(matches!(secondary.open(&frame), Err(Error::BadMac)));
+}
+
+#[test]
+fn test_scope() {
+    let now = std::time::Instant::now();
+    let primary_addr = "192.0.2.50".parse().unwrap(); /* 3221226034 % 100 == 34 */
+    let secondary_addr = "192.0.2.10".parse().unwrap(); /* 3221225994 % 100 == 94 */
+
+    let f = Failover::new(FailoverConfig {
+        split: 90,
+        ..test_config(FailoverRole::Secondary, FailoverMode::SplitScope)
+    });
+    f.status.lock().unwrap().recovered = true;
+    assert!(f.may_allocate_at(secondary_addr, now));
+    assert!(!f.may_allocate_at(primary_addr, now));
+    assert!(f.is_serving());
+
+    let f = Failover::new(test_config(
+        FailoverRole::Secondary,
+        FailoverMode::ActiveStandby,
+    ));
+    f.status.lock().unwrap().last_contact = now;
+    f.status.lock().unwrap().recovered = true;
+    assert_eq!(f.state_at(now), State::CommunicationsInterrupted);
+    assert!(!f.may_allocate_at(primary_addr, now));
+    assert!(!f.is_serving());
+    /* After partner-down-after, the secondary starts serving, but can't hand out new addresses
+     * until max-client-lead-time has passed.
+     */
+    let later = now + std::time::Duration::from_secs(600);
+    assert_eq!(f.state_at(later), State::PartnerDown);
+    assert!(!f.may_allocate_at(primary_addr, later));
+    let later = std::time::Instant::now() + std::time::Duration::from_secs(7200);
+    assert!(f.may_allocate_at(primary_addr, later));
+
+    f.status.lock().unwrap().synced = true;
+    assert_eq!(f.state_at(later), State::Normal);
+    assert!(!f.may_allocate_at(primary_addr, later));
+    assert_eq!(f.max_lease(), None);
+}
+
+#[tokio::test]
+async fn test_unwritten_leases_not_sent() {
+    let f = std::sync::Arc::new(Failover::new(test_config(
+        FailoverRole::Primary,
+        FailoverMode::SplitScope,
+    )));
+    let store = super::worker::FailingStore::default();
+    let fail = store.fail.clone();
+    let mut pool = pool::Pool::new_with_store(Box::new(store));
+    pool.set_failover(f.clone());
+    let pool = PoolWorker::new(pool);
+    let addr: std::net::Ipv4Addr = "192.0.2.10".parse().unwrap();
+    let lease = pool::LeaseInfo {
+        ip: addr,
+        client_id: vec![1],
+        start: 1,
+        expire: 2,
+        options: vec![],
+    };
+    pool.call(move |p| p.update_lease(&lease)).await.unwrap();
+    let extend = move |p: &mut pool::Pool| p.extend_lease(addr, std::time::Duration::from_secs(60));
+
+    /* The peer mustn't hear about a change that was never written */
+    fail.store(true, std::sync::atomic::Ordering::Relaxed);
+    assert!(pool.call(extend).await.is_err());
+    let mut updates = f.updates_rx.try_lock().unwrap();
+    assert!(updates.try_recv().is_err());
+
+    fail.store(false, std::sync::atomic::Ordering::Relaxed);
+    let extended = pool.call(extend).await.unwrap();
+    assert_eq!(updates.try_recv().unwrap(), extended);
+}
+
+#[tokio::test]
+async fn test_recover() {
+    let now = std::time::Instant::now();
+    let secondary_addr: std::net::Ipv4Addr = "192.0.2.10".parse().unwrap();
+    let addresses: pool::PoolAddresses = [secondary_addr].iter().copied().collect();
+
+    /* The secondary has just restarted, and knows nothing of the lease that the primary handed out
+     * from the secondary's scope while the secondary was down.
+     */
+    let secondary = std::sync::Arc::new(Failover::new(FailoverConfig {
+        split: 90,
+        ..test_config(FailoverRole::Secondary, FailoverMode::SplitScope)
+    }));
+    assert_eq!(secondary.state_at(now), State::Recover);
+    let mut secondary_pool = pool::Pool::new_in_memory().unwrap();
+    secondary_pool.set_failover(secondary.clone());
+    /* Client 9 held the address before the restart, but it can't be revived yet */
+    secondary_pool
+        .update_lease(&pool::LeaseInfo {
+            ip: secondary_addr,
+            client_id: vec![9],
+            start: 1,
+            expire: 2,
+            options: vec![],
+        })
+        .unwrap();
+    let secondary_pool = PoolWorker::new(secondary_pool);
+    let allocate = |clientid: u8| {
+        let addresses = addresses.clone();
+        secondary_pool.call(move |p| {
+            p.allocate_address(
+                &[clientid],
+                None,
+                &addresses,
+                std::time::Duration::from_secs(300),
+                std::time::Duration::from_secs(86400),
+                &[],
+            )
+        })
+    };
+    assert!(allocate(9).await.is_err());
+
+    /* Once the primary's leases have arrived, the address is known to be in use */
+    let peer_lease = pool::LeaseInfo {
+        ip: secondary_addr,
+        client_id: vec![2],
+        start: 1,
+        expire: u32::MAX,
+        options: vec![],
+    };
+    secondary
+        .handle_message(&secondary_pool, Message::Sync(peer_lease))
+        .await
+        .unwrap();
+    secondary
+        .handle_message(&secondary_pool, Message::SyncDone)
+        .await
+        .unwrap();
+    assert_eq!(secondary.state(), State::Normal);
+    assert!(allocate(9).await.is_err());
+    assert_eq!(allocate(2).await.unwrap().ip, secondary_addr);
+
+    /* If the peer never turns up, then after max-client-lead-time any leases it handed out
+     * without us have expired.
+     */
+    let f = Failover::new(test_config(
+        FailoverRole::Secondary,
+        FailoverMode::SplitScope,
+    ));
+    let later = std::time::Instant::now() + std::time::Duration::from_secs(7200);
+    assert_ne!(f.state_at(later), State::Recover);
+}
+
+#[tokio::test]
+async fn test_sync() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let primary = Failover::new(FailoverConfig {
+        peer: listener.local_addr().unwrap(),
+        ..test_config(FailoverRole::Primary, FailoverMode::ActiveStandby)
+    });
+    let secondary = std::sync::Arc::new(Failover::new(test_config(
+        FailoverRole::Secondary,
+        FailoverMode::ActiveStandby,
+    )));
+    let mut primary_pool = pool::Pool::new_in_memory().unwrap();
+    let mut secondary_pool = pool::Pool::new_in_memory().unwrap();
+    let primary = std::sync::Arc::new(primary);
+    primary_pool.set_failover(primary.clone());
+    secondary_pool.set_failover(secondary.clone());
+
+    /* A lease the secondary handed out while the primary was down */
+    let existing = pool::LeaseInfo {
+        ip: "192.0.2.20".parse().unwrap(),
+        client_id: vec![2],
+        start: 1,
+        expire: u32::MAX,
+        options: vec![],
+    };
+    secondary_pool.update_lease(&existing).unwrap();
+    let primary_pool = PoolWorker::new(primary_pool);
+    let secondary_pool = PoolWorker::new(secondary_pool);
+
+    let addresses: pool::PoolAddresses = ["192.0.2.10".parse().unwrap()].iter().copied().collect();
+    let check = async {
+        while primary.state() != State::Normal || secondary.state() != State::Normal {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            primary_pool.call(|p| p.get_leases()).await.unwrap(),
+            vec![existing.clone()]
+        );
+        let lease = primary_pool
+            .call(move |p| {
+                p.allocate_address(
+                    &[1],
+                    None,
+                    &addresses,
+                    std::time::Duration::from_secs(300),
+                    std::time::Duration::from_secs(86400),
+                    &[],
+                )
+            })
+            .await
+            .unwrap();
+        loop {
+            if let Some(l) = secondary_pool
+                .call(|p| p.get_leases())
+                .await
+                .unwrap()
+                .iter()
+                .find(|l| l.ip == lease.ip)
+            {
+                assert_eq!(l.client_id, vec![1]);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    };
+    let timeout = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        tokio::select! {
+            _ = primary.connect(&primary_pool) => unreachable!(),
+            _ = secondary.serve(listener, &secondary_pool) => unreachable!(),
+            () = check => (),
+        }
+    });
+    timeout.await.expect("Failover peers failed to synchronise");
+}
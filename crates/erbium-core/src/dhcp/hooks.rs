@@ -0,0 +1,414 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Tells things outside of erbium when leases change.
+ *
+ *  Hooks are run one at a time from their own task, fed by a bounded queue, so a slow hook never
+ *  holds up handing out addresses.  If the queue fills up, notifications are dropped rather than
+ *  waited for.
+ *
+ *  Clients don't tell us when their lease runs out, so leases that have been acknowledged are
+ *  remembered, and periodically checked to see if they have expired.
+ */
+
+use super::config::{HookAction, LeaseEvent};
+use super::{dhcppkt, pool, worker::PoolWorker, DHCPRequest};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::sync::mpsc;
+
+const QUEUE_SIZE: usize = 256;
+const HOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const EXPIRY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+lazy_static::lazy_static! {
+    static ref DHCP_HOOK_DROPPED: prometheus::IntCounter =
+        prometheus::register_int_counter!("dhcp_hook_dropped",
+            "Lease notifications dropped because too many hooks were waiting to run")
+        .unwrap();
+    static ref DHCP_HOOK_FAILURES: prometheus::IntCounter =
+        prometheus::register_int_counter!("dhcp_hook_failures",
+            "Lease hooks that failed or timed out")
+        .unwrap();
+}
+
+fn now() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .expect("clock failure")
+        .as_secs() as u32
+}
+
+fn format_client_id(client_id: &[u8]) -> String {
+    client_id
+        .iter()
+        .map(|b| format!("{:0>2x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// What happened to a lease.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub event: LeaseEvent,
+    pub ip: Ipv4Addr,
+    pub client_id: Vec<u8>,
+    pub expire: u32,
+    pub host_name: Option<String>,
+}
+
+impl Notification {
+    fn from_lease(event: LeaseEvent, lease: &pool::LeaseInfo) -> Self {
+        Notification {
+            event,
+            ip: lease.ip,
+            client_id: lease.client_id.clone(),
+            expire: lease.expire,
+            host_name: dhcppkt::parse_options(crate::pktparser::Buffer::new(&lease.options))
+                .ok()
+                .and_then(|o| o.get_hostname()),
+        }
+    }
+
+    /// Works out what replying to a request did to the client's lease, if anything.  Acks only
+    /// add a lease if the client didn't already think it had it.
+    pub fn from_reply(request: &DHCPRequest, reply: Option<&dhcppkt::Dhcp>) -> Option<Self> {
+        let event = match (request.pkt.options.get_messagetype(), reply) {
+            (Some(dhcppkt::DHCPRELEASE), None) => LeaseEvent::Release,
+            (Some(dhcppkt::DHCPREQUEST), Some(reply))
+                if reply.options.get_messagetype() == Some(dhcppkt::DHCPACK) =>
+            {
+                match super::get_request_state(&request.pkt) {
+                    super::RequestState::RenewingOrRebinding => LeaseEvent::Renew,
+                    _ => LeaseEvent::Add,
+                }
+            }
+            _ => return None,
+        };
+        let (ip, expire) = match reply {
+            Some(reply) => (
+                reply.yiaddr,
+                now().saturating_add(
+                    reply
+                        .options
+                        .get_option::<u32>(&dhcppkt::OPTION_LEASETIME)
+                        .unwrap_or(0),
+                ),
+            ),
+            None => (request.pkt.ciaddr, now()),
+        };
+        Some(Notification {
+            event,
+            ip,
+            client_id: request.pkt.get_client_id(),
+            expire,
+            host_name: request.pkt.options.get_hostname(),
+        })
+    }
+
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("ERBIUM_EVENT", self.event.to_string()),
+            ("ERBIUM_ADDRESS", self.ip.to_string()),
+            ("ERBIUM_CLIENT_ID", format_client_id(&self.client_id)),
+            ("ERBIUM_EXPIRE", self.expire.to_string()),
+        ];
+        if let Some(host_name) = &self.host_name {
+            env.push(("ERBIUM_HOST_NAME", host_name.clone()));
+        }
+        env
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{ \"event\": \"{}\", \"ip\": \"{}\", \"client_id\": \"{}\", \"expire\": {}{} }}\n",
+            self.event,
+            self.ip,
+            format_client_id(&self.client_id),
+            self.expire,
+            self.host_name
+                .as_ref()
+                .map(|h| format!(", \"host-name\": {:?}", h))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/* Finds which of the acknowledged leases have expired, and forgets them.  Leases that have been
+ * deleted, or now belong to a different client, are forgotten without being reported.
+ */
+fn find_expired(
+    active: &mut HashMap<Ipv4Addr, Vec<u8>>,
+    leases: &[pool::LeaseInfo],
+    ts: u32,
+) -> Vec<Notification> {
+    let leases = leases
+        .iter()
+        .map(|lease| (lease.ip, lease))
+        .collect::<HashMap<_, _>>();
+    let mut expired = vec![];
+    active.retain(|ip, client_id| match leases.get(ip) {
+        Some(lease) if lease.client_id == *client_id && lease.expire >= ts => true,
+        Some(lease) if lease.client_id == *client_id => {
+            expired.push(Notification::from_lease(LeaseEvent::Expire, lease));
+            false
+        }
+        _ => false,
+    });
+    expired
+}
+
+async fn exec_hook(path: &std::path::Path, notification: &Notification) -> Result<(), String> {
+    let status = tokio::process::Command::new(path)
+        .envs(notification.env())
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", path.display(), e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed: {}", path.display(), status))
+    }
+}
+
+async fn post_hook(host: &str, path: &str, notification: &Notification) -> Result<(), String> {
+    let body = notification.to_json();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    let mut stream = tokio::net::TcpStream::connect(host)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", host, e))?;
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to POST to {}: {}", host, e))?;
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| format!("Failed to read response from {}: {}", host, e))?;
+    /* The status line looks like "HTTP/1.1 200 OK" */
+    let status = response
+        .split(|&b| b == b'\n')
+        .next()
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .and_then(|line| line.split_ascii_whitespace().nth(1))
+        .unwrap_or("");
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(format!("POST to {}{} returned {:?}", host, path, status))
+    }
+}
+
+async fn run_hooks(conf: &crate::config::SharedConfig, notification: &Notification) {
+    let hooks = conf.read().await.dhcp.lease_hooks.clone();
+    for hook in hooks
+        .iter()
+        .filter(|h| h.events.contains(&notification.event))
+    {
+        let result = match &hook.action {
+            HookAction::Exec(path) => {
+                tokio::time::timeout(HOOK_TIMEOUT, exec_hook(path, notification)).await
+            }
+            HookAction::Post { host, path } => {
+                tokio::time::timeout(HOOK_TIMEOUT, post_hook(host, path, notification)).await
+            }
+        };
+        match result {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                log::warn!("Lease hook for {}: {}", notification.ip, e);
+                DHCP_HOOK_FAILURES.inc();
+            }
+            Err(_) => {
+                log::warn!("Lease hook for {} timed out", notification.ip);
+                DHCP_HOOK_FAILURES.inc();
+            }
+        }
+    }
+}
+
+pub struct Hooks {
+    queue: mpsc::Sender<Notification>,
+    notifications: tokio::sync::Mutex<mpsc::Receiver<Notification>>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        let (queue, notifications) = mpsc::channel(QUEUE_SIZE);
+        Hooks {
+            queue,
+            notifications: tokio::sync::Mutex::new(notifications),
+        }
+    }
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hooks {
+    /// Queues the hooks for a lease change to be run, without waiting for them.
+    pub fn notify(&self, notification: Notification) {
+        if let Err(mpsc::error::TrySendError::Full(notification)) =
+            self.queue.try_send(notification)
+        {
+            log::warn!(
+                "Too many lease hooks waiting to run, dropping {} for {}",
+                notification.event,
+                notification.ip
+            );
+            DHCP_HOOK_DROPPED.inc();
+        }
+    }
+
+    /// Runs hooks as leases change.  This never returns.
+    pub async fn run(&self, conf: &crate::config::SharedConfig, pool: &PoolWorker) {
+        let mut notifications = self.notifications.lock().await;
+        /* Leases that were in use before we started will expire too */
+        let ts = now();
+        let mut active = match pool.call(|pool| pool.get_leases()).await {
+            Ok(leases) => leases
+                .into_iter()
+                .filter(|lease| !lease.client_id.is_empty() && lease.expire >= ts)
+                .map(|lease| (lease.ip, lease.client_id))
+                .collect(),
+            Err(e) => {
+                log::warn!("Failed to get leases for lease hooks: {}", e);
+                HashMap::new()
+            }
+        };
+        let mut expiry_check = tokio::time::interval(EXPIRY_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                Some(notification) = notifications.recv() => {
+                    match notification.event {
+                        LeaseEvent::Add | LeaseEvent::Renew => {
+                            active.insert(notification.ip, notification.client_id.clone());
+                        }
+                        LeaseEvent::Release | LeaseEvent::Expire => {
+                            active.remove(&notification.ip);
+                        }
+                    }
+                    run_hooks(conf, &notification).await;
+                }
+                _ = expiry_check.tick() => {
+                    if conf.read().await.dhcp.lease_hooks.is_empty() {
+                        continue;
+                    }
+                    match pool.call(|pool| pool.get_leases()).await {
+                        Ok(leases) => {
+                            for notification in find_expired(&mut active, &leases, now()) {
+                                run_hooks(conf, &notification).await;
+                            }
+                        }
+                        Err(e) => log::warn!("Failed to check for expired leases: {}", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_find_expired() {
+    let lease = |n: u8, client: u8, expire| pool::LeaseInfo {
+        ip: Ipv4Addr::new(192, 0, 2, n),
+        client_id: vec![client],
+        start: 0,
+        expire,
+        options: vec![],
+    };
+    let mut active: HashMap<_, _> = (1..=4)
+        .map(|n| (Ipv4Addr::new(192, 0, 2, n), vec![n]))
+        .collect();
+    let leases = vec![
+        lease(1, 1, 2000), /* Still in use */
+        lease(2, 2, 999),  /* Expired */
+        lease(3, 9, 2000), /* Now belongs to someone else */
+                           /* 4 has been deleted */
+    ];
+
+    let expired = find_expired(&mut active, &leases, 1000);
+    assert_eq!(
+        expired,
+        vec![Notification::from_lease(
+            LeaseEvent::Expire,
+            &lease(2, 2, 999)
+        )]
+    );
+    assert_eq!(active.keys().collect::<Vec<_>>(), vec![&lease(1, 1, 0).ip]);
+
+    /* Expiry is only reported once */
+    assert!(find_expired(&mut active, &leases, 3000).len() == 1);
+    assert!(find_expired(&mut active, &leases, 3000).is_empty());
+}
+
+#[test]
+fn test_notification() {
+    let notification = Notification {
+        event: LeaseEvent::Add,
+        ip: "192.0.2.1".parse().unwrap(),
+        client_id: vec![0x01, 0xab],
+        expire: 1000,
+        host_name: Some("host".into()),
+    };
+    assert_eq!(
+        notification.to_json(),
+        "{ \"event\": \"add\", \"ip\": \"192.0.2.1\", \"client_id\": \"01:ab\", \"expire\": 1000, \"host-name\": \"host\" }\n"
+    );
+    assert!(notification
+        .env()
+        .contains(&("ERBIUM_CLIENT_ID", "01:ab".to_string())));
+}
+
+#[tokio::test]
+async fn test_post_hook() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let server = async {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = vec![0; 4096];
+        let len = stream.read(&mut request).await.unwrap();
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .await
+            .unwrap();
+        String::from_utf8(request[..len].to_vec()).unwrap()
+    };
+    let notification = Notification {
+        event: LeaseEvent::Expire,
+        ip: "192.0.2.1".parse().unwrap(),
+        client_id: vec![1],
+        expire: 1000,
+        host_name: None,
+    };
+    let (request, result) = futures::join!(server, post_hook(&host, "/leases", &notification));
+    assert_eq!(result, Ok(()));
+    assert!(request.starts_with("POST /leases HTTP/1.1\r\n"));
+    assert!(request.ends_with(&notification.to_json()));
+}
@@ -19,7 +19,6 @@
 use std::collections;
 use std::convert::TryInto as _;
 use std::net;
-use std::ops::Sub as _;
 use std::sync::Arc;
 use tokio::sync;
 
@@ -31,11 +30,19 @@ use erbium_net::udp;
 
 pub mod config;
 pub mod dhcppkt;
+pub mod failover;
+pub mod hooks;
 pub mod pool;
+mod probe;
+pub mod storage;
 #[cfg(test)]
 mod test;
+pub mod worker;
 
 type UdpSocket = udp::UdpSocket;
+
+/* How many addresses to probe for conflicts before giving up and offering an address anyway. */
+const MAX_CONFLICT_PROBES: usize = 3;
 type ServerIds = std::collections::HashSet<net::Ipv4Addr>;
 pub type SharedServerIds = Arc<sync::Mutex<ServerIds>>;
 
@@ -68,6 +75,18 @@ lazy_static::lazy_static! {
         "Counts of leases that are currently expired"
     )
     .unwrap();
+    static ref DHCP_REQUEST_LATENCY: prometheus::HistogramVec =
+        prometheus::register_histogram_vec!(
+            "dhcp_request_latency",
+            "Time taken to handle DHCP requests",
+            &["type"]
+        )
+        .unwrap();
+    static ref DHCP_CONFLICTS: prometheus::IntCounter = prometheus::register_int_counter!(
+        "dhcp_conflicts",
+        "Number of addresses that were found to be in use before they were offered"
+    )
+    .unwrap();
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -113,6 +132,8 @@ impl DhcpError {
             NoPolicyConfigured => "NO_POLICY",
             PoolError(pool::Error::NoAssignableAddress) => "NO_ADDRESS",
             PoolError(pool::Error::RequestedAddressInUse) => "ADDRESS_IN_USE",
+            PoolError(pool::Error::NoSuchLease) => "NO_SUCH_LEASE",
+            PoolError(pool::Error::PeerAddress) => "PEER_ADDRESS",
             PoolError(_) => "INTERNAL_POOL_ERROR",
         }
     }
@@ -126,10 +147,68 @@ pub struct DHCPRequest {
     pub serverip: std::net::Ipv4Addr,
     /// The interface index that the request was received on.
     pub ifindex: u32,
+    /// The name of the interface that the request was received on, if known.
+    pub if_name: Option<String>,
     pub if_mtu: Option<u32>,
     pub if_router: Option<std::net::Ipv4Addr>,
 }
 
+impl DHCPRequest {
+    /// The address used to select which subnet the client is on.  RFC3011 allows the client (or
+    /// a relay) to explicitly ask for a subnet, as does the RFC3527 link selection sub-option of
+    /// option 82.  Otherwise RFC2131 Section 4.3.1 says to use the relay address if the request
+    /// was relayed, and the address of the receiving interface if not.
+    pub fn get_subnet_address(&self) -> std::net::Ipv4Addr {
+        if let Some(subnet) = self
+            .pkt
+            .options
+            .get_option::<std::net::Ipv4Addr>(&dhcppkt::OPTION_SUBNETSELECT)
+        {
+            subnet
+        } else if let Some(subnet) = self
+            .pkt
+            .options
+            .get_relay_agent_info()
+            .and_then(|info| info.link_selection)
+        {
+            subnet
+        } else if !self.pkt.giaddr.is_unspecified() {
+            self.pkt.giaddr
+        } else {
+            self.serverip
+        }
+    }
+
+    /// RFC4578 Section 2.1: The client system architectures, in order of preference.
+    pub fn get_client_archs(&self) -> Vec<u16> {
+        self.pkt
+            .options
+            .get_raw_option(&dhcppkt::OPTION_CLIENTARCH)
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .map(|arch| u16::from_be_bytes([arch[0], arch[1]]))
+            .collect()
+    }
+
+    /// PXE firmware identifies itself with a vendor class starting with "PXEClient".
+    pub fn is_pxe_client(&self) -> bool {
+        self.pkt
+            .options
+            .get_raw_option(&dhcppkt::OPTION_VENDOR_CLASS)
+            .map(|class| class.starts_with(b"PXEClient"))
+            .unwrap_or(false)
+    }
+
+    /// iPXE sets the user class to "iPXE".  This is usually sent as a bare string rather than
+    /// in the RFC3004 format, so accept either.
+    pub fn is_ipxe(&self) -> bool {
+        matches!(
+            self.pkt.options.get_raw_option(&dhcppkt::OPTION_USERCLASS),
+            Some(b"iPXE") | Some(b"\x04iPXE")
+        )
+    }
+}
+
 #[cfg(test)]
 impl std::default::Default for DHCPRequest {
     fn default() -> Self {
@@ -156,6 +235,7 @@ impl std::default::Default for DHCPRequest {
             },
             serverip: "0.0.0.0".parse().unwrap(),
             ifindex: 0,
+            if_name: None,
             if_mtu: None,
             if_router: None,
         }
@@ -171,19 +251,56 @@ enum PolicyMatch {
 
 fn check_policy(req: &DHCPRequest, policy: &config::Policy) -> PolicyMatch {
     let mut outcome = PolicyMatch::NoMatch;
-    //if let Some(policy.match_interface ...
     if policy.match_all {
         outcome = PolicyMatch::MatchSucceeded;
     }
+    if let Some(match_interface) = &policy.match_interface {
+        outcome = PolicyMatch::MatchSucceeded;
+        if req.if_name != *match_interface {
+            return PolicyMatch::MatchFailed;
+        }
+    }
     if let Some(match_chaddr) = &policy.match_chaddr {
         outcome = PolicyMatch::MatchSucceeded;
         if req.pkt.chaddr != *match_chaddr {
             return PolicyMatch::MatchFailed;
         }
     }
+    if policy.match_circuit_id.is_some() || policy.match_remote_id.is_some() {
+        outcome = PolicyMatch::MatchSucceeded;
+        let info = req.pkt.options.get_relay_agent_info().unwrap_or_default();
+        if policy.match_circuit_id.is_some() && info.circuit_id != policy.match_circuit_id {
+            return PolicyMatch::MatchFailed;
+        }
+        if policy.match_remote_id.is_some() && info.remote_id != policy.match_remote_id {
+            return PolicyMatch::MatchFailed;
+        }
+    }
     if let Some(match_subnet) = &policy.match_subnet {
         outcome = PolicyMatch::MatchSucceeded;
-        if !match_subnet.contains(req.serverip) {
+        if !match_subnet.contains(req.get_subnet_address()) {
+            return PolicyMatch::MatchFailed;
+        }
+    }
+    if let Some(match_client_arch) = &policy.match_client_arch {
+        outcome = PolicyMatch::MatchSucceeded;
+        if !req
+            .get_client_archs()
+            .iter()
+            .any(|arch| match_client_arch.contains(arch))
+        {
+            return PolicyMatch::MatchFailed;
+        }
+    }
+    if let Some(match_pxe_client) = policy.match_pxe_client {
+        outcome = PolicyMatch::MatchSucceeded;
+        if req.is_pxe_client() != match_pxe_client {
+            return PolicyMatch::MatchFailed;
+        }
+    }
+    if let Some(match_ipxe) = policy.match_ipxe {
+        outcome = PolicyMatch::MatchSucceeded;
+        if req.is_ipxe() != match_ipxe {
             return PolicyMatch::MatchFailed;
         }
     }
@@ -230,6 +347,17 @@ fn apply_policy(req: &DHCPRequest, policy: &config::Policy, response: &mut Respo
         response.address = Some(address.clone()); /* HELP: I tried to make the lifetimes worked, and failed */
     }
 
+    /* Likewise for lease times */
+    response.default_lease = policy.apply_default_lease.or(response.default_lease);
+    response.minlease = policy.apply_min_lease.or(response.minlease);
+    response.maxlease = policy.apply_max_lease.or(response.maxlease);
+    response.renewal_time = policy.apply_renewal_time.or(response.renewal_time);
+    response.rebind_time = policy.apply_rebind_time.or(response.rebind_time);
+    response.next_server = policy.apply_next_server.or(response.next_server);
+    if let Some(boot_file) = &policy.apply_boot_file {
+        response.boot_file = Some(boot_file.clone());
+    }
+
     /* Now get the list of parameters we will apply from the parameter list from the client.
      */
     // TODO: This should probably just be a u128 bitvector
@@ -362,17 +490,68 @@ impl ResponseOptions {
 struct Response {
     options: ResponseOptions,
     address: Option<pool::PoolAddresses>,
+    default_lease: Option<std::time::Duration>,
     minlease: Option<std::time::Duration>,
     maxlease: Option<std::time::Duration>,
+    renewal_time: Option<std::time::Duration>,
+    rebind_time: Option<std::time::Duration>,
+    next_server: Option<net::Ipv4Addr>,
+    boot_file: Option<String>,
 }
 
+impl Response {
+    /// Returns the (min, max) lease time that the pool may give this client.
+    ///
+    /// RFC2131 Section 4.3.1: If the client asked for a particular lease time, then it gets that
+    /// lease time, limited by the configured min and max lease.  Otherwise the client gets at
+    /// least the default lease, and the pool may extend that for clients that keep coming back,
+    /// up to the max lease.
+    fn lease_bounds(&self, req: &DHCPRequest) -> (std::time::Duration, std::time::Duration) {
+        let max = self.maxlease.unwrap_or(pool::DEFAULT_MAX_LEASE);
+        let min = std::cmp::min(self.minlease.unwrap_or(pool::DEFAULT_MIN_LEASE), max);
+        let clamp = |lease| std::cmp::min(std::cmp::max(lease, min), max);
+        match req
+            .pkt
+            .options
+            .get_option::<u32>(&dhcppkt::OPTION_LEASETIME)
+        {
+            Some(requested) => {
+                let lease = clamp(std::time::Duration::from_secs(requested.into()));
+                (lease, lease)
+            }
+            None => (clamp(self.default_lease.unwrap_or(min)), max),
+        }
+    }
+
+    /// Returns the renewal (T1) and rebinding (T2) times for a lease.
+    ///
+    /// RFC2131 Section 4.4.5: T1 defaults to 0.5 * duration_of_lease, T2 defaults to 0.875 *
+    /// duration_of_lease.  If the configured times don't fit inside the lease, the defaults are
+    /// used instead.
+    fn renewal_times(
+        &self,
+        lease: std::time::Duration,
+    ) -> (std::time::Duration, std::time::Duration) {
+        let default_t1 = lease / 2;
+        let default_t2 = lease * 7 / 8;
+        let t1 = self.renewal_time.unwrap_or(default_t1);
+        let t2 = self.rebind_time.unwrap_or(default_t2);
+        if t1 <= t2 && t2 <= lease {
+            (t1, t2)
+        } else {
+            (default_t1, default_t2)
+        }
+    }
+}
+
+/// Handles a DHCPDISCOVER, returning the offer and how the offered address was chosen.
 fn handle_discover<'l>(
     pools: &mut pool::Pool,
     req: &DHCPRequest,
     _serverids: &ServerIds,
     base: &[config::Policy],
     conf: &'l super::config::Config,
-) -> Result<dhcppkt::Dhcp, DhcpError> {
+) -> Result<(dhcppkt::Dhcp, pool::LeaseType), DhcpError> {
     /* Build the default response we are about to reply with, it will be filled in later */
     let mut response: Response = Response {
         options: ResponseOptions {
@@ -389,16 +568,17 @@ fn handle_discover<'l>(
     if !base_policy && !conf_policy {
         /* If none of the policies applied at all, then provide a warning back to the caller */
         Err(DhcpError::NoPolicyConfigured)
-    } else if let Some(addresses) = response.address {
+    } else if let Some(addresses) = response.address.take() {
         /* At least one policy matched, and provided addresses.  So now go allocate an address */
         let mut raw_options = Vec::new();
         req.pkt.options.serialise(&mut raw_options);
+        let (min_lease, max_lease) = response.lease_bounds(req);
         match pools.allocate_address(
             &req.pkt.get_client_id(),
             req.pkt.options.get_address_request(),
             &addresses,
-            response.minlease.unwrap_or(pool::DEFAULT_MIN_LEASE),
-            response.maxlease.unwrap_or(pool::DEFAULT_MAX_LEASE),
+            min_lease,
+            max_lease,
             &raw_options,
         ) {
             /* Now we have an address, build the reply */
@@ -413,7 +593,7 @@ fn handle_discover<'l>(
                     lease.lease_type
                 );
 
-                Ok(dhcppkt::Dhcp {
+                let reply = dhcppkt::Dhcp {
                     op: dhcppkt::OP_BOOTREPLY,
                     htype: dhcppkt::HWTYPE_ETHERNET,
                     hlen: 6,
@@ -423,17 +603,18 @@ fn handle_discover<'l>(
                     flags: req.pkt.flags,
                     ciaddr: net::Ipv4Addr::UNSPECIFIED,
                     yiaddr: lease.ip,
-                    siaddr: net::Ipv4Addr::UNSPECIFIED,
+                    siaddr: response.next_server.unwrap_or(net::Ipv4Addr::UNSPECIFIED),
                     giaddr: req.pkt.giaddr,
                     chaddr: req.pkt.chaddr.clone(),
                     sname: vec![],
-                    file: vec![],
+                    file: response.boot_file.clone().unwrap_or_default().into_bytes(),
                     options: response
                         .options
                         .clone()
                         .set_option(&dhcppkt::OPTION_SERVERID, &req.serverip)
                         .to_options(),
-                })
+                };
+                Ok((reply, lease.lease_type))
             }
             /* Some error occurred, document it. */
             Err(e) => Err(DhcpError::PoolError(e)),
@@ -444,6 +625,13 @@ fn handle_discover<'l>(
     }
 }
 
+fn check_serverid(req: &DHCPRequest, serverids: &ServerIds) -> Result<(), DhcpError> {
+    match req.pkt.options.get_serverid() {
+        Some(si) if !serverids.contains(&si) => Err(DhcpError::OtherServer(si)),
+        _ => Ok(()),
+    }
+}
+
 fn handle_request(
     pools: &mut pool::Pool,
     req: &DHCPRequest,
@@ -451,11 +639,7 @@ fn handle_request(
     base: &[config::Policy],
     conf: &super::config::Config,
 ) -> Result<dhcppkt::Dhcp, DhcpError> {
-    if let Some(si) = req.pkt.options.get_serverid() {
-        if !serverids.contains(&si) {
-            return Err(DhcpError::OtherServer(si));
-        }
-    }
+    check_serverid(req, serverids)?;
     let mut response: Response = Response {
         options: ResponseOptions {
             ..Default::default()
@@ -468,21 +652,35 @@ fn handle_request(
     let conf_policy = apply_policies(req, &conf.dhcp.policies, &mut response);
     if !base_policy && !conf_policy {
         Err(DhcpError::NoPolicyConfigured)
-    } else if let Some(addresses) = response.address {
+    } else if let Some(addresses) = response.address.take() {
         let mut raw_options = Vec::new();
         req.pkt.options.serialise(&mut raw_options);
-        match pools.allocate_address(
-            &req.pkt.get_client_id(),
-            if !req.pkt.ciaddr.is_unspecified() {
-                Some(req.pkt.ciaddr)
-            } else {
-                req.pkt.options.get_address_request()
-            },
-            &addresses,
-            response.minlease.unwrap_or(pool::DEFAULT_MIN_LEASE),
-            response.maxlease.unwrap_or(pool::DEFAULT_MAX_LEASE),
-            &raw_options,
-        ) {
+        let requested = if !req.pkt.ciaddr.is_unspecified() {
+            Some(req.pkt.ciaddr)
+        } else {
+            req.pkt.options.get_address_request()
+        };
+        let (min_lease, max_lease) = response.lease_bounds(req);
+        let allocation = if let Some(requested) = requested {
+            pools.allocate_requested_address(
+                &req.pkt.get_client_id(),
+                requested,
+                &addresses,
+                min_lease,
+                max_lease,
+                &raw_options,
+            )
+        } else {
+            pools.allocate_address(
+                &req.pkt.get_client_id(),
+                None,
+                &addresses,
+                min_lease,
+                max_lease,
+                &raw_options,
+            )
+        };
+        match allocation {
             Ok(lease) => {
                 DHCP_ALLOCATIONS
                     .with_label_values(&[&format!("{:?}", lease.lease_type)])
@@ -493,6 +691,7 @@ fn handle_request(
                     lease.expire,
                     lease.lease_type
                 );
+                let (renewal_time, rebind_time) = response.renewal_times(lease.expire);
                 Ok(dhcppkt::Dhcp {
                     op: dhcppkt::OP_BOOTREPLY,
                     htype: dhcppkt::HWTYPE_ETHERNET,
@@ -503,11 +702,11 @@ fn handle_request(
                     flags: req.pkt.flags,
                     ciaddr: req.pkt.ciaddr,
                     yiaddr: lease.ip,
-                    siaddr: net::Ipv4Addr::UNSPECIFIED,
+                    siaddr: response.next_server.unwrap_or(net::Ipv4Addr::UNSPECIFIED),
                     giaddr: req.pkt.giaddr,
                     chaddr: req.pkt.chaddr.clone(),
                     sname: vec![],
-                    file: vec![],
+                    file: response.boot_file.clone().unwrap_or_default().into_bytes(),
                     options: response
                         .options
                         .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPACK)
@@ -516,9 +715,39 @@ fn handle_request(
                             &req.pkt.options.get_serverid().unwrap_or(req.serverip),
                         )
                         .set_option(&dhcppkt::OPTION_LEASETIME, &(lease.expire.as_secs() as u32))
+                        .set_option(
+                            &dhcppkt::OPTION_RENEWALTIME,
+                            &(renewal_time.as_secs() as u32),
+                        )
+                        .set_option(&dhcppkt::OPTION_REBINDTIME, &(rebind_time.as_secs() as u32))
                         .to_options(),
                 })
             }
+            /* RFC2131 Section 4.3.2: If the DHCP server has knowledge of the client's binding, and
+             * the requested address is incorrect (eg the client has moved to a new subnet, or the
+             * address belongs to someone else) the server SHOULD respond with a DHCPNAK.
+             */
+            Err(e @ pool::Error::NoAssignableAddress)
+            | Err(e @ pool::Error::RequestedAddressInUse)
+                if requested.is_some() =>
+            {
+                let requested = requested.unwrap();
+                log::warn!(
+                    "{}: Rejecting request for {} in {:?}: {}",
+                    format_client(&req.pkt),
+                    requested,
+                    get_request_state(&req.pkt),
+                    e
+                );
+                DHCP_ALLOCATIONS.with_label_values(&["Nak"]).inc();
+                DHCP_ERRORS
+                    .with_label_values(&[DhcpError::PoolError(e).get_variant_name()])
+                    .inc();
+                Ok(build_nak(
+                    req,
+                    &format!("Requested address {} is not available", requested),
+                ))
+            }
             Err(e) => Err(DhcpError::PoolError(e)),
         }
     } else {
@@ -526,6 +755,162 @@ fn handle_request(
     }
 }
 
+/* RFC2131 Section 4.3.2 describes which state a client is in based on which fields are filled in.
+ * RENEWING and REBINDING only differ in whether the packet was unicast or broadcast, and are
+ * treated identically here.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestState {
+    Selecting,
+    InitReboot,
+    RenewingOrRebinding,
+}
+
+fn get_request_state(pkt: &dhcppkt::Dhcp) -> RequestState {
+    if pkt.options.get_serverid().is_some() {
+        RequestState::Selecting
+    } else if pkt.ciaddr.is_unspecified() {
+        RequestState::InitReboot
+    } else {
+        RequestState::RenewingOrRebinding
+    }
+}
+
+fn build_nak(req: &DHCPRequest, message: &str) -> dhcppkt::Dhcp {
+    /* RFC2131 Section 4.3.2: If giaddr is set, the server sends the DHCPNAK to the relay with the
+     * broadcast bit set, so the relay broadcasts it to the client.
+     */
+    let flags = if req.pkt.giaddr.is_unspecified() {
+        req.pkt.flags
+    } else {
+        req.pkt.flags | dhcppkt::FLAG_BROADCAST
+    };
+    /* RFC2131 Table 3: A DHCPNAK carries only the message type, server identifier and optionally a
+     * message.  ciaddr, yiaddr and siaddr are all zero.
+     */
+    dhcppkt::Dhcp {
+        op: dhcppkt::OP_BOOTREPLY,
+        htype: dhcppkt::HWTYPE_ETHERNET,
+        hlen: 6,
+        hops: 0,
+        xid: req.pkt.xid,
+        secs: 0,
+        flags,
+        ciaddr: net::Ipv4Addr::UNSPECIFIED,
+        yiaddr: net::Ipv4Addr::UNSPECIFIED,
+        siaddr: net::Ipv4Addr::UNSPECIFIED,
+        giaddr: req.pkt.giaddr,
+        chaddr: req.pkt.chaddr.clone(),
+        sname: vec![],
+        file: vec![],
+        options: dhcppkt::DhcpOptions::default()
+            .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPNAK)
+            .set_option(
+                &dhcppkt::OPTION_SERVERID,
+                &req.pkt.options.get_serverid().unwrap_or(req.serverip),
+            )
+            .set_option(&dhcppkt::OPTION_MESSAGE, &message.to_string()),
+    }
+}
+
+/* RFC2131 Section 4.1: In all cases, when 'giaddr' is zero, the server broadcasts any DHCPNAK
+ * messages to 0xffffffff.
+ */
+fn must_broadcast(reply: &dhcppkt::Dhcp) -> bool {
+    reply.giaddr.is_unspecified() && reply.options.get_messagetype() == Some(dhcppkt::DHCPNAK)
+}
+
+fn handle_release(
+    pools: &mut pool::Pool,
+    req: &DHCPRequest,
+    serverids: &ServerIds,
+) -> Result<(), DhcpError> {
+    check_serverid(req, serverids)?;
+    /* RFC2131 Section 4.3.4: Upon receipt of a DHCPRELEASE message, the server marks the network
+     * address as not allocated.
+     */
+    pools
+        .release_address(&req.pkt.get_client_id(), req.pkt.ciaddr)
+        .map_err(DhcpError::PoolError)?;
+    log::info!("Released Lease: {}", req.pkt.ciaddr);
+    Ok(())
+}
+
+fn handle_decline(
+    pools: &mut pool::Pool,
+    req: &DHCPRequest,
+    serverids: &ServerIds,
+) -> Result<(), DhcpError> {
+    check_serverid(req, serverids)?;
+    /* RFC2131 Section 4.3.3: If the server receives a DHCPDECLINE message, the client has
+     * discovered through some other means that the suggested network address is already in use.
+     * The server MUST mark the network address as not available and SHOULD notify the local
+     * system administrator of a possible configuration problem.
+     */
+    let addr = req
+        .pkt
+        .options
+        .get_address_request()
+        .ok_or(DhcpError::ParseError(dhcppkt::ParseError::InvalidPacket))?;
+    pools
+        .decline_address(
+            &req.pkt.get_client_id(),
+            addr,
+            pool::DEFAULT_DECLINE_HOLDDOWN,
+        )
+        .map_err(DhcpError::PoolError)?;
+    log::warn!(
+        "{}: Declined {}, is something else using this address?",
+        format_client(&req.pkt),
+        addr
+    );
+    Ok(())
+}
+
+fn handle_inform(
+    req: &DHCPRequest,
+    base: &[config::Policy],
+    conf: &super::config::Config,
+) -> Result<dhcppkt::Dhcp, DhcpError> {
+    let mut response: Response = Response {
+        options: ResponseOptions {
+            ..Default::default()
+        }
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPACK)
+        .set_option(&dhcppkt::OPTION_SERVERID, &req.serverip),
+        ..Default::default()
+    };
+    let base_policy = apply_policies(req, base, &mut response);
+    let conf_policy = apply_policies(req, &conf.dhcp.policies, &mut response);
+    if !base_policy && !conf_policy {
+        return Err(DhcpError::NoPolicyConfigured);
+    }
+    /* RFC2131 Section 3.4: The server SHOULD check the network address in a DHCPINFORM message
+     * for consistency, but MUST NOT check for an existing lease.  The server forms a DHCPACK
+     * message containing the configuration parameters for the requesting client and sends the
+     * DHCPACK message directly to the client.
+     *
+     * No address is allocated, and no lease time is sent.
+     */
+    Ok(dhcppkt::Dhcp {
+        op: dhcppkt::OP_BOOTREPLY,
+        htype: dhcppkt::HWTYPE_ETHERNET,
+        hlen: 6,
+        hops: 0,
+        xid: req.pkt.xid,
+        secs: 0,
+        flags: req.pkt.flags,
+        ciaddr: req.pkt.ciaddr,
+        yiaddr: net::Ipv4Addr::UNSPECIFIED,
+        siaddr: response.next_server.unwrap_or(net::Ipv4Addr::UNSPECIFIED),
+        giaddr: req.pkt.giaddr,
+        chaddr: req.pkt.chaddr.clone(),
+        sname: vec![],
+        file: response.boot_file.clone().unwrap_or_default().into_bytes(),
+        options: response.options.to_options(),
+    })
+}
+
 fn format_mac(v: &[u8]) -> String {
     v.iter()
         .map(|b| format!("{:0>2x}", b))
@@ -675,18 +1060,14 @@ pub async fn build_default_config(
                 use crate::config::Match as _;
                 use crate::config::PrefixOps as _;
                 let subnet = erbium_net::Ipv4Subnet::new(p4.network(), p4.prefixlen).ok()?;
+                let mut addresses = config::subnet_addresses(&subnet).difference(&all_addrs);
+                // TODO: This removes one IP from the list, it should also remove any
+                // others found on the local machine.  Probably fine for now, but
+                // likely to cause confusion in the future.
+                addresses.remove(request.serverip);
                 let mut ret = config::Policy {
                     match_subnet: Some(subnet),
-                    apply_address: Some(
-                        (1..((1 << (32 - p4.prefixlen)) - 2))
-                            .map(|offset| (u32::from(subnet.network()) + offset).into())
-                            // TODO: This removes one IP from the list, it should also remove any
-                            // others found on the local machine.  Probably fine for now, but
-                            // likely to cause confusion in the future.
-                            .filter(|ip4| *ip4 != request.serverip)
-                            .collect::<pool::PoolAddresses>()
-                            .sub(&all_addrs),
-                    ),
+                    apply_address: Some(addresses),
                     ..Default::default()
                 };
                 /* If this is the interface the request is coming in, then we can do extra stuff */
@@ -721,19 +1102,54 @@ pub async fn handle_pkt(
     request: &DHCPRequest,
     serverids: ServerIds,
     conf: &super::config::Config,
-) -> Result<dhcppkt::Dhcp, DhcpError> {
+) -> Result<Option<dhcppkt::Dhcp>, DhcpError> {
+    let base = [build_default_config(conf, request).await];
+    handle_pkt_lease(pools, request, &serverids, &base, conf)
+        .map(|reply| reply.map(|(reply, _)| reply))
+}
+
+/// Like handle_pkt, but if the reply offers an address, also returns how that address was
+/// chosen, so new addresses can be probed before they are offered.  This takes the default
+/// policy already built, so that it can be run on the pool's thread.
+fn handle_pkt_lease(
+    pools: &mut pool::Pool,
+    request: &DHCPRequest,
+    serverids: &ServerIds,
+    base: &[config::Policy],
+    conf: &super::config::Config,
+) -> Result<Option<(dhcppkt::Dhcp, Option<pool::LeaseType>)>, DhcpError> {
     match request.pkt.options.get_messagetype() {
-        Some(dhcppkt::DHCPDISCOVER) => {
-            let base = [build_default_config(conf, request).await];
-            handle_discover(pools, request, &serverids, &base, conf)
-        }
+        Some(dhcppkt::DHCPDISCOVER) => handle_discover(pools, request, serverids, base, conf)
+            .map(|(reply, lease_type)| Some((reply, Some(lease_type)))),
         Some(dhcppkt::DHCPREQUEST) => {
-            let base = [build_default_config(conf, request).await];
-            handle_request(pools, request, &serverids, &base, conf)
+            handle_request(pools, request, serverids, base, conf).map(|reply| Some((reply, None)))
+        }
+        Some(dhcppkt::DHCPINFORM) => {
+            handle_inform(request, base, conf).map(|reply| Some((reply, None)))
         }
+        /* RELEASE and DECLINE don't get a reply */
+        Some(dhcppkt::DHCPRELEASE) => handle_release(pools, request, serverids).map(|_| None),
+        Some(dhcppkt::DHCPDECLINE) => handle_decline(pools, request, serverids).map(|_| None),
         Some(x) => Err(DhcpError::UnknownMessageType(x)),
         None => Err(DhcpError::ParseError(dhcppkt::ParseError::InvalidPacket)),
     }
+    .map(|reply| reply.map(|(reply, lease_type)| (echo_options(request, reply), lease_type)))
+}
+
+/* RFC3011 Section 3: Servers configured to support this option MUST return an identical copy of
+ * the option to any client that sends it, regardless of whether or not the client requests the
+ * option in a parameter request list.
+ *
+ * RFC3046 Section 2.2: DHCP servers claiming to support the Relay Agent Information option SHALL
+ * echo the entire contents of the Relay Agent Information option in all replies.
+ */
+fn echo_options(request: &DHCPRequest, mut reply: dhcppkt::Dhcp) -> dhcppkt::Dhcp {
+    for option in &[dhcppkt::OPTION_SUBNETSELECT, dhcppkt::OPTION_RELAYINFO] {
+        if let Some(value) = request.pkt.options.get_raw_option(option) {
+            reply.options = reply.options.set_raw_option(option, value);
+        }
+    }
+    reply
 }
 
 async fn send_raw(raw: Arc<raw::RawSocket>, buf: &[u8], intf: i32) -> Result<(), std::io::Error> {
@@ -783,7 +1199,9 @@ pub struct DhcpService {
     netinfo: erbium_net::netinfo::SharedNetInfo,
     conf: crate::config::SharedConfig,
     rawsock: std::sync::Arc<erbium_net::raw::RawSocket>,
-    pool: std::sync::Arc<sync::Mutex<pool::Pool>>,
+    pool: worker::PoolWorker,
+    failover: Option<std::sync::Arc<failover::Failover>>,
+    hooks: hooks::Hooks,
     serverids: SharedServerIds,
     listener: UdpSocket,
 }
@@ -814,8 +1232,16 @@ impl DhcpService {
             }
             Ok(req) => req,
         };
+        let _timer = DHCP_REQUEST_LATENCY
+            .with_label_values(&[&req
+                .options
+                .get_messagetype()
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "NONE".into())])
+            .start_timer();
 
         /* Log what we've got */
+        let if_name = self.netinfo.get_name_by_ifidx(intf).await;
         let if_mtu = self.netinfo.get_mtu_by_ifidx(intf).await;
         let if_router = match self.netinfo.get_ipv4_default_route().await {
             /* If the default route points out a different interface, then this is the default route */
@@ -825,30 +1251,97 @@ impl DhcpService {
             _ => None,
         };
 
-        let request = DHCPRequest {
+        let request = Arc::new(DHCPRequest {
             pkt: req,
             serverip: optional_dst.unwrap(),
             ifindex: intf,
+            if_name,
             if_mtu,
             if_router,
-        };
+        });
         log_pkt(&request, &self.netinfo).await;
 
+        /* If our failover peer is answering clients, then stay quiet */
+        if matches!(&self.failover, Some(f) if !f.is_serving()) {
+            return;
+        }
+
         /* Now, lets process the packet we've found */
-        let reply;
-        {
-            /* Limit the amount of time we have these locked to just handling the packet */
-            let mut pool = self.pool.lock().await;
-            let lockedconf = self.conf.read().await;
-
-            reply = match handle_pkt(
-                &mut pool,
-                &request,
-                get_serverids(&self.serverids).await,
-                &lockedconf,
-            )
-            .await
+        let mut probes = 0;
+        let reply = loop {
+            let (result, conflict_detection) = {
+                /* Limit the amount of time we have the config locked to just handling the packet */
+                let lockedconf = self.conf.clone().read_owned().await;
+                let base = [build_default_config(&lockedconf, &request).await];
+                let serverids = get_serverids(&self.serverids).await;
+                let conflict_detection = lockedconf.dhcp.conflict_detection.clone();
+                let request = request.clone();
+                (
+                    self.pool
+                        .call(move |pool| {
+                            Ok(handle_pkt_lease(
+                                pool,
+                                &request,
+                                &serverids,
+                                &base,
+                                &lockedconf,
+                            ))
+                        })
+                        .await
+                        .map_err(DhcpError::PoolError)
+                        .and_then(|result| result),
+                    conflict_detection,
+                )
+            };
+
+            /* Before offering an address that we've not given to this client before, check that
+             * nothing else is already using it.  This is done without the pool locked, as it can
+             * take a while.  If something answers, then take the address out of the pool and try
+             * again.
+             */
+            if let (Ok(Some((offer, Some(pool::LeaseType::NewAddress)))), Some(cd)) =
+                (&result, &conflict_detection)
             {
+                if probes < MAX_CONFLICT_PROBES {
+                    probes += 1;
+                    if probe::address_in_use(
+                        cd,
+                        &self.netinfo,
+                        intf,
+                        !request.pkt.giaddr.is_unspecified(),
+                        offer.yiaddr,
+                    )
+                    .await
+                    {
+                        log::warn!(
+                            "{}: Not offering {}, as something else is using it",
+                            format_client(&request.pkt),
+                            offer.yiaddr
+                        );
+                        DHCP_CONFLICTS.inc();
+                        let (addr, holddown) = (offer.yiaddr, cd.holddown);
+                        if let Err(e) = self
+                            .pool
+                            .call(move |pool| pool.conflict_address(addr, holddown))
+                            .await
+                        {
+                            log::warn!("Failed to mark {} as conflicted: {}", offer.yiaddr, e);
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            if let Ok(reply) = &result {
+                if let Some(notification) = hooks::Notification::from_reply(
+                    &request,
+                    reply.as_ref().map(|(reply, _)| reply),
+                ) {
+                    self.hooks.notify(notification);
+                }
+            }
+
+            break match result.map(|reply| reply.map(|(reply, _)| reply)) {
                 Err(e) => {
                     log::warn!(
                         "{}: Failed to handle {}: {}",
@@ -864,9 +1357,11 @@ impl DhcpService {
                     DHCP_ERRORS.with_label_values(&[e.get_variant_name()]).inc();
                     return;
                 }
-                Ok(r) => r,
+                /* Nothing to send */
+                Ok(None) => return,
+                Ok(Some(r)) => r,
             };
-        }
+        };
 
         /* Now, we should have a packet ready to send */
         /* First, if we're claiming to be particular IP, we should remember that as an IP that is one
@@ -897,6 +1392,34 @@ impl DhcpService {
         );
         log_options(&reply);
 
+        /* RFC2131 Section 4.1: If the 'giaddr' field in a DHCP message from a client is non-zero,
+         * the server sends any return messages to the 'DHCP server' port on the BOOTP relay agent
+         * whose address appears in 'giaddr'.
+         */
+        if !reply.giaddr.is_unspecified() {
+            let cmsg = udp::ControlMessage::new().set_send_from(Some(request.serverip.into()));
+            DHCP_TX_PACKETS.inc();
+            if let Err(e) = self
+                .listener
+                .send_msg(
+                    &reply.serialise_max(request.pkt.options.get_max_message_size()),
+                    &cmsg,
+                    udp::MsgFlags::empty(),
+                    Some(&reply.giaddr.with_port(67)),
+                )
+                .await
+            {
+                log::warn!(
+                    "{}: Failed to send reply to relay {}: {}",
+                    format_client(&reply),
+                    reply.giaddr,
+                    e
+                );
+                DHCP_ERRORS.with_label_values(&["SEND_ERROR"]).inc();
+            }
+            return;
+        }
+
         /* Collect metadata ready to send */
         let srcll = if let Some(erbium_net::netinfo::LinkLayer::Ethernet(srcll)) =
             self.netinfo.get_linkaddr_by_ifidx(intf).await
@@ -920,13 +1443,25 @@ impl DhcpService {
             return;
         };
 
+        let (dst, dstll) = if must_broadcast(&reply) {
+            (
+                *net::Ipv4Addr::BROADCAST
+                    .with_port(68)
+                    .as_sockaddr_in()
+                    .unwrap(),
+                [0xff; 6],
+            )
+        } else {
+            (*ip4, chaddr)
+        };
+
         /* Construct the raw packet from the reply to send */
-        let replybuf = reply.serialise();
+        let replybuf = reply.serialise_max(request.pkt.options.get_max_message_size());
         let etherbuf = packet::Fragment::new_udp4(
             *request.serverip.with_port(67).as_sockaddr_in().unwrap(),
             &srcll,
-            *ip4,
-            &chaddr,
+            dst,
+            &dstll,
             packet::Tail::Payload(&replybuf),
         )
         .flatten();
@@ -943,9 +1478,18 @@ impl DhcpService {
     ) -> Result<Self, RunError> {
         let rawsock =
             Arc::new(raw::RawSocket::new(raw::EthProto::ALL).map_err(RunError::ListenError)?);
-        let pool = Arc::new(sync::Mutex::new(
-            pool::Pool::new().map_err(RunError::PoolError)?,
-        ));
+        let mut pool = pool::Pool::open(&*conf.read().await).map_err(RunError::PoolError)?;
+        let failover = conf
+            .read()
+            .await
+            .dhcp
+            .failover
+            .clone()
+            .map(|f| Arc::new(failover::Failover::new(f)));
+        if let Some(f) = &failover {
+            pool.set_failover(f.clone());
+        }
+        let pool = worker::PoolWorker::new(pool);
         let serverids: SharedServerIds =
             Arc::new(sync::Mutex::new(std::collections::HashSet::new()));
         let listener = UdpSocket::bind(&[UNSPECIFIED4.with_port(67)])
@@ -966,6 +1510,8 @@ impl DhcpService {
             conf,
             rawsock,
             pool,
+            failover,
+            hooks: hooks::Hooks::new(),
             serverids,
             listener,
         })
@@ -1007,14 +1553,29 @@ impl DhcpService {
     }
 
     pub async fn run(self: std::sync::Arc<Self>) -> Result<(), String> {
-        match self.run_internal(&self.listener).await {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e.to_string()),
+        let failover = async {
+            match &self.failover {
+                Some(f) => f.run(&self.pool).await.map_err(|e| e.to_string()),
+                None => futures::future::pending().await,
+            }
+        };
+        tokio::select! {
+            ret = self.run_internal(&self.listener) => match ret {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            },
+            ret = failover => ret,
+            () = self.hooks.run(&self.conf, &self.pool) => Ok(()),
         }
     }
 
+    /// A handle to the lease pool, for other services that want to see the leases.
+    pub fn pool(&self) -> worker::PoolWorker {
+        self.pool.clone()
+    }
+
     pub async fn update_metrics(self: &std::sync::Arc<Self>) {
-        match self.pool.lock().await.get_pool_metrics() {
+        match self.pool.call(|pool| pool.get_pool_metrics()).await {
             Ok((in_use, expired)) => {
                 DHCP_ACTIVE_LEASES.set(in_use.into());
                 DHCP_EXPIRED_LEASES.set(expired.into());
@@ -1024,7 +1585,7 @@ impl DhcpService {
     }
 
     pub async fn get_leases(self: &std::sync::Arc<Self>) -> Vec<pool::LeaseInfo> {
-        let ret = self.pool.lock().await.get_leases();
+        let ret = self.pool.call(|pool| pool.get_leases()).await;
         match ret {
             Ok(l) => l,
             Err(e) => {
@@ -1033,6 +1594,41 @@ impl DhcpService {
             }
         }
     }
+
+    pub async fn reserve_lease(
+        self: &std::sync::Arc<Self>,
+        clientid: Vec<u8>,
+        addr: std::net::Ipv4Addr,
+        duration: std::time::Duration,
+    ) -> Result<pool::LeaseInfo, pool::Error> {
+        self.pool
+            .call(move |pool| pool.reserve_lease(&clientid, addr, duration))
+            .await
+    }
+
+    pub async fn extend_lease(
+        self: &std::sync::Arc<Self>,
+        addr: std::net::Ipv4Addr,
+        duration: std::time::Duration,
+    ) -> Result<pool::LeaseInfo, pool::Error> {
+        self.pool
+            .call(move |pool| pool.extend_lease(addr, duration))
+            .await
+    }
+
+    pub async fn expire_lease(
+        self: &std::sync::Arc<Self>,
+        addr: std::net::Ipv4Addr,
+    ) -> Result<pool::LeaseInfo, pool::Error> {
+        self.pool.call(move |pool| pool.expire_lease(addr)).await
+    }
+
+    pub async fn delete_lease(
+        self: &std::sync::Arc<Self>,
+        addr: std::net::Ipv4Addr,
+    ) -> Result<(), pool::Error> {
+        self.pool.call(move |pool| pool.delete_lease(addr)).await
+    }
 }
 
 #[test]
@@ -1118,6 +1714,7 @@ dhcp-policies:
             },
             serverip: "192.168.0.67".parse().unwrap(),
             ifindex: 1,
+            if_name: None,
             if_mtu: None,
             if_router: None,
         },
@@ -1191,7 +1788,7 @@ async fn test_defaults() {
     };
     let base = [build_default_config(&conf, &pkt).await];
     println!("base={:?}", base);
-    let resp =
+    let (resp, _) =
         handle_discover(&mut p, &pkt, &serverids, &base, &conf).expect("Failed to handle request");
     assert_eq!(
         resp.options
@@ -1253,6 +1850,7 @@ async fn test_base() {
                 apply_address: Some(apply_address),
                 ..Default::default()
             }],
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -1267,7 +1865,7 @@ async fn test_base() {
         .contains(&"192.0.2.3".parse().unwrap()));
     println!("base={:#?}", base);
     println!("pkt={:?}", pkt);
-    let resp = handle_discover(&mut pool, &pkt, &serverids, &[base], &conf)
+    let (resp, _) = handle_discover(&mut pool, &pkt, &serverids, &[base], &conf)
         .expect("Failed to handle request");
     assert_eq!(
         resp.options
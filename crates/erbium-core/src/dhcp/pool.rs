@@ -17,15 +17,192 @@
  *  DHCP Pool Management.
  */
 
-use rusqlite::OptionalExtension;
+use super::storage::LeaseStore;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::hash::Hash;
 use std::hash::Hasher;
 
 pub const DEFAULT_MIN_LEASE: std::time::Duration = std::time::Duration::from_secs(300);
 pub const DEFAULT_MAX_LEASE: std::time::Duration = std::time::Duration::from_secs(86400);
+/* How long an address that a client has declined is kept out of the pool. */
+pub const DEFAULT_DECLINE_HOLDDOWN: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// A set of IPv4 addresses, kept as a sorted list of ranges so that large prefixes are cheap to
+/// store, and finding an address is O(log n).
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct PoolAddresses {
+    /* Inclusive ranges, sorted, and with no overlapping or adjacent ranges */
+    ranges: Vec<(u32, u32)>,
+    /* How many addresses there are in all the ranges before each range */
+    offsets: Vec<u64>,
+}
+
+impl PoolAddresses {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// All the addresses from `start` to `end` inclusive.
+    pub fn from_range(start: std::net::Ipv4Addr, end: std::net::Ipv4Addr) -> Self {
+        Self::from_ranges(vec![(start.into(), end.into())])
+    }
+
+    fn from_ranges(mut ranges: Vec<(u32, u32)>) -> Self {
+        ranges.retain(|(start, end)| start <= end);
+        ranges.sort_unstable();
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some((_, last)) if u64::from(start) <= u64::from(*last) + 1 => {
+                    *last = std::cmp::max(*last, end)
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        let offsets = merged
+            .iter()
+            .scan(0, |count, (start, end)| {
+                let offset = *count;
+                *count += u64::from(end - start) + 1;
+                Some(offset)
+            })
+            .collect();
+        PoolAddresses {
+            ranges: merged,
+            offsets,
+        }
+    }
+
+    pub fn insert(&mut self, addr: std::net::Ipv4Addr) {
+        self.insert_range(addr, addr)
+    }
+
+    pub fn insert_range(&mut self, start: std::net::Ipv4Addr, end: std::net::Ipv4Addr) {
+        let mut ranges = std::mem::take(&mut self.ranges);
+        ranges.push((start.into(), end.into()));
+        *self = Self::from_ranges(ranges);
+    }
+
+    pub fn extend(&mut self, other: &PoolAddresses) {
+        let mut ranges = std::mem::take(&mut self.ranges);
+        ranges.extend_from_slice(&other.ranges);
+        *self = Self::from_ranges(ranges);
+    }
+
+    pub fn remove(&mut self, addr: std::net::Ipv4Addr) {
+        *self = self.difference(&Self::from_range(addr, addr));
+    }
+
+    /// Returns the addresses in this set that are not in `other`.
+    pub fn difference(&self, other: &PoolAddresses) -> PoolAddresses {
+        let mut ranges = vec![];
+        let mut skip = 0;
+        for &(start, end) in &self.ranges {
+            /* Ranges that end before this one starts can't overlap any later range either */
+            while matches!(other.ranges.get(skip), Some(&(_, oend)) if oend < start) {
+                skip += 1;
+            }
+            let mut next = u64::from(start);
+            for &(ostart, oend) in &other.ranges[skip..] {
+                if ostart > end {
+                    break;
+                }
+                if u64::from(ostart) > next {
+                    ranges.push((next as u32, ostart - 1));
+                }
+                next = std::cmp::max(next, u64::from(oend) + 1);
+            }
+            if next <= u64::from(end) {
+                ranges.push((next as u32, end));
+            }
+        }
+        Self::from_ranges(ranges)
+    }
+
+    pub fn contains(&self, addr: &std::net::Ipv4Addr) -> bool {
+        let addr = u32::from(*addr);
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < addr {
+                    std::cmp::Ordering::Less
+                } else if start > addr {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn len(&self) -> u64 {
+        match (self.ranges.last(), self.offsets.last()) {
+            (Some((start, end)), Some(offset)) => offset + u64::from(end - start) + 1,
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the `index`th lowest address.
+    pub fn get(&self, index: u64) -> Option<std::net::Ipv4Addr> {
+        if index >= self.len() {
+            return None;
+        }
+        let i = match self.offsets.binary_search(&index) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        Some((self.ranges[i].0 + (index - self.offsets[i]) as u32).into())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = std::net::Ipv4Addr> + '_ {
+        self.ranges
+            .iter()
+            .flat_map(|&(start, end)| (start..=end).map(std::net::Ipv4Addr::from))
+    }
+
+    /* The ranges in order starting from `addr` (which must be in the set), wrapping around at the
+     * end, and finishing just before `addr`.
+     */
+    fn ranges_from(&self, addr: u32) -> Vec<(u32, u32)> {
+        let i = self.ranges.partition_point(|&(_, end)| end < addr);
+        let (start, end) = self.ranges[i];
+        let mut ranges = vec![(addr, end)];
+        ranges.extend_from_slice(&self.ranges[i + 1..]);
+        ranges.extend_from_slice(&self.ranges[..i]);
+        if start < addr {
+            ranges.push((start, addr - 1));
+        }
+        ranges
+    }
+}
+
+impl std::fmt::Debug for PoolAddresses {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.ranges.iter().map(|&(start, end)| {
+                format!(
+                    "{}-{}",
+                    std::net::Ipv4Addr::from(start),
+                    std::net::Ipv4Addr::from(end)
+                )
+            }))
+            .finish()
+    }
+}
 
-pub type PoolAddresses = std::collections::HashSet<std::net::Ipv4Addr>;
+impl std::iter::FromIterator<std::net::Ipv4Addr> for PoolAddresses {
+    fn from_iter<I: IntoIterator<Item = std::net::Ipv4Addr>>(iter: I) -> Self {
+        Self::from_ranges(
+            iter.into_iter()
+                .map(|addr| (addr.into(), addr.into()))
+                .collect(),
+        )
+    }
+}
 
 #[derive(Debug)]
 pub enum LeaseType {
@@ -42,7 +219,7 @@ pub struct Lease {
     pub lease_type: LeaseType,
 }
 
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub struct LeaseInfo {
     pub ip: std::net::Ipv4Addr,
     pub client_id: Vec<u8>,
@@ -51,16 +228,160 @@ pub struct LeaseInfo {
     pub options: Vec<u8>,
 }
 
+/* Which addresses have leases that haven't expired, kept as runs of consecutive addresses so that
+ * the first free address from any point can be found in O(log n).
+ */
+#[derive(Default)]
+struct InUse {
+    /* Inclusive runs of leased addresses, from start to end, with no adjacent runs */
+    runs: BTreeMap<u32, u32>,
+    expiry: HashMap<u32, u32>,
+    /* The same leases ordered by (expiry, address), so expired leases can be found quickly */
+    by_expiry: BTreeSet<(u32, u32)>,
+}
+
+impl InUse {
+    fn run_containing(&self, addr: u32) -> Option<(u32, u32)> {
+        self.runs
+            .range(..=addr)
+            .next_back()
+            .map(|(&start, &end)| (start, end))
+            .filter(|&(_, end)| end >= addr)
+    }
+
+    fn update(&mut self, addr: u32, expire: Option<u32>) {
+        if let Some(old) = self.expiry.remove(&addr) {
+            self.by_expiry.remove(&(old, addr));
+        } else if expire.is_some() {
+            /* Join up with the runs either side */
+            let mut start = addr;
+            let mut end = addr;
+            if let Some((before, _)) = addr.checked_sub(1).and_then(|a| self.run_containing(a)) {
+                self.runs.remove(&before);
+                start = before;
+            }
+            if let Some(after) = addr.checked_add(1).and_then(|a| self.runs.remove(&a)) {
+                end = after;
+            }
+            self.runs.insert(start, end);
+        }
+        match expire {
+            Some(expire) => {
+                self.expiry.insert(addr, expire);
+                self.by_expiry.insert((expire, addr));
+            }
+            None => {
+                if let Some((start, end)) = self.run_containing(addr) {
+                    self.runs.remove(&start);
+                    if start < addr {
+                        self.runs.insert(start, addr - 1);
+                    }
+                    if addr < end {
+                        self.runs.insert(addr + 1, end);
+                    }
+                }
+            }
+        }
+    }
+
+    /* Leases are in use up to and including their expiry time */
+    fn expire(&mut self, ts: u32) {
+        while let Some(&(expire, addr)) = self.by_expiry.iter().next() {
+            if expire >= ts {
+                break;
+            }
+            self.update(addr, None);
+        }
+    }
+
+    fn first_free(&self, addr: u32) -> Option<u32> {
+        match self.run_containing(addr) {
+            Some((_, end)) => end.checked_add(1),
+            None => Some(addr),
+        }
+    }
+}
+
+/* The lease store, along with an index of the addresses in use that is built the first time it's
+ * needed, and kept up to date with every change after that.
+ */
+struct IndexedStore {
+    store: Box<dyn LeaseStore>,
+    in_use: Option<InUse>,
+    /* Counts every change, so that anything derived from the leases knows when to look again */
+    changes: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl IndexedStore {
+    /* Returns the first address from `addr` onwards that isn't leased at `ts`. */
+    fn first_free(&mut self, addr: u32, ts: u32) -> Result<Option<u32>, Error> {
+        let in_use = match &mut self.in_use {
+            Some(in_use) => in_use,
+            None => {
+                let mut in_use = InUse::default();
+                for lease in self.store.get_all()? {
+                    in_use.update(lease.ip.into(), Some(lease.expire));
+                }
+                self.in_use.insert(in_use)
+            }
+        };
+        in_use.expire(ts);
+        Ok(in_use.first_free(addr))
+    }
+}
+
+impl LeaseStore for IndexedStore {
+    fn get(&mut self, addr: std::net::Ipv4Addr) -> Result<Option<LeaseInfo>, Error> {
+        self.store.get(addr)
+    }
+    fn get_by_client(&mut self, client_id: &[u8]) -> Result<Vec<LeaseInfo>, Error> {
+        self.store.get_by_client(client_id)
+    }
+    fn get_all(&mut self) -> Result<Vec<LeaseInfo>, Error> {
+        self.store.get_all()
+    }
+    fn put(&mut self, lease: &LeaseInfo) -> Result<(), Error> {
+        self.store.put(lease)?;
+        if let Some(in_use) = &mut self.in_use {
+            in_use.update(lease.ip.into(), Some(lease.expire));
+        }
+        self.changes
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+    fn delete(&mut self, addr: std::net::Ipv4Addr) -> Result<(), Error> {
+        self.store.delete(addr)?;
+        if let Some(in_use) = &mut self.in_use {
+            in_use.update(addr.into(), None);
+        }
+        self.changes
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+    fn begin(&mut self) -> Result<(), Error> {
+        self.store.begin()
+    }
+    fn commit(&mut self) -> Result<(), Error> {
+        self.store.commit()
+    }
+}
+
 pub struct Pool {
-    conn: rusqlite::Connection,
+    store: IndexedStore,
+    failover: Option<std::sync::Arc<super::failover::Failover>>,
+    /* Updates for the failover peer, held back until the batch they're in has been written */
+    unsent: Option<Vec<LeaseInfo>>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
     DbError(String),
     CorruptDatabase(String),
     NoAssignableAddress,
     RequestedAddressInUse,
+    NoSuchLease,
+    PeerAddress,
+    InvalidDuration,
 }
 
 impl std::fmt::Display for Error {
@@ -70,6 +391,9 @@ impl std::fmt::Display for Error {
             Error::CorruptDatabase(s) => write!(f, "Corrupt Database: {}", s),
             Error::NoAssignableAddress => write!(f, "No Assignable Address"),
             Error::RequestedAddressInUse => write!(f, "Requested address is in use"),
+            Error::NoSuchLease => write!(f, "No such lease"),
+            Error::PeerAddress => write!(f, "Address belongs to the failover peer"),
+            Error::InvalidDuration => write!(f, "Lease would last beyond 2106"),
         }
     }
 }
@@ -77,11 +401,31 @@ impl std::fmt::Display for Error {
 impl std::error::Error for Error {}
 
 impl Error {
-    fn emit(reason: &str, e: &rusqlite::Error) -> Error {
+    pub(crate) fn emit(reason: &str, e: &rusqlite::Error) -> Error {
         Error::DbError(format!("{} ({})", reason, e))
     }
 }
 
+/* Leases store their expiry as a u32, so a duration that would go past that is refused. */
+fn expiry_after(ts: u64, duration: std::time::Duration) -> Result<u32, Error> {
+    use std::convert::TryFrom as _;
+    ts.checked_add(duration.as_secs())
+        .and_then(|expire| u32::try_from(expire).ok())
+        .ok_or(Error::InvalidDuration)
+}
+
+/* Clients that don't send a client identifier are known by their hardware address, but
+ * reservations by MAC address are made for the ethernet hardware type followed by the MAC, which
+ * is what most clients that do send one use.  Both refer to the same client.
+ */
+fn ethernet_client_id(clientid: &[u8]) -> Option<Vec<u8>> {
+    if clientid.len() == 6 {
+        Some([&[1u8][..], clientid].concat())
+    } else {
+        None
+    }
+}
+
 fn calculate_hash<S: Hash, T: Hash>(s: &S, t: &T) -> u64 {
     let mut h = DefaultHasher::new();
     s.hash(&mut h);
@@ -90,168 +434,150 @@ fn calculate_hash<S: Hash, T: Hash>(s: &S, t: &T) -> u64 {
 }
 
 impl Pool {
-    // - upgrade_schema_from_no_version should install the latest schema
-    //   directly so that new installations need not go through the
-    //   upgrade chain.
-    // - upgrade_schema_from_version_* should perform upgrades one
-    //   version at a time since that is the only thing that is tested.
-
-    fn upgrade_schema_from_no_version(&self) -> Result<usize, Error> {
-        if self
-            .conn
-            .query_row("SELECT 1 FROM leases LIMIT 1", rusqlite::params![], |_| {
-                Ok(())
-            })
-            .optional()
-            .err()
-            .is_none()
-        {
-            // This is not a fresh new database but just one with
-            // the schema_version missing. We know that this is the
-            // same as version 0.
-            return Ok(0);
+    pub fn new_with_store(store: Box<dyn LeaseStore>) -> Pool {
+        Pool {
+            store: IndexedStore {
+                store,
+                in_use: None,
+                changes: Default::default(),
+            },
+            failover: None,
+            unsent: None,
         }
-        // Else, probably a brand new database. Create it directly
-        // with the latest version.
-        self.conn
-            .execute(
-                "CREATE TABLE leases (
-                address TEXT NOT NULL,
-                chaddr BLOB,
-                clientid BLOB,
-                start INTEGER NOT NULL,
-                expiry INTEGER NOT NULL,
-                options BLOB,
-                PRIMARY KEY (address)
-              )",
-                rusqlite::params![],
-            )
-            .map_err(|e| Error::emit("Creating table leases", &e))?;
-        Ok(1)
     }
 
-    fn upgrade_schema_from_version_0(&self) -> Result<usize, Error> {
-        self.conn
-            .execute(
-                "ALTER TABLE leases ADD COLUMN options BLOB",
-                rusqlite::params![],
-            )
-            .map_err(|e| Error::emit("Upgrading to schema version 1", &e))?;
-        Ok(1)
-    }
-
-    fn setup_db(self) -> Result<Self, Error> {
-        // Dummy primary key for the schema_version table.
-        // If the same sqlite database were used by another module,
-        // that module could use a different key within the same schema_version
-        // table to track the schema of its own table(s).
-        const DB_SCHEMA_KEY: &str = "pool";
-
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS schema_version (
-                    key TEXT NOT NULL,
-                    version INTEGER NOT NULL,
-                    PRIMARY KEY (key)
-                )",
-                rusqlite::params![],
-            )
-            .map_err(|e| Error::emit("Creating table schema_version", &e))?;
-
-        loop {
-            let upgraded_to_version = match self.conn
-                .query_row(
-                    "SELECT version FROM schema_version
-                        WHERE key = ?1",
-                    rusqlite::params![DB_SCHEMA_KEY],
-                    |row| row.get(0),
-                )
-                .optional()
-                .map_err(|e| Error::emit("Querying schema version", &e))?
-            {
-                None => self.upgrade_schema_from_no_version()?,
-                Some(0) => self.upgrade_schema_from_version_0()?,
-                Some(1) => break,  // up to date
-                Some(v) => return Err(Error::DbError(format!(
-                    "Lease database has version {} which is newer than 1, the newest supported version",
-                    v
-                ))),
-            };
-            self.conn
-                .execute(
-                    "INSERT OR REPLACE INTO schema_version (key, version)
-                 VALUES (?1, ?2)",
-                    rusqlite::params![DB_SCHEMA_KEY, upgraded_to_version],
-                )
-                .map_err(|e| Error::emit("Creating updating schema version", &e))?;
+    //#[cfg(any(test, fuzzing))]
+    pub fn new_in_memory() -> Result<Pool, Error> {
+        Ok(Self::new_with_store(Box::new(
+            super::storage::SqliteStore::new_in_memory()?,
+        )))
+    }
+
+    /// Opens the lease storage configured in `conf`.
+    pub fn open(conf: &crate::config::Config) -> Result<Pool, Error> {
+        Ok(Self::new_with_store(super::storage::open(conf)?))
+    }
+
+    /// A counter that goes up every time a lease is changed.
+    pub fn changes(&self) -> std::sync::Arc<std::sync::atomic::AtomicU64> {
+        self.store.changes.clone()
+    }
+
+    /// Starts grouping changes together, so they can be written to storage all at once by
+    /// commit_batch.
+    pub fn begin_batch(&mut self) -> Result<(), Error> {
+        self.unsent = Some(vec![]);
+        self.store.begin()
+    }
+
+    /// Makes sure every change since begin_batch has been written to storage.
+    pub fn commit_batch(&mut self) -> Result<(), Error> {
+        self.store.commit()?;
+        let unsent = self.unsent.take().unwrap_or_default();
+        if let Some(failover) = &self.failover {
+            for lease in unsent {
+                failover.lease_updated(lease);
+            }
         }
-        Ok(self)
+        Ok(())
     }
 
-    fn new_with_conn(conn: rusqlite::Connection) -> Result<Self, Error> {
-        Pool { conn }.setup_db()
+    /// Forgets about a batch that couldn't be written to storage.  Changes made during the batch
+    /// may have been lost, so nothing that was remembered about them can be trusted.
+    pub fn abort_batch(&mut self) {
+        self.unsent = None;
+        self.store.in_use = None;
+        self.store
+            .changes
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
 
-    //#[cfg(any(test, fuzzing))]
-    pub fn new_in_memory() -> Result<Pool, Error> {
-        let conn = rusqlite::Connection::open_in_memory()
-            .map_err(|e| Error::emit("Creating database in memory database", &e))?;
+    /// Shares all changes to leases with a failover peer, and restricts which addresses can be
+    /// handed out based on the failover state.
+    pub fn set_failover(&mut self, failover: std::sync::Arc<super::failover::Failover>) {
+        self.failover = Some(failover);
+    }
+
+    /* Returns true if this server can hand out `addr` to a client that doesn't already have it */
+    fn may_allocate(&self, addr: std::net::Ipv4Addr) -> bool {
+        self.failover
+            .as_ref()
+            .map(|f| f.may_allocate(addr))
+            .unwrap_or(true)
+    }
 
-        Self::new_with_conn(conn)
+    /* Returns true if no new addresses can be handed out at all right now */
+    fn recovering(&self) -> bool {
+        self.failover
+            .as_ref()
+            .map(|f| f.state() == super::failover::State::Recover)
+            .unwrap_or(false)
     }
 
-    pub fn new() -> Result<Pool, Error> {
-        let conn = rusqlite::Connection::open("/var/lib/erbium/leases.sqlite")
-            .map_err(|e| Error::emit("Creating database /var/lib/erbium/leases.sqlite", &e))?;
+    fn notify_failover(&mut self, addr: std::net::Ipv4Addr) -> Result<(), Error> {
+        if self.failover.is_some() {
+            if let Some(lease) = self.get_lease(addr)? {
+                self.send_failover(lease);
+            }
+        }
+        Ok(())
+    }
 
-        Self::new_with_conn(conn)
+    /* Tells the failover peer about a lease, once it's been written to storage */
+    fn send_failover(&mut self, lease: LeaseInfo) {
+        match (&mut self.unsent, &self.failover) {
+            (Some(unsent), Some(_)) => unsent.push(lease),
+            (None, Some(failover)) => failover.lease_updated(lease),
+            (_, None) => (),
+        }
     }
 
+    /// Returns the number of leases that are in use, and the number that have expired.
     pub fn get_pool_metrics(&mut self) -> Result<(u32, u32), Error> {
-        let ts: u32 = std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .expect("clock failure")
-            .as_secs() as u32;
-        self.conn
-            .query_row(
-                "SELECT
-             SUM(CASE WHEN expiry < ?1 THEN 1 ELSE 0 END) as active,
-             SUM(CASE WHEN expiry >= ?1 THEN 1 ELSE 0 END) as expired
-             FROM leases",
-                rusqlite::params![ts],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
-            .map_err(|e| Error::DbError(e.to_string()))
+        let ts = now() as u32;
+        let leases = self.store.get_all()?;
+        let active = leases.iter().filter(|l| l.expire >= ts).count() as u32;
+        Ok((active, leases.len() as u32 - active))
     }
 
     pub fn get_leases(&mut self) -> Result<Vec<LeaseInfo>, Error> {
-        self.conn
-            .prepare_cached(
-                "SELECT
-                  address,
-                  clientid,
-                  start,
-                  expiry,
-                  options
-                 FROM
-                  leases",
-            )
-            .map_err(|e| Error::DbError(e.to_string()))?
-            .query_map([], |row| {
-                Ok(LeaseInfo {
-                    ip: row
-                        .get::<_, String>(0)?
-                        .parse::<std::net::Ipv4Addr>()
-                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
-                    client_id: row.get(1)?,
-                    start: row.get(2)?,
-                    expire: row.get(3)?,
-                    options: row.get::<usize, Option<Vec<u8>>>(4)?.unwrap_or_default(),
-                })
-            })
-            .map_err(|e| Error::DbError(e.to_string()))?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| Error::DbError(e.to_string()))
+        self.store.get_all()
+    }
+
+    fn get_lease(&mut self, addr: std::net::Ipv4Addr) -> Result<Option<LeaseInfo>, Error> {
+        self.store.get(addr)
+    }
+
+    /* Leases are in use up to and including their expiry time */
+    fn in_use(&mut self, addr: std::net::Ipv4Addr, ts: u32) -> Result<bool, Error> {
+        Ok(self
+            .get_lease(addr)?
+            .map(|lease| lease.expire >= ts)
+            .unwrap_or(false))
+    }
+
+    /// Replaces our lease for an address with one from the failover peer.
+    pub fn update_lease(&mut self, lease: &LeaseInfo) -> Result<(), Error> {
+        self.store.put(lease)
+    }
+
+    /// Combines a lease from the failover peer with ours, after we may have both been handing out
+    /// addresses without being able to tell each other.  Whichever lease lasts longer is kept, so
+    /// an address that either of us thinks is in use stays in use.
+    pub fn merge_lease(&mut self, lease: &LeaseInfo) -> Result<(), Error> {
+        match self.get_lease(lease.ip)? {
+            Some(ours) if ours.expire >= lease.expire => Ok(()),
+            Some(ours) => {
+                if ours.client_id != lease.client_id && ours.expire as u64 > now() {
+                    log::warn!(
+                        "{} was leased to different clients by both failover peers",
+                        lease.ip
+                    );
+                }
+                self.update_lease(lease)
+            }
+            None => self.update_lease(lease),
+        }
     }
 
     fn select_requested_address(
@@ -262,21 +588,9 @@ impl Pool {
     ) -> Result<Lease, Error> {
         if !addresses.contains(&requested) {
             Err(Error::NoAssignableAddress)
-        } else if self
-            .conn
-            .query_row(
-                "SELECT
-                      true
-                     FROM
-                      leases
-                     WHERE expiry >= ?1
-                     AND address = ?2",
-                rusqlite::params![ts, requested.to_string()],
-                |_row| Ok(Some(())),
-            )
-            .or_else(map_no_row_to_none)?
-            .is_none()
-        {
+        } else if !self.may_allocate(requested) {
+            Err(Error::PeerAddress)
+        } else if !self.in_use(requested, ts)? {
             Ok(Lease {
                 ip: requested,
                 expire: std::time::Duration::from_secs(0), /* We rely on the min_lease_time below */
@@ -293,42 +607,32 @@ impl Pool {
         addresses: &PoolAddresses,
         clientid: &[u8],
     ) -> Result<Lease, Error> {
-        /* This performs a consistent hash of the clientid and the IP addresses
-         * then orders by the distance from the hash of the clientid
+        /* Start from an address chosen by hashing the clientid, so that a client tends to get the
+         * same address back even if its old lease has been forgotten, then take the first free
+         * address from there.
          */
-        let clienthash = calculate_hash(&0, &clientid);
-        let mut addresses = addresses
-            .iter()
-            .map(|ip| (calculate_hash(&clienthash, ip), ip))
-            .collect::<Vec<_>>();
-        addresses.sort_unstable();
-        let addresses = addresses
-            .iter()
-            .map(|(_dist, ip)| ip)
-            .copied()
-            .collect::<Vec<_>>();
-        /* Now for each address, see if it's in use, and if so, return it */
-        for i in addresses {
-            if self
-                .conn
-                .query_row(
-                    "SELECT
-                      true
-                     FROM
-                      leases
-                     WHERE expiry >= ?1
-                     AND address = ?2",
-                    rusqlite::params![ts, i.to_string()],
-                    |_row| Ok(Some(())),
-                )
-                .or_else(map_no_row_to_none)?
-                .is_none()
-            {
-                return Ok(Lease {
-                    ip: *i,
-                    expire: std::time::Duration::from_secs(0), /* We rely on the min_lease_time below */
-                    lease_type: LeaseType::NewAddress,
-                });
+        let count = addresses.len();
+        if count == 0 || self.recovering() {
+            return Err(Error::NoAssignableAddress);
+        }
+        let first = addresses
+            .get(calculate_hash(&0, &clientid) % count)
+            .expect("address index out of range");
+        for (start, end) in addresses.ranges_from(first.into()) {
+            let mut addr = start;
+            while let Some(free) = self.store.first_free(addr, ts)?.filter(|&free| free <= end) {
+                let ip = free.into();
+                if self.may_allocate(ip) {
+                    return Ok(Lease {
+                        ip,
+                        expire: std::time::Duration::from_secs(0), /* We rely on the min_lease_time below */
+                        lease_type: LeaseType::NewAddress,
+                    });
+                }
+                match free.checked_add(1) {
+                    Some(next) => addr = next,
+                    None => break,
+                }
             }
         }
         Err(Error::NoAssignableAddress)
@@ -340,61 +644,34 @@ impl Pool {
         requested: Option<std::net::Ipv4Addr>,
         addresses: &PoolAddresses,
     ) -> Result<Lease, Error> {
-        let ts = std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .expect("clock failure")
-            .as_secs();
+        let ts = now();
+        /* The client's leases, with any lease on the address it asked for first, then the most
+         * recent.
+         */
+        let mut leases = self.store.get_by_client(clientid)?;
+        if let Some(ethernet) = ethernet_client_id(clientid) {
+            leases.extend(self.store.get_by_client(&ethernet)?);
+        }
+        leases.sort_by_key(|lease| (Some(lease.ip) != requested, std::cmp::Reverse(lease.expire)));
+
         /* RFC2131 Section 4.3.1:
          * If an address is available, the new address SHOULD be chosen as follows:
          *
          * o The client's current address as recorded in the client's current
          *   binding, ELSE */
-        if let Some(lease) = self
-            .conn
-            .query_row(
-                "SELECT
-               address,
-               expiry,
-               start
-             FROM
-               leases
-             WHERE clientid = ?1
-             AND expiry > ?2
-             ORDER BY
-              address=?3 DESC,
-              expiry DESC
-             LIMIT 1",
-                rusqlite::params![
-                    clientid,
-                    ts as u32,
-                    requested
-                        .map(|ip| ip.to_string())
-                        .unwrap_or_else(|| "".into())
-                ],
-                |row| {
-                    Ok(Some((
-                        row.get::<usize, String>(0)?,
-                        row.get::<usize, u32>(1)?,
-                        row.get::<usize, u32>(2)?,
-                    )))
-                },
-            )
-            .or_else(map_no_row_to_none)?
-        {
-            if let Ok(ip) = lease.0.parse::<std::net::Ipv4Addr>() {
-                if addresses.contains(&ip) {
-                    // We want leases to double in size.  But normally you renew your
-                    // lease at ½ the duration.  We don't want to always just double
-                    // the lease, because you can accidentally end up with a ridiculously
-                    // long lease if you renew rapidly.
-                    // So instead we just use 3*renew.
-                    let expiry = (ts as u32).saturating_sub(lease.2).saturating_mul(3);
-                    return Ok(Lease {
-                        ip,
-                        expire: std::time::Duration::from_secs(expiry.into()),
-                        lease_type: LeaseType::ReusingLease,
-                    });
-                }
+        if let Some(lease) = leases.iter().find(|lease| lease.expire as u64 > ts) {
+            if addresses.contains(&lease.ip) {
+                // We want leases to double in size.  But normally you renew your
+                // lease at ½ the duration.  We don't want to always just double
+                // the lease, because you can accidentally end up with a ridiculously
+                // long lease if you renew rapidly.
+                // So instead we just use 3*renew.
+                let expiry = (ts as u32).saturating_sub(lease.start).saturating_mul(3);
+                return Ok(Lease {
+                    ip: lease.ip,
+                    expire: std::time::Duration::from_secs(expiry.into()),
+                    lease_type: LeaseType::ReusingLease,
+                });
             }
         }
 
@@ -402,50 +679,20 @@ impl Pool {
          * expired or released) binding, if that address is in the server's
          * pool of available addresses and not already allocated, ELSE */
 
-        if let Some(lease) = self
-            .conn
-            .query_row(
-                "SELECT
-               address,
-               start,
-               max(expiry) as expire_time
-             FROM
-               leases
-             WHERE clientid = ?1
-             GROUP BY 1
-             ORDER BY
-               address=?2 DESC,
-               expire_time DESC
-             LIMIT 1
-             ",
-                rusqlite::params![
-                    clientid,
-                    requested
-                        .map(|ip| ip.to_string())
-                        .unwrap_or_else(|| "".into())
-                ],
-                |row| {
-                    Ok(Some((
-                        row.get::<usize, String>(0)?,
-                        row.get::<usize, u32>(1)?,
-                        row.get::<usize, u32>(2)?,
-                    )))
-                },
-            )
-            .or_else(map_no_row_to_none)?
-        {
-            if let Ok(ip) = lease.0.parse::<std::net::Ipv4Addr>() {
-                if addresses.contains(&ip) {
-                    return Ok(Lease {
-                        ip,
-                        /* If a device is constantly asking for the same lease, we should double
-                         * the lease time.  This means transient devices get short leases, and
-                         * devices that are more permanent get longer leases.
-                         */
-                        expire: std::time::Duration::from_secs(2 * (lease.2 - lease.1) as u64),
-                        lease_type: LeaseType::Revived,
-                    });
-                }
+        if let Some(lease) = leases.first() {
+            /* The failover peer may have handed the address out since */
+            if addresses.contains(&lease.ip) && self.may_allocate(lease.ip) {
+                return Ok(Lease {
+                    ip: lease.ip,
+                    /* If a device is constantly asking for the same lease, we should double
+                     * the lease time.  This means transient devices get short leases, and
+                     * devices that are more permanent get longer leases.
+                     */
+                    expire: std::time::Duration::from_secs(
+                        2 * lease.expire.saturating_sub(lease.start) as u64,
+                    ),
+                    lease_type: LeaseType::Revived,
+                });
             }
         }
 
@@ -456,6 +703,7 @@ impl Pool {
             match self.select_requested_address(addr, ts as u32, addresses) {
                 Err(Error::NoAssignableAddress) => (),
                 Err(Error::RequestedAddressInUse) => (),
+                Err(Error::PeerAddress) => (),
                 x => return x,
             }
         }
@@ -479,6 +727,64 @@ impl Pool {
     ) -> Result<Lease, Error> {
         let lease = self.select_address(clientid, requested, addresses)?;
 
+        self.commit_lease(
+            clientid,
+            lease,
+            min_expire_time,
+            max_expire_time,
+            raw_options,
+        )
+    }
+
+    /// Allocates exactly the requested address to the client.  Unlike allocate_address this will
+    /// not fall back to some other address, but instead fails without touching the database.
+    /// This is used for DHCPREQUESTs, where the client is asking to confirm an address it
+    /// believes it has.
+    pub fn allocate_requested_address(
+        &mut self,
+        clientid: &[u8],
+        requested: std::net::Ipv4Addr,
+        addresses: &PoolAddresses,
+        min_expire_time: std::time::Duration,
+        max_expire_time: std::time::Duration,
+        raw_options: &[u8],
+    ) -> Result<Lease, Error> {
+        if !addresses.contains(&requested) {
+            return Err(Error::NoAssignableAddress);
+        }
+
+        let lease = self.select_address(clientid, Some(requested), addresses)?;
+        if lease.ip != requested {
+            /* If the address belongs to our failover peer, then leave it to the peer to answer. */
+            if !self.may_allocate(requested) {
+                return Err(Error::PeerAddress);
+            }
+            /* Either someone else has this address, or this client has a different one. */
+            return Err(Error::RequestedAddressInUse);
+        }
+
+        self.commit_lease(
+            clientid,
+            lease,
+            min_expire_time,
+            max_expire_time,
+            raw_options,
+        )
+    }
+
+    fn commit_lease(
+        &mut self,
+        clientid: &[u8],
+        lease: Lease,
+        min_expire_time: std::time::Duration,
+        max_expire_time: std::time::Duration,
+        raw_options: &[u8],
+    ) -> Result<Lease, Error> {
+        /* While the failover peer can't be told about leases, keep them short */
+        let max_expire_time = match self.failover.as_ref().and_then(|f| f.max_lease()) {
+            Some(max_lease) => std::cmp::min(max_expire_time, max_lease),
+            None => max_expire_time,
+        };
         let lease = Lease {
             expire: std::cmp::min(
                 std::cmp::max(lease.expire, min_expire_time),
@@ -487,29 +793,179 @@ impl Pool {
             ..lease
         };
 
-        let ts = std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .expect("clock failure")
-            .as_secs();
-
-        self.conn
-            .execute(
-                "INSERT OR REPLACE
-                 INTO leases (address, clientid, start, expiry, options)
-                 VALUES (?1, ?2, ?3, ?4, ?5)",
-                rusqlite::params![
-                    lease.ip.to_string(),
-                    clientid,
-                    ts as u32,
-                    (ts + lease.expire.as_secs()) as u32,
-                    raw_options,
-                ],
-            )
+        let ts = now();
+
+        self.store
+            .put(&LeaseInfo {
+                ip: lease.ip,
+                client_id: clientid.to_vec(),
+                start: ts as u32,
+                expire: (ts + lease.expire.as_secs()) as u32,
+                options: raw_options.to_vec(),
+            })
             .map_err(|e| Error::DbError(format!("Failed to update lease: {}", e)))?;
+        self.notify_failover(lease.ip)?;
 
         Ok(lease)
     }
 
+    /// Marks a lease as expired, as per RFC2131 Section 4.3.4.  The lease is kept so that if the
+    /// client comes back it can be given the same address again.
+    pub fn release_address(
+        &mut self,
+        clientid: &[u8],
+        addr: std::net::Ipv4Addr,
+    ) -> Result<(), Error> {
+        /* Leases are considered in use up to and including their expiry time, so set the expiry
+         * to one second ago.
+         */
+        let expire = (now() as u32).saturating_sub(1);
+
+        match self.get_lease(addr)? {
+            Some(lease) if lease.client_id == clientid && lease.expire > expire => {
+                self.store
+                    .put(&LeaseInfo { expire, ..lease })
+                    .map_err(|e| Error::DbError(format!("Failed to release lease: {}", e)))?;
+                self.notify_failover(addr)
+            }
+            _ => Err(Error::NoSuchLease),
+        }
+    }
+
+    /// Takes an address that a client has told us is in use by someone else out of the pool for
+    /// `holddown`.  The lease is disassociated from the client, so it will not be handed back to
+    /// them either.
+    pub fn decline_address(
+        &mut self,
+        clientid: &[u8],
+        addr: std::net::Ipv4Addr,
+        holddown: std::time::Duration,
+    ) -> Result<(), Error> {
+        let ts = now();
+
+        match self.get_lease(addr)? {
+            Some(lease) if !clientid.is_empty() && lease.client_id == clientid => {
+                self.store
+                    .put(&LeaseInfo {
+                        client_id: vec![],
+                        start: ts as u32,
+                        expire: (ts + holddown.as_secs()) as u32,
+                        ..lease
+                    })
+                    .map_err(|e| Error::DbError(format!("Failed to decline lease: {}", e)))?;
+                self.notify_failover(addr)
+            }
+            _ => Err(Error::NoSuchLease),
+        }
+    }
+
+    /// Takes an address that answered a conflict detection probe out of the pool for `holddown`.
+    /// Any lease on the address (such as the one that was about to be offered) is replaced.
+    pub fn conflict_address(
+        &mut self,
+        addr: std::net::Ipv4Addr,
+        holddown: std::time::Duration,
+    ) -> Result<(), Error> {
+        let ts = now();
+
+        self.store
+            .put(&LeaseInfo {
+                ip: addr,
+                client_id: vec![],
+                start: ts as u32,
+                expire: (ts + holddown.as_secs()) as u32,
+                options: vec![],
+            })
+            .map_err(|e| Error::DbError(format!("Failed to mark address conflicted: {}", e)))?;
+
+        self.notify_failover(addr)
+    }
+
+    /// Leases `addr` to a client that hasn't asked for it yet, for `duration`.  When the client
+    /// does ask for an address, it will be given this one.
+    pub fn reserve_lease(
+        &mut self,
+        clientid: &[u8],
+        addr: std::net::Ipv4Addr,
+        duration: std::time::Duration,
+    ) -> Result<LeaseInfo, Error> {
+        let ts = now();
+        let expire = expiry_after(ts, duration)?;
+
+        match self.get_lease(addr)? {
+            Some(lease) if lease.client_id != clientid && lease.expire as u64 >= ts => {
+                return Err(Error::RequestedAddressInUse)
+            }
+            Some(_) => (),
+            None if !self.may_allocate(addr) => return Err(Error::PeerAddress),
+            None => (),
+        }
+
+        let lease = LeaseInfo {
+            ip: addr,
+            client_id: clientid.to_vec(),
+            start: ts as u32,
+            expire,
+            options: vec![],
+        };
+        self.store
+            .put(&lease)
+            .map_err(|e| Error::DbError(format!("Failed to reserve lease: {}", e)))?;
+        self.notify_failover(addr)?;
+        Ok(lease)
+    }
+
+    /// Changes an existing lease to expire `duration` from now.
+    pub fn extend_lease(
+        &mut self,
+        addr: std::net::Ipv4Addr,
+        duration: std::time::Duration,
+    ) -> Result<LeaseInfo, Error> {
+        let expire = expiry_after(now(), duration)?;
+        let lease = self.get_lease(addr)?.ok_or(Error::NoSuchLease)?;
+        let lease = LeaseInfo { expire, ..lease };
+        self.store
+            .put(&lease)
+            .map_err(|e| Error::DbError(format!("Failed to extend lease: {}", e)))?;
+        self.notify_failover(addr)?;
+        Ok(lease)
+    }
+
+    /// Expires a lease immediately, as if the client had released it.
+    pub fn expire_lease(&mut self, addr: std::net::Ipv4Addr) -> Result<LeaseInfo, Error> {
+        let lease = self.get_lease(addr)?.ok_or(Error::NoSuchLease)?;
+        let lease = LeaseInfo {
+            expire: std::cmp::min(lease.expire, (now() as u32).saturating_sub(1)),
+            ..lease
+        };
+        self.store
+            .put(&lease)
+            .map_err(|e| Error::DbError(format!("Failed to expire lease: {}", e)))?;
+        self.notify_failover(addr)?;
+        Ok(lease)
+    }
+
+    /// Forgets a lease entirely, so the address is free and no longer associated with its client.
+    pub fn delete_lease(&mut self, addr: std::net::Ipv4Addr) -> Result<(), Error> {
+        if self.get_lease(addr)?.is_none() {
+            return Err(Error::NoSuchLease);
+        }
+        self.store
+            .delete(addr)
+            .map_err(|e| Error::DbError(format!("Failed to delete lease: {}", e)))?;
+        /* The peer's copy can't be deleted by an update, but an expired lease with no client is
+         * just as free.
+         */
+        self.send_failover(LeaseInfo {
+            ip: addr,
+            client_id: vec![],
+            start: 0,
+            expire: 0,
+            options: vec![],
+        });
+        Ok(())
+    }
+
     #[cfg(test)]
     fn reserve_address_internal(
         &mut self,
@@ -517,26 +973,23 @@ impl Pool {
         addr: std::net::Ipv4Addr,
         expired: bool,
     ) {
-        self.conn
-            .execute(
-                "INSERT INTO leases (address, clientid, start, expiry)
-             VALUES (?1, ?2, ?3, ?4)",
-                rusqlite::params![
-                    addr.to_string(),
-                    client_id,
-                    0, /* Reserved from the beginning of time */
-                    if expired {
-                        0
-                    } else {
-                        0xFFFFFFFFu32 /* Until the end of time */
-                    }
-                ],
-            )
+        self.store
+            .put(&LeaseInfo {
+                ip: addr,
+                client_id: client_id.to_vec(),
+                start: 0, /* Reserved from the beginning of time */
+                expire: if expired {
+                    0
+                } else {
+                    0xFFFFFFFFu32 /* Until the end of time */
+                },
+                options: vec![],
+            })
             .expect("Failed to add existing lease to pool");
     }
 
     #[cfg(test)]
-    fn reserve_address(&mut self, client_id: &[u8], addr: std::net::Ipv4Addr) {
+    pub(crate) fn reserve_address(&mut self, client_id: &[u8], addr: std::net::Ipv4Addr) {
         self.reserve_address_internal(client_id, addr, false);
     }
 
@@ -546,34 +999,11 @@ impl Pool {
     }
 }
 
-fn map_no_row_to_none<T>(e: rusqlite::Error) -> Result<Option<T>, Error> {
-    if e == rusqlite::Error::QueryReturnedNoRows {
-        Ok(None)
-    } else {
-        Err(Error::emit("Database query Error", &e))
-    }
-}
-
-#[test]
-fn schema_upgrade_test() {
-    let conn =
-        rusqlite::Connection::open_in_memory().expect("Failed to create in-memory sqlite database");
-    // Install a copy of the original unversioned schema and expect all
-    // of the upgrades to happen one step at a time. That way, this
-    // single test should invoke them all.
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS leases (
-            address TEXT NOT NULL,
-            chaddr BLOB,
-            clientid BLOB,
-            start INTEGER NOT NULL,
-            expiry INTEGER NOT NULL,
-            PRIMARY KEY (address)
-         )",
-        rusqlite::params![],
-    )
-    .expect("Failed to set up test database with old schema");
-    Pool::new_with_conn(conn).expect("setup_db failed");
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .expect("clock failure")
+        .as_secs()
 }
 
 #[test]
@@ -763,3 +1193,489 @@ fn dont_hand_out_old_stale_lease() {
     /* Do not assigned the old_reserved address! */
     assert_ne!(lease.ip, old_reserved);
 }
+
+#[test]
+fn allocate_requested_address() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let mut addrpool: PoolAddresses = Default::default();
+    addrpool.insert("192.168.0.100".parse().unwrap());
+    addrpool.insert("192.168.0.101".parse().unwrap());
+    p.reserve_address(b"other-client", "192.168.0.100".parse().unwrap());
+
+    /* Not in the pool at all */
+    assert_eq!(
+        p.allocate_requested_address(
+            b"client",
+            "10.0.0.1".parse().unwrap(),
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect_err("Allocated an address outside the pool"),
+        Error::NoAssignableAddress
+    );
+    /* In use by someone else */
+    assert_eq!(
+        p.allocate_requested_address(
+            b"client",
+            "192.168.0.100".parse().unwrap(),
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect_err("Allocated an address in use"),
+        Error::RequestedAddressInUse
+    );
+    /* Neither of those should have left anything behind */
+    assert_eq!(p.get_leases().expect("error calling get_leases()").len(), 1);
+
+    let lease = p
+        .allocate_requested_address(
+            b"client",
+            "192.168.0.101".parse().unwrap(),
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate free address");
+    assert_eq!(
+        lease.ip,
+        "192.168.0.101".parse::<std::net::Ipv4Addr>().unwrap()
+    );
+}
+
+#[test]
+fn release_lease() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let mut addrpool: PoolAddresses = Default::default();
+    addrpool.insert("192.168.0.100".parse().unwrap());
+    let lease = p
+        .allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address");
+
+    /* Someone else can't release our lease */
+    assert_eq!(
+        p.release_address(b"other-client", lease.ip),
+        Err(Error::NoSuchLease)
+    );
+    p.release_address(b"client", lease.ip)
+        .expect("Failed to release lease");
+    /* Releasing twice is an error */
+    assert_eq!(
+        p.release_address(b"client", lease.ip),
+        Err(Error::NoSuchLease)
+    );
+
+    /* Once released, the address can go to another client */
+    let lease2 = p
+        .allocate_address(
+            b"other-client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate released address");
+    assert_eq!(lease2.ip, lease.ip);
+}
+
+#[test]
+fn decline_lease() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let mut addrpool: PoolAddresses = Default::default();
+    addrpool.insert("192.168.0.100".parse().unwrap());
+    addrpool.insert("192.168.0.101".parse().unwrap());
+    let lease = p
+        .allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address");
+
+    p.decline_address(b"client", lease.ip, DEFAULT_DECLINE_HOLDDOWN)
+        .expect("Failed to decline lease");
+
+    /* The client should now get a different address, even if it asks for the old one. */
+    let lease2 = p
+        .allocate_address(
+            b"client",
+            Some(lease.ip),
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address");
+    assert_ne!(lease2.ip, lease.ip);
+
+    /* And with the only other address gone, nothing is left. */
+    assert_eq!(
+        p.allocate_address(
+            b"other-client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect_err("Allocated a declined address"),
+        Error::NoAssignableAddress
+    );
+
+    /* The quarantined address still shows up in the lease list */
+    assert_eq!(p.get_leases().expect("error calling get_leases()").len(), 2);
+}
+
+#[test]
+fn conflict_address() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let mut addrpool: PoolAddresses = Default::default();
+    addrpool.insert("192.168.0.100".parse().unwrap());
+    addrpool.insert("192.168.0.101".parse().unwrap());
+    let lease = p
+        .allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address");
+    assert!(matches!(lease.lease_type, LeaseType::NewAddress));
+
+    /* Something else answered for the address, so the client should be offered the other one. */
+    p.conflict_address(lease.ip, DEFAULT_DECLINE_HOLDDOWN)
+        .expect("Failed to mark address conflicted");
+    let lease2 = p
+        .allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address");
+    assert_ne!(lease2.ip, lease.ip);
+
+    /* Addresses that were never leased can be marked too. */
+    p.conflict_address(lease2.ip, DEFAULT_DECLINE_HOLDDOWN)
+        .expect("Failed to mark address conflicted");
+    p.conflict_address(lease2.ip, DEFAULT_DECLINE_HOLDDOWN)
+        .expect("Failed to mark address conflicted twice");
+    assert_eq!(
+        p.allocate_address(
+            b"other-client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect_err("Allocated a conflicted address"),
+        Error::NoAssignableAddress
+    );
+}
+
+#[test]
+fn merge_lease() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let ip = "192.0.2.1".parse().unwrap();
+    let ours = LeaseInfo {
+        ip,
+        client_id: vec![1],
+        start: 1000,
+        expire: 2000,
+        options: vec![],
+    };
+    p.update_lease(&ours).expect("Failed to add lease");
+
+    /* A peer's lease that expires sooner doesn't replace ours */
+    let shorter = LeaseInfo {
+        client_id: vec![2],
+        expire: 1500,
+        ..ours.clone()
+    };
+    p.merge_lease(&shorter).expect("Failed to merge lease");
+    assert_eq!(p.get_leases().unwrap(), vec![ours.clone()]);
+
+    /* But one that lasts longer does */
+    let longer = LeaseInfo {
+        expire: 3000,
+        ..shorter.clone()
+    };
+    p.merge_lease(&longer).expect("Failed to merge lease");
+    assert_eq!(p.get_leases().unwrap(), vec![longer]);
+
+    /* Updates always replace the existing lease, such as when the peer released it */
+    p.update_lease(&shorter).expect("Failed to update lease");
+    assert_eq!(p.get_leases().unwrap(), vec![shorter]);
+}
+
+#[test]
+fn pool_addresses() {
+    let ip = |s: &str| s.parse::<std::net::Ipv4Addr>().unwrap();
+    let mut addrs = PoolAddresses::from_range(ip("192.0.2.10"), ip("192.0.2.19"));
+    addrs.insert_range(ip("192.0.2.20"), ip("192.0.2.29"));
+    addrs.insert(ip("192.0.2.100"));
+    assert_eq!(
+        format!("{:?}", addrs),
+        "[\"192.0.2.10-192.0.2.29\", \"192.0.2.100-192.0.2.100\"]"
+    );
+    assert_eq!(addrs.len(), 21);
+    assert_eq!(addrs.get(0), Some(ip("192.0.2.10")));
+    assert_eq!(addrs.get(19), Some(ip("192.0.2.29")));
+    assert_eq!(addrs.get(20), Some(ip("192.0.2.100")));
+    assert_eq!(addrs.get(21), None);
+    assert!(addrs.contains(&ip("192.0.2.15")));
+    assert!(!addrs.contains(&ip("192.0.2.30")));
+
+    let holes: PoolAddresses = [ip("192.0.2.10"), ip("192.0.2.15"), ip("192.0.2.100")]
+        .iter()
+        .copied()
+        .collect();
+    let rest = addrs.difference(&holes);
+    assert_eq!(rest.len(), 18);
+    assert_eq!(
+        rest.iter().take(5).collect::<Vec<_>>(),
+        vec![
+            ip("192.0.2.11"),
+            ip("192.0.2.12"),
+            ip("192.0.2.13"),
+            ip("192.0.2.14"),
+            ip("192.0.2.16")
+        ]
+    );
+    assert!(rest.difference(&addrs).is_empty());
+
+    /* The whole address space doesn't overflow */
+    let all = PoolAddresses::from_range(ip("0.0.0.0"), ip("255.255.255.255"));
+    assert_eq!(all.len(), 1 << 32);
+    assert_eq!(all.get((1 << 32) - 1), Some(ip("255.255.255.255")));
+    let mut most = all.difference(&rest);
+    most.remove(ip("255.255.255.255"));
+    assert_eq!(most.len(), (1 << 32) - 19);
+}
+
+#[test]
+fn large_pool() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let addrpool = PoolAddresses::from_range(
+        "10.0.0.1".parse().unwrap(),
+        "10.255.255.254".parse().unwrap(),
+    );
+    let lease = p
+        .allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address");
+    assert!(addrpool.contains(&lease.ip));
+    /* The same client gets the same address back */
+    assert_eq!(
+        p.allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate address")
+        .ip,
+        lease.ip
+    );
+}
+
+#[test]
+fn in_use_runs() {
+    let mut in_use = InUse::default();
+    for addr in [10, 11, 13, 12, 20].iter() {
+        in_use.update(*addr, Some(100));
+    }
+    in_use.update(u32::MAX, Some(200));
+    assert_eq!(
+        in_use.runs.iter().collect::<Vec<_>>(),
+        vec![(&10, &13), (&20, &20), (&u32::MAX, &u32::MAX)]
+    );
+    assert_eq!(in_use.first_free(9), Some(9));
+    assert_eq!(in_use.first_free(11), Some(14));
+    assert_eq!(in_use.first_free(u32::MAX), None);
+    /* Renewing a lease doesn't change the runs, removing one splits them */
+    in_use.update(12, Some(300));
+    in_use.update(11, None);
+    assert_eq!(
+        in_use.runs.iter().collect::<Vec<_>>(),
+        vec![(&10, &10), (&12, &13), (&20, &20), (&u32::MAX, &u32::MAX)]
+    );
+    /* Leases are still in use at their expiry time, and free after it */
+    in_use.expire(100);
+    assert_eq!(in_use.first_free(10), Some(11));
+    in_use.expire(101);
+    assert_eq!(
+        in_use.runs.iter().collect::<Vec<_>>(),
+        vec![(&12, &12), (&u32::MAX, &u32::MAX)]
+    );
+    assert_eq!(in_use.expiry.len(), 2);
+    assert_eq!(in_use.by_expiry.len(), 2);
+}
+
+#[test]
+fn exhaust_pool() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let mut addrpool =
+        PoolAddresses::from_range("192.0.2.1".parse().unwrap(), "192.0.2.100".parse().unwrap());
+    addrpool.insert_range(
+        "198.51.100.1".parse().unwrap(),
+        "198.51.100.100".parse().unwrap(),
+    );
+    let mut leased = std::collections::HashSet::new();
+    for client in 0..addrpool.len() {
+        let lease = p
+            .allocate_address(
+                format!("client{}", client).as_bytes(),
+                None,
+                &addrpool,
+                DEFAULT_MIN_LEASE,
+                DEFAULT_MAX_LEASE,
+                b"",
+            )
+            .expect("Failed to allocate address");
+        assert!(addrpool.contains(&lease.ip));
+        assert!(leased.insert(lease.ip));
+    }
+    assert_eq!(
+        p.allocate_address(
+            b"late-client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .err(),
+        Some(Error::NoAssignableAddress)
+    );
+    /* Once an address is freed up, it's the only one that can be handed out */
+    let freed = "198.51.100.50".parse().unwrap();
+    p.delete_lease(freed).expect("Failed to delete lease");
+    assert_eq!(
+        p.allocate_address(
+            b"late-client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            b"",
+        )
+        .expect("Failed to allocate freed address")
+        .ip,
+        freed
+    );
+}
+
+#[test]
+fn manage_leases() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let addrpool: PoolAddresses = ["192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap()]
+        .iter()
+        .copied()
+        .collect();
+    let reserved: std::net::Ipv4Addr = "192.0.2.2".parse().unwrap();
+    let hour = std::time::Duration::from_secs(3600);
+
+    /* A reserved address is handed to its client, and nobody else */
+    p.reserve_lease(b"client", reserved, hour).unwrap();
+    assert_eq!(
+        p.reserve_lease(b"other", reserved, hour),
+        Err(Error::RequestedAddressInUse)
+    );
+    let lease = p
+        .allocate_address(
+            b"client",
+            None,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            &[],
+        )
+        .unwrap();
+    assert_eq!(lease.ip, reserved);
+
+    /* Extending and expiring change when the lease ends */
+    let lease = p
+        .extend_lease(reserved, std::time::Duration::from_secs(86400 * 7))
+        .unwrap();
+    assert!(lease.expire as u64 > now() + DEFAULT_MAX_LEASE.as_secs());
+    let lease = p.expire_lease(reserved).unwrap();
+    assert!((lease.expire as u64) < now());
+    assert_eq!(lease.client_id, b"client");
+
+    /* Once deleted, the address can go to anyone */
+    p.delete_lease(reserved).unwrap();
+    assert_eq!(p.delete_lease(reserved), Err(Error::NoSuchLease));
+    assert_eq!(p.extend_lease(reserved, hour), Err(Error::NoSuchLease));
+    assert!(p.get_leases().unwrap().is_empty());
+    p.reserve_lease(b"other", reserved, hour).unwrap();
+
+    /* Leases can't outlast their u32 expiry time */
+    let forever = std::time::Duration::from_secs(u64::MAX);
+    assert_eq!(
+        p.reserve_lease(b"other", reserved, forever),
+        Err(Error::InvalidDuration)
+    );
+    assert_eq!(
+        p.extend_lease(reserved, std::time::Duration::from_secs(u32::MAX.into())),
+        Err(Error::InvalidDuration)
+    );
+}
+
+#[test]
+fn reserve_by_mac() {
+    let mut p = Pool::new_in_memory().expect("Failed to create in memory pools");
+    let addrpool: PoolAddresses = ["192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap()]
+        .iter()
+        .copied()
+        .collect();
+    let reserved: std::net::Ipv4Addr = "192.0.2.2".parse().unwrap();
+    let mac = [0x00, 0x00, 0x5e, 0x00, 0x53, 0x01];
+
+    /* Reserved for the client identifier 01:<mac>, but the client only has its chaddr */
+    p.reserve_lease(
+        &[&[1u8][..], &mac].concat(),
+        reserved,
+        std::time::Duration::from_secs(3600),
+    )
+    .unwrap();
+    let lease = p
+        .allocate_requested_address(
+            &mac,
+            reserved,
+            &addrpool,
+            DEFAULT_MIN_LEASE,
+            DEFAULT_MAX_LEASE,
+            &[],
+        )
+        .unwrap();
+    assert_eq!(lease.ip, reserved);
+}
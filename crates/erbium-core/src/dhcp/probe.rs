@@ -0,0 +1,162 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Checks if something is already using an address before we offer it to a client.
+ */
+
+use super::config::{ConflictDetection, ProbeMethod};
+use erbium_net::addr::{ToNetAddr as _, WithPort as _};
+use erbium_net::packet;
+use erbium_net::raw;
+use rand::Rng as _;
+use std::convert::TryInto as _;
+
+const ETHERTYPE_ARP: [u8; 2] = [0x08, 0x06];
+const ICMP_ECHO_REPLY: u8 = 0;
+
+/* Returns true if `frame` is an ARP packet sent by `addr`.  Both replies to our probe, and any
+ * requests that `addr` happens to send while we're waiting show that the address is in use.
+ */
+fn is_arp_from(frame: &[u8], addr: std::net::Ipv4Addr) -> bool {
+    /* 14 bytes of ethernet header, then the sender protocol address is at offset 14 in the ARP
+     * packet.
+     */
+    frame.len() >= 14 + 28 && frame[12..14] == ETHERTYPE_ARP && frame[28..32] == addr.octets()
+}
+
+/* Returns true if `pkt` (including its IPv4 header) is an ICMP echo reply from `addr` to the
+ * echo request with `id`.
+ */
+fn is_echo_reply(pkt: &[u8], addr: std::net::Ipv4Addr, id: u16) -> bool {
+    if pkt.len() < 20 {
+        return false;
+    }
+    let ihl = ((pkt[0] & 0x0F) as usize) * 4;
+    pkt.len() >= ihl + 8
+        && pkt[12..16] == addr.octets()
+        && pkt[ihl] == ICMP_ECHO_REPLY
+        && pkt[ihl + 4..ihl + 6] == id.to_be_bytes()
+}
+
+async fn probe_arp(
+    netinfo: &erbium_net::netinfo::SharedNetInfo,
+    ifindex: u32,
+    addr: std::net::Ipv4Addr,
+    timeout: std::time::Duration,
+) -> Result<bool, std::io::Error> {
+    let srcmac = match netinfo.get_linkaddr_by_ifidx(ifindex).await {
+        Some(erbium_net::netinfo::LinkLayer::Ethernet(mac)) => mac,
+        _ => return Err(std::io::Error::other("Interface has no ethernet address")),
+    };
+    let sock = raw::RawSocket::new(raw::EthProto::ARP)?;
+    sock.send_msg(
+        &packet::Fragment::new_arp_probe(&srcmac, &addr).flatten(),
+        &raw::ControlMessage::new(),
+        raw::MsgFlags::empty(),
+        Some(&erbium_net::addr::linkaddr_for_ifindex(ifindex.try_into().unwrap()).to_net_addr()),
+    )
+    .await?;
+    let wait = async {
+        loop {
+            let rm = sock.recv_msg(1500, raw::MsgFlags::empty()).await?;
+            if is_arp_from(&rm.buffer, addr) {
+                return Ok(true);
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(ret) => ret,
+        Err(_) => Ok(false),
+    }
+}
+
+async fn probe_icmp(
+    addr: std::net::Ipv4Addr,
+    timeout: std::time::Duration,
+) -> Result<bool, std::io::Error> {
+    let id: u16 = rand::thread_rng().gen();
+    let sock = raw::Raw4Socket::new(raw::IpProto::ICMP)?;
+    sock.send_msg(
+        &packet::Fragment::new_icmp4_echo(id, 1, packet::Tail::Payload(b"erbium")).flatten(),
+        &raw::ControlMessage::new(),
+        raw::MsgFlags::empty(),
+        Some(&addr.with_port(0)),
+    )
+    .await?;
+    let wait = async {
+        loop {
+            let rm = sock.recv_msg(1500, raw::MsgFlags::empty()).await?;
+            if is_echo_reply(&rm.buffer, addr, id) {
+                return Ok(true);
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(ret) => ret,
+        Err(_) => Ok(false),
+    }
+}
+
+/// Returns true if something answered for `addr`.
+///
+/// ARP only works for clients on the same link as us, so relayed requests are always probed with
+/// ICMP.  If the probe itself fails, then the address is assumed to be free.
+pub async fn address_in_use(
+    conf: &ConflictDetection,
+    netinfo: &erbium_net::netinfo::SharedNetInfo,
+    ifindex: u32,
+    relayed: bool,
+    addr: std::net::Ipv4Addr,
+) -> bool {
+    let ret = match conf.method {
+        ProbeMethod::Arp if !relayed => probe_arp(netinfo, ifindex, addr, conf.timeout).await,
+        _ => probe_icmp(addr, conf.timeout).await,
+    };
+    match ret {
+        Ok(in_use) => in_use,
+        Err(e) => {
+            log::warn!("Failed to probe {} for conflicts: {}", addr, e);
+            false
+        }
+    }
+}
+
+#[test]
+fn test_is_arp_from() {
+    let addr = "192.0.2.1".parse().unwrap();
+    let mut reply = packet::Fragment::new_arp_probe(&[2, 0, 0, 0, 0, 1], &addr).flatten();
+    /* Our own probe has an unspecified sender address */
+    assert!(!is_arp_from(&reply, addr));
+    reply[28..32].copy_from_slice(&addr.octets());
+    assert!(is_arp_from(&reply, addr));
+    assert!(!is_arp_from(&reply, "192.0.2.2".parse().unwrap()));
+    assert!(!is_arp_from(&reply[..30], addr));
+}
+
+#[test]
+fn test_is_echo_reply() {
+    let addr: std::net::Ipv4Addr = "192.0.2.1".parse().unwrap();
+    let mut pkt = vec![0x45, 0, 0, 28, 0, 0, 0, 0, 64, 1, 0, 0];
+    pkt.extend_from_slice(&addr.octets());
+    pkt.extend_from_slice(&[192, 0, 2, 67]);
+    pkt.extend_from_slice(&[ICMP_ECHO_REPLY, 0, 0, 0, 0x12, 0x34, 0, 1]);
+    assert!(is_echo_reply(&pkt, addr, 0x1234));
+    assert!(!is_echo_reply(&pkt, addr, 0x4321));
+    assert!(!is_echo_reply(&pkt, "192.0.2.2".parse().unwrap(), 0x1234));
+    /* Our own echo request looped back isn't a reply */
+    pkt[20] = 8;
+    assert!(!is_echo_reply(&pkt, addr, 0x1234));
+}
@@ -0,0 +1,728 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Places to keep DHCP leases.
+ */
+
+use super::config::LeaseStorage;
+use super::pool::{Error, LeaseInfo};
+use rusqlite::OptionalExtension as _;
+use std::collections::HashMap;
+use std::io::{BufRead as _, Seek as _, Write as _};
+use std::net::Ipv4Addr;
+use std::os::unix::fs::MetadataExt as _;
+
+/* The DHCPv6 pool shares the SQLite database, using its own tables. */
+pub const SQLITE_DB_NAME: &str = "leases.sqlite";
+pub const JOURNAL_NAME: &str = "leases.journal";
+
+/// Somewhere to keep leases.
+///
+/// There is at most one lease per address.  Leases with an empty client id are for addresses that
+/// have been taken out of the pool (eg because a client declined them).
+pub trait LeaseStore: Send {
+    fn get(&mut self, addr: Ipv4Addr) -> Result<Option<LeaseInfo>, Error>;
+    /// Returns every lease the client has had, which never includes quarantined addresses.
+    fn get_by_client(&mut self, client_id: &[u8]) -> Result<Vec<LeaseInfo>, Error>;
+    fn get_all(&mut self) -> Result<Vec<LeaseInfo>, Error>;
+    /// Adds the lease, replacing any existing lease for the same address.
+    fn put(&mut self, lease: &LeaseInfo) -> Result<(), Error>;
+    /// Forgets the lease for an address, if there is one.
+    fn delete(&mut self, addr: Ipv4Addr) -> Result<(), Error>;
+    /// Starts a batch of changes.  Until commit is called, changes may not have been written to
+    /// storage.
+    fn begin(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn commit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Opens the lease storage that has been configured.
+pub fn open(conf: &crate::config::Config) -> Result<Box<dyn LeaseStore>, Error> {
+    Ok(match conf.dhcp.lease_storage {
+        LeaseStorage::Sqlite => Box::new(SqliteStore::open(&conf.state_dir.join(SQLITE_DB_NAME))?),
+        LeaseStorage::Journal => Box::new(JournalStore::open(&conf.state_dir.join(JOURNAL_NAME))?),
+        LeaseStorage::Memory => Box::new(MemoryStore::default()),
+    })
+}
+
+pub struct SqliteStore {
+    conn: rusqlite::Connection,
+}
+
+impl SqliteStore {
+    // - upgrade_schema_from_no_version should install the latest schema
+    //   directly so that new installations need not go through the
+    //   upgrade chain.
+    // - upgrade_schema_from_version_* should perform upgrades one
+    //   version at a time since that is the only thing that is tested.
+
+    fn upgrade_schema_from_no_version(&self) -> Result<usize, Error> {
+        if self
+            .conn
+            .query_row("SELECT 1 FROM leases LIMIT 1", rusqlite::params![], |_| {
+                Ok(())
+            })
+            .optional()
+            .err()
+            .is_none()
+        {
+            // This is not a fresh new database but just one with
+            // the schema_version missing. We know that this is the
+            // same as version 0.
+            return Ok(0);
+        }
+        // Else, probably a brand new database. Create it directly
+        // with the latest version.
+        self.conn
+            .execute(
+                "CREATE TABLE leases (
+                address TEXT NOT NULL,
+                chaddr BLOB,
+                clientid BLOB,
+                start INTEGER NOT NULL,
+                expiry INTEGER NOT NULL,
+                options BLOB,
+                PRIMARY KEY (address)
+              )",
+                rusqlite::params![],
+            )
+            .map_err(|e| Error::emit("Creating table leases", &e))?;
+        Ok(1)
+    }
+
+    fn upgrade_schema_from_version_0(&self) -> Result<usize, Error> {
+        self.conn
+            .execute(
+                "ALTER TABLE leases ADD COLUMN options BLOB",
+                rusqlite::params![],
+            )
+            .map_err(|e| Error::emit("Upgrading to schema version 1", &e))?;
+        Ok(1)
+    }
+
+    fn setup_db(self) -> Result<Self, Error> {
+        // Dummy primary key for the schema_version table.
+        // If the same sqlite database were used by another module,
+        // that module could use a different key within the same schema_version
+        // table to track the schema of its own table(s).
+        const DB_SCHEMA_KEY: &str = "pool";
+
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS schema_version (
+                    key TEXT NOT NULL,
+                    version INTEGER NOT NULL,
+                    PRIMARY KEY (key)
+                )",
+                rusqlite::params![],
+            )
+            .map_err(|e| Error::emit("Creating table schema_version", &e))?;
+
+        loop {
+            let upgraded_to_version = match self.conn
+                .query_row(
+                    "SELECT version FROM schema_version
+                        WHERE key = ?1",
+                    rusqlite::params![DB_SCHEMA_KEY],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| Error::emit("Querying schema version", &e))?
+            {
+                None => self.upgrade_schema_from_no_version()?,
+                Some(0) => self.upgrade_schema_from_version_0()?,
+                Some(1) => break,  // up to date
+                Some(v) => return Err(Error::DbError(format!(
+                    "Lease database has version {} which is newer than 1, the newest supported version",
+                    v
+                ))),
+            };
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO schema_version (key, version)
+                 VALUES (?1, ?2)",
+                    rusqlite::params![DB_SCHEMA_KEY, upgraded_to_version],
+                )
+                .map_err(|e| Error::emit("Creating updating schema version", &e))?;
+        }
+        Ok(self)
+    }
+
+    fn new_with_conn(conn: rusqlite::Connection) -> Result<Self, Error> {
+        SqliteStore { conn }.setup_db()
+    }
+
+    pub fn new_in_memory() -> Result<Self, Error> {
+        let conn = rusqlite::Connection::open_in_memory()
+            .map_err(|e| Error::emit("Creating database in memory database", &e))?;
+
+        Self::new_with_conn(conn)
+    }
+
+    pub fn open(path: &std::path::Path) -> Result<Self, Error> {
+        let conn = rusqlite::Connection::open(path)
+            .map_err(|e| Error::emit(&format!("Creating database {}", path.display()), &e))?;
+        /* With a write ahead log, readers (such as DNS) don't block writes, and each commit only
+         * needs to wait for one sync.
+         */
+        conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))
+            .map_err(|e| Error::emit("Enabling write ahead logging", &e))?;
+
+        Self::new_with_conn(conn)
+    }
+}
+
+fn lease_from_row(row: &rusqlite::Row) -> Result<LeaseInfo, rusqlite::Error> {
+    Ok(LeaseInfo {
+        ip: row
+            .get::<_, String>(0)?
+            .parse::<Ipv4Addr>()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+        /* Quarantined addresses have no client */
+        client_id: row.get::<usize, Option<Vec<u8>>>(1)?.unwrap_or_default(),
+        start: row.get(2)?,
+        expire: row.get(3)?,
+        options: row.get::<usize, Option<Vec<u8>>>(4)?.unwrap_or_default(),
+    })
+}
+
+impl LeaseStore for SqliteStore {
+    fn get(&mut self, addr: Ipv4Addr) -> Result<Option<LeaseInfo>, Error> {
+        self.conn
+            .query_row(
+                "SELECT
+                  address,
+                  clientid,
+                  start,
+                  expiry,
+                  options
+                 FROM
+                  leases
+                 WHERE address = ?1",
+                rusqlite::params![addr.to_string()],
+                lease_from_row,
+            )
+            .optional()
+            .map_err(|e| Error::emit("Database query Error", &e))
+    }
+
+    fn get_by_client(&mut self, client_id: &[u8]) -> Result<Vec<LeaseInfo>, Error> {
+        self.conn
+            .prepare_cached(
+                "SELECT
+                  address,
+                  clientid,
+                  start,
+                  expiry,
+                  options
+                 FROM
+                  leases
+                 WHERE clientid = ?1",
+            )
+            .map_err(|e| Error::DbError(e.to_string()))?
+            .query_map(rusqlite::params![client_id], lease_from_row)
+            .map_err(|e| Error::DbError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::DbError(e.to_string()))
+    }
+
+    fn get_all(&mut self) -> Result<Vec<LeaseInfo>, Error> {
+        self.conn
+            .prepare_cached(
+                "SELECT
+                  address,
+                  clientid,
+                  start,
+                  expiry,
+                  options
+                 FROM
+                  leases",
+            )
+            .map_err(|e| Error::DbError(e.to_string()))?
+            .query_map([], lease_from_row)
+            .map_err(|e| Error::DbError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::DbError(e.to_string()))
+    }
+
+    fn put(&mut self, lease: &LeaseInfo) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE
+                 INTO leases (address, clientid, start, expiry, options)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    lease.ip.to_string(),
+                    Some(&lease.client_id).filter(|c| !c.is_empty()),
+                    lease.start,
+                    lease.expire,
+                    Some(&lease.options).filter(|o| !o.is_empty()),
+                ],
+            )
+            .map_err(|e| Error::DbError(format!("Failed to update lease: {}", e)))?;
+        Ok(())
+    }
+
+    fn delete(&mut self, addr: Ipv4Addr) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "DELETE FROM leases WHERE address = ?1",
+                rusqlite::params![addr.to_string()],
+            )
+            .map_err(|e| Error::DbError(format!("Failed to delete lease: {}", e)))?;
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        self.conn
+            .execute_batch("BEGIN")
+            .map_err(|e| Error::emit("Starting transaction", &e))
+    }
+
+    fn commit(&mut self) -> Result<(), Error> {
+        self.conn.execute_batch("COMMIT").map_err(|e| {
+            /* If the commit fails, the transaction may still be open */
+            let _ = self.conn.execute_batch("ROLLBACK");
+            Error::emit("Committing transaction", &e)
+        })
+    }
+}
+
+/// Keeps leases in memory only, so they are lost on restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    leases: HashMap<Ipv4Addr, LeaseInfo>,
+}
+
+impl LeaseStore for MemoryStore {
+    fn get(&mut self, addr: Ipv4Addr) -> Result<Option<LeaseInfo>, Error> {
+        Ok(self.leases.get(&addr).cloned())
+    }
+
+    fn get_by_client(&mut self, client_id: &[u8]) -> Result<Vec<LeaseInfo>, Error> {
+        Ok(self
+            .leases
+            .values()
+            .filter(|lease| !lease.client_id.is_empty() && lease.client_id == client_id)
+            .cloned()
+            .collect())
+    }
+
+    fn get_all(&mut self) -> Result<Vec<LeaseInfo>, Error> {
+        Ok(self.leases.values().cloned().collect())
+    }
+
+    fn put(&mut self, lease: &LeaseInfo) -> Result<(), Error> {
+        self.leases.insert(lease.ip, lease.clone());
+        Ok(())
+    }
+
+    fn delete(&mut self, addr: Ipv4Addr) -> Result<(), Error> {
+        self.leases.remove(&addr);
+        Ok(())
+    }
+}
+
+/* Once the journal has this many records, and less than half of them are still current, it is
+ * rewritten with just the current ones.
+ */
+const JOURNAL_COMPACT_RECORDS: usize = 1000;
+
+fn to_hex(v: &[u8]) -> String {
+    if v.is_empty() {
+        "-".into()
+    } else {
+        v.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s == "-" {
+        return Some(vec![]);
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [_, _] => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/* Each record is a line of: address start expiry clientid options
+ * A line with just an address means that its lease was deleted.
+ */
+fn format_record(lease: &LeaseInfo) -> String {
+    format!(
+        "{} {} {} {} {}\n",
+        lease.ip,
+        lease.start,
+        lease.expire,
+        to_hex(&lease.client_id),
+        to_hex(&lease.options)
+    )
+}
+
+fn format_deletion(addr: Ipv4Addr) -> String {
+    format!("{}\n", addr)
+}
+
+fn parse_deletion(line: &str) -> Option<Ipv4Addr> {
+    line.trim_end_matches('\n').parse().ok()
+}
+
+fn parse_record(line: &str) -> Option<LeaseInfo> {
+    let mut fields = line.split_ascii_whitespace();
+    let lease = LeaseInfo {
+        ip: fields.next()?.parse().ok()?,
+        start: fields.next()?.parse().ok()?,
+        expire: fields.next()?.parse().ok()?,
+        client_id: from_hex(fields.next()?)?,
+        options: from_hex(fields.next()?)?,
+    };
+    if fields.next().is_some() {
+        None
+    } else {
+        Some(lease)
+    }
+}
+
+/// Keeps leases in memory, and appends every change to a file so they can be recovered on restart.
+///
+/// Other processes (such as erbium-dns) can open the same journal to read the leases, and pick up
+/// changes as they are written.
+pub struct JournalStore {
+    path: std::path::PathBuf,
+    file: std::fs::File,
+    leases: HashMap<Ipv4Addr, LeaseInfo>,
+    /* How much of the file has been read, and which file it was, so that we notice if it is
+     * appended to, or replaced when it's compacted.
+     */
+    offset: u64,
+    inode: u64,
+    records: usize,
+    /* If a batch has been started, then records aren't synced until it is committed. */
+    batch: bool,
+}
+
+impl JournalStore {
+    pub fn open(path: &std::path::Path) -> Result<Self, Error> {
+        let mut store = JournalStore {
+            path: path.to_path_buf(),
+            file: Self::open_file(path)?,
+            leases: HashMap::new(),
+            offset: 0,
+            inode: 0,
+            records: 0,
+            batch: false,
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    fn io_error(&self, e: std::io::Error) -> Error {
+        Error::DbError(format!("{}: {}", self.path.display(), e))
+    }
+
+    fn open_file(path: &std::path::Path) -> Result<std::fs::File, Error> {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| Error::DbError(format!("Opening journal {}: {}", path.display(), e)))
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.file = Self::open_file(&self.path)?;
+        self.inode = self.file.metadata().map_err(|e| self.io_error(e))?.ino();
+        self.leases.clear();
+        self.offset = 0;
+        self.records = 0;
+        self.read_new_records()
+    }
+
+    fn read_new_records(&mut self) -> Result<(), Error> {
+        let mut reader = std::io::BufReader::new(&self.file);
+        reader
+            .seek(std::io::SeekFrom::Start(self.offset))
+            .map_err(|e| Error::DbError(format!("{}: {}", self.path.display(), e)))?;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let len = reader
+                .read_line(&mut line)
+                .map_err(|e| Error::CorruptDatabase(format!("{}: {}", self.path.display(), e)))?;
+            /* A record without a newline is still being written (or was interrupted by a crash) */
+            if len == 0 || !line.ends_with('\n') {
+                return Ok(());
+            }
+            if let Some(addr) = parse_deletion(&line) {
+                self.leases.remove(&addr);
+            } else {
+                let lease = parse_record(&line).ok_or_else(|| {
+                    Error::CorruptDatabase(format!(
+                        "{}: Invalid record at offset {}",
+                        self.path.display(),
+                        self.offset
+                    ))
+                })?;
+                self.leases.insert(lease.ip, lease);
+            }
+            self.offset += len as u64;
+            self.records += 1;
+        }
+    }
+
+    /* Picks up any changes made by another process. */
+    fn refresh(&mut self) -> Result<(), Error> {
+        let metadata = std::fs::metadata(&self.path).map_err(|e| self.io_error(e))?;
+        if metadata.ino() != self.inode || metadata.len() < self.offset {
+            self.reload()
+        } else if metadata.len() > self.offset {
+            self.read_new_records()
+        } else {
+            Ok(())
+        }
+    }
+
+    /* Replaces the journal with one that only has the current leases. */
+    fn compact(&mut self) -> Result<(), Error> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".new");
+        let tmp = std::path::PathBuf::from(tmp);
+        let contents: String = self.leases.values().map(format_record).collect();
+        let mut file = std::fs::File::create(&tmp).map_err(|e| self.io_error(e))?;
+        file.write_all(contents.as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| self.io_error(e))?;
+        self.reload()
+    }
+
+    /* Adds a record to the end of the journal.  The caller updates the leases to match. */
+    fn append(&mut self, record: &str) -> Result<(), Error> {
+        self.refresh()?;
+        /* Throw away any partial record left behind by a crash, so that ours starts on a new line */
+        self.file
+            .set_len(self.offset)
+            .map_err(|e| self.io_error(e))?;
+        (&self.file)
+            .write_all(record.as_bytes())
+            .and_then(|_| {
+                if self.batch {
+                    Ok(())
+                } else {
+                    self.file.sync_data()
+                }
+            })
+            .map_err(|e| self.io_error(e))?;
+        self.offset += record.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn maybe_compact(&mut self) -> Result<(), Error> {
+        if self.records >= JOURNAL_COMPACT_RECORDS && self.records > 2 * self.leases.len() {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+impl LeaseStore for JournalStore {
+    fn get(&mut self, addr: Ipv4Addr) -> Result<Option<LeaseInfo>, Error> {
+        self.refresh()?;
+        Ok(self.leases.get(&addr).cloned())
+    }
+
+    fn get_by_client(&mut self, client_id: &[u8]) -> Result<Vec<LeaseInfo>, Error> {
+        self.refresh()?;
+        Ok(self
+            .leases
+            .values()
+            .filter(|lease| !lease.client_id.is_empty() && lease.client_id == client_id)
+            .cloned()
+            .collect())
+    }
+
+    fn get_all(&mut self) -> Result<Vec<LeaseInfo>, Error> {
+        self.refresh()?;
+        Ok(self.leases.values().cloned().collect())
+    }
+
+    fn put(&mut self, lease: &LeaseInfo) -> Result<(), Error> {
+        self.append(&format_record(lease))?;
+        self.leases.insert(lease.ip, lease.clone());
+        self.maybe_compact()
+    }
+
+    fn delete(&mut self, addr: Ipv4Addr) -> Result<(), Error> {
+        self.append(&format_deletion(addr))?;
+        self.leases.remove(&addr);
+        self.maybe_compact()
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        self.batch = true;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Error> {
+        self.batch = false;
+        self.file.sync_data().map_err(|e| self.io_error(e))
+    }
+}
+
+#[test]
+fn schema_upgrade_test() {
+    let conn =
+        rusqlite::Connection::open_in_memory().expect("Failed to create in-memory sqlite database");
+    // Install a copy of the original unversioned schema and expect all
+    // of the upgrades to happen one step at a time. That way, this
+    // single test should invoke them all.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS leases (
+            address TEXT NOT NULL,
+            chaddr BLOB,
+            clientid BLOB,
+            start INTEGER NOT NULL,
+            expiry INTEGER NOT NULL,
+            PRIMARY KEY (address)
+         )",
+        rusqlite::params![],
+    )
+    .expect("Failed to set up test database with old schema");
+    SqliteStore::new_with_conn(conn).expect("setup_db failed");
+}
+
+#[test]
+fn test_record() {
+    let lease = LeaseInfo {
+        ip: "192.0.2.1".parse().unwrap(),
+        client_id: vec![0x01, 0xab],
+        start: 1000,
+        expire: 2000,
+        options: vec![],
+    };
+    assert_eq!(format_record(&lease), "192.0.2.1 1000 2000 01ab -\n");
+    assert_eq!(parse_deletion(&format_deletion(lease.ip)), Some(lease.ip));
+    assert_eq!(parse_deletion(&format_record(&lease)), None);
+    assert_eq!(parse_record(&format_record(&lease)), Some(lease));
+    assert_eq!(parse_record("192.0.2.1 1000 2000 01a -\n"), None);
+    assert_eq!(parse_record("192.0.2.1 1000 2000 01ab\n"), None);
+    assert_eq!(parse_record("192.0.2.1 1000 2000 01ab - -\n"), None);
+}
+
+#[test]
+fn test_journal() {
+    let path = std::env::temp_dir().join(format!("erbium-test-{}.journal", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let lease = |n: u8, expire| LeaseInfo {
+        ip: Ipv4Addr::new(192, 0, 2, n),
+        client_id: vec![n],
+        start: 0,
+        expire,
+        options: vec![],
+    };
+
+    let mut writer = JournalStore::open(&path).unwrap();
+    let mut reader = JournalStore::open(&path).unwrap();
+    writer.put(&lease(1, 100)).unwrap();
+    writer.put(&lease(2, 100)).unwrap();
+    writer.put(&lease(1, 200)).unwrap();
+    assert_eq!(reader.get(lease(1, 0).ip).unwrap(), Some(lease(1, 200)));
+    assert_eq!(reader.get_by_client(&[2]).unwrap(), vec![lease(2, 100)]);
+
+    /* A partially written record is ignored until it's complete */
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"192.0.2.3 0 100")
+        .unwrap();
+    assert_eq!(reader.get(lease(3, 0).ip).unwrap(), None);
+
+    /* Leases survive a restart, and the partial record is discarded */
+    drop(writer);
+    let mut writer = JournalStore::open(&path).unwrap();
+    assert_eq!(writer.records, 3);
+    assert_eq!(writer.get(lease(2, 0).ip).unwrap(), Some(lease(2, 100)));
+    writer.put(&lease(3, 100)).unwrap();
+    assert_eq!(reader.get(lease(3, 0).ip).unwrap(), Some(lease(3, 100)));
+
+    /* Deleted leases stay deleted after a restart */
+    writer.put(&lease(4, 100)).unwrap();
+    writer.delete(lease(4, 0).ip).unwrap();
+    assert_eq!(reader.get(lease(4, 0).ip).unwrap(), None);
+    assert_eq!(
+        JournalStore::open(&path)
+            .unwrap()
+            .get(lease(4, 0).ip)
+            .unwrap(),
+        None
+    );
+
+    /* Compacting the journal removes old records, and readers notice */
+    for expire in 0..JOURNAL_COMPACT_RECORDS as u32 {
+        writer.put(&lease(2, expire)).unwrap();
+    }
+    assert!(writer.records < JOURNAL_COMPACT_RECORDS);
+    let mut leases = reader.get_all().unwrap();
+    leases.sort();
+    assert_eq!(
+        leases,
+        vec![
+            lease(1, 200),
+            lease(2, JOURNAL_COMPACT_RECORDS as u32 - 1),
+            lease(3, 100),
+        ]
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sqlite_batch() {
+    let path = std::env::temp_dir().join(format!("erbium-test-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let lease = LeaseInfo {
+        ip: "192.0.2.1".parse().unwrap(),
+        client_id: vec![1],
+        start: 0,
+        expire: 100,
+        options: vec![],
+    };
+
+    let mut writer = SqliteStore::open(&path).unwrap();
+    let mut reader = SqliteStore::open(&path).unwrap();
+    writer.begin().unwrap();
+    writer.put(&lease).unwrap();
+    /* Nothing is visible to others until the batch is committed */
+    assert_eq!(reader.get_all().unwrap(), vec![]);
+    writer.commit().unwrap();
+    assert_eq!(reader.get_all().unwrap(), vec![lease]);
+
+    drop((writer, reader));
+    for suffix in &["", "-wal", "-shm"] {
+        let mut file = path.clone().into_os_string();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
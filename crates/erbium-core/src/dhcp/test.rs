@@ -72,6 +72,7 @@ pub fn mk_dhcp_request() -> dhcp::DHCPRequest {
         pkt: mk_dhcp_request_pkt(),
         serverip: SERVER_IP,
         ifindex: 1,
+        if_name: None,
         if_mtu: None,
         if_router: None,
     }
@@ -92,6 +93,7 @@ pub fn mk_default_config() -> crate::config::Config {
                 apply_address: Some(apply_address),
                 ..Default::default()
             }],
+            ..Default::default()
         },
         ..Default::default()
     }
@@ -215,7 +217,7 @@ async fn confirm_yiaddr_set() {
     let pkt = mk_dhcp_request();
     let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
     let conf = mk_default_config();
-    let reply = dhcp::handle_discover(&mut p, &pkt, &serverids, &[], &conf)
+    let (reply, _) = dhcp::handle_discover(&mut p, &pkt, &serverids, &[], &conf)
         .expect("Failed to handle request");
     assert_ne!(
         reply.yiaddr,
@@ -247,8 +249,100 @@ async fn confirm_yiaddr_set() {
 
 /* rfc2131 Section 3.2 Step 1: The server MUST broadcast the DHCPNAK message to the 0xffffffff broadcast address because the client may not have a correct network address or subnet mask, and the client may not be answering ARP requests.  Otherwise, the server MUST send the DHCPNAK message to the IP address of the BOOTP relay agent, as recorded in 'giaddr'.
  */
-fn broadcast_failed_renew() {
-    /* TODO */
+#[tokio::test]
+async fn broadcast_failed_renew() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let mut serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+    serverids.insert(SERVER_IP);
+    let conf = mk_default_config();
+
+    /* A client in RENEWING with an address that isn't in the pool */
+    let mut request = mk_dhcp_request();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPREQUEST);
+    request.pkt.ciaddr = "198.51.100.1".parse().unwrap();
+    let nak = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &conf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(nak.options.get_messagetype(), Some(dhcppkt::DHCPNAK));
+    assert!(dhcp::must_broadcast(&nak));
+
+    /* The same, but via a relay */
+    request.pkt.giaddr = EXAMPLE_IP4;
+    let nak = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(nak.options.get_messagetype(), Some(dhcppkt::DHCPNAK));
+    assert_eq!(nak.giaddr, EXAMPLE_IP4);
+    assert_eq!(nak.flags & dhcppkt::FLAG_BROADCAST, dhcppkt::FLAG_BROADCAST);
+    assert!(!dhcp::must_broadcast(&nak));
+}
+
+/* rfc2131 Section 4.3.2: INIT-REBOOT state: If the DHCP server detects that the client is on the
+ * wrong network (i.e., the result of applying the local subnet mask or remote subnet mask (if
+ * 'giaddr' is not zero) to 'requested IP address' option value doesn't match reality), then the
+ * server SHOULD send a DHCPNAK message to the client.
+ */
+#[tokio::test]
+async fn nak_init_reboot_wrong_network() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+    let conf = mk_default_config();
+
+    let mut request = mk_dhcp_request();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPREQUEST)
+        .set_option(
+            &dhcppkt::OPTION_ADDRESSREQUEST,
+            &"198.51.100.1".parse::<net::Ipv4Addr>().unwrap(),
+        );
+    let nak = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(nak.options.get_messagetype(), Some(dhcppkt::DHCPNAK));
+    assert_eq!(nak.options.get_serverid(), Some(SERVER_IP));
+    assert_eq!(nak.yiaddr, net::Ipv4Addr::UNSPECIFIED);
+    assert_eq!(nak.ciaddr, net::Ipv4Addr::UNSPECIFIED);
+    assert_eq!(
+        nak.options.get_option::<u32>(&dhcppkt::OPTION_LEASETIME),
+        None
+    );
+    /* Nothing should have been allocated */
+    assert!(p.get_leases().expect("Failed to get leases").is_empty());
+}
+
+#[tokio::test]
+async fn nak_address_in_use() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let mut serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+    serverids.insert(SERVER_IP);
+    let conf = mk_default_config();
+    p.reserve_address(b"Other Client", EXAMPLE_IP2);
+
+    /* SELECTING with an address that belongs to another client */
+    let mut request = mk_dhcp_request();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPREQUEST)
+        .set_option(&dhcppkt::OPTION_SERVERID, &SERVER_IP)
+        .set_option(&dhcppkt::OPTION_ADDRESSREQUEST, &EXAMPLE_IP2);
+    let nak = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(nak.options.get_messagetype(), Some(dhcppkt::DHCPNAK));
+    assert_eq!(nak.yiaddr, net::Ipv4Addr::UNSPECIFIED);
 }
 
 /* rfc2131 Section 3.2 Step 3: If the client detects that the IP address in the DHCPACK message is
@@ -261,8 +355,95 @@ fn broadcast_failed_renew() {
 /* rfc2131 Section 3.4: The server SHOULD check the network address in a DHCPINFORM message for
  * consistency, but MUST NOT check for an existing lease.
  */
-fn dhcpinform_dont_check_existing_lease() {
-    /* TODO */
+#[tokio::test]
+async fn dhcpinform_dont_check_existing_lease() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let mut request = mk_dhcp_request();
+    request.pkt.ciaddr = EXAMPLE_IP4;
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPINFORM)
+        .set_option(&dhcppkt::OPTION_PARAMLIST, &vec![1u8, 6u8]);
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+    let conf = crate::config::Config {
+        dns_servers: vec!["192.0.2.53".parse().unwrap()],
+        ..mk_default_config()
+    };
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
+        .await
+        .expect("Failed to handle inform")
+        .expect("No reply to inform");
+    assert_eq!(reply.options.get_messagetype(), Some(dhcppkt::DHCPACK));
+    assert_eq!(reply.ciaddr, EXAMPLE_IP4);
+    assert_eq!(reply.yiaddr, net::Ipv4Addr::UNSPECIFIED);
+    /* RFC2131 Section 4.3.1: IP address lease time MUST NOT (DHCPINFORM) */
+    assert!(reply
+        .options
+        .get_raw_option(&dhcppkt::OPTION_LEASETIME)
+        .is_none());
+    assert_eq!(
+        reply
+            .options
+            .get_option::<Vec<net::Ipv4Addr>>(&dhcppkt::OPTION_DOMAINSERVER),
+        Some(vec!["192.0.2.53".parse().unwrap()])
+    );
+    /* Nothing should have been allocated */
+    assert!(p.get_leases().expect("Failed to get leases").is_empty());
+}
+
+/* rfc2131 Section 4.3.3: If the server receives a DHCPDECLINE message, the client has discovered
+ * through some other means that the suggested network address is already in use.  The server MUST
+ * mark the network address as not available and SHOULD notify the local system administrator of a
+ * possible configuration problem.
+ */
+#[tokio::test]
+async fn decline_marks_address_unavailable() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let mut serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+    serverids.insert(SERVER_IP);
+    let conf = mk_default_config();
+
+    let mut request = mk_dhcp_request();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPREQUEST);
+    let ack = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &conf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+
+    let mut request = mk_dhcp_request();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDECLINE)
+        .set_option(&dhcppkt::OPTION_SERVERID, &SERVER_IP)
+        .set_option(&dhcppkt::OPTION_ADDRESSREQUEST, &ack.yiaddr);
+    assert_eq!(
+        dhcp::handle_pkt(&mut p, &request, serverids.clone(), &conf)
+            .await
+            .expect("Failed to handle decline"),
+        None
+    );
+
+    /* Asking for the same address again must not return it */
+    let mut request = mk_dhcp_request();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER)
+        .set_option(&dhcppkt::OPTION_ADDRESSREQUEST, &ack.yiaddr);
+    let offer = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
+        .await
+        .expect("Failed to handle discover")
+        .expect("No reply");
+    assert_ne!(offer.yiaddr, ack.yiaddr);
 }
 
 /* rfc2131 Section 3.5: If the client includes a list of parameters in a DHCPDISCOVER message, it
@@ -286,7 +467,7 @@ async fn server_address_set() {
     let pkt = mk_dhcp_request();
     let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
     let conf = mk_default_config();
-    let reply = dhcp::handle_discover(&mut p, &pkt, &serverids, &[], &conf)
+    let (reply, _) = dhcp::handle_discover(&mut p, &pkt, &serverids, &[], &conf)
         .expect("Failed to handle request");
     assert_ne!(
         reply
@@ -472,7 +653,8 @@ async fn offer_required() {
     let conf = mk_default_config();
     let reply = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
         .await
-        .expect("Failed to handle request");
+        .expect("Failed to handle request")
+        .expect("No reply");
     assert_eq!(reply.op, dhcppkt::OP_BOOTREPLY);
     assert_eq!(reply.htype, dhcppkt::HWTYPE_ETHERNET);
     assert_eq!(reply.hlen, 6);
@@ -525,7 +707,8 @@ async fn ack_required() {
     let conf = mk_default_config();
     let reply = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
         .await
-        .expect("Failed to handle request");
+        .expect("Failed to handle request")
+        .expect("No reply");
     assert_eq!(reply.op, dhcppkt::OP_BOOTREPLY);
     assert_eq!(reply.htype, dhcppkt::HWTYPE_ETHERNET);
     assert_eq!(reply.hlen, 6);
@@ -582,7 +765,8 @@ async fn test_renew_unknown() {
     let conf = mk_default_config();
     let reply = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
         .await
-        .expect("Failed to handle request");
+        .expect("Failed to handle request")
+        .expect("No reply");
     assert_eq!(reply.yiaddr, EXAMPLE_IP2);
 }
 
@@ -609,7 +793,8 @@ async fn test_full() {
 
     let offer = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &conf)
         .await
-        .expect("Failed to handle request");
+        .expect("Failed to handle request")
+        .expect("No reply");
 
     serverids.insert(offer.options.get_serverid().unwrap());
 
@@ -628,7 +813,8 @@ async fn test_full() {
 
     let ack = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &conf)
         .await
-        .expect("Failed to handle request");
+        .expect("Failed to handle request")
+        .expect("No reply");
 
     assert_eq!(ack.options.get_messagetype(), Some(dhcppkt::DHCPACK));
     assert_eq!(ack.yiaddr, offer.yiaddr); /* make sure we don't needlessly change our mind */
@@ -646,7 +832,8 @@ async fn test_full() {
     /* no server id */
     let ack = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &conf)
         .await
-        .expect("Failed to handle request");
+        .expect("Failed to handle request")
+        .expect("No reply");
     assert_eq!(ack.options.get_messagetype(), Some(dhcppkt::DHCPACK));
     assert_eq!(ack.yiaddr, offer.yiaddr); /* Did we get back the same address? */
 
@@ -654,16 +841,31 @@ async fn test_full() {
     let mut request = mk_dhcp_request();
     /* xid and seconds are not copied from the previous requests */
     request.pkt.secs = 0;
+    request.pkt.ciaddr = offer.yiaddr;
     request.pkt.options = request
         .pkt
         .options
         .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
-        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPRELEASE);
-    /* no server id */
-    /* release is not supported, so we expect an error here.  But we shouldn't crash */
-    let _ack = dhcp::handle_pkt(&mut p, &request, serverids, &conf)
-        .await
-        .expect_err("Failed to handle request");
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPRELEASE)
+        .set_option(
+            &dhcppkt::OPTION_SERVERID,
+            &offer.options.get_serverid().unwrap(),
+        );
+    /* RELEASE does not get a reply */
+    assert_eq!(
+        dhcp::handle_pkt(&mut p, &request, serverids, &conf)
+            .await
+            .expect("Failed to handle release"),
+        None
+    );
+    /* And the lease should now be expired */
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let leases = p.get_leases().expect("Failed to get leases");
+    assert_eq!(leases.len(), 1);
+    assert!(leases[0].expire <= now);
 }
 
 #[tokio::test]
@@ -681,7 +883,7 @@ dhcp-policies:
     .expect("Failed to parse test config");
     let lockedconf = conf.read().await;
     let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
-    let reply = dhcp::handle_discover(&mut p, &pkt, &serverids, &[], &lockedconf)
+    let (reply, _) = dhcp::handle_discover(&mut p, &pkt, &serverids, &[], &lockedconf)
         .expect("Failed to handle request");
     /* We've asked that netmask doesn't get set, so check it's not set */
     assert_eq!(
@@ -704,3 +906,431 @@ dhcp-policies:
  *    the DHCPREQUEST message use the message as notification that the client has declined that
  *    server's offer.
 */
+
+/* RFC2131 Section 4.3.1: If that address is not available or the client is on a different network,
+ * the server selects an address from the subnet of the 'giaddr' field (if not zero) or from the
+ * network on which the request was received.
+ */
+#[tokio::test]
+async fn relayed_discover() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let conf = crate::config::load_config_from_string_for_test(
+        "
+dhcp-policies:
+  - match-subnet: 192.0.2.0/24
+    apply-range: {start: 192.0.2.10, end: 192.0.2.20}
+  - match-subnet: 198.51.100.0/24
+    apply-range: {start: 198.51.100.10, end: 198.51.100.20}
+  - match-subnet: 203.0.113.0/24
+    apply-range: {start: 203.0.113.10, end: 203.0.113.20}
+",
+    )
+    .expect("Failed to parse test config");
+    let lockedconf = conf.read().await;
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+
+    let mut request = mk_dhcp_request();
+    request.pkt.giaddr = "198.51.100.1".parse().unwrap();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER);
+    let (reply, _) = dhcp::handle_discover(&mut p, &request, &serverids, &[], &lockedconf)
+        .expect("Failed to handle request");
+    assert!(
+        erbium_net::Ipv4Subnet::new("198.51.100.0".parse().unwrap(), 24)
+            .unwrap()
+            .contains(reply.yiaddr),
+        "{} not from the relay's subnet",
+        reply.yiaddr
+    );
+    assert_eq!(reply.giaddr, request.pkt.giaddr);
+
+    /* RFC3011 Section 3: the subnet selection option overrides giaddr, and must be echoed back */
+    let subnet: net::Ipv4Addr = "203.0.113.0".parse().unwrap();
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_SUBNETSELECT, &subnet);
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids, &lockedconf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert!(
+        erbium_net::Ipv4Subnet::new("203.0.113.0".parse().unwrap(), 24)
+            .unwrap()
+            .contains(reply.yiaddr),
+        "{} not from the selected subnet",
+        reply.yiaddr
+    );
+    assert_eq!(
+        reply
+            .options
+            .get_option::<net::Ipv4Addr>(&dhcppkt::OPTION_SUBNETSELECT),
+        Some(subnet)
+    );
+}
+
+/* RFC3046 Section 2.2: DHCP servers claiming to support the Relay Agent Information option SHALL
+ * echo the entire contents of the Relay Agent Information option in all replies.
+ */
+#[tokio::test]
+async fn relay_agent_information() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let conf = crate::config::load_config_from_string_for_test(
+        "
+dhcp-policies:
+  - match-subnet: 192.0.2.0/24
+    apply-range: {start: 192.0.2.10, end: 192.0.2.20}
+    policies:
+      - { match-circuit-id: Gi1/0/1, apply-address: 192.0.2.101 }
+      - { match-circuit-id: Gi1/0/2, match-remote-id: switch1, apply-address: 192.0.2.102 }
+      - { match-circuit-id: {hex: 00:04:00:0a}, apply-address: 192.0.2.103 }
+      - { match-circuit-id: 'de:ad', apply-address: 192.0.2.104 }
+",
+    )
+    .expect("Failed to parse test config");
+    let lockedconf = conf.read().await;
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+
+    let relayinfo = [
+        &[dhcppkt::RELAYINFO_CIRCUITID, 7][..],
+        b"Gi1/0/1",
+        &[dhcppkt::RELAYINFO_REMOTEID, 7],
+        b"switch1",
+    ]
+    .concat();
+    let mut request = mk_dhcp_request();
+    request.pkt.giaddr = EXAMPLE_IP4;
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER)
+        .set_raw_option(&dhcppkt::OPTION_RELAYINFO, &relayinfo);
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &lockedconf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(
+        reply.yiaddr,
+        "192.0.2.101".parse::<net::Ipv4Addr>().unwrap()
+    );
+    assert_eq!(
+        reply.options.get_raw_option(&dhcppkt::OPTION_RELAYINFO),
+        Some(relayinfo.as_slice())
+    );
+
+    /* Circuit ID matches, but the Remote ID doesn't, so falls back to the range */
+    let relayinfo = [
+        &[dhcppkt::RELAYINFO_CIRCUITID, 7][..],
+        b"Gi1/0/2",
+        &[dhcppkt::RELAYINFO_REMOTEID, 7],
+        b"switch2",
+    ]
+    .concat();
+    let mut request = mk_dhcp_request();
+    request.pkt.giaddr = EXAMPLE_IP4;
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &&b"Other Client"[..])
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER)
+        .set_raw_option(&dhcppkt::OPTION_RELAYINFO, &relayinfo);
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &lockedconf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_ne!(
+        reply.yiaddr,
+        "192.0.2.102".parse::<net::Ipv4Addr>().unwrap()
+    );
+
+    /* Binary circuit IDs can be matched by writing them in hex */
+    let relayinfo = [dhcppkt::RELAYINFO_CIRCUITID, 4, 0x00, 0x04, 0x00, 0x0a];
+    let mut request = mk_dhcp_request();
+    request.pkt.giaddr = EXAMPLE_IP4;
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &&b"Third Client"[..])
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER)
+        .set_raw_option(&dhcppkt::OPTION_RELAYINFO, &relayinfo);
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &lockedconf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(
+        reply.yiaddr,
+        "192.0.2.103".parse::<net::Ipv4Addr>().unwrap()
+    );
+
+    /* Strings are always matched as text, even if they look like hex */
+    let relayinfo = [&[dhcppkt::RELAYINFO_CIRCUITID, 5][..], b"de:ad"].concat();
+    let mut request = mk_dhcp_request();
+    request.pkt.giaddr = EXAMPLE_IP4;
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &&b"Fourth Client"[..])
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER)
+        .set_raw_option(&dhcppkt::OPTION_RELAYINFO, &relayinfo);
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids, &lockedconf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert_eq!(
+        reply.yiaddr,
+        "192.0.2.104".parse::<net::Ipv4Addr>().unwrap()
+    );
+
+    /* A missing value is an error, rather than matching everything */
+    assert!(crate::config::load_config_from_string_for_test(
+        "
+dhcp-policies:
+  - match-circuit-id:
+    apply-address: 192.0.2.105
+",
+    )
+    .is_err());
+}
+
+#[tokio::test]
+async fn match_interface() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let conf = crate::config::load_config_from_string_for_test(
+        "
+dhcp-policies:
+  - match-interface: lan
+    apply-range: {start: 192.0.2.10, end: 192.0.2.20}
+  - match-interface: guest
+    apply-range: {start: 192.0.2.100, end: 192.0.2.110}
+",
+    )
+    .expect("Failed to parse test config");
+    let lockedconf = conf.read().await;
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+
+    let mut request = mk_dhcp_request();
+    request.if_name = Some("guest".into());
+    request.pkt.options = request
+        .pkt
+        .options
+        .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+        .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER);
+    let reply = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &lockedconf)
+        .await
+        .expect("Failed to handle request")
+        .expect("No reply");
+    assert!(
+        (u32::from(reply.yiaddr) >= u32::from("192.0.2.100".parse::<net::Ipv4Addr>().unwrap()))
+            && (u32::from(reply.yiaddr)
+                <= u32::from("192.0.2.110".parse::<net::Ipv4Addr>().unwrap())),
+        "{} not from the guest range",
+        reply.yiaddr
+    );
+
+    /* Requests from interfaces that no policy matches are ignored */
+    request.if_name = Some("dmz".into());
+    assert!(dhcp::handle_pkt(&mut p, &request, serverids, &lockedconf)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn lease_times() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let conf = crate::config::load_config_from_string_for_test(
+        "
+dhcp-policies:
+  - match-subnet: 192.0.2.0/24
+    apply-range: {start: 192.0.2.10, end: 192.0.2.20}
+    apply-default-lease: 1h
+    apply-min-lease: 10m
+    apply-max-lease: 2h
+    policies:
+      - match-hardware-address: 00:00:5E:00:53:01
+        apply-renewal-time: 10m
+        apply-rebind-time: 20m
+",
+    )
+    .expect("Failed to parse test config");
+    let lockedconf = conf.read().await;
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+
+    let mut times = vec![];
+    for (client, chaddr, requested) in &[
+        (&b"default"[..], 0u8, None),
+        (b"too short", 0, Some(60u32)),
+        (b"too long", 0, Some(86400)),
+        (b"in range", 0, Some(1800)),
+        (b"renewal", 1, None),
+    ] {
+        let mut request = mk_dhcp_request();
+        request.pkt.chaddr[5] = *chaddr;
+        request.pkt.options = request
+            .pkt
+            .options
+            .set_option(&dhcppkt::OPTION_CLIENTID, client)
+            .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPREQUEST);
+        if let Some(requested) = requested {
+            request.pkt.options = request
+                .pkt
+                .options
+                .set_option(&dhcppkt::OPTION_LEASETIME, requested);
+        }
+        let reply = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &lockedconf)
+            .await
+            .expect("Failed to handle request")
+            .expect("No reply");
+        assert_eq!(reply.options.get_messagetype(), Some(dhcppkt::DHCPACK));
+        times.push((
+            reply
+                .options
+                .get_option::<u32>(&dhcppkt::OPTION_LEASETIME)
+                .unwrap(),
+            reply
+                .options
+                .get_option::<u32>(&dhcppkt::OPTION_RENEWALTIME)
+                .unwrap(),
+            reply
+                .options
+                .get_option::<u32>(&dhcppkt::OPTION_REBINDTIME)
+                .unwrap(),
+        ));
+    }
+    assert_eq!(
+        times,
+        vec![
+            /* No lease time requested gets the default lease */
+            (3600, 1800, 3150),
+            /* Requested lease times are limited by the min and max lease */
+            (600, 300, 525),
+            (7200, 3600, 6300),
+            (1800, 900, 1575),
+            /* Configured T1/T2 override the RFC2131 defaults */
+            (3600, 600, 1200),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn pxe_boot() {
+    let mut p = pool::Pool::new_in_memory().expect("Failed to create pool");
+    let conf = crate::config::load_config_from_string_for_test(
+        "
+dhcp-policies:
+  - match-subnet: 192.0.2.0/24
+    apply-range: {start: 192.0.2.10, end: 192.0.2.20}
+    policies:
+      - match-ipxe: true
+        apply-boot-file: http://192.0.2.1/boot.ipxe
+      - match-pxe-client: true
+        match-client-arch: [7, 9]
+        apply-next-server: 192.0.2.1
+        apply-boot-file: ipxe.efi
+      - match-pxe-client: true
+        apply-next-server: 192.0.2.1
+        apply-boot-file: undionly.kpxe
+",
+    )
+    .expect("Failed to parse test config");
+    let lockedconf = conf.read().await;
+    let serverids: dhcp::ServerIds = dhcp::ServerIds::new();
+
+    let mut boot = vec![];
+    for (vendor_class, user_class, arch) in &[
+        (None, None, None),
+        (
+            Some(&b"PXEClient:Arch:00000:UNDI:002001"[..]),
+            None,
+            Some(0u16),
+        ),
+        (Some(b"PXEClient:Arch:00007:UNDI:003016"), None, Some(7)),
+        (
+            Some(b"PXEClient:Arch:00007:UNDI:003016"),
+            Some(&b"iPXE"[..]),
+            Some(7),
+        ),
+    ] {
+        let mut request = mk_dhcp_request();
+        let mut options = request
+            .pkt
+            .options
+            .set_option(&dhcppkt::OPTION_CLIENTID, &CLIENTID)
+            .set_option(&dhcppkt::OPTION_MSGTYPE, &dhcppkt::DHCPDISCOVER);
+        if let Some(vendor_class) = vendor_class {
+            options = options.set_raw_option(&dhcppkt::OPTION_VENDOR_CLASS, vendor_class);
+        }
+        if let Some(user_class) = user_class {
+            options = options.set_raw_option(&dhcppkt::OPTION_USERCLASS, user_class);
+        }
+        if let Some(arch) = arch {
+            options = options.set_option(&dhcppkt::OPTION_CLIENTARCH, arch);
+        }
+        request.pkt.options = options;
+        let reply = dhcp::handle_pkt(&mut p, &request, serverids.clone(), &lockedconf)
+            .await
+            .expect("Failed to handle request")
+            .expect("No reply");
+        boot.push((
+            reply.siaddr.to_string(),
+            String::from_utf8(reply.file).unwrap(),
+        ));
+    }
+    assert_eq!(
+        boot,
+        vec![
+            /* Clients that aren't network booting don't get a boot file */
+            ("0.0.0.0".into(), "".into()),
+            /* PXE clients are chainloaded into iPXE for their architecture */
+            ("192.0.2.1".into(), "undionly.kpxe".into()),
+            ("192.0.2.1".into(), "ipxe.efi".into()),
+            /* And then iPXE gets the script URL */
+            ("0.0.0.0".into(), "http://192.0.2.1/boot.ipxe".into()),
+        ]
+    );
+}
+
+#[test]
+fn option_overload() {
+    let long_file = format!("http://192.0.2.1/{}/boot.ipxe", "x".repeat(200));
+    let mut reply = mk_dhcp_request_pkt();
+    reply.op = dhcppkt::OP_BOOTREPLY;
+    reply.file = long_file.clone().into_bytes();
+    for (i, opt) in [4u8, 7, 9, 41, 42, 44].iter().enumerate() {
+        reply.options = reply.options.set_option(
+            &dhcppkt::DhcpOption::new(*opt),
+            &vec![net::Ipv4Addr::new(192, 0, 2, i as u8); 6],
+        );
+    }
+
+    /* Without a size limit, everything goes in the options field */
+    let pkt = dhcppkt::parse(&reply.serialise()).expect("Failed to parse");
+    assert!(pkt.file.is_empty());
+    assert_eq!(
+        pkt.options.get_option::<String>(&dhcppkt::OPTION_BOOTFILE),
+        Some(long_file.clone())
+    );
+
+    /* The file name is too long to fit in the file field, so it is sent as an option, and then
+     * the other options overflow into the sname and file fields.
+     */
+    let buf = reply.serialise_max(dhcppkt::DhcpOptions::default().get_max_message_size());
+    assert!(buf.len() <= 548, "{} bytes is too large", buf.len());
+    let pkt = dhcppkt::parse(&buf).expect("Failed to parse");
+    assert_eq!(
+        pkt.options.get_raw_option(&dhcppkt::OPTION_OVERLOAD),
+        None,
+        "overload option should be consumed by parsing"
+    );
+    assert!(pkt.file.is_empty());
+    assert!(pkt.sname.is_empty());
+    assert_eq!(
+        pkt.options,
+        reply
+            .options
+            .set_raw_option(&dhcppkt::OPTION_BOOTFILE, long_file.as_bytes())
+    );
+}
@@ -0,0 +1,281 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Runs DHCP pool operations on their own thread.
+ *
+ *  Lease storage does blocking IO (and waits for it to reach the disk), which would otherwise
+ *  stall every other task on the async runtime.  Operations are queued to a dedicated thread, which
+ *  runs whatever has queued up as one batch, so that a burst of requests only has to wait for the
+ *  disk once.
+ */
+
+use super::pool;
+use std::time::Instant;
+
+/* The most operations that are written to storage together. */
+const MAX_BATCH: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref POOL_QUEUE_LATENCY: prometheus::Histogram = prometheus::register_histogram!(
+        "dhcp_pool_queue_latency",
+        "Time that DHCP pool operations wait to be run"
+    )
+    .unwrap();
+    static ref POOL_COMMIT_LATENCY: prometheus::Histogram = prometheus::register_histogram!(
+        "dhcp_pool_commit_latency",
+        "Time taken to write a batch of lease changes to storage"
+    )
+    .unwrap();
+    static ref POOL_BATCH_SIZE: prometheus::Histogram = prometheus::register_histogram!(
+        "dhcp_pool_batch_size",
+        "Number of DHCP pool operations written to storage together",
+        vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0]
+    )
+    .unwrap();
+}
+
+/* Called with the result of writing the batch the operation was in. */
+type Completion = Box<dyn FnOnce(Result<(), pool::Error>) + Send>;
+type Job<P> = Box<dyn FnOnce(&mut P) -> Completion + Send>;
+
+/// A pool whose changes can be written to storage in batches.
+pub trait Batched: Send + 'static {
+    /// Starts grouping changes together, so they can be written to storage all at once by
+    /// commit_batch.
+    fn begin_batch(&mut self) -> Result<(), pool::Error>;
+    /// Makes sure every change since begin_batch has been written to storage.
+    fn commit_batch(&mut self) -> Result<(), pool::Error>;
+    /// Called instead of commit_batch succeeding, when the changes since begin_batch may not have
+    /// been written to storage.
+    fn abort_batch(&mut self) {}
+    /// A counter that goes up every time a lease is changed.
+    fn changes(&self) -> std::sync::Arc<std::sync::atomic::AtomicU64> {
+        Default::default()
+    }
+}
+
+impl Batched for pool::Pool {
+    fn begin_batch(&mut self) -> Result<(), pool::Error> {
+        pool::Pool::begin_batch(self)
+    }
+    fn commit_batch(&mut self) -> Result<(), pool::Error> {
+        pool::Pool::commit_batch(self)
+    }
+    fn abort_batch(&mut self) {
+        pool::Pool::abort_batch(self)
+    }
+    fn changes(&self) -> std::sync::Arc<std::sync::atomic::AtomicU64> {
+        pool::Pool::changes(self)
+    }
+}
+
+/// A handle to a pool that is owned by a worker thread.  The thread exits once every handle has
+/// been dropped.
+pub struct PoolWorker<P = pool::Pool> {
+    queue: std::sync::mpsc::Sender<(Instant, Job<P>)>,
+    changes: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl<P> Clone for PoolWorker<P> {
+    fn clone(&self) -> Self {
+        PoolWorker {
+            queue: self.queue.clone(),
+            changes: self.changes.clone(),
+        }
+    }
+}
+
+impl<P: Batched> PoolWorker<P> {
+    pub fn new(pool: P) -> Self {
+        let changes = pool.changes();
+        let (queue, jobs) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("dhcp-pool".into())
+            .spawn(move || run(pool, jobs))
+            .expect("Failed to start DHCP pool thread");
+        PoolWorker { queue, changes }
+    }
+
+    /// A counter that goes up every time a lease is changed, which can be read without waiting
+    /// for the pool's thread.
+    pub fn changes(&self) -> u64 {
+        self.changes.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Runs `f` on the pool's thread.  This returns once any changes that `f` made have been
+    /// written to storage, or the error if they could not be.
+    pub async fn call<F, R>(&self, f: F) -> Result<R, pool::Error>
+    where
+        F: FnOnce(&mut P) -> Result<R, pool::Error> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let job: Job<P> = Box::new(move |pool| {
+            let ret = f(pool);
+            Box::new(move |committed| {
+                /* If the caller has gone away, there's nobody to tell */
+                let _ = tx.send(committed.and(ret));
+            })
+        });
+        let exited = || pool::Error::DbError("DHCP pool thread has exited".into());
+        self.queue
+            .send((Instant::now(), job))
+            .map_err(|_| exited())?;
+        rx.await.map_err(|_| exited())?
+    }
+}
+
+fn run<P: Batched>(mut pool: P, jobs: std::sync::mpsc::Receiver<(Instant, Job<P>)>) {
+    /* Wait for an operation, then take whatever else has queued up behind it */
+    while let Ok(first) = jobs.recv() {
+        let batch = std::iter::once(first)
+            .chain(jobs.try_iter().take(MAX_BATCH - 1))
+            .collect::<Vec<_>>();
+        POOL_BATCH_SIZE.observe(batch.len() as f64);
+
+        let began = pool.begin_batch();
+        let completions = batch
+            .into_iter()
+            .map(|(queued, job)| {
+                POOL_QUEUE_LATENCY.observe(queued.elapsed().as_secs_f64());
+                job(&mut pool)
+            })
+            .collect::<Vec<_>>();
+
+        let timer = POOL_COMMIT_LATENCY.start_timer();
+        let committed = began.and_then(|()| pool.commit_batch());
+        timer.observe_duration();
+        if let Err(e) = &committed {
+            log::warn!("Failed to write lease changes: {}", e);
+            pool.abort_batch();
+        }
+
+        for completion in completions {
+            completion(committed.clone());
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_worker() {
+    let worker = PoolWorker::new(pool::Pool::new_in_memory().unwrap());
+    let addresses: pool::PoolAddresses = ["192.0.2.1".parse().unwrap()].iter().copied().collect();
+
+    /* Requests that arrive together are handled in order */
+    let allocate = |client: &'static [u8]| {
+        let addresses = addresses.clone();
+        worker.call(move |p| {
+            p.allocate_address(
+                client,
+                None,
+                &addresses,
+                pool::DEFAULT_MIN_LEASE,
+                pool::DEFAULT_MAX_LEASE,
+                &[],
+            )
+        })
+    };
+    let (first, second) = futures::join!(allocate(b"first"), allocate(b"second"));
+    assert_eq!(
+        first.unwrap().ip,
+        "192.0.2.1".parse::<std::net::Ipv4Addr>().unwrap()
+    );
+    assert_eq!(second.unwrap_err(), pool::Error::NoAssignableAddress);
+
+    let leases = worker.call(|p| p.get_leases()).await.unwrap();
+    assert_eq!(leases.len(), 1);
+    assert_eq!(leases[0].client_id, b"first");
+}
+
+/* Keeps leases in memory, and can be made to fail to commit a batch, which throws away the batch's
+ * changes the way a database would.
+ */
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct FailingStore {
+    leases: std::collections::HashMap<std::net::Ipv4Addr, pool::LeaseInfo>,
+    committed: std::collections::HashMap<std::net::Ipv4Addr, pool::LeaseInfo>,
+    pub(crate) fail: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(test)]
+impl super::storage::LeaseStore for FailingStore {
+    fn get(&mut self, addr: std::net::Ipv4Addr) -> Result<Option<pool::LeaseInfo>, pool::Error> {
+        Ok(self.leases.get(&addr).cloned())
+    }
+    fn get_by_client(&mut self, client_id: &[u8]) -> Result<Vec<pool::LeaseInfo>, pool::Error> {
+        Ok(self
+            .leases
+            .values()
+            .filter(|l| l.client_id == client_id)
+            .cloned()
+            .collect())
+    }
+    fn get_all(&mut self) -> Result<Vec<pool::LeaseInfo>, pool::Error> {
+        Ok(self.leases.values().cloned().collect())
+    }
+    fn put(&mut self, lease: &pool::LeaseInfo) -> Result<(), pool::Error> {
+        self.leases.insert(lease.ip, lease.clone());
+        Ok(())
+    }
+    fn delete(&mut self, addr: std::net::Ipv4Addr) -> Result<(), pool::Error> {
+        self.leases.remove(&addr);
+        Ok(())
+    }
+    fn commit(&mut self) -> Result<(), pool::Error> {
+        if self.fail.load(std::sync::atomic::Ordering::Relaxed) {
+            self.leases = self.committed.clone();
+            Err(pool::Error::DbError("Disk on fire".into()))
+        } else {
+            self.committed = self.leases.clone();
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_failed_commit() {
+    let store = FailingStore::default();
+    let fail = store.fail.clone();
+    let worker = PoolWorker::new(pool::Pool::new_with_store(Box::new(store)));
+    let addresses: pool::PoolAddresses = ["192.0.2.1".parse().unwrap()].iter().copied().collect();
+    let allocate = |client: &'static [u8]| {
+        let addresses = addresses.clone();
+        worker.call(move |p| {
+            p.allocate_address(
+                client,
+                None,
+                &addresses,
+                pool::DEFAULT_MIN_LEASE,
+                pool::DEFAULT_MAX_LEASE,
+                &[],
+            )
+        })
+    };
+
+    fail.store(true, std::sync::atomic::Ordering::Relaxed);
+    assert!(matches!(
+        allocate(b"first").await,
+        Err(pool::Error::DbError(_))
+    ));
+
+    /* The lease was never written, so the address is still free */
+    fail.store(false, std::sync::atomic::Ordering::Relaxed);
+    assert_eq!(
+        allocate(b"second").await.unwrap().ip,
+        "192.0.2.1".parse::<std::net::Ipv4Addr>().unwrap()
+    );
+}
@@ -0,0 +1,120 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  DHCPv6 Configuration.
+ */
+
+pub use crate::config::*;
+use yaml_rust::yaml;
+
+/// A prefix that smaller prefixes are delegated to downstream routers from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delegation {
+    pub prefix: Prefix6,
+    pub delegated_length: u8,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub delegations: Vec<Delegation>,
+}
+
+impl Config {
+    /// Returns true if the prefix overlaps with any prefix we delegate from.
+    pub fn is_delegated(&self, prefix: &Prefix6) -> bool {
+        self.delegations.iter().any(|d| {
+            d.prefix.contains(prefix.network())
+                || Prefix6::new(prefix.network(), prefix.prefixlen).contains(d.prefix.network())
+        })
+    }
+}
+
+pub fn parse_delegation(name: &str, fragment: &yaml::Yaml) -> Result<Option<Delegation>, Error> {
+    if let Some(h) = fragment.as_hash() {
+        let mut prefix = None;
+        let mut delegated_length = None;
+        for (k, v) in h {
+            match (k.as_str(), v) {
+                (Some("prefix"), p) => prefix = parse_string_prefix6("prefix", p)?,
+                (Some("delegated-length"), l) => {
+                    delegated_length = parse_num("delegated-length", l)?
+                }
+                (Some(n), _) => {
+                    return Err(Error::InvalidConfig(format!("Unknown {} key: {}", name, n)))
+                }
+                (None, _) => {
+                    return Err(Error::InvalidConfig(format!(
+                        "{} keys should be String, not {}",
+                        name,
+                        type_to_name(fragment)
+                    )))
+                }
+            }
+        }
+        let prefix =
+            prefix.ok_or_else(|| Error::InvalidConfig(format!("{} is missing a prefix", name)))?;
+        let prefix = Prefix6::new(prefix.network(), prefix.prefixlen);
+        let delegated_length = delegated_length.unwrap_or(56);
+        if delegated_length < prefix.prefixlen || delegated_length > 128 {
+            return Err(Error::InvalidConfig(format!(
+                "{}: delegated-length {} must be between {} and 128",
+                name, delegated_length, prefix.prefixlen
+            )));
+        }
+        Ok(Some(Delegation {
+            prefix,
+            delegated_length,
+        }))
+    } else {
+        Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(fragment)
+        )))
+    }
+}
+
+pub fn parse(fragment: &yaml::Yaml) -> Result<Option<Config>, Error> {
+    Ok(
+        parse_array("prefix-delegation", fragment, parse_delegation)?
+            .map(|delegations| Config { delegations }),
+    )
+}
+
+#[test]
+fn test_parse_delegation() {
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "- prefix: 2001:db8:100::1/48\n  delegated-length: 60\n- prefix: 2001:db8:200::/48\n",
+    )
+    .unwrap();
+    let conf = parse(&y[0]).unwrap().unwrap();
+    assert_eq!(
+        conf.delegations,
+        vec![
+            Delegation {
+                prefix: Prefix6::new("2001:db8:100::".parse().unwrap(), 48),
+                delegated_length: 60,
+            },
+            Delegation {
+                prefix: Prefix6::new("2001:db8:200::".parse().unwrap(), 48),
+                delegated_length: 56,
+            }
+        ]
+    );
+    assert!(conf.is_delegated(&Prefix6::new("2001:db8:100:1::".parse().unwrap(), 64)));
+    assert!(conf.is_delegated(&Prefix6::new("2001:db8::".parse().unwrap(), 32)));
+    assert!(!conf.is_delegated(&Prefix6::new("2001:db8::".parse().unwrap(), 64)));
+}
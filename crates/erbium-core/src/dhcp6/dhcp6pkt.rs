@@ -0,0 +1,592 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Parsing/Serialisation for a DHCPv6 Packet (RFC8415).
+ */
+
+use crate::pktparser::Buffer;
+use std::fmt;
+use std::net;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedEndOfInput,
+    InvalidPacket,
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEndOfInput => write!(f, "Unexpected End Of Input"),
+            ParseError::InvalidPacket => write!(f, "Invalid Packet"),
+        }
+    }
+}
+
+impl ParseError {
+    pub const fn get_variant_name(&self) -> &'static str {
+        use ParseError::*;
+        match self {
+            UnexpectedEndOfInput => "TRUNCATED_PACKET",
+            InvalidPacket => "INVALID_PACKET",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct MessageType(pub u8);
+pub const SOLICIT: MessageType = MessageType(1);
+pub const ADVERTISE: MessageType = MessageType(2);
+pub const REQUEST: MessageType = MessageType(3);
+pub const CONFIRM: MessageType = MessageType(4);
+pub const RENEW: MessageType = MessageType(5);
+pub const REBIND: MessageType = MessageType(6);
+pub const REPLY: MessageType = MessageType(7);
+pub const RELEASE: MessageType = MessageType(8);
+pub const DECLINE: MessageType = MessageType(9);
+pub const RECONFIGURE: MessageType = MessageType(10);
+pub const INFORMATION_REQUEST: MessageType = MessageType(11);
+pub const RELAY_FORW: MessageType = MessageType(12);
+pub const RELAY_REPL: MessageType = MessageType(13);
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SOLICIT => write!(f, "SOLICIT"),
+            ADVERTISE => write!(f, "ADVERTISE"),
+            REQUEST => write!(f, "REQUEST"),
+            CONFIRM => write!(f, "CONFIRM"),
+            RENEW => write!(f, "RENEW"),
+            REBIND => write!(f, "REBIND"),
+            REPLY => write!(f, "REPLY"),
+            RELEASE => write!(f, "RELEASE"),
+            DECLINE => write!(f, "DECLINE"),
+            RECONFIGURE => write!(f, "RECONFIGURE"),
+            INFORMATION_REQUEST => write!(f, "INFORMATION-REQUEST"),
+            RELAY_FORW => write!(f, "RELAY-FORW"),
+            RELAY_REPL => write!(f, "RELAY-REPL"),
+            MessageType(x) => write!(f, "#{}", x),
+        }
+    }
+}
+
+impl fmt::Debug for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Dhcp6Option(pub u16);
+pub const OPTION_CLIENTID: Dhcp6Option = Dhcp6Option(1);
+pub const OPTION_SERVERID: Dhcp6Option = Dhcp6Option(2);
+pub const OPTION_IA_NA: Dhcp6Option = Dhcp6Option(3);
+pub const OPTION_IA_TA: Dhcp6Option = Dhcp6Option(4);
+pub const OPTION_IAADDR: Dhcp6Option = Dhcp6Option(5);
+pub const OPTION_ORO: Dhcp6Option = Dhcp6Option(6);
+pub const OPTION_PREFERENCE: Dhcp6Option = Dhcp6Option(7);
+pub const OPTION_ELAPSED_TIME: Dhcp6Option = Dhcp6Option(8);
+pub const OPTION_STATUS_CODE: Dhcp6Option = Dhcp6Option(13);
+pub const OPTION_RAPID_COMMIT: Dhcp6Option = Dhcp6Option(14);
+pub const OPTION_DNS_SERVERS: Dhcp6Option = Dhcp6Option(23); /* RFC3646 */
+pub const OPTION_DOMAIN_LIST: Dhcp6Option = Dhcp6Option(24); /* RFC3646 */
+pub const OPTION_IA_PD: Dhcp6Option = Dhcp6Option(25); /* RFC8415 */
+pub const OPTION_IAPREFIX: Dhcp6Option = Dhcp6Option(26); /* RFC8415 */
+pub const OPTION_INFORMATION_REFRESH_TIME: Dhcp6Option = Dhcp6Option(32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StatusCode(pub u16);
+pub const STATUS_SUCCESS: StatusCode = StatusCode(0);
+pub const STATUS_UNSPECFAIL: StatusCode = StatusCode(1);
+pub const STATUS_NOADDRSAVAIL: StatusCode = StatusCode(2);
+pub const STATUS_NOBINDING: StatusCode = StatusCode(3);
+pub const STATUS_NOTONLINK: StatusCode = StatusCode(4);
+pub const STATUS_USEMULTICAST: StatusCode = StatusCode(5);
+pub const STATUS_NOPREFIXAVAIL: StatusCode = StatusCode(6);
+
+/* RFC8415 Section 11.2: DUID types */
+pub const DUID_LL: u16 = 3;
+pub const HWTYPE_ETHERNET: u16 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Status {
+    pub code: StatusCode,
+    pub message: String,
+}
+
+impl Status {
+    pub fn new(code: StatusCode, message: &str) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn parse(v: &[u8]) -> Option<Self> {
+        let mut buf = Buffer::new(v);
+        let code = StatusCode(buf.get_be16()?);
+        let message = String::from_utf8_lossy(buf.get_bytes(buf.remaining())?).to_string();
+        Some(Self { code, message })
+    }
+
+    fn serialise(&self, v: &mut Vec<u8>) {
+        v.extend(self.code.0.to_be_bytes().iter());
+        v.extend(self.message.as_bytes());
+    }
+}
+
+/// An address inside an IA_NA (RFC8415 Section 21.6)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IaAddr {
+    pub addr: net::Ipv6Addr,
+    pub preferred: Duration,
+    pub valid: Duration,
+    pub status: Option<Status>,
+}
+
+impl IaAddr {
+    fn parse(v: &[u8]) -> Option<Self> {
+        let mut buf = Buffer::new(v);
+        let addr = get_ipv6(&mut buf)?;
+        let preferred = Duration::from_secs(buf.get_be32()?.into());
+        let valid = Duration::from_secs(buf.get_be32()?.into());
+        let options = parse_options(&mut buf).ok()?;
+        Some(Self {
+            addr,
+            preferred,
+            valid,
+            status: options
+                .get_option(&OPTION_STATUS_CODE)
+                .and_then(Status::parse),
+        })
+    }
+
+    fn serialise(&self, v: &mut Vec<u8>) {
+        v.extend(self.addr.octets().iter());
+        v.extend(lifetime_to_u32(self.preferred).to_be_bytes().iter());
+        v.extend(lifetime_to_u32(self.valid).to_be_bytes().iter());
+        if let Some(status) = &self.status {
+            let mut s = vec![];
+            status.serialise(&mut s);
+            serialise_option(v, &OPTION_STATUS_CODE, &s);
+        }
+    }
+}
+
+/// An Identity Association for Non-temporary Addresses (RFC8415 Section 21.4)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IaNa {
+    pub iaid: u32,
+    pub t1: Duration,
+    pub t2: Duration,
+    pub addresses: Vec<IaAddr>,
+    pub status: Option<Status>,
+}
+
+impl IaNa {
+    fn parse(v: &[u8]) -> Option<Self> {
+        let mut buf = Buffer::new(v);
+        let iaid = buf.get_be32()?;
+        let t1 = Duration::from_secs(buf.get_be32()?.into());
+        let t2 = Duration::from_secs(buf.get_be32()?.into());
+        let options = parse_options(&mut buf).ok()?;
+        Some(Self {
+            iaid,
+            t1,
+            t2,
+            addresses: options
+                .get_all_options(&OPTION_IAADDR)
+                .filter_map(IaAddr::parse)
+                .collect(),
+            status: options
+                .get_option(&OPTION_STATUS_CODE)
+                .and_then(Status::parse),
+        })
+    }
+
+    pub fn serialise(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.extend(self.iaid.to_be_bytes().iter());
+        v.extend(lifetime_to_u32(self.t1).to_be_bytes().iter());
+        v.extend(lifetime_to_u32(self.t2).to_be_bytes().iter());
+        for addr in &self.addresses {
+            let mut a = vec![];
+            addr.serialise(&mut a);
+            serialise_option(&mut v, &OPTION_IAADDR, &a);
+        }
+        if let Some(status) = &self.status {
+            let mut s = vec![];
+            status.serialise(&mut s);
+            serialise_option(&mut v, &OPTION_STATUS_CODE, &s);
+        }
+        v
+    }
+}
+
+/// A prefix inside an IA_PD (RFC8415 Section 21.22)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IaPrefix {
+    pub preferred: Duration,
+    pub valid: Duration,
+    pub prefixlen: u8,
+    pub prefix: net::Ipv6Addr,
+    pub status: Option<Status>,
+}
+
+impl IaPrefix {
+    fn parse(v: &[u8]) -> Option<Self> {
+        let mut buf = Buffer::new(v);
+        let preferred = Duration::from_secs(buf.get_be32()?.into());
+        let valid = Duration::from_secs(buf.get_be32()?.into());
+        let prefixlen = buf.get_u8()?;
+        let prefix = get_ipv6(&mut buf)?;
+        let options = parse_options(&mut buf).ok()?;
+        Some(Self {
+            preferred,
+            valid,
+            prefixlen,
+            prefix,
+            status: options
+                .get_option(&OPTION_STATUS_CODE)
+                .and_then(Status::parse),
+        })
+    }
+
+    fn serialise(&self, v: &mut Vec<u8>) {
+        v.extend(lifetime_to_u32(self.preferred).to_be_bytes().iter());
+        v.extend(lifetime_to_u32(self.valid).to_be_bytes().iter());
+        v.push(self.prefixlen);
+        v.extend(self.prefix.octets().iter());
+        if let Some(status) = &self.status {
+            let mut s = vec![];
+            status.serialise(&mut s);
+            serialise_option(v, &OPTION_STATUS_CODE, &s);
+        }
+    }
+}
+
+/// An Identity Association for Prefix Delegation (RFC8415 Section 21.21)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IaPd {
+    pub iaid: u32,
+    pub t1: Duration,
+    pub t2: Duration,
+    pub prefixes: Vec<IaPrefix>,
+    pub status: Option<Status>,
+}
+
+impl IaPd {
+    fn parse(v: &[u8]) -> Option<Self> {
+        let mut buf = Buffer::new(v);
+        let iaid = buf.get_be32()?;
+        let t1 = Duration::from_secs(buf.get_be32()?.into());
+        let t2 = Duration::from_secs(buf.get_be32()?.into());
+        let options = parse_options(&mut buf).ok()?;
+        Some(Self {
+            iaid,
+            t1,
+            t2,
+            prefixes: options
+                .get_all_options(&OPTION_IAPREFIX)
+                .filter_map(IaPrefix::parse)
+                .collect(),
+            status: options
+                .get_option(&OPTION_STATUS_CODE)
+                .and_then(Status::parse),
+        })
+    }
+
+    pub fn serialise(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.extend(self.iaid.to_be_bytes().iter());
+        v.extend(lifetime_to_u32(self.t1).to_be_bytes().iter());
+        v.extend(lifetime_to_u32(self.t2).to_be_bytes().iter());
+        for prefix in &self.prefixes {
+            let mut p = vec![];
+            prefix.serialise(&mut p);
+            serialise_option(&mut v, &OPTION_IAPREFIX, &p);
+        }
+        if let Some(status) = &self.status {
+            let mut s = vec![];
+            status.serialise(&mut s);
+            serialise_option(&mut v, &OPTION_STATUS_CODE, &s);
+        }
+        v
+    }
+}
+
+/* RFC8415 Section 7.7: A value of 0xffffffff is "infinity" */
+fn lifetime_to_u32(d: Duration) -> u32 {
+    std::convert::TryInto::try_into(d.as_secs()).unwrap_or(u32::MAX)
+}
+
+fn get_ipv6(buf: &mut Buffer) -> Option<net::Ipv6Addr> {
+    let mut octets = [0_u8; 16];
+    octets.copy_from_slice(buf.get_bytes(16)?);
+    Some(octets.into())
+}
+
+/// Options may be repeated in DHCPv6 (eg multiple IA_NA's), so these are kept in order in a Vec.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dhcp6Options {
+    pub other: Vec<(Dhcp6Option, Vec<u8>)>,
+}
+
+impl Dhcp6Options {
+    pub fn get_option(&self, option: &Dhcp6Option) -> Option<&[u8]> {
+        self.get_all_options(option).next()
+    }
+
+    pub fn get_all_options<'a>(
+        &'a self,
+        option: &Dhcp6Option,
+    ) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let option = *option;
+        self.other
+            .iter()
+            .filter(move |(o, _)| *o == option)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    pub fn get_clientid(&self) -> Option<Vec<u8>> {
+        self.get_option(&OPTION_CLIENTID).map(|v| v.to_vec())
+    }
+
+    pub fn get_serverid(&self) -> Option<Vec<u8>> {
+        self.get_option(&OPTION_SERVERID).map(|v| v.to_vec())
+    }
+
+    pub fn get_ia_na(&self) -> Vec<IaNa> {
+        self.get_all_options(&OPTION_IA_NA)
+            .filter_map(IaNa::parse)
+            .collect()
+    }
+
+    pub fn get_ia_pd(&self) -> Vec<IaPd> {
+        self.get_all_options(&OPTION_IA_PD)
+            .filter_map(IaPd::parse)
+            .collect()
+    }
+
+    /// The Option Request Option (RFC8415 Section 21.7)
+    pub fn get_oro(&self) -> Vec<Dhcp6Option> {
+        self.get_option(&OPTION_ORO)
+            .map(|v| {
+                v.chunks_exact(2)
+                    .map(|c| Dhcp6Option(u16::from_be_bytes([c[0], c[1]])))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn has_rapid_commit(&self) -> bool {
+        self.get_option(&OPTION_RAPID_COMMIT).is_some()
+    }
+
+    #[must_use]
+    pub fn add_option(mut self, option: &Dhcp6Option, value: &[u8]) -> Self {
+        self.other.push((*option, value.to_vec()));
+        self
+    }
+
+    #[must_use]
+    pub fn add_ia_na(self, ia: &IaNa) -> Self {
+        self.add_option(&OPTION_IA_NA, &ia.serialise())
+    }
+
+    #[must_use]
+    pub fn add_ia_pd(self, ia: &IaPd) -> Self {
+        self.add_option(&OPTION_IA_PD, &ia.serialise())
+    }
+
+    #[must_use]
+    pub fn add_status(self, status: &Status) -> Self {
+        let mut v = vec![];
+        status.serialise(&mut v);
+        self.add_option(&OPTION_STATUS_CODE, &v)
+    }
+
+    #[must_use]
+    pub fn add_dns_servers(self, servers: &[net::Ipv6Addr]) -> Self {
+        let v = servers
+            .iter()
+            .flat_map(|s| s.octets().to_vec())
+            .collect::<Vec<u8>>();
+        self.add_option(&OPTION_DNS_SERVERS, &v)
+    }
+
+    #[must_use]
+    pub fn add_domain_list(self, domains: &[String]) -> Self {
+        let mut v = vec![];
+        for domain in domains {
+            for label in domain.split('.').filter(|l| !l.is_empty()) {
+                v.push(label.len() as u8);
+                v.extend(label.as_bytes());
+            }
+            v.push(0);
+        }
+        self.add_option(&OPTION_DOMAIN_LIST, &v)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Dhcp6 {
+    pub msgtype: MessageType,
+    /// Only the bottom 24 bits are used.
+    pub xid: u32,
+    pub options: Dhcp6Options,
+}
+
+impl fmt::Debug for Dhcp6 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dhcp6")
+            .field("msgtype", &self.msgtype)
+            .field("xid", &format!("{:06x}", self.xid))
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl Dhcp6 {
+    pub fn serialise(&self) -> Vec<u8> {
+        let mut v = vec![self.msgtype.0];
+        v.extend(&self.xid.to_be_bytes()[1..]);
+        for (option, value) in &self.options.other {
+            serialise_option(&mut v, option, value);
+        }
+        v
+    }
+}
+
+fn serialise_option(v: &mut Vec<u8>, option: &Dhcp6Option, value: &[u8]) {
+    v.extend(option.0.to_be_bytes().iter());
+    v.extend((value.len() as u16).to_be_bytes().iter());
+    v.extend(value);
+}
+
+fn parse_options(buf: &mut Buffer) -> Result<Dhcp6Options, ParseError> {
+    let mut options = Dhcp6Options::default();
+    while !buf.empty() {
+        let option = Dhcp6Option(buf.get_be16().ok_or(ParseError::UnexpectedEndOfInput)?);
+        let len = buf.get_be16().ok_or(ParseError::UnexpectedEndOfInput)?;
+        let value = buf
+            .get_vec(len.into())
+            .ok_or(ParseError::UnexpectedEndOfInput)?;
+        options.other.push((option, value));
+    }
+    Ok(options)
+}
+
+pub fn parse(pkt: &[u8]) -> Result<Dhcp6, ParseError> {
+    let mut buf = Buffer::new(pkt);
+    let msgtype = MessageType(buf.get_u8().ok_or(ParseError::UnexpectedEndOfInput)?);
+    let xid = buf
+        .get_bytes(3)
+        .ok_or(ParseError::UnexpectedEndOfInput)?
+        .iter()
+        .fold(0_u32, |acc, &b| (acc << 8) | b as u32);
+    /* Relay messages have a completely different format, which we don't (yet) support. */
+    if msgtype == RELAY_FORW || msgtype == RELAY_REPL {
+        return Err(ParseError::InvalidPacket);
+    }
+    let options = parse_options(&mut buf)?;
+    Ok(Dhcp6 {
+        msgtype,
+        xid,
+        options,
+    })
+}
+
+#[test]
+fn test_parse_inverse_serialise() {
+    let ia = IaNa {
+        iaid: 0x12345678,
+        t1: Duration::from_secs(1800),
+        t2: Duration::from_secs(2880),
+        addresses: vec![IaAddr {
+            addr: "2001:db8::1".parse().unwrap(),
+            preferred: Duration::from_secs(3600),
+            valid: Duration::from_secs(7200),
+            status: None,
+        }],
+        status: Some(Status::new(STATUS_SUCCESS, "ok")),
+    };
+    let pkt = Dhcp6 {
+        msgtype: REQUEST,
+        xid: 0x00abcdef,
+        options: Dhcp6Options::default()
+            .add_option(&OPTION_CLIENTID, &[0, 3, 0, 1, 2, 0, 0, 0, 0, 1])
+            .add_ia_na(&ia)
+            .add_option(&OPTION_ORO, &[0, 23, 0, 24]),
+    };
+    let parsed = parse(&pkt.serialise()).expect("Failed to parse packet");
+    assert_eq!(parsed, pkt);
+    assert_eq!(parsed.options.get_ia_na(), vec![ia]);
+    assert_eq!(
+        parsed.options.get_oro(),
+        vec![OPTION_DNS_SERVERS, OPTION_DOMAIN_LIST]
+    );
+}
+
+#[test]
+fn test_ia_pd() {
+    let ia = IaPd {
+        iaid: 1,
+        t1: Duration::from_secs(1800),
+        t2: Duration::from_secs(2880),
+        prefixes: vec![IaPrefix {
+            preferred: Duration::from_secs(3600),
+            valid: Duration::from_secs(7200),
+            prefixlen: 56,
+            prefix: "2001:db8:100::".parse().unwrap(),
+            status: None,
+        }],
+        status: None,
+    };
+    let pkt = Dhcp6 {
+        msgtype: REPLY,
+        xid: 0x000001,
+        options: Dhcp6Options::default().add_ia_pd(&ia),
+    };
+    let parsed = parse(&pkt.serialise()).expect("Failed to parse packet");
+    assert_eq!(parsed.options.get_ia_pd(), vec![ia]);
+    assert_eq!(parsed.options.get_ia_na(), vec![]);
+}
+
+#[test]
+fn test_domain_list() {
+    let opts = Dhcp6Options::default().add_domain_list(&["example.com".into()]);
+    assert_eq!(
+        opts.get_option(&OPTION_DOMAIN_LIST),
+        Some(&b"\x07example\x03com\x00"[..])
+    );
+}
+
+#[test]
+fn test_truncated() {
+    assert_eq!(parse(&[1, 0, 0]), Err(ParseError::UnexpectedEndOfInput));
+    assert_eq!(
+        parse(&[1, 0, 0, 1, 0, 1, 0, 4, 0]),
+        Err(ParseError::UnexpectedEndOfInput)
+    );
+}
@@ -0,0 +1,952 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Main DHCPv6 Code.
+ */
+
+use std::convert::TryInto as _;
+use std::sync::Arc;
+
+use crate::config::{Match as _, Prefix6, PrefixOps as _};
+use crate::dhcp::pool::Error as PoolError;
+use crate::dhcp::worker::PoolWorker;
+use erbium_net::addr::{
+    NetAddr, NetAddrExt as _, WithPort as _, ALL_DHCP_RELAY_AGENTS_AND_SERVERS, UNSPECIFIED6,
+};
+use erbium_net::udp;
+
+pub mod config;
+pub mod dhcp6pkt;
+pub mod pool;
+
+#[cfg(test)]
+mod test;
+
+type UdpSocket = udp::UdpSocket;
+
+pub const DEFAULT_VALID_LIFETIME: std::time::Duration = std::time::Duration::from_secs(86400);
+pub const DEFAULT_PREFERRED_LIFETIME: std::time::Duration = std::time::Duration::from_secs(43200);
+/// How often to check for delegated prefixes whose routes need to be removed.
+const ROUTE_EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+lazy_static::lazy_static! {
+    static ref DHCP6_RX_PACKETS: prometheus::IntCounter =
+        prometheus::register_int_counter!("dhcp6_received_packets", "Number of DHCPv6 packets received")
+            .unwrap();
+    static ref DHCP6_TX_PACKETS: prometheus::IntCounter =
+        prometheus::register_int_counter!("dhcp6_sent_packets", "Number of DHCPv6 packets sent")
+            .unwrap();
+    static ref DHCP6_ERRORS: prometheus::IntCounterVec =
+        prometheus::register_int_counter_vec!("dhcp6_errors",
+            "Counts of reasons that DHCPv6 replies cannot be sent",
+            &["reason"])
+            .unwrap();
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Dhcp6Error {
+    ParseError(dhcp6pkt::ParseError),
+    PoolError(PoolError),
+    UnknownMessageType(dhcp6pkt::MessageType),
+    MissingClientId,
+    UnexpectedServerId,
+    OtherServer,
+}
+
+impl std::error::Error for Dhcp6Error {}
+
+impl std::fmt::Display for Dhcp6Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dhcp6Error::ParseError(e) => write!(f, "Failed to parse packet: {}", e),
+            Dhcp6Error::PoolError(e) => write!(f, "Failed to allocate address: {}", e),
+            Dhcp6Error::UnknownMessageType(m) => {
+                write!(f, "Unknown Message Type: {}", m)
+            }
+            Dhcp6Error::MissingClientId => write!(f, "Packet is missing a client identifier"),
+            Dhcp6Error::UnexpectedServerId => {
+                write!(f, "Packet has a server identifier where none is allowed")
+            }
+            Dhcp6Error::OtherServer => write!(f, "Packet is for a different server"),
+        }
+    }
+}
+
+impl Dhcp6Error {
+    const fn get_variant_name(&self) -> &'static str {
+        use Dhcp6Error::*;
+        match self {
+            ParseError(e) => e.get_variant_name(),
+            PoolError(crate::dhcp::pool::Error::DbError(_)) => "DB_ERROR",
+            PoolError(crate::dhcp::pool::Error::CorruptDatabase(_)) => "CORRUPT_DATABASE",
+            PoolError(crate::dhcp::pool::Error::NoAssignableAddress) => "NO_ADDRESS",
+            PoolError(crate::dhcp::pool::Error::RequestedAddressInUse) => "ADDRESS_IN_USE",
+            PoolError(crate::dhcp::pool::Error::NoSuchLease) => "NO_SUCH_LEASE",
+            PoolError(crate::dhcp::pool::Error::PeerAddress) => "PEER_ADDRESS",
+            PoolError(crate::dhcp::pool::Error::InvalidDuration) => "INVALID_DURATION",
+            UnknownMessageType(_) => "UNKNOWN_MESSAGE_TYPE",
+            MissingClientId => "MISSING_CLIENTID",
+            UnexpectedServerId => "UNEXPECTED_SERVERID",
+            OtherServer => "OTHER_SERVER",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Dhcp6Request {
+    /// The DHCPv6 request packet.
+    pub pkt: dhcp6pkt::Dhcp6,
+    /// Our DUID on the interface the request was received on.
+    pub serverid: Vec<u8>,
+    /// The interface index that the request was received on.
+    pub ifindex: u32,
+    /// The prefixes that addresses can be assigned from on this link.
+    pub prefixes: Vec<Prefix6>,
+    /// An address of ours on this link, used to replace "$self6" in the config.
+    pub selfaddr: Option<std::net::Ipv6Addr>,
+}
+
+/* RFC8415 Section 11.4: DUID-LL.  We build this from the link layer address of the interface the
+ * request arrived on.
+ */
+pub fn make_duid_ll(lladdr: &[u8; 6]) -> Vec<u8> {
+    let mut duid = vec![];
+    duid.extend(dhcp6pkt::DUID_LL.to_be_bytes().iter());
+    duid.extend(dhcp6pkt::HWTYPE_ETHERNET.to_be_bytes().iter());
+    duid.extend(lladdr.iter());
+    duid
+}
+
+/* RFC8415 Section 21.4: Recommended values for T1 and T2 are .5 and .8 times the shortest
+ * preferred lifetime of the addresses in the IA that the server is willing to extend.
+ */
+fn ia_with_address(iaid: u32, addr: std::net::Ipv6Addr) -> dhcp6pkt::IaNa {
+    dhcp6pkt::IaNa {
+        iaid,
+        t1: DEFAULT_PREFERRED_LIFETIME / 2,
+        t2: DEFAULT_PREFERRED_LIFETIME * 4 / 5,
+        addresses: vec![dhcp6pkt::IaAddr {
+            addr,
+            preferred: DEFAULT_PREFERRED_LIFETIME,
+            valid: DEFAULT_VALID_LIFETIME,
+            status: None,
+        }],
+        status: None,
+    }
+}
+
+fn iapd_with_prefix(iaid: u32, prefix: &Prefix6) -> dhcp6pkt::IaPd {
+    dhcp6pkt::IaPd {
+        iaid,
+        t1: DEFAULT_PREFERRED_LIFETIME / 2,
+        t2: DEFAULT_PREFERRED_LIFETIME * 4 / 5,
+        prefixes: vec![dhcp6pkt::IaPrefix {
+            preferred: DEFAULT_PREFERRED_LIFETIME,
+            valid: DEFAULT_VALID_LIFETIME,
+            prefixlen: prefix.prefixlen,
+            prefix: prefix.network(),
+            status: None,
+        }],
+        status: None,
+    }
+}
+
+fn iapd_with_status(iaid: u32, code: dhcp6pkt::StatusCode, message: &str) -> dhcp6pkt::IaPd {
+    dhcp6pkt::IaPd {
+        iaid,
+        t1: std::time::Duration::from_secs(0),
+        t2: std::time::Duration::from_secs(0),
+        prefixes: vec![],
+        status: Some(dhcp6pkt::Status::new(code, message)),
+    }
+}
+
+fn ia_with_status(iaid: u32, code: dhcp6pkt::StatusCode, message: &str) -> dhcp6pkt::IaNa {
+    dhcp6pkt::IaNa {
+        iaid,
+        t1: std::time::Duration::from_secs(0),
+        t2: std::time::Duration::from_secs(0),
+        addresses: vec![],
+        status: Some(dhcp6pkt::Status::new(code, message)),
+    }
+}
+
+fn get_clientid(req: &Dhcp6Request) -> Result<Vec<u8>, Dhcp6Error> {
+    req.pkt
+        .options
+        .get_clientid()
+        .ok_or(Dhcp6Error::MissingClientId)
+}
+
+/* RFC8415 Section 16: Which messages must, and must not have a Server Identifier. */
+fn check_serverid(req: &Dhcp6Request, required: bool) -> Result<(), Dhcp6Error> {
+    match req.pkt.options.get_serverid() {
+        Some(si) if si != req.serverid => Err(Dhcp6Error::OtherServer),
+        Some(_) => Ok(()),
+        None if required => Err(Dhcp6Error::OtherServer),
+        None => Ok(()),
+    }
+}
+
+fn build_reply(
+    req: &Dhcp6Request,
+    msgtype: dhcp6pkt::MessageType,
+    conf: &crate::config::Config,
+) -> dhcp6pkt::Dhcp6 {
+    let mut options =
+        dhcp6pkt::Dhcp6Options::default().add_option(&dhcp6pkt::OPTION_SERVERID, &req.serverid);
+    if let Some(clientid) = req.pkt.options.get_clientid() {
+        options = options.add_option(&dhcp6pkt::OPTION_CLIENTID, &clientid);
+    }
+
+    /* Stateless configuration, which is included in all replies if the client asked for it. */
+    let oro = req.pkt.options.get_oro();
+    if oro.contains(&dhcp6pkt::OPTION_DNS_SERVERS) {
+        let servers = conf
+            .dns_servers
+            .iter()
+            .filter_map(|ip| match ip {
+                ip if *ip == crate::config::INTERFACE6 => req.selfaddr,
+                std::net::IpAddr::V6(ip6) => Some(*ip6),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !servers.is_empty() {
+            options = options.add_dns_servers(&servers);
+        }
+    }
+    if oro.contains(&dhcp6pkt::OPTION_DOMAIN_LIST) && !conf.dns_search.is_empty() {
+        options = options.add_domain_list(&conf.dns_search);
+    }
+
+    dhcp6pkt::Dhcp6 {
+        msgtype,
+        xid: req.pkt.xid,
+        options,
+    }
+}
+
+fn assign_addresses(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    clientid: &[u8],
+    mut reply: dhcp6pkt::Dhcp6,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    for ia in req.pkt.options.get_ia_na() {
+        let requested = ia.addresses.first().map(|a| a.addr);
+        let ia = match pool.allocate_address(
+            clientid,
+            ia.iaid,
+            requested,
+            &req.prefixes,
+            DEFAULT_VALID_LIFETIME,
+        ) {
+            Ok(lease) => {
+                log::info!("Allocated Lease: {} for IA {:x}", lease.ip, ia.iaid);
+                ia_with_address(ia.iaid, lease.ip)
+            }
+            Err(PoolError::NoAssignableAddress) => ia_with_status(
+                ia.iaid,
+                dhcp6pkt::STATUS_NOADDRSAVAIL,
+                "No addresses available",
+            ),
+            Err(e) => return Err(Dhcp6Error::PoolError(e)),
+        };
+        reply.options = reply.options.add_ia_na(&ia);
+    }
+    Ok(reply)
+}
+
+fn assign_prefixes(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    clientid: &[u8],
+    conf: &crate::config::Config,
+    mut reply: dhcp6pkt::Dhcp6,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    for ia in req.pkt.options.get_ia_pd() {
+        /* RFC8415 Section 18.2.1: The client can include a prefix with a length, but an
+         * unspecified prefix as a hint for the size of prefix it would like.  We only delegate
+         * fixed length prefixes so only use it if it's a real prefix.
+         */
+        let requested = ia
+            .prefixes
+            .first()
+            .filter(|p| !p.prefix.is_unspecified())
+            .map(|p| Prefix6::new(p.prefix, p.prefixlen.min(128)));
+        let ia = match pool.allocate_prefix(
+            clientid,
+            ia.iaid,
+            requested,
+            &conf.dhcp6.delegations,
+            DEFAULT_VALID_LIFETIME,
+        ) {
+            Ok(delegated) => {
+                log::info!(
+                    "Delegated Prefix: {}/{} for IA {:x}",
+                    delegated.prefix.addr,
+                    delegated.prefix.prefixlen,
+                    ia.iaid
+                );
+                iapd_with_prefix(ia.iaid, &delegated.prefix)
+            }
+            Err(PoolError::NoAssignableAddress) => iapd_with_status(
+                ia.iaid,
+                dhcp6pkt::STATUS_NOPREFIXAVAIL,
+                "No prefixes available",
+            ),
+            Err(e) => return Err(Dhcp6Error::PoolError(e)),
+        };
+        reply.options = reply.options.add_ia_pd(&ia);
+    }
+    Ok(reply)
+}
+
+fn renew_prefixes(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    clientid: &[u8],
+    conf: &crate::config::Config,
+    mut reply: dhcp6pkt::Dhcp6,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    for ia in req.pkt.options.get_ia_pd() {
+        let mut renewed = dhcp6pkt::IaPd {
+            iaid: ia.iaid,
+            t1: std::time::Duration::from_secs(0),
+            t2: std::time::Duration::from_secs(0),
+            prefixes: vec![],
+            status: None,
+        };
+        for prefix in &ia.prefixes {
+            match pool.renew_prefix(
+                clientid,
+                ia.iaid,
+                Prefix6::new(prefix.prefix, prefix.prefixlen.min(128)),
+                &conf.dhcp6.delegations,
+                DEFAULT_VALID_LIFETIME,
+            ) {
+                Ok(delegated) => {
+                    /* Keep any prefixes already returned with lifetimes of 0 */
+                    let delegated = iapd_with_prefix(ia.iaid, &delegated.prefix);
+                    renewed.t1 = delegated.t1;
+                    renewed.t2 = delegated.t2;
+                    renewed.prefixes.extend(delegated.prefixes);
+                }
+                /* RFC8415 Section 18.3.4: Prefixes that are no longer appropriate are returned
+                 * with lifetimes of 0.
+                 */
+                Err(PoolError::NoAssignableAddress) | Err(PoolError::RequestedAddressInUse) => {
+                    renewed.prefixes.push(dhcp6pkt::IaPrefix {
+                        preferred: std::time::Duration::from_secs(0),
+                        valid: std::time::Duration::from_secs(0),
+                        status: None,
+                        ..prefix.clone()
+                    });
+                }
+                Err(e) => return Err(Dhcp6Error::PoolError(e)),
+            }
+        }
+        if renewed.prefixes.iter().all(|p| p.valid.as_secs() == 0) {
+            match pool.allocate_prefix(
+                clientid,
+                ia.iaid,
+                None,
+                &conf.dhcp6.delegations,
+                DEFAULT_VALID_LIFETIME,
+            ) {
+                Ok(delegated) => {
+                    let prefixes = renewed.prefixes;
+                    renewed = iapd_with_prefix(ia.iaid, &delegated.prefix);
+                    renewed.prefixes.extend(prefixes);
+                }
+                Err(PoolError::NoAssignableAddress) => {
+                    renewed.status = Some(dhcp6pkt::Status::new(
+                        dhcp6pkt::STATUS_NOBINDING,
+                        "No binding for IA",
+                    ))
+                }
+                Err(e) => return Err(Dhcp6Error::PoolError(e)),
+            }
+        }
+        reply.options = reply.options.add_ia_pd(&renewed);
+    }
+    Ok(reply)
+}
+
+fn handle_solicit(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    conf: &crate::config::Config,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    /* RFC8415 Section 16.2: Discard any Solicit that includes a Server Identifier */
+    if req.pkt.options.get_serverid().is_some() {
+        return Err(Dhcp6Error::UnexpectedServerId);
+    }
+    let clientid = get_clientid(req)?;
+    /* RFC8415 Section 18.3.1: If the client has included a Rapid Commit option and the server
+     * has been configured to respond with committed lease assignments, the server responds with
+     * a Reply message.
+     */
+    if req.pkt.options.has_rapid_commit() {
+        let reply = build_reply(req, dhcp6pkt::REPLY, conf);
+        let reply = assign_addresses(pool, req, &clientid, reply)?;
+        let mut reply = assign_prefixes(pool, req, &clientid, conf, reply)?;
+        reply.options = reply
+            .options
+            .add_option(&dhcp6pkt::OPTION_RAPID_COMMIT, &[]);
+        Ok(reply)
+    } else {
+        let reply = build_reply(req, dhcp6pkt::ADVERTISE, conf);
+        let reply = assign_addresses(pool, req, &clientid, reply)?;
+        assign_prefixes(pool, req, &clientid, conf, reply)
+    }
+}
+
+fn handle_request(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    conf: &crate::config::Config,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    check_serverid(req, true)?;
+    let clientid = get_clientid(req)?;
+    let reply = build_reply(req, dhcp6pkt::REPLY, conf);
+    let reply = assign_addresses(pool, req, &clientid, reply)?;
+    assign_prefixes(pool, req, &clientid, conf, reply)
+}
+
+fn handle_renew(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    conf: &crate::config::Config,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    /* RFC8415 Section 16: Renew must have our server identifier, Rebind must not have one. */
+    if req.pkt.msgtype == dhcp6pkt::RENEW {
+        check_serverid(req, true)?;
+    } else if req.pkt.options.get_serverid().is_some() {
+        return Err(Dhcp6Error::UnexpectedServerId);
+    }
+    let clientid = get_clientid(req)?;
+    let mut reply = build_reply(req, dhcp6pkt::REPLY, conf);
+    for ia in req.pkt.options.get_ia_na() {
+        let mut renewed = dhcp6pkt::IaNa {
+            iaid: ia.iaid,
+            t1: std::time::Duration::from_secs(0),
+            t2: std::time::Duration::from_secs(0),
+            addresses: vec![],
+            status: None,
+        };
+        for addr in &ia.addresses {
+            match pool.renew_address(
+                &clientid,
+                ia.iaid,
+                addr.addr,
+                &req.prefixes,
+                DEFAULT_VALID_LIFETIME,
+            ) {
+                Ok(lease) => {
+                    log::info!("Renewed Lease: {} for IA {:x}", lease.ip, ia.iaid);
+                    /* Keep any addresses already returned with lifetimes of 0 */
+                    let lease = ia_with_address(ia.iaid, lease.ip);
+                    renewed.t1 = lease.t1;
+                    renewed.t2 = lease.t2;
+                    renewed.addresses.extend(lease.addresses);
+                }
+                /* RFC8415 Section 18.3.4: If the server finds that any of the addresses are not
+                 * appropriate for the link to which the client is attached, the server returns
+                 * the address to the client with lifetimes of 0.
+                 */
+                Err(PoolError::NoAssignableAddress) | Err(PoolError::RequestedAddressInUse) => {
+                    renewed.addresses.push(dhcp6pkt::IaAddr {
+                        addr: addr.addr,
+                        preferred: std::time::Duration::from_secs(0),
+                        valid: std::time::Duration::from_secs(0),
+                        status: None,
+                    });
+                }
+                Err(e) => return Err(Dhcp6Error::PoolError(e)),
+            }
+        }
+        if renewed.addresses.iter().all(|a| a.valid.as_secs() == 0) {
+            /* Nothing could be renewed, so try and find them a new address instead. */
+            match pool.allocate_address(
+                &clientid,
+                ia.iaid,
+                None,
+                &req.prefixes,
+                DEFAULT_VALID_LIFETIME,
+            ) {
+                Ok(lease) => {
+                    log::info!("Allocated Lease: {} for IA {:x}", lease.ip, ia.iaid);
+                    let addresses = renewed.addresses;
+                    renewed = ia_with_address(ia.iaid, lease.ip);
+                    renewed.addresses.extend(addresses);
+                }
+                Err(PoolError::NoAssignableAddress) => {
+                    renewed.status = Some(dhcp6pkt::Status::new(
+                        dhcp6pkt::STATUS_NOBINDING,
+                        "No binding for IA",
+                    ))
+                }
+                Err(e) => return Err(Dhcp6Error::PoolError(e)),
+            }
+        }
+        reply.options = reply.options.add_ia_na(&renewed);
+    }
+    renew_prefixes(pool, req, &clientid, conf, reply)
+}
+
+fn handle_release(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    conf: &crate::config::Config,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    check_serverid(req, true)?;
+    let clientid = get_clientid(req)?;
+    let mut reply = build_reply(req, dhcp6pkt::REPLY, conf);
+    /* RFC8415 Section 18.3.7: For each IA in the Release message for which the server has no
+     * binding information, the server adds an IA option using the IAID from the Release message
+     * and includes a Status Code option with the value NoBinding in the IA option.
+     */
+    for ia in req.pkt.options.get_ia_na() {
+        for addr in &ia.addresses {
+            match pool.release_address(&clientid, ia.iaid, addr.addr) {
+                Ok(()) => log::info!("Released Lease: {} for IA {:x}", addr.addr, ia.iaid),
+                Err(PoolError::NoSuchLease) => {
+                    reply.options = reply.options.add_ia_na(&ia_with_status(
+                        ia.iaid,
+                        dhcp6pkt::STATUS_NOBINDING,
+                        "No binding for IA",
+                    ));
+                    break;
+                }
+                Err(e) => return Err(Dhcp6Error::PoolError(e)),
+            }
+        }
+    }
+    for ia in req.pkt.options.get_ia_pd() {
+        for prefix in &ia.prefixes {
+            let prefix = Prefix6::new(prefix.prefix, prefix.prefixlen.min(128));
+            match pool.release_prefix(&clientid, ia.iaid, &prefix) {
+                Ok(()) => log::info!(
+                    "Released Prefix: {}/{} for IA {:x}",
+                    prefix.addr,
+                    prefix.prefixlen,
+                    ia.iaid
+                ),
+                Err(PoolError::NoSuchLease) => {
+                    reply.options = reply.options.add_ia_pd(&iapd_with_status(
+                        ia.iaid,
+                        dhcp6pkt::STATUS_NOBINDING,
+                        "No binding for IA",
+                    ));
+                    break;
+                }
+                Err(e) => return Err(Dhcp6Error::PoolError(e)),
+            }
+        }
+    }
+    reply.options = reply.options.add_status(&dhcp6pkt::Status::new(
+        dhcp6pkt::STATUS_SUCCESS,
+        "Release received",
+    ));
+    Ok(reply)
+}
+
+fn handle_information_request(
+    req: &Dhcp6Request,
+    conf: &crate::config::Config,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    check_serverid(req, false)?;
+    Ok(build_reply(req, dhcp6pkt::REPLY, conf))
+}
+
+pub fn handle_pkt(
+    pool: &mut pool::Pool6,
+    req: &Dhcp6Request,
+    conf: &crate::config::Config,
+) -> Result<dhcp6pkt::Dhcp6, Dhcp6Error> {
+    match req.pkt.msgtype {
+        dhcp6pkt::SOLICIT => handle_solicit(pool, req, conf),
+        dhcp6pkt::REQUEST => handle_request(pool, req, conf),
+        dhcp6pkt::RENEW | dhcp6pkt::REBIND => handle_renew(pool, req, conf),
+        dhcp6pkt::RELEASE => handle_release(pool, req, conf),
+        dhcp6pkt::INFORMATION_REQUEST => handle_information_request(req, conf),
+        x => Err(Dhcp6Error::UnknownMessageType(x)),
+    }
+}
+
+/// Which prefixes can addresses be assigned from on an interface.  If the interface has Router
+/// Advertisement configuration, then the prefixes from that are used, otherwise any prefix on the
+/// interface which is listed in the top level addresses.  Space that is set aside for prefix
+/// delegation is never used for addresses.
+fn get_prefixes(
+    conf: &crate::config::Config,
+    ifname: &str,
+    if_prefixes: &[(std::net::IpAddr, u8)],
+) -> Vec<Prefix6> {
+    let prefixes: Vec<Prefix6> =
+        if let Some(intf) = conf.ra.interfaces.iter().find(|intf| intf.name == ifname) {
+            intf.prefixes
+                .iter()
+                .map(|p| Prefix6::new(p.addr, p.prefixlen))
+                .collect()
+        } else {
+            if_prefixes
+                .iter()
+                .filter(|(addr, _)| conf.addresses.iter().any(|p| p.contains(*addr)))
+                .filter_map(|(addr, prefixlen)| match addr {
+                    std::net::IpAddr::V6(ip6) => Some(Prefix6::new(*ip6, *prefixlen)),
+                    _ => None,
+                })
+                .collect()
+        };
+    prefixes
+        .into_iter()
+        .map(|p| Prefix6::new(p.network(), p.prefixlen))
+        .filter(|p| !conf.dhcp6.is_delegated(p))
+        .collect()
+}
+
+/// DHCPv6 is only needed if there are prefixes to delegate, or if router advertisements tell
+/// hosts to ask for addresses (M) or other configuration (O) over DHCPv6.
+pub(crate) fn is_configured(conf: &crate::config::Config) -> bool {
+    !conf.dhcp6.delegations.is_empty()
+        || conf
+            .ra
+            .interfaces
+            .iter()
+            .any(|intf| intf.managed || intf.other)
+}
+
+pub struct Dhcp6Service {
+    netinfo: erbium_net::netinfo::SharedNetInfo,
+    conf: crate::config::SharedConfig,
+    pool: PoolWorker<pool::Pool6>,
+    listener: UdpSocket,
+}
+
+impl Dhcp6Service {
+    /// Returns None if DHCPv6 isn't configured.
+    pub async fn new(
+        netinfo: erbium_net::netinfo::SharedNetInfo,
+        conf: crate::config::SharedConfig,
+    ) -> Result<Option<Self>, String> {
+        if !is_configured(&*conf.read().await) {
+            return Ok(None);
+        }
+        let pool = PoolWorker::new(
+            pool::Pool6::open(&*conf.read().await)
+                .map_err(|e| format!("DHCPv6 Pool Error: {}", e))?,
+        );
+        let listener = UdpSocket::bind(&[UNSPECIFIED6.with_port(547)])
+            .await
+            .map_err(|e| format!("Failed to listen on DHCPv6: {}", e))?;
+        listener
+            .set_opt_ipv6_packet_info(true)
+            .map_err(|e| format!("Failed to listen on DHCPv6: {}", e))?;
+        /* TODO: Interfaces that appear after startup won't have joined the multicast group. */
+        for ifidx in netinfo.get_ifindexes().await {
+            if !netinfo
+                .get_flags_by_ifidx(ifidx)
+                .await
+                .map(|f| f.has_multicast())
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            if let Err(e) =
+                listener.set_opt_ipv6_add_membership(ALL_DHCP_RELAY_AGENTS_AND_SERVERS, ifidx)
+            {
+                log::warn!(
+                    "Failed to join DHCPv6 multicast group on {}: {}",
+                    netinfo.get_safe_name_by_ifidx(ifidx).await,
+                    e
+                );
+            }
+        }
+        log::info!(
+            "Listening for DHCPv6 on {}",
+            listener
+                .local_addr()
+                .map_err(|e| format!("I/O Error in DHCPv6: {}", e))?
+        );
+        Ok(Some(Self {
+            netinfo,
+            conf,
+            pool,
+            listener,
+        }))
+    }
+
+    async fn build_request(
+        &self,
+        pkt: dhcp6pkt::Dhcp6,
+        intf: u32,
+    ) -> Result<Dhcp6Request, &'static str> {
+        let serverid = match self.netinfo.get_linkaddr_by_ifidx(intf).await {
+            Some(erbium_net::netinfo::LinkLayer::Ethernet(lladdr)) => make_duid_ll(&lladdr),
+            _ => return Err("UNUSABLE_LINKLAYER"),
+        };
+        let ifname = self.netinfo.get_safe_name_by_ifidx(intf).await;
+        let if_prefixes = self
+            .netinfo
+            .get_prefixes_by_ifidx(intf)
+            .await
+            .unwrap_or_default();
+        let prefixes = get_prefixes(&*self.conf.read().await, &ifname, &if_prefixes);
+        /* Prefer an address from the prefixes we're handing out, otherwise any address. */
+        let self6 = if_prefixes
+            .iter()
+            .filter_map(|(addr, _)| match addr {
+                std::net::IpAddr::V6(ip6) => Some(*ip6),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let selfaddr = self6
+            .iter()
+            .find(|ip6| prefixes.iter().any(|p| p.contains(**ip6)))
+            .or_else(|| self6.first())
+            .copied();
+        Ok(Dhcp6Request {
+            pkt,
+            serverid,
+            ifindex: intf,
+            prefixes,
+            selfaddr,
+        })
+    }
+
+    async fn recvdhcp6(&self, pkt: &[u8], src: NetAddr, intf: u32) {
+        let pkt = match dhcp6pkt::parse(pkt) {
+            Err(e) => {
+                log::warn!("Failed to parse DHCPv6 packet: {}", e);
+                DHCP6_ERRORS
+                    .with_label_values(&[e.get_variant_name()])
+                    .inc();
+                return;
+            }
+            Ok(pkt) => pkt,
+        };
+        let req = match self.build_request(pkt, intf).await {
+            Ok(req) => req,
+            Err(reason) => {
+                log::warn!(
+                    "Can't serve DHCPv6 on {}: {}",
+                    self.netinfo.get_safe_name_by_ifidx(intf).await,
+                    reason
+                );
+                DHCP6_ERRORS.with_label_values(&[reason]).inc();
+                return;
+            }
+        };
+        log::info!(
+            "{}: {} on {} ({:?})",
+            src,
+            req.pkt.msgtype,
+            self.netinfo.get_safe_name_by_ifidx(intf).await,
+            req.pkt.options
+        );
+
+        let msgtype = req.pkt.msgtype;
+        let conf = self.conf.clone();
+        let reply = self
+            .pool
+            .call(move |pool| Ok(handle_pkt(pool, &req, &conf.blocking_read())))
+            .await
+            .map_err(Dhcp6Error::PoolError);
+        let reply = match reply.and_then(|reply| reply) {
+            Ok(reply) => reply,
+            Err(e) => {
+                log::warn!("{}: Failed to handle {}: {}", src, msgtype, e);
+                DHCP6_ERRORS
+                    .with_label_values(&[e.get_variant_name()])
+                    .inc();
+                return;
+            }
+        };
+
+        if reply.msgtype == dhcp6pkt::REPLY {
+            if msgtype == dhcp6pkt::RELEASE {
+                self.expire_routes().await;
+            } else if let Some(std::net::IpAddr::V6(nexthop)) = src.ip() {
+                for ia in reply.options.get_ia_pd() {
+                    for prefix in ia.prefixes.iter().filter(|p| p.valid.as_secs() > 0) {
+                        self.install_route(pool::DelegatedRoute {
+                            prefix: Prefix6::new(prefix.prefix, prefix.prefixlen),
+                            nexthop,
+                            ifindex: intf,
+                        })
+                        .await;
+                    }
+                }
+            }
+        }
+
+        log::info!(
+            "{}: Sending {} on {} ({:?})",
+            src,
+            reply.msgtype,
+            self.netinfo.get_safe_name_by_ifidx(intf).await,
+            reply.options
+        );
+        let cmsg = udp::ControlMessage::new().set_src6_intf(intf);
+        DHCP6_TX_PACKETS.inc();
+        if let Err(e) = self
+            .listener
+            .send_msg(
+                &reply.serialise(),
+                &cmsg,
+                udp::MsgFlags::empty(),
+                Some(&src),
+            )
+            .await
+        {
+            log::warn!("{}: Failed to send reply: {}", src, e);
+            DHCP6_ERRORS.with_label_values(&["SEND_ERROR"]).inc();
+        }
+    }
+
+    /// Routes delegated prefixes towards the router they were delegated to.
+    async fn install_route(&self, route: pool::DelegatedRoute) {
+        match self
+            .netinfo
+            .add_route6(
+                route.prefix.network(),
+                route.prefix.prefixlen,
+                route.nexthop,
+                route.ifindex,
+            )
+            .await
+        {
+            Ok(()) => {
+                if let Err(e) = self
+                    .pool
+                    .call(move |pool| {
+                        pool.set_prefix_route(&route.prefix, Some((route.nexthop, route.ifindex)))
+                    })
+                    .await
+                {
+                    log::warn!("Failed to record route: {}", e);
+                }
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to add route to {}/{} via {}: {}",
+                    route.prefix.network(),
+                    route.prefix.prefixlen,
+                    route.nexthop,
+                    e
+                );
+                DHCP6_ERRORS.with_label_values(&["ROUTE_ERROR"]).inc();
+            }
+        }
+    }
+
+    /// Removes routes for delegations that have expired or been released.
+    async fn expire_routes(&self) {
+        let routes = match self.pool.call(|pool| pool.get_expired_routes()).await {
+            Ok(routes) => routes,
+            Err(e) => {
+                log::warn!("Failed to find expired routes: {}", e);
+                return;
+            }
+        };
+        for route in routes {
+            if let Err(e) = self
+                .netinfo
+                .del_route6(
+                    route.prefix.network(),
+                    route.prefix.prefixlen,
+                    route.nexthop,
+                    route.ifindex,
+                )
+                .await
+            {
+                /* There's not much else we can do, so just forget about the route. */
+                log::warn!(
+                    "Failed to remove route to {}/{} via {}: {}",
+                    route.prefix.network(),
+                    route.prefix.prefixlen,
+                    route.nexthop,
+                    e
+                );
+                DHCP6_ERRORS.with_label_values(&["ROUTE_ERROR"]).inc();
+            }
+            if let Err(e) = self
+                .pool
+                .call(move |pool| pool.set_prefix_route(&route.prefix, None))
+                .await
+            {
+                log::warn!("Failed to record route removal: {}", e);
+            }
+        }
+    }
+
+    async fn run_routes(&self) -> Result<(), String> {
+        /* Routes may have been lost (eg on reboot), so put back any that should still exist. */
+        let routes = self
+            .pool
+            .call(|pool| pool.get_active_routes())
+            .await
+            .map_err(|e| format!("Failed to load delegated routes: {}", e))?;
+        for route in routes {
+            self.install_route(route).await;
+        }
+        loop {
+            self.expire_routes().await;
+            tokio::time::sleep(ROUTE_EXPIRY_INTERVAL).await;
+        }
+    }
+
+    async fn run_requests(self: Arc<Self>) -> Result<(), String> {
+        loop {
+            let rm = match self.listener.recv_msg(65536, udp::MsgFlags::empty()).await {
+                Ok(m) => m,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Failed to receive a packet for DHCPv6: {}", e)),
+            };
+            DHCP6_RX_PACKETS.inc();
+            let self2 = self.clone();
+            tokio::spawn(async move {
+                if let (Some(src), Some(intf)) = (rm.address, rm.local_intf()) {
+                    self2
+                        .recvdhcp6(&rm.buffer, src, intf.try_into().unwrap())
+                        .await
+                }
+            });
+        }
+    }
+
+    pub async fn run(self: Arc<Self>) -> Result<(), String> {
+        use futures::StreamExt as _;
+        let mut services = futures::stream::FuturesUnordered::new();
+        let req_self = self.clone();
+        let route_self = self.clone();
+        services.push(tokio::spawn(async move { req_self.run_requests().await }));
+        services.push(tokio::spawn(async move { route_self.run_routes().await }));
+        while !services.is_empty() {
+            let ret = match services.next().await {
+                None => "No DHCPv6 services found".into(),
+                Some(Ok(Ok(()))) => "DHCPv6 service unexpectedly exited successfully".into(),
+                Some(Ok(Err(e))) => e,         /* If the service failed */
+                Some(Err(e)) => e.to_string(), /* If the spawn failed */
+            };
+            log::error!("DHCPv6 service shutdown: {}", ret);
+        }
+        Err("DHCPv6 service shutdown".into())
+    }
+}
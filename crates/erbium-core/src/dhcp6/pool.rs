@@ -0,0 +1,781 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  DHCPv6 IA_NA Pool Management.
+ */
+
+use crate::config::{Match as _, Prefix6, PrefixOps as _};
+use crate::dhcp::pool::Error;
+use crate::dhcp6::config::Delegation;
+use rusqlite::OptionalExtension as _;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/* How many candidate addresses to try before giving up.  The address space in a /64 is so large
+ * that collisions are vanishingly unlikely, so this only really matters for tiny prefixes.
+ */
+const MAX_CANDIDATES: u32 = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lease6 {
+    pub ip: std::net::Ipv6Addr,
+    pub expire: std::time::Duration,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DelegatedPrefix {
+    pub prefix: Prefix6,
+    pub expire: std::time::Duration,
+}
+
+/// A route towards a delegated prefix, via the requesting router.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DelegatedRoute {
+    pub prefix: Prefix6,
+    pub nexthop: std::net::Ipv6Addr,
+    pub ifindex: u32,
+}
+
+pub struct Pool6 {
+    conn: rusqlite::Connection,
+}
+
+fn now() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .expect("clock failure")
+        .as_secs() as u32
+}
+
+fn candidate_address(prefix: &Prefix6, duid: &[u8], iaid: u32, attempt: u32) -> std::net::Ipv6Addr {
+    let mut h = DefaultHasher::new();
+    duid.hash(&mut h);
+    iaid.hash(&mut h);
+    attempt.hash(&mut h);
+    let lo = h.finish();
+    lo.hash(&mut h);
+    let hi = h.finish();
+    let hostmask = !u128::from(prefix.netmask());
+    let host = ((u128::from(hi) << 64) | u128::from(lo)) & hostmask;
+    (u128::from(prefix.network()) | host).into()
+}
+
+fn candidate_prefix(
+    delegation: &Delegation,
+    duid: &[u8],
+    iaid: u32,
+    attempt: u32,
+) -> Option<Prefix6> {
+    let bits = u32::from(delegation.delegated_length - delegation.prefix.prefixlen);
+    let mut h = DefaultHasher::new();
+    duid.hash(&mut h);
+    iaid.hash(&mut h);
+    /* If there are only a handful of prefixes to delegate, walk through all of them starting
+     * from the hashed one, otherwise keep picking new hashed ones.
+     */
+    let index = if bits < 64 && (1_u64 << bits) <= u64::from(MAX_CANDIDATES) {
+        if attempt as u64 >= 1_u64 << bits {
+            return None;
+        }
+        (h.finish().wrapping_add(attempt.into())) & ((1_u64 << bits) - 1)
+    } else {
+        attempt.hash(&mut h);
+        h.finish() & 1_u64.checked_shl(bits).map(|b| b - 1).unwrap_or(u64::MAX)
+    };
+    let shift = 128 - u32::from(delegation.delegated_length);
+    let offset = u128::from(index).checked_shl(shift).unwrap_or(0);
+    Some(Prefix6::new(
+        (u128::from(delegation.prefix.network()) | offset).into(),
+        delegation.delegated_length,
+    ))
+}
+
+fn route_from_row(row: &rusqlite::Row) -> rusqlite::Result<Option<DelegatedRoute>> {
+    let prefix = row.get::<usize, String>(0)?.parse().ok();
+    let nexthop = row.get::<usize, String>(2)?.parse().ok();
+    Ok(prefix.zip(nexthop).map(|(prefix, nexthop)| DelegatedRoute {
+        prefix: Prefix6::new(prefix, row.get(1).unwrap_or(128)),
+        nexthop,
+        ifindex: row.get(3).unwrap_or(0),
+    }))
+}
+
+impl Pool6 {
+    fn setup_db(self) -> Result<Self, Error> {
+        /* This shares the schema_version table with the DHCPv4 pool, under a different key. */
+        const DB_SCHEMA_KEY: &str = "pool6";
+
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS schema_version (
+                    key TEXT NOT NULL,
+                    version INTEGER NOT NULL,
+                    PRIMARY KEY (key)
+                )",
+                rusqlite::params![],
+            )
+            .map_err(|e| Error::emit("Creating table schema_version", &e))?;
+
+        loop {
+            let upgraded_to_version = match self
+                .conn
+                .query_row(
+                    "SELECT version FROM schema_version WHERE key = ?1",
+                    rusqlite::params![DB_SCHEMA_KEY],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| Error::emit("Querying schema version", &e))?
+            {
+                None => {
+                    self.conn
+                        .execute(
+                            "CREATE TABLE leases6 (
+                                address TEXT NOT NULL,
+                                duid BLOB NOT NULL,
+                                iaid INTEGER NOT NULL,
+                                start INTEGER NOT NULL,
+                                expiry INTEGER NOT NULL,
+                                PRIMARY KEY (address)
+                            )",
+                            rusqlite::params![],
+                        )
+                        .map_err(|e| Error::emit("Creating table leases6", &e))?;
+                    1
+                }
+                Some(1) => {
+                    self.conn
+                        .execute(
+                            "CREATE TABLE leases6_pd (
+                                prefix TEXT NOT NULL,
+                                prefixlen INTEGER NOT NULL,
+                                duid BLOB NOT NULL,
+                                iaid INTEGER NOT NULL,
+                                start INTEGER NOT NULL,
+                                expiry INTEGER NOT NULL,
+                                nexthop TEXT,
+                                ifindex INTEGER,
+                                PRIMARY KEY (prefix, prefixlen)
+                            )",
+                            rusqlite::params![],
+                        )
+                        .map_err(|e| Error::emit("Creating table leases6_pd", &e))?;
+                    2
+                }
+                Some(2) => break, // up to date
+                Some(v) => {
+                    return Err(Error::DbError(format!(
+                    "Lease database has v6 version {} which is newer than 2, the newest supported version",
+                    v
+                )))
+                }
+            };
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO schema_version (key, version) VALUES (?1, ?2)",
+                    rusqlite::params![DB_SCHEMA_KEY, upgraded_to_version],
+                )
+                .map_err(|e| Error::emit("Creating updating schema version", &e))?;
+        }
+        Ok(self)
+    }
+
+    pub fn new_in_memory() -> Result<Self, Error> {
+        let conn = rusqlite::Connection::open_in_memory()
+            .map_err(|e| Error::emit("Creating database in memory database", &e))?;
+        Pool6 { conn }.setup_db()
+    }
+
+    /// Opens the lease database in the configured state directory.  DHCPv6 leases are always kept
+    /// in SQLite, unless the DHCPv4 leases are only being kept in memory.
+    pub fn open(conf: &crate::config::Config) -> Result<Self, Error> {
+        if conf.dhcp.lease_storage == crate::dhcp::config::LeaseStorage::Memory {
+            return Self::new_in_memory();
+        }
+        let path = conf.state_dir.join(crate::dhcp::storage::SQLITE_DB_NAME);
+        let conn = rusqlite::Connection::open(&path)
+            .map_err(|e| Error::emit(&format!("Creating database {}", path.display()), &e))?;
+        Pool6 { conn }.setup_db()
+    }
+
+    /// Returns true if nobody other than this IA has a current lease on the address.
+    fn is_available(
+        &self,
+        addr: std::net::Ipv6Addr,
+        duid: &[u8],
+        iaid: u32,
+        ts: u32,
+    ) -> Result<bool, Error> {
+        self.conn
+            .query_row(
+                "SELECT true FROM leases6
+                 WHERE address = ?1
+                 AND expiry >= ?2
+                 AND NOT (duid = ?3 AND iaid = ?4)",
+                rusqlite::params![addr.to_string(), ts, duid, iaid],
+                |_row| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_none())
+            .map_err(|e| Error::emit("Checking address availability", &e))
+    }
+
+    fn commit_lease(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        addr: std::net::Ipv6Addr,
+        lifetime: std::time::Duration,
+        ts: u32,
+    ) -> Result<Lease6, Error> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO leases6 (address, duid, iaid, start, expiry)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    addr.to_string(),
+                    duid,
+                    iaid,
+                    ts,
+                    ts.saturating_add(lifetime.as_secs() as u32)
+                ],
+            )
+            .map_err(|e| Error::emit("Updating lease", &e))?;
+        Ok(Lease6 {
+            ip: addr,
+            expire: lifetime,
+        })
+    }
+
+    /// Allocates an address for an IA.  Addresses are chosen in the following order:
+    ///  * The IA's existing binding, if it's still on one of the prefixes.
+    ///  * The address the client asked for, if it's on one of the prefixes and is free.
+    ///  * An address derived from a hash of the DUID and IAID, so that clients tend to get the
+    ///    same address again even if their binding has expired.
+    pub fn allocate_address(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        requested: Option<std::net::Ipv6Addr>,
+        prefixes: &[Prefix6],
+        lifetime: std::time::Duration,
+    ) -> Result<Lease6, Error> {
+        let ts = now();
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT address FROM leases6
+                 WHERE duid = ?1 AND iaid = ?2 AND expiry >= ?3
+                 ORDER BY expiry DESC
+                 LIMIT 1",
+                rusqlite::params![duid, iaid, ts],
+                |row| row.get::<usize, String>(0),
+            )
+            .optional()
+            .map_err(|e| Error::emit("Finding existing binding", &e))?
+            .and_then(|addr| addr.parse::<std::net::Ipv6Addr>().ok());
+        if let Some(addr) = existing {
+            if prefixes.iter().any(|p| p.contains(addr)) {
+                return self.commit_lease(duid, iaid, addr, lifetime, ts);
+            }
+        }
+
+        if let Some(addr) = requested {
+            if prefixes.iter().any(|p| p.contains(addr))
+                && self.is_available(addr, duid, iaid, ts)?
+            {
+                return self.commit_lease(duid, iaid, addr, lifetime, ts);
+            }
+        }
+
+        for prefix in prefixes {
+            /* Don't try and hand out addresses from prefixes with no room for hosts. */
+            if prefix.prefixlen >= 127 {
+                continue;
+            }
+            for attempt in 0..MAX_CANDIDATES {
+                let addr = candidate_address(prefix, duid, iaid, attempt);
+                /* The all zeros host is the subnet-router anycast address (RFC4291 2.6.1) */
+                if addr == prefix.network() {
+                    continue;
+                }
+                if self.is_available(addr, duid, iaid, ts)? {
+                    return self.commit_lease(duid, iaid, addr, lifetime, ts);
+                }
+            }
+        }
+
+        Err(Error::NoAssignableAddress)
+    }
+
+    /// Extends the lease on an address the client already has.
+    pub fn renew_address(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        addr: std::net::Ipv6Addr,
+        prefixes: &[Prefix6],
+        lifetime: std::time::Duration,
+    ) -> Result<Lease6, Error> {
+        let ts = now();
+        if !prefixes.iter().any(|p| p.contains(addr)) {
+            Err(Error::NoAssignableAddress)
+        } else if !self.is_available(addr, duid, iaid, ts)? {
+            Err(Error::RequestedAddressInUse)
+        } else {
+            self.commit_lease(duid, iaid, addr, lifetime, ts)
+        }
+    }
+
+    pub fn release_address(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        addr: std::net::Ipv6Addr,
+    ) -> Result<(), Error> {
+        let ts = now().saturating_sub(1);
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE leases6 SET expiry = ?1
+                 WHERE address = ?2 AND duid = ?3 AND iaid = ?4 AND expiry > ?1",
+                rusqlite::params![ts, addr.to_string(), duid, iaid],
+            )
+            .map_err(|e| Error::emit("Releasing lease", &e))?;
+        if updated == 0 {
+            Err(Error::NoSuchLease)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_prefix_available(
+        &self,
+        prefix: &Prefix6,
+        duid: &[u8],
+        iaid: u32,
+        ts: u32,
+    ) -> Result<bool, Error> {
+        self.conn
+            .query_row(
+                "SELECT true FROM leases6_pd
+                 WHERE prefix = ?1 AND prefixlen = ?2
+                 AND expiry >= ?3
+                 AND NOT (duid = ?4 AND iaid = ?5)",
+                rusqlite::params![
+                    prefix.network().to_string(),
+                    prefix.prefixlen,
+                    ts,
+                    duid,
+                    iaid
+                ],
+                |_row| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_none())
+            .map_err(|e| Error::emit("Checking prefix availability", &e))
+    }
+
+    fn commit_prefix(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        prefix: Prefix6,
+        lifetime: std::time::Duration,
+        ts: u32,
+    ) -> Result<DelegatedPrefix, Error> {
+        /* Keep the route columns if this is a renewal of the same binding */
+        self.conn
+            .execute(
+                "INSERT INTO leases6_pd (prefix, prefixlen, duid, iaid, start, expiry)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (prefix, prefixlen) DO UPDATE SET
+                    duid = excluded.duid,
+                    iaid = excluded.iaid,
+                    start = excluded.start,
+                    expiry = excluded.expiry",
+                rusqlite::params![
+                    prefix.network().to_string(),
+                    prefix.prefixlen,
+                    duid,
+                    iaid,
+                    ts,
+                    ts.saturating_add(lifetime.as_secs() as u32)
+                ],
+            )
+            .map_err(|e| Error::emit("Updating delegated prefix", &e))?;
+        Ok(DelegatedPrefix {
+            prefix,
+            expire: lifetime,
+        })
+    }
+
+    fn in_delegations(prefix: &Prefix6, delegations: &[Delegation]) -> bool {
+        delegations
+            .iter()
+            .any(|d| d.delegated_length == prefix.prefixlen && d.prefix.contains(prefix.network()))
+    }
+
+    /// Delegates a prefix to an IA_PD, preferring their existing binding, then the prefix they
+    /// asked for, then one derived from a hash of the DUID and IAID.
+    pub fn allocate_prefix(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        requested: Option<Prefix6>,
+        delegations: &[Delegation],
+        lifetime: std::time::Duration,
+    ) -> Result<DelegatedPrefix, Error> {
+        let ts = now();
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT prefix, prefixlen FROM leases6_pd
+                 WHERE duid = ?1 AND iaid = ?2 AND expiry >= ?3
+                 ORDER BY expiry DESC
+                 LIMIT 1",
+                rusqlite::params![duid, iaid, ts],
+                |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, u8>(1)?)),
+            )
+            .optional()
+            .map_err(|e| Error::emit("Finding existing delegation", &e))?
+            .and_then(|(prefix, prefixlen)| {
+                prefix
+                    .parse()
+                    .ok()
+                    .map(|prefix| Prefix6::new(prefix, prefixlen))
+            });
+        if let Some(prefix) = existing {
+            if Self::in_delegations(&prefix, delegations) {
+                return self.commit_prefix(duid, iaid, prefix, lifetime, ts);
+            }
+        }
+
+        if let Some(prefix) = requested {
+            let prefix = Prefix6::new(prefix.network(), prefix.prefixlen);
+            if Self::in_delegations(&prefix, delegations)
+                && self.is_prefix_available(&prefix, duid, iaid, ts)?
+            {
+                return self.commit_prefix(duid, iaid, prefix, lifetime, ts);
+            }
+        }
+
+        for delegation in delegations {
+            for attempt in 0..MAX_CANDIDATES {
+                let prefix = match candidate_prefix(delegation, duid, iaid, attempt) {
+                    Some(prefix) => prefix,
+                    None => break,
+                };
+                if self.is_prefix_available(&prefix, duid, iaid, ts)? {
+                    return self.commit_prefix(duid, iaid, prefix, lifetime, ts);
+                }
+            }
+        }
+
+        Err(Error::NoAssignableAddress)
+    }
+
+    /// Extends the lifetime of a prefix the router already has.
+    pub fn renew_prefix(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        prefix: Prefix6,
+        delegations: &[Delegation],
+        lifetime: std::time::Duration,
+    ) -> Result<DelegatedPrefix, Error> {
+        let ts = now();
+        let prefix = Prefix6::new(prefix.network(), prefix.prefixlen);
+        if !Self::in_delegations(&prefix, delegations) {
+            Err(Error::NoAssignableAddress)
+        } else if !self.is_prefix_available(&prefix, duid, iaid, ts)? {
+            Err(Error::RequestedAddressInUse)
+        } else {
+            self.commit_prefix(duid, iaid, prefix, lifetime, ts)
+        }
+    }
+
+    pub fn release_prefix(
+        &mut self,
+        duid: &[u8],
+        iaid: u32,
+        prefix: &Prefix6,
+    ) -> Result<(), Error> {
+        let ts = now().saturating_sub(1);
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE leases6_pd SET expiry = ?1
+                 WHERE prefix = ?2 AND prefixlen = ?3 AND duid = ?4 AND iaid = ?5 AND expiry > ?1",
+                rusqlite::params![
+                    ts,
+                    prefix.network().to_string(),
+                    prefix.prefixlen,
+                    duid,
+                    iaid
+                ],
+            )
+            .map_err(|e| Error::emit("Releasing delegated prefix", &e))?;
+        if updated == 0 {
+            Err(Error::NoSuchLease)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Records the route that has been installed for a delegated prefix, or None if the route
+    /// has been removed.
+    pub fn set_prefix_route(
+        &mut self,
+        prefix: &Prefix6,
+        route: Option<(std::net::Ipv6Addr, u32)>,
+    ) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "UPDATE leases6_pd SET nexthop = ?1, ifindex = ?2
+                 WHERE prefix = ?3 AND prefixlen = ?4",
+                rusqlite::params![
+                    route.map(|(nexthop, _)| nexthop.to_string()),
+                    route.map(|(_, ifindex)| ifindex),
+                    prefix.network().to_string(),
+                    prefix.prefixlen
+                ],
+            )
+            .map_err(|e| Error::emit("Updating delegated route", &e))?;
+        Ok(())
+    }
+
+    fn get_routes(&self, sql: &str) -> Result<Vec<DelegatedRoute>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|e| Error::emit("Preparing route query", &e))?;
+        let rows = stmt
+            .query_map(rusqlite::params![now()], route_from_row)
+            .map_err(|e| Error::emit("Querying routes", &e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::emit("Reading routes", &e))?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// Routes for delegations that are still current, so they can be reinstalled at startup.
+    pub fn get_active_routes(&self) -> Result<Vec<DelegatedRoute>, Error> {
+        self.get_routes(
+            "SELECT prefix, prefixlen, nexthop, ifindex FROM leases6_pd
+             WHERE nexthop IS NOT NULL AND expiry >= ?1",
+        )
+    }
+
+    /// Routes for delegations that have expired or been released, which should be removed.
+    pub fn get_expired_routes(&self) -> Result<Vec<DelegatedRoute>, Error> {
+        self.get_routes(
+            "SELECT prefix, prefixlen, nexthop, ifindex FROM leases6_pd
+             WHERE nexthop IS NOT NULL AND expiry < ?1",
+        )
+    }
+}
+
+impl crate::dhcp::worker::Batched for Pool6 {
+    fn begin_batch(&mut self) -> Result<(), Error> {
+        self.conn
+            .execute_batch("BEGIN")
+            .map_err(|e| Error::emit("Starting transaction", &e))
+    }
+
+    fn commit_batch(&mut self) -> Result<(), Error> {
+        self.conn.execute_batch("COMMIT").map_err(|e| {
+            /* If the commit fails, the transaction may still be open */
+            let _ = self.conn.execute_batch("ROLLBACK");
+            Error::emit("Committing transaction", &e)
+        })
+    }
+}
+
+#[cfg(test)]
+fn test_prefixes() -> Vec<Prefix6> {
+    vec![Prefix6::new("2001:db8::".parse().unwrap(), 64)]
+}
+
+#[test]
+fn stable_allocation() {
+    let mut p = Pool6::new_in_memory().expect("Failed to create pool");
+    let lifetime = std::time::Duration::from_secs(3600);
+    let first = p
+        .allocate_address(b"client", 1, None, &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    assert!(test_prefixes()[0].contains(first.ip));
+    let second = p
+        .allocate_address(b"client", 1, None, &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    assert_eq!(first, second);
+    /* A different IA gets a different address */
+    let other = p
+        .allocate_address(b"client", 2, None, &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    assert_ne!(first.ip, other.ip);
+}
+
+#[test]
+fn requested_in_use() {
+    let mut p = Pool6::new_in_memory().expect("Failed to create pool");
+    let lifetime = std::time::Duration::from_secs(3600);
+    let first = p
+        .allocate_address(b"client1", 1, None, &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    let second = p
+        .allocate_address(b"client2", 1, Some(first.ip), &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    assert_ne!(first.ip, second.ip);
+    assert_eq!(
+        p.renew_address(b"client2", 1, first.ip, &test_prefixes(), lifetime),
+        Err(Error::RequestedAddressInUse)
+    );
+    assert_eq!(
+        p.renew_address(
+            b"client2",
+            1,
+            "2001:db8:1::1".parse().unwrap(),
+            &test_prefixes(),
+            lifetime
+        ),
+        Err(Error::NoAssignableAddress)
+    );
+}
+
+#[test]
+fn release() {
+    let mut p = Pool6::new_in_memory().expect("Failed to create pool");
+    let lifetime = std::time::Duration::from_secs(3600);
+    let lease = p
+        .allocate_address(b"client1", 1, None, &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    p.release_address(b"client1", 1, lease.ip)
+        .expect("Failed to release");
+    assert_eq!(
+        p.release_address(b"client1", 1, lease.ip),
+        Err(Error::NoSuchLease)
+    );
+    /* Now someone else can have it */
+    let other = p
+        .allocate_address(b"client2", 1, Some(lease.ip), &test_prefixes(), lifetime)
+        .expect("Failed to allocate");
+    assert_eq!(other.ip, lease.ip);
+}
+
+#[cfg(test)]
+fn test_delegations() -> Vec<Delegation> {
+    vec![Delegation {
+        prefix: Prefix6::new("2001:db8:100::".parse().unwrap(), 56),
+        delegated_length: 60,
+    }]
+}
+
+#[test]
+fn delegate_prefix() {
+    let mut p = Pool6::new_in_memory().expect("Failed to create pool");
+    let lifetime = std::time::Duration::from_secs(3600);
+    let first = p
+        .allocate_prefix(b"router1", 1, None, &test_delegations(), lifetime)
+        .expect("Failed to delegate");
+    assert_eq!(first.prefix.prefixlen, 60);
+    assert!(test_delegations()[0]
+        .prefix
+        .contains(first.prefix.network()));
+    assert_eq!(
+        p.allocate_prefix(b"router1", 1, None, &test_delegations(), lifetime)
+            .expect("Failed to delegate"),
+        first
+    );
+    /* Someone else can't have the same prefix */
+    let second = p
+        .allocate_prefix(
+            b"router2",
+            1,
+            Some(first.prefix.clone()),
+            &test_delegations(),
+            lifetime,
+        )
+        .expect("Failed to delegate");
+    assert_ne!(first.prefix, second.prefix);
+    assert_eq!(
+        p.renew_prefix(
+            b"router2",
+            1,
+            first.prefix.clone(),
+            &test_delegations(),
+            lifetime
+        ),
+        Err(Error::RequestedAddressInUse)
+    );
+    /* A /56 only has 16 /60s in it */
+    for router in 3..=16 {
+        p.allocate_prefix(
+            format!("router{}", router).as_bytes(),
+            1,
+            None,
+            &test_delegations(),
+            lifetime,
+        )
+        .expect("Failed to delegate");
+    }
+    assert_eq!(
+        p.allocate_prefix(b"router17", 1, None, &test_delegations(), lifetime),
+        Err(Error::NoAssignableAddress)
+    );
+}
+
+#[test]
+fn delegated_routes() {
+    let mut p = Pool6::new_in_memory().expect("Failed to create pool");
+    let lifetime = std::time::Duration::from_secs(3600);
+    let delegated = p
+        .allocate_prefix(b"router1", 1, None, &test_delegations(), lifetime)
+        .expect("Failed to delegate");
+    let nexthop: std::net::Ipv6Addr = "fe80::1".parse().unwrap();
+    p.set_prefix_route(&delegated.prefix, Some((nexthop, 2)))
+        .expect("Failed to set route");
+    let route = DelegatedRoute {
+        prefix: delegated.prefix.clone(),
+        nexthop,
+        ifindex: 2,
+    };
+    assert_eq!(p.get_active_routes(), Ok(vec![route]));
+    assert_eq!(p.get_expired_routes(), Ok(vec![]));
+    p.release_prefix(b"router1", 1, &delegated.prefix)
+        .expect("Failed to release");
+    assert_eq!(p.get_active_routes(), Ok(vec![]));
+    assert_eq!(p.get_expired_routes().map(|r| r.len()), Ok(1));
+    p.set_prefix_route(&delegated.prefix, None)
+        .expect("Failed to clear route");
+    assert_eq!(p.get_expired_routes(), Ok(vec![]));
+}
+
+#[tokio::test]
+async fn pool_worker() {
+    let worker = crate::dhcp::worker::PoolWorker::new(Pool6::new_in_memory().unwrap());
+    let lifetime = std::time::Duration::from_secs(3600);
+    let lease = worker
+        .call(move |p| p.allocate_address(b"client", 1, None, &test_prefixes(), lifetime))
+        .await
+        .expect("Failed to allocate");
+    /* The allocation was committed, so it's still there afterwards */
+    assert_eq!(
+        worker
+            .call(move |p| p.renew_address(b"other", 1, lease.ip, &test_prefixes(), lifetime))
+            .await,
+        Err(Error::RequestedAddressInUse)
+    );
+}
@@ -0,0 +1,436 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Sections quoted from RFCs are covered by the terms specified in RFC3978.
+ *
+ *  Tests for DHCPv6 functionality.
+ */
+
+use crate::config::{Match as _, Prefix6};
+use crate::dhcp6;
+use crate::dhcp6::dhcp6pkt;
+use crate::dhcp6::pool;
+use std::net;
+
+const CLIENTID: &[u8] = b"Client DUID";
+const SERVERID: &[u8] = &[0, 3, 0, 1, 0x00, 0x00, 0x5E, 0x00, 0x53, 0x01];
+const SERVER_IP6: net::Ipv6Addr = net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+const IAID: u32 = 0x1234;
+
+fn mk_config() -> crate::config::Config {
+    crate::config::Config {
+        dns_servers: vec![crate::config::INTERFACE6, "2001:db8::53".parse().unwrap()],
+        dns_search: vec!["example.com".into()],
+        ..Default::default()
+    }
+}
+
+fn mk_request(msgtype: dhcp6pkt::MessageType, ia: Option<dhcp6pkt::IaNa>) -> dhcp6::Dhcp6Request {
+    let mut options = dhcp6pkt::Dhcp6Options::default()
+        .add_option(&dhcp6pkt::OPTION_CLIENTID, CLIENTID)
+        .add_option(&dhcp6pkt::OPTION_ORO, &[0, 23, 0, 24]);
+    if let Some(ia) = ia {
+        options = options.add_ia_na(&ia);
+    }
+    dhcp6::Dhcp6Request {
+        pkt: dhcp6pkt::Dhcp6 {
+            msgtype,
+            xid: 0x123456,
+            options,
+        },
+        serverid: SERVERID.to_vec(),
+        ifindex: 1,
+        prefixes: vec![Prefix6::new("2001:db8::".parse().unwrap(), 64)],
+        selfaddr: Some(SERVER_IP6),
+    }
+}
+
+fn mk_ia(addresses: &[net::Ipv6Addr]) -> dhcp6pkt::IaNa {
+    dhcp6pkt::IaNa {
+        iaid: IAID,
+        t1: std::time::Duration::from_secs(0),
+        t2: std::time::Duration::from_secs(0),
+        addresses: addresses
+            .iter()
+            .map(|addr| dhcp6pkt::IaAddr {
+                addr: *addr,
+                preferred: std::time::Duration::from_secs(0),
+                valid: std::time::Duration::from_secs(0),
+                status: None,
+            })
+            .collect(),
+        status: None,
+    }
+}
+
+fn with_serverid(mut req: dhcp6::Dhcp6Request) -> dhcp6::Dhcp6Request {
+    req.pkt.options = req
+        .pkt
+        .options
+        .add_option(&dhcp6pkt::OPTION_SERVERID, SERVERID);
+    req
+}
+
+fn get_address(reply: &dhcp6pkt::Dhcp6) -> net::Ipv6Addr {
+    let ias = reply.options.get_ia_na();
+    assert_eq!(ias.len(), 1);
+    assert_eq!(ias[0].iaid, IAID);
+    assert_eq!(ias[0].status, None);
+    assert!(ias[0].addresses[0].valid.as_secs() > 0);
+    ias[0].addresses[0].addr
+}
+
+#[test]
+fn solicit_request_renew_release() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let conf = mk_config();
+
+    let advertise = dhcp6::handle_pkt(
+        &mut p,
+        &mk_request(dhcp6pkt::SOLICIT, Some(mk_ia(&[]))),
+        &conf,
+    )
+    .expect("Failed to handle Solicit");
+    assert_eq!(advertise.msgtype, dhcp6pkt::ADVERTISE);
+    assert_eq!(advertise.xid, 0x123456);
+    assert_eq!(advertise.options.get_serverid(), Some(SERVERID.to_vec()));
+    assert_eq!(advertise.options.get_clientid(), Some(CLIENTID.to_vec()));
+    let addr = get_address(&advertise);
+    assert!(Prefix6::new("2001:db8::".parse().unwrap(), 64).contains(addr));
+
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &with_serverid(mk_request(dhcp6pkt::REQUEST, Some(mk_ia(&[addr])))),
+        &conf,
+    )
+    .expect("Failed to handle Request");
+    assert_eq!(reply.msgtype, dhcp6pkt::REPLY);
+    assert_eq!(get_address(&reply), addr);
+
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &with_serverid(mk_request(dhcp6pkt::RENEW, Some(mk_ia(&[addr])))),
+        &conf,
+    )
+    .expect("Failed to handle Renew");
+    assert_eq!(get_address(&reply), addr);
+
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &mk_request(dhcp6pkt::REBIND, Some(mk_ia(&[addr]))),
+        &conf,
+    )
+    .expect("Failed to handle Rebind");
+    assert_eq!(get_address(&reply), addr);
+
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &with_serverid(mk_request(dhcp6pkt::RELEASE, Some(mk_ia(&[addr])))),
+        &conf,
+    )
+    .expect("Failed to handle Release");
+    assert_eq!(reply.msgtype, dhcp6pkt::REPLY);
+    assert_eq!(reply.options.get_ia_na(), vec![]);
+}
+
+#[test]
+fn rapid_commit() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let mut req = mk_request(dhcp6pkt::SOLICIT, Some(mk_ia(&[])));
+    req.pkt.options = req
+        .pkt
+        .options
+        .add_option(&dhcp6pkt::OPTION_RAPID_COMMIT, &[]);
+    let reply = dhcp6::handle_pkt(&mut p, &req, &mk_config()).expect("Failed to handle Solicit");
+    assert_eq!(reply.msgtype, dhcp6pkt::REPLY);
+    assert!(reply.options.has_rapid_commit());
+    get_address(&reply);
+}
+
+/* RFC8415 Section 18.3.4:
+ * If the server finds that any of the addresses are not appropriate for the link to which the
+ * client is attached, the server returns the address to the client with lifetimes of 0.
+ */
+#[test]
+fn renew_wrong_link() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let wrong: net::Ipv6Addr = "2001:db8:1::1".parse().unwrap();
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &with_serverid(mk_request(dhcp6pkt::RENEW, Some(mk_ia(&[wrong])))),
+        &mk_config(),
+    )
+    .expect("Failed to handle Renew");
+    let ias = reply.options.get_ia_na();
+    assert_eq!(ias.len(), 1);
+    let old = ias[0]
+        .addresses
+        .iter()
+        .find(|a| a.addr == wrong)
+        .expect("Old address not returned");
+    assert_eq!(old.valid.as_secs(), 0);
+    assert_eq!(old.preferred.as_secs(), 0);
+    /* And they get given a new address instead */
+    assert!(ias[0].addresses.iter().any(|a| a.valid.as_secs() > 0));
+}
+
+#[test]
+fn renew_with_unknown_address() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let conf = mk_config();
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &mk_request(dhcp6pkt::SOLICIT, Some(mk_ia(&[]))),
+        &conf,
+    )
+    .expect("Failed to handle Solicit");
+    let addr = get_address(&reply);
+    dhcp6::handle_pkt(
+        &mut p,
+        &with_serverid(mk_request(dhcp6pkt::REQUEST, Some(mk_ia(&[addr])))),
+        &conf,
+    )
+    .expect("Failed to handle Request");
+
+    /* Both addresses come back, only the one we know about with a lifetime. */
+    let wrong: net::Ipv6Addr = "2001:db8:1::1".parse().unwrap();
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &with_serverid(mk_request(dhcp6pkt::RENEW, Some(mk_ia(&[wrong, addr])))),
+        &conf,
+    )
+    .expect("Failed to handle Renew");
+    let ias = reply.options.get_ia_na();
+    assert_eq!(ias.len(), 1);
+    assert_eq!(
+        ias[0]
+            .addresses
+            .iter()
+            .map(|a| (a.addr, a.valid.as_secs() > 0))
+            .collect::<Vec<_>>(),
+        vec![(wrong, false), (addr, true)]
+    );
+    assert!(ias[0].t1.as_secs() > 0);
+}
+
+#[test]
+fn no_addresses_available() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let mut req = mk_request(dhcp6pkt::SOLICIT, Some(mk_ia(&[])));
+    req.prefixes = vec![];
+    let reply = dhcp6::handle_pkt(&mut p, &req, &mk_config()).expect("Failed to handle Solicit");
+    let ias = reply.options.get_ia_na();
+    assert_eq!(ias.len(), 1);
+    assert_eq!(ias[0].addresses, vec![]);
+    assert_eq!(
+        ias[0].status.as_ref().map(|s| s.code),
+        Some(dhcp6pkt::STATUS_NOADDRSAVAIL)
+    );
+}
+
+#[test]
+fn information_request() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let reply = dhcp6::handle_pkt(
+        &mut p,
+        &mk_request(dhcp6pkt::INFORMATION_REQUEST, None),
+        &mk_config(),
+    )
+    .expect("Failed to handle Information-request");
+    assert_eq!(reply.msgtype, dhcp6pkt::REPLY);
+    assert_eq!(reply.options.get_ia_na(), vec![]);
+    let mut servers = vec![];
+    servers.extend(SERVER_IP6.octets().iter());
+    servers.extend(
+        "2001:db8::53"
+            .parse::<net::Ipv6Addr>()
+            .unwrap()
+            .octets()
+            .iter(),
+    );
+    assert_eq!(
+        reply.options.get_option(&dhcp6pkt::OPTION_DNS_SERVERS),
+        Some(&servers[..])
+    );
+    assert_eq!(
+        reply.options.get_option(&dhcp6pkt::OPTION_DOMAIN_LIST),
+        Some(&b"\x07example\x03com\x00"[..])
+    );
+}
+
+/* RFC8415 Section 16: Servers MUST discard any received Request/Renew/Release message that
+ * does not include a Server Identifier option, or where it does not match the server's DUID.
+ * Servers MUST discard any received Solicit or Rebind message that includes a Server Identifier
+ * option.
+ */
+#[test]
+fn server_identifier_checks() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let conf = mk_config();
+    assert_eq!(
+        dhcp6::handle_pkt(
+            &mut p,
+            &mk_request(dhcp6pkt::REQUEST, Some(mk_ia(&[]))),
+            &conf
+        ),
+        Err(dhcp6::Dhcp6Error::OtherServer)
+    );
+    let mut other = with_serverid(mk_request(dhcp6pkt::REQUEST, Some(mk_ia(&[]))));
+    other.serverid = b"Another server".to_vec();
+    assert_eq!(
+        dhcp6::handle_pkt(&mut p, &other, &conf),
+        Err(dhcp6::Dhcp6Error::OtherServer)
+    );
+    assert_eq!(
+        dhcp6::handle_pkt(
+            &mut p,
+            &with_serverid(mk_request(dhcp6pkt::SOLICIT, Some(mk_ia(&[])))),
+            &conf
+        ),
+        Err(dhcp6::Dhcp6Error::UnexpectedServerId)
+    );
+    let mut noclient = mk_request(dhcp6pkt::SOLICIT, Some(mk_ia(&[])));
+    noclient.pkt.options = dhcp6pkt::Dhcp6Options::default();
+    assert_eq!(
+        dhcp6::handle_pkt(&mut p, &noclient, &conf),
+        Err(dhcp6::Dhcp6Error::MissingClientId)
+    );
+}
+
+#[test]
+fn prefix_delegation() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let mut conf = mk_config();
+    conf.dhcp6.delegations = vec![crate::dhcp6::config::Delegation {
+        prefix: Prefix6::new("2001:db8:100::".parse().unwrap(), 48),
+        delegated_length: 56,
+    }];
+    let ia_pd = dhcp6pkt::IaPd {
+        iaid: IAID,
+        t1: std::time::Duration::from_secs(0),
+        t2: std::time::Duration::from_secs(0),
+        prefixes: vec![],
+        status: None,
+    };
+    let mut req = mk_request(dhcp6pkt::SOLICIT, None);
+    req.pkt.options = req.pkt.options.add_ia_pd(&ia_pd);
+    let advertise = dhcp6::handle_pkt(&mut p, &req, &conf).expect("Failed to handle Solicit");
+    let pds = advertise.options.get_ia_pd();
+    assert_eq!(pds.len(), 1);
+    assert_eq!(pds[0].status, None);
+    let delegated = pds[0].prefixes[0].clone();
+    assert_eq!(delegated.prefixlen, 56);
+    assert!(conf.dhcp6.delegations[0].prefix.contains(delegated.prefix));
+
+    let mut req = with_serverid(mk_request(dhcp6pkt::RENEW, None));
+    req.pkt.options = req.pkt.options.add_ia_pd(&dhcp6pkt::IaPd {
+        prefixes: vec![delegated.clone()],
+        ..ia_pd.clone()
+    });
+    let reply = dhcp6::handle_pkt(&mut p, &req, &conf).expect("Failed to handle Renew");
+    assert_eq!(
+        reply.options.get_ia_pd()[0].prefixes,
+        vec![delegated.clone()]
+    );
+
+    /* RFC8415 Section 18.3.4: A prefix we didn't delegate comes back with lifetimes of 0,
+     * alongside the renewed one.
+     */
+    let unknown = dhcp6pkt::IaPrefix {
+        prefix: "2001:db8:200::".parse().unwrap(),
+        ..delegated.clone()
+    };
+    let mut req = with_serverid(mk_request(dhcp6pkt::RENEW, None));
+    req.pkt.options = req.pkt.options.add_ia_pd(&dhcp6pkt::IaPd {
+        prefixes: vec![unknown.clone(), delegated.clone()],
+        ..ia_pd.clone()
+    });
+    let reply = dhcp6::handle_pkt(&mut p, &req, &conf).expect("Failed to handle Renew");
+    assert_eq!(
+        reply.options.get_ia_pd()[0].prefixes,
+        vec![
+            dhcp6pkt::IaPrefix {
+                preferred: std::time::Duration::from_secs(0),
+                valid: std::time::Duration::from_secs(0),
+                ..unknown
+            },
+            delegated.clone()
+        ]
+    );
+
+    let mut req = with_serverid(mk_request(dhcp6pkt::RELEASE, None));
+    req.pkt.options = req.pkt.options.add_ia_pd(&dhcp6pkt::IaPd {
+        prefixes: vec![delegated],
+        ..ia_pd
+    });
+    let reply = dhcp6::handle_pkt(&mut p, &req, &conf).expect("Failed to handle Release");
+    assert_eq!(reply.options.get_ia_pd(), vec![]);
+}
+
+#[test]
+fn no_prefix_delegation_configured() {
+    let mut p = pool::Pool6::new_in_memory().expect("Failed to create pool");
+    let mut req = mk_request(dhcp6pkt::SOLICIT, None);
+    req.pkt.options = req.pkt.options.add_ia_pd(&dhcp6pkt::IaPd {
+        iaid: IAID,
+        t1: std::time::Duration::from_secs(0),
+        t2: std::time::Duration::from_secs(0),
+        prefixes: vec![],
+        status: None,
+    });
+    let reply = dhcp6::handle_pkt(&mut p, &req, &mk_config()).expect("Failed to handle Solicit");
+    assert_eq!(
+        reply.options.get_ia_pd()[0].status.as_ref().map(|s| s.code),
+        Some(dhcp6pkt::STATUS_NOPREFIXAVAIL)
+    );
+}
+
+#[test]
+fn prefixes_within_configured_addresses() {
+    let mut conf = mk_config();
+    conf.addresses = vec![crate::config::Prefix::new(
+        "2001:db8::".parse().unwrap(),
+        48,
+    )];
+    let if_prefixes = [
+        ("2001:db8:0:1::1".parse().unwrap(), 64),
+        ("2001:db8:1::1".parse().unwrap(), 64),
+        ("192.0.2.1".parse().unwrap(), 24),
+    ];
+    assert_eq!(
+        dhcp6::get_prefixes(&conf, "eth0", &if_prefixes),
+        vec![Prefix6::new("2001:db8:0:1::".parse().unwrap(), 64)]
+    );
+}
+
+#[test]
+fn only_configured_when_needed() {
+    let mut conf = mk_config();
+    assert!(!dhcp6::is_configured(&conf));
+
+    conf.ra.interfaces = vec![crate::radv::config::Interface {
+        other: true,
+        ..Default::default()
+    }];
+    assert!(dhcp6::is_configured(&conf));
+
+    let mut conf = mk_config();
+    conf.dhcp6.delegations = vec![crate::dhcp6::config::Delegation {
+        prefix: Prefix6::new("2001:db8:100::".parse().unwrap(), 48),
+        delegated_length: 56,
+    }];
+    assert!(dhcp6::is_configured(&conf));
+}
@@ -39,11 +39,17 @@ impl DnsAclHandler {
         }
     }
 
+    #[cfg(feature = "dhcp")]
+    pub async fn set_dhcp_pool(&self, pool: crate::dhcp::worker::PoolWorker) {
+        self.next.set_dhcp_pool(pool).await
+    }
+
     pub async fn handle_query(&self, msg: &DnsMessage) -> Result<dnspkt::DNSPkt, Error> {
         acl::require_permission(
             &self.config.read().await.acls,
             &acl::Attributes {
                 addr: msg.remote_addr,
+                dns_protocol: Some(msg.protocol),
             },
             acl::PermissionType::DnsRecursion,
         )
@@ -16,10 +16,15 @@
  *
  *  Simple DNS cache.
  *  Caching in Erbium is applied on the "out" side, not on the "in" side as might be more common.
+ *
+ *  The cache is split into shards, each with their own lock, so that busy names don't hold up
+ *  queries for other names.  Each shard is bounded, and when it is full, entries are evicted using
+ *  the CLOCK algorithm, which approximates LRU without needing a write lock on every cache hit.
  */
 
 use super::Error;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time::{Duration, Instant};
@@ -41,19 +46,38 @@ lazy_static::lazy_static! {
         prometheus::register_int_gauge!("dns_cache_size",
             "Number of entries in the cache")
         .unwrap();
+
+    static ref DNS_CACHE_BYTES: prometheus::IntGauge =
+        prometheus::register_int_gauge!("dns_cache_bytes",
+            "Estimated memory used by entries in the cache")
+        .unwrap();
+
+    static ref DNS_CACHE_EVICTIONS: prometheus::IntCounter =
+        prometheus::register_int_counter!("dns_cache_evictions",
+            "Entries removed from the cache to make room for new ones")
+        .unwrap();
 }
 
-#[derive(Eq, PartialEq, Hash)]
-#[cfg_attr(test, derive(Clone))]
+/* Queries are spread over this many independently locked shards. */
+const SHARDS: usize = 16;
+
+#[derive(Eq, PartialEq, Hash, Clone)]
 struct CacheKey {
     qname: dnspkt::Domain,
     qtype: dnspkt::Type,
+    /* Replies for clients that asked us not to validate (CD) are kept apart from validated ones. */
+    checking_disabled: bool,
 }
 
 struct CacheValue {
     reply: Result<dnspkt::DNSPkt, Error>,
     birth: Instant,
     lifetime: Duration,
+    size: usize,
+    /* Set on every hit, and cleared as the CLOCK hand passes, so entries that have been used
+     * recently get another trip around before being evicted.
+     */
+    referenced: AtomicBool,
 }
 
 impl CacheValue {
@@ -62,12 +86,110 @@ impl CacheValue {
     }
 }
 
-type Cache = HashMap<CacheKey, CacheValue>;
+/* Roughly how much memory an entry uses.  The reply's wire format is a reasonable stand in for the
+ * size of its records, plus the overhead of storing each one.
+ */
+fn entry_size(ck: &CacheKey, reply: &Result<dnspkt::DNSPkt, Error>) -> usize {
+    let reply_size = match reply {
+        Ok(pkt) => {
+            pkt.serialise().len()
+                + std::mem::size_of::<dnspkt::RR>()
+                    * (pkt.answer.len() + pkt.nameserver.len() + pkt.additional.len())
+        }
+        Err(_) => 0,
+    };
+    std::mem::size_of::<CacheKey>()
+        + std::mem::size_of::<CacheValue>()
+        + ck.qname
+            .labels()
+            .iter()
+            .map(|l| l.as_bytes().len())
+            .sum::<usize>()
+        + reply_size
+}
+
+/* One shard of the cache. */
+struct Cache {
+    entries: HashMap<CacheKey, CacheValue>,
+    /* Every key in entries, in the order the CLOCK hand visits them. */
+    clock: VecDeque<CacheKey>,
+    bytes: usize,
+    limits: super::config::CacheLimits,
+}
+
+impl Cache {
+    fn new(limits: super::config::CacheLimits) -> Self {
+        Cache {
+            entries: HashMap::new(),
+            clock: VecDeque::new(),
+            bytes: 0,
+            limits,
+        }
+    }
+
+    fn remove(&mut self, ck: &CacheKey) {
+        if let Some(entry) = self.entries.remove(ck) {
+            self.bytes -= entry.size;
+            DNS_CACHE_SIZE.dec();
+            DNS_CACHE_BYTES.sub(entry.size as i64);
+        }
+    }
+
+    fn insert(&mut self, ck: CacheKey, value: CacheValue) {
+        /* Don't let one huge reply flush everything else out */
+        if self.limits.max_entries == 0 || value.size > self.limits.max_bytes {
+            DNS_CACHE.with_label_values(&["UNCACHABLE_SIZE"]).inc();
+            return;
+        }
+        if self.entries.contains_key(&ck) {
+            self.remove(&ck);
+        } else {
+            self.clock.push_back(ck.clone());
+        }
+        self.bytes += value.size;
+        DNS_CACHE_SIZE.inc();
+        DNS_CACHE_BYTES.add(value.size as i64);
+        self.entries.insert(ck, value);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.limits.max_entries || self.bytes > self.limits.max_bytes {
+            let ck = match self.clock.pop_front() {
+                Some(ck) => ck,
+                None => break,
+            };
+            match self.entries.get(&ck) {
+                Some(entry) if entry.referenced.swap(false, Ordering::Relaxed) => {
+                    self.clock.push_back(ck)
+                }
+                Some(_) => {
+                    self.remove(&ck);
+                    DNS_CACHE_EVICTIONS.inc();
+                }
+                None => (),
+            }
+        }
+    }
+}
+
+/* Per shard limits, rounded up so that the cache as a whole can hold at least as much as asked. */
+fn shard_limits(limits: super::config::CacheLimits) -> super::config::CacheLimits {
+    super::config::CacheLimits {
+        max_entries: limits.max_entries.div_ceil(SHARDS),
+        max_bytes: limits.max_bytes.div_ceil(SHARDS),
+    }
+}
+
+/* Validating a reply can need more lookups to fetch keys, which need validating themselves.  A
+ * broken zone could make these loop forever, so give up after this many.
+ */
+const MAX_DNSSEC_DEPTH: usize = 16;
 
 #[derive(Clone)]
 pub struct CacheHandler {
     next: outquery::OutQuery,
-    cache: Arc<RwLock<Cache>>,
+    shards: Arc<Vec<RwLock<Cache>>>,
 }
 
 /* std::io::Error is not clonable (for good reason), but we want to clone it.
@@ -101,15 +223,19 @@ fn clone_out_reply(reply: &Result<dnspkt::DNSPkt, Error>) -> Result<dnspkt::DNSP
         Err(OutReply(OutReplyError::Internal(msg))) => {
             Err(OutReply(OutReplyError::Internal(msg.clone())))
         }
+        Err(Dnssec(code, why)) => Err(Dnssec(*code, why.clone())),
         Err(Denied(x)) => Err(Denied(x.clone())),
         Err(Blocked) => Err(Blocked),
         Err(NoRouteConfigured) => Err(NoRouteConfigured),
         /* These errors cannot occur */
         Err(ListenError(..)) => unreachable!(),
+        Err(TlsConfig(_)) => unreachable!(),
         Err(AcceptError(..)) => unreachable!(),
         Err(RecvError(_)) => unreachable!(),
         Err(ParseError(_)) => unreachable!(),
         Err(RefusedByAcl(_)) => unreachable!(),
+        #[cfg(feature = "dhcp")]
+        Err(LeaseError(_)) => unreachable!(),
     }
 }
 
@@ -127,38 +253,57 @@ fn clone_with_ttl_decrement_out_reply(
 }
 
 impl CacheHandler {
-    pub async fn new() -> Self {
-        let cache = Arc::new(RwLock::new(Cache::new()));
-        let cache_copy = cache.clone();
-        tokio::spawn(async move {
-            Self::expire_thread(cache_copy).await;
-        });
+    fn with_limits(limits: super::config::CacheLimits) -> Self {
+        let limits = shard_limits(limits);
         CacheHandler {
             next: outquery::OutQuery::new(),
-            cache,
+            shards: Arc::new(
+                (0..SHARDS)
+                    .map(|_| RwLock::new(Cache::new(limits)))
+                    .collect(),
+            ),
         }
     }
 
+    pub async fn new(limits: super::config::CacheLimits) -> Self {
+        let handler = Self::with_limits(limits);
+        let shards = handler.shards.clone();
+        tokio::spawn(async move {
+            Self::expire_thread(shards).await;
+        });
+        handler
+    }
+
+    fn shard(&self, ck: &CacheKey) -> &RwLock<Cache> {
+        use std::hash::{Hash as _, Hasher as _};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        ck.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
     /* Expires entries, returns the time for the next expiration run. */
     fn expire(cache: &mut Cache, now: Instant) -> Instant {
-        use std::convert::TryInto as _;
-
         /* We don't have any notification from the resolvers if this time needs to go down.
          * So if we get a spike of resolutions we might have to start doing expiries, so poll
          * at least every this time.
          */
         let mut next_cycle = now + Duration::from_secs(1800);
-        cache.retain(|_k, v| {
-            if v.expiry() >= now {
-                next_cycle = std::cmp::min(next_cycle, v.expiry());
-                true
-            } else {
-                false
-            }
-        });
-
-        /* Update the new cache size. */
-        DNS_CACHE_SIZE.set(cache.len().try_into().unwrap_or(i64::MAX));
+        let expired = cache
+            .entries
+            .iter()
+            .filter(|(_k, v)| v.expiry() < now)
+            .map(|(k, _v)| k.clone())
+            .collect::<Vec<_>>();
+        for ck in &expired {
+            cache.remove(ck);
+        }
+        if !expired.is_empty() {
+            let entries = &cache.entries;
+            cache.clock.retain(|ck| entries.contains_key(ck));
+        }
+        for v in cache.entries.values() {
+            next_cycle = std::cmp::min(next_cycle, v.expiry());
+        }
 
         /* Don't waste cpu cycling too often.  If we have a lot of entries expiring at about
          * the same time, cap this to poll a bit more infrequently, it's more efficient to do
@@ -168,14 +313,14 @@ impl CacheHandler {
         std::cmp::max(next_cycle, Instant::now() + Duration::from_secs(30))
     }
 
-    async fn expire_thread(cache: Arc<RwLock<Cache>>) {
+    async fn expire_thread(shards: Arc<Vec<RwLock<Cache>>>) {
         loop {
-            let next_cycle;
+            let mut next_cycle = Instant::now() + Duration::from_secs(1800);
 
-            /* Expire all the old entries */
-            {
-                let mut rwcache = cache.write().await;
-                next_cycle = Self::expire(&mut rwcache, Instant::now());
+            /* Expire all the old entries, one shard at a time so queries can use the others */
+            for shard in shards.iter() {
+                let mut rwcache = shard.write().await;
+                next_cycle = std::cmp::min(next_cycle, Self::expire(&mut rwcache, Instant::now()));
             }
 
             /* Now wait until then. */
@@ -193,8 +338,9 @@ impl CacheHandler {
         now: Instant,
     ) -> Option<Result<dnspkt::DNSPkt, Error>> {
         /* Check to see if we have a cache hit that is still valid, if so, return it */
-        if let Some(entry) = cache.get(ck) {
+        if let Some(entry) = cache.entries.get(ck) {
             if entry.expiry() >= now {
+                entry.referenced.store(true, Ordering::Relaxed);
                 let remaining = (entry.birth + entry.lifetime) - now;
                 log::trace!("Cache hit ({:?} remaining)", remaining);
                 DNS_CACHE.with_label_values(&["HIT"]).inc();
@@ -226,6 +372,10 @@ impl CacheHandler {
             | Err(Error::OutReply(outquery::Error::FailedToRecv(_)))
             | Err(Error::OutReply(outquery::Error::TcpConnection(_)))
             | Err(Error::OutReply(outquery::Error::Parse(_))) => std::time::Duration::from_secs(8),
+            /* Bogus replies are unlikely to fix themselves quickly, and are expensive to check, but
+             * don't hold onto them so long that fixing the zone doesn't take effect.
+             */
+            Err(Error::Dnssec(..)) => std::time::Duration::from_secs(60),
             /* Otherwise do not cache the error */
             _ => std::time::Duration::from_secs(0),
         }
@@ -238,53 +388,92 @@ impl CacheHandler {
         out_result: &Result<dnspkt::DNSPkt, Error>,
         expiry: Duration,
     ) {
-        use std::convert::TryInto as _;
-
+        let size = entry_size(&ck, out_result);
         cache.insert(
             ck,
             CacheValue {
                 reply: clone_out_reply(out_result),
                 birth: Instant::now(),
                 lifetime: expiry,
+                size,
+                referenced: AtomicBool::new(false),
             },
         );
+    }
 
-        DNS_CACHE_SIZE.set(cache.len().try_into().unwrap_or(i64::MAX));
+    /* Sends the query upstream, and validates the reply if the route asks for it. */
+    async fn resolve(
+        &self,
+        msg: &super::DnsMessage,
+        forwarders: &super::config::Forwarders,
+        depth: usize,
+    ) -> Result<dnspkt::DNSPkt, Error> {
+        let mut reply = self.next.handle_query(msg, forwarders).await?;
+        match &forwarders.dnssec {
+            /* RFC4035 Section 3.2.2: The client will do its own validation, so hand it the reply
+             * as is, rather than a failure it can't see past.  We haven't vouched for it, so AD is
+             * cleared.
+             */
+            Some(_) if msg.in_query.cd => {
+                reply.ad = false;
+                Ok(reply)
+            }
+            Some(anchors) => {
+                let lookup = DnssecLookup {
+                    handler: self,
+                    msg,
+                    forwarders,
+                    depth,
+                };
+                super::dnssec::validate(&lookup, anchors, reply, super::local::now()).await
+            }
+            None => Ok(reply),
+        }
     }
 
     pub async fn handle_query(
         &self,
         msg: &super::DnsMessage,
-        addr: std::net::SocketAddr,
+        forwarders: &super::config::Forwarders,
+    ) -> Result<dnspkt::DNSPkt, Error> {
+        self.handle_query_depth(msg, forwarders, 0).await
+    }
+
+    async fn handle_query_depth(
+        &self,
+        msg: &super::DnsMessage,
+        forwarders: &super::config::Forwarders,
+        depth: usize,
     ) -> Result<dnspkt::DNSPkt, Error> {
         let q = &msg.in_query.question;
         /* Only do caching for IN queries */
         if q.qclass != dnspkt::CLASS_IN {
             log::trace!("[{:x}] Not caching non-IN query", msg.in_query.qid);
             DNS_CACHE.with_label_values(&["UNCACHABLE_CLASS"]).inc();
-            return self.next.handle_query(msg, addr).await;
+            return self.next.handle_query(msg, forwarders).await;
         }
 
         let ck = CacheKey {
             qname: q.qdomain.clone(),
             qtype: q.qtype,
+            checking_disabled: msg.in_query.cd && forwarders.dnssec.is_some(),
         };
 
         {
-            let rocache = self.cache.read().await;
+            let rocache = self.shard(&ck).read().await;
             if let Some(result) = Self::get_entry(&rocache, &ck, Instant::now()) {
                 return result;
             }
         }
 
         /* Cache miss: Go attempt the resolve, and return the result */
-        let out_result = self.next.handle_query(msg, addr).await;
+        let out_result = self.resolve(msg, forwarders, depth).await;
 
         let expiry = self.calculate_expiry(&out_result);
 
         /* Only insert into the cache if the duration is reasonable */
         if expiry > Duration::from_secs(0) {
-            let mut rwcache = self.cache.write().await;
+            let mut rwcache = self.shard(&ck).write().await;
             self.insert_cache_entry(&mut rwcache, ck, &out_result, expiry);
         }
 
@@ -296,3 +485,46 @@ impl CacheHandler {
         out_result
     }
 }
+
+/* Fetches the keys needed to validate a reply, through the cache, from the same upstream servers
+ * as the original query.
+ */
+struct DnssecLookup<'a> {
+    handler: &'a CacheHandler,
+    msg: &'a super::DnsMessage,
+    forwarders: &'a super::config::Forwarders,
+    depth: usize,
+}
+
+#[async_trait::async_trait]
+impl super::dnssec::Lookup for DnssecLookup<'_> {
+    async fn lookup(
+        &self,
+        qname: &dnspkt::Domain,
+        qtype: dnspkt::Type,
+    ) -> Result<dnspkt::DNSPkt, Error> {
+        if self.depth >= MAX_DNSSEC_DEPTH {
+            return Err(Error::Dnssec(
+                dnspkt::EDE_DNSSEC_INDETERMINATE,
+                format!("Too many lookups needed to validate {} {}", qname, qtype),
+            ));
+        }
+        let msg = super::DnsMessage {
+            in_query: dnspkt::DNSPkt {
+                question: dnspkt::Question {
+                    qdomain: qname.clone(),
+                    qtype,
+                    qclass: dnspkt::CLASS_IN,
+                },
+                ..self.msg.in_query.clone()
+            },
+            in_size: self.msg.in_size,
+            local_ip: self.msg.local_ip,
+            remote_addr: self.msg.remote_addr,
+            protocol: self.msg.protocol,
+        };
+        self.handler
+            .handle_query_depth(&msg, self.forwarders, self.depth + 1)
+            .await
+    }
+}
@@ -22,23 +22,21 @@ use crate::dns::dnspkt::*;
 
 #[tokio::test]
 async fn test_expiry() {
-    let handler = CacheHandler {
-        next: outquery::OutQuery::new(),
-        cache: Arc::new(RwLock::new(Cache::new())),
-    };
+    let handler = CacheHandler::with_limits(Default::default());
 
     let example_net: dnspkt::Domain = "example.net".parse().unwrap();
 
     let ck = CacheKey {
         qname: example_net.clone(),
         qtype: RR_A,
+        checking_disabled: false,
     };
 
     let mut now = Instant::now();
 
     /* First verify the entry doesn't exist in an empty cache */
     {
-        let rocache = handler.cache.read().await;
+        let rocache = handler.shard(&ck).read().await;
         assert!(CacheHandler::get_entry(&rocache, &ck, now).is_none());
     }
 
@@ -66,7 +64,7 @@ async fn test_expiry() {
             class: CLASS_IN,
             rrtype: RR_A,
             ttl: 600,
-            rdata: dnspkt::RData::Other(vec![192, 0, 2, 1]),
+            rdata: dnspkt::RData::A("192.0.2.1".parse().unwrap()),
         }],
         nameserver: vec![],
         additional: vec![],
@@ -78,24 +76,24 @@ async fn test_expiry() {
 
     /* Insert an entry */
     {
-        let mut rwcache = handler.cache.write().await;
+        let mut rwcache = handler.shard(&ck).write().await;
         handler.insert_cache_entry(&mut rwcache, ck.clone(), &out_result, expiry);
-        assert_eq!(rwcache.len(), 1);
+        assert_eq!(rwcache.entries.len(), 1);
     }
 
     /* Now test that it comes back 5s later */
     now += Duration::from_secs(5);
     {
-        let rocache = handler.cache.read().await;
+        let rocache = handler.shard(&ck).read().await;
         assert!(CacheHandler::get_entry(&rocache, &ck, now).is_some());
     }
 
     /* Now run a GC after 60s and check the entry isn't removed */
     now += Duration::from_secs(60);
     {
-        let mut rwcache = handler.cache.write().await;
+        let mut rwcache = handler.shard(&ck).write().await;
         let next = CacheHandler::expire(&mut rwcache, now);
-        assert_eq!(rwcache.len(), 1);
+        assert_eq!(rwcache.entries.len(), 1);
         assert!(CacheHandler::get_entry(&rwcache, &ck, now).is_some());
         assert!(next < now + Duration::from_secs(1800)); // We have an entry that is newer than that.
         assert!(next > now + Duration::from_secs(30)); // But not too frequently!
@@ -104,17 +102,142 @@ async fn test_expiry() {
     /* Test that it is expired after 15 minutes */
     now += Duration::from_secs(900);
     {
-        let rocache = handler.cache.read().await;
+        let rocache = handler.shard(&ck).read().await;
         assert!(CacheHandler::get_entry(&rocache, &ck, now).is_none());
     }
 
     /* Test that after an hour, the expiry garbage collection removes the entry */
     now += Duration::from_secs(3600);
     {
-        let mut rwcache = handler.cache.write().await;
+        let mut rwcache = handler.shard(&ck).write().await;
         let next = CacheHandler::expire(&mut rwcache, now);
         assert!(CacheHandler::get_entry(&rwcache, &ck, now).is_none());
-        assert_eq!(rwcache.len(), 0);
+        assert_eq!(rwcache.entries.len(), 0);
         assert!(next >= now + Duration::from_secs(1800)); // There are no entries left, so re-run infrequently.
     }
 }
+
+#[tokio::test]
+async fn test_eviction() {
+    use crate::dns::config::CacheLimits;
+    let handler = CacheHandler::with_limits(Default::default());
+    let key = |name: &str| CacheKey {
+        qname: name.parse().unwrap(),
+        qtype: RR_A,
+        checking_disabled: false,
+    };
+    let reply = |name: &str| {
+        let mut reply = crate::dns::local::mk_query(name, RR_A);
+        reply.answer.push(dnspkt::RR {
+            domain: name.parse().unwrap(),
+            class: CLASS_IN,
+            rrtype: RR_A,
+            ttl: 600,
+            rdata: dnspkt::RData::A("192.0.2.1".parse().unwrap()),
+        });
+        Ok(reply)
+    };
+    let expiry = Duration::from_secs(600);
+    let now = Instant::now();
+
+    let mut cache = Cache::new(CacheLimits {
+        max_entries: 2,
+        max_bytes: usize::MAX,
+    });
+    for name in ["a.example", "b.example"].iter() {
+        handler.insert_cache_entry(&mut cache, key(name), &reply(name), expiry);
+    }
+    /* a.example has been used since it was inserted, so b.example is evicted instead */
+    assert!(CacheHandler::get_entry(&cache, &key("a.example"), now).is_some());
+    handler.insert_cache_entry(&mut cache, key("c.example"), &reply("c.example"), expiry);
+    assert_eq!(cache.entries.len(), 2);
+    assert!(CacheHandler::get_entry(&cache, &key("a.example"), now).is_some());
+    assert!(CacheHandler::get_entry(&cache, &key("b.example"), now).is_none());
+    assert!(CacheHandler::get_entry(&cache, &key("c.example"), now).is_some());
+    /* Replacing an entry doesn't evict anything */
+    handler.insert_cache_entry(&mut cache, key("c.example"), &reply("c.example"), expiry);
+    assert_eq!(cache.entries.len(), 2);
+    assert_eq!(cache.clock.len(), 2);
+
+    /* The byte limit is enforced too, and a reply that can never fit isn't cached at all */
+    let size = entry_size(&key("a.example"), &reply("a.example"));
+    let mut cache = Cache::new(CacheLimits {
+        max_entries: 100,
+        max_bytes: size * 2,
+    });
+    for name in ["a.example", "b.example", "c.example"].iter() {
+        handler.insert_cache_entry(&mut cache, key(name), &reply(name), expiry);
+    }
+    assert_eq!(cache.entries.len(), 2);
+    assert!(cache.bytes <= size * 2);
+    let mut big = reply("big.example").unwrap();
+    big.answer = vec![big.answer[0].clone(); 100];
+    handler.insert_cache_entry(&mut cache, key("big.example"), &Ok(big), expiry);
+    assert_eq!(cache.entries.len(), 2);
+    assert!(CacheHandler::get_entry(&cache, &key("big.example"), now).is_none());
+}
+
+#[tokio::test]
+async fn test_checking_disabled() {
+    use crate::dns::config::{Forwarders, Strategy, Transport};
+    use erbium_net::addr::WithPort as _;
+
+    /* A nameserver that claims to have validated unsigned answers, which can't be validated. */
+    let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 65536];
+        while let Ok((len, client)) = server.recv_from(&mut buf).await {
+            let mut reply = crate::dns::parse::PktParser::new(&buf[..len])
+                .get_dns()
+                .unwrap();
+            reply.qr = true;
+            reply.ad = true;
+            if reply.question.qtype == RR_A {
+                reply.answer.push(dnspkt::RR {
+                    domain: reply.question.qdomain.clone(),
+                    class: CLASS_IN,
+                    rrtype: RR_A,
+                    ttl: 600,
+                    rdata: dnspkt::RData::A("192.0.2.1".parse().unwrap()),
+                });
+            }
+            server.send_to(&reply.serialise(), client).await.unwrap();
+        }
+    });
+
+    let handler = CacheHandler::with_limits(Default::default());
+    let forwarders = Forwarders {
+        servers: vec![server_addr],
+        strategy: Strategy::Failover,
+        transport: Transport::Dns,
+        dnssec: Some(crate::dns::dnssec::root_trust_anchors()),
+    };
+    let msg = |cd| {
+        let mut in_query = crate::dns::local::mk_query("www.example", RR_A);
+        in_query.cd = cd;
+        crate::dns::DnsMessage {
+            in_query,
+            in_size: 0,
+            local_ip: std::net::Ipv4Addr::LOCALHOST.into(),
+            remote_addr: std::net::Ipv4Addr::LOCALHOST.with_port(5353),
+            protocol: crate::dns::Protocol::Udp,
+        }
+    };
+
+    /* The failure is cached, but clients that do their own validation still get the reply */
+    assert!(matches!(
+        handler.handle_query(&msg(false), &forwarders).await,
+        Err(Error::Dnssec(..))
+    ));
+    for _ in 0..2 {
+        let reply = handler.handle_query(&msg(true), &forwarders).await.unwrap();
+        assert_eq!(reply.rcode, NOERROR);
+        assert_eq!(reply.answer.len(), 1);
+        assert!(!reply.ad);
+    }
+    assert!(matches!(
+        handler.handle_query(&msg(false), &forwarders).await,
+        Err(Error::Dnssec(..))
+    ));
+}
@@ -20,15 +20,106 @@
 use crate::config::*;
 use yaml_rust::yaml;
 
+/// How to choose which of a route's nameservers to send a query to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Use the first healthy server, in the order they are configured.
+    Failover,
+    /// Spread queries over all the healthy servers.
+    RoundRobin,
+    /// Prefer the healthy server with the lowest smoothed round trip time.
+    Fastest,
+}
+
+/// How a dns-over-tls or dns-over-https server's certificate is checked.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TlsAuth {
+    /// The name the certificate must be valid for, signed by one of the system's trusted CAs.
+    pub auth_name: Option<String>,
+    /// SHA-256 digests of public keys (SubjectPublicKeyInfo), one of which must be in the
+    /// certificate chain, as per RFC7858 Section 4.2.
+    pub spki_pins: Vec<Vec<u8>>,
+}
+
+/// Where a dns-over-https server answers queries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HttpsEndpoint {
+    /// The URL queries are POSTed to.
+    pub url: hyper::Uri,
+    pub tls: TlsAuth,
+}
+
+/// How queries are sent to a route's nameservers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// Plain DNS over UDP, falling back to TCP.
+    Dns,
+    /// DNS over TLS (RFC7858).
+    DnsOverTls(TlsAuth),
+    /// DNS over HTTPS (RFC8484).
+    DnsOverHttps(HttpsEndpoint),
+}
+
+#[derive(Debug)]
+pub struct Forwarders {
+    pub servers: Vec<std::net::SocketAddr>,
+    pub strategy: Strategy,
+    pub transport: Transport,
+    /// The trust anchors to validate replies with, if DNSSEC validation is enabled.
+    pub dnssec: Option<Vec<super::dnssec::TrustAnchor>>,
+}
+
 #[derive(Debug)]
 pub enum Handler {
-    Forward(Vec<std::net::SocketAddr>),
+    Forward(Forwarders),
     ForgeNxDomain,
+    LocalZone(Vec<super::dnspkt::RR>),
+    #[cfg(feature = "dhcp")]
+    DhcpLeases,
+}
+
+enum HandlerTransport {
+    Dns,
+    Tls,
+    Https,
 }
 
 enum HandlerType {
     Forward,
     ForgeNxDomain,
+    LocalZone,
+    #[cfg(feature = "dhcp")]
+    DhcpLeases,
+}
+
+/// How much the DNS cache may hold, before it starts evicting the least recently used entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheLimits {
+    pub max_entries: usize,
+    /// An estimate of the memory used by the cached replies.
+    pub max_bytes: usize,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        CacheLimits {
+            max_entries: 10000,
+            max_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Serving DNS over TLS to clients.
+#[derive(Debug)]
+pub struct TlsListeners {
+    /// PEM file with the certificate chain, starting with the server's own certificate.
+    pub certificate: std::path::PathBuf,
+    /// PEM file with the certificate's private key.
+    pub key: std::path::PathBuf,
+    /// Where to listen, or None to use the dns-listeners addresses on port 853.
+    pub listeners: Option<Vec<erbium_net::addr::NetAddr>>,
+    /// Where to listen for DNS over HTTPS.
+    pub https_listeners: Vec<erbium_net::addr::NetAddr>,
 }
 
 #[derive(Debug)]
@@ -37,20 +128,259 @@ pub struct Route {
     pub dest: Handler,
 }
 
+const DEFAULT_RECORD_TTL: u32 = 300;
+
+fn str_domain(name: &str, value: &str) -> Result<super::dnspkt::Domain, Error> {
+    value
+        .parse::<super::dnspkt::Domain>()
+        .map(|d| d.to_ascii_lowercase())
+        .map_err(|m| {
+            Error::InvalidConfig(format!(
+                "{}: {:?} is not a valid domain: {}",
+                name, value, m
+            ))
+        })
+}
+
+fn str_rdata(
+    name: &str,
+    rrtype: &str,
+    value: &str,
+) -> Result<(super::dnspkt::Type, super::dnspkt::RData), Error> {
+    use super::dnspkt::*;
+    let invalid = || {
+        Error::InvalidConfig(format!(
+            "{}: invalid {} record value {:?}",
+            name, rrtype, value
+        ))
+    };
+    let fields = value.split_whitespace().collect::<Vec<_>>();
+    match rrtype.to_ascii_uppercase().as_str() {
+        "A" => {
+            let ip: std::net::Ipv4Addr = value.parse().map_err(|_| invalid())?;
+            Ok((RR_A, RData::A(ip)))
+        }
+        "AAAA" => {
+            let ip: std::net::Ipv6Addr = value.parse().map_err(|_| invalid())?;
+            Ok((RR_AAAA, RData::Aaaa(ip)))
+        }
+        "CNAME" => Ok((RR_CNAME, RData::CName(str_domain(name, value)?))),
+        "PTR" => Ok((RR_PTR, RData::Ptr(str_domain(name, value)?))),
+        "MX" => match fields[..] {
+            [pref, domain] => Ok((
+                RR_MX,
+                RData::Mx(PrefDomainData {
+                    pref: pref.parse().map_err(|_| invalid())?,
+                    domain: str_domain(name, domain)?,
+                }),
+            )),
+            _ => Err(invalid()),
+        },
+        "TXT" => {
+            /* Long strings need to be split into multiple <character-string>s */
+            let mut strings = value
+                .as_bytes()
+                .chunks(255)
+                .map(|chunk| chunk.to_vec())
+                .collect::<Vec<_>>();
+            if strings.is_empty() {
+                strings.push(vec![]);
+            }
+            Ok((RR_TXT, RData::Txt(strings)))
+        }
+        "SRV" => match fields[..] {
+            [priority, weight, port, target] => Ok((
+                RR_SRV,
+                RData::Srv(SrvData {
+                    priority: priority.parse().map_err(|_| invalid())?,
+                    weight: weight.parse().map_err(|_| invalid())?,
+                    port: port.parse().map_err(|_| invalid())?,
+                    target: str_domain(name, target)?,
+                }),
+            )),
+            _ => Err(invalid()),
+        },
+        _ => Err(Error::InvalidConfig(format!(
+            "{}: record type {} not supported",
+            name, rrtype
+        ))),
+    }
+}
+
+pub fn parse_dns_record(
+    name: &str,
+    fragment: &yaml::Yaml,
+) -> Result<Option<super::dnspkt::RR>, Error> {
+    if let Some(h) = fragment.as_hash() {
+        let mut domain = None;
+        let mut rrtype = None;
+        let mut value = None;
+        let mut ttl = None;
+        for (k, v) in h {
+            match k.as_str() {
+                Some("name") => domain = parse_string("name", v)?,
+                Some("type") => rrtype = parse_string("type", v)?,
+                Some("value") => value = parse_string("value", v)?,
+                Some("ttl") => ttl = parse_num("ttl", v)?,
+                Some(opt) => {
+                    return Err(Error::InvalidConfig(format!(
+                        "Unknown {} keyword {}",
+                        name, opt
+                    )))
+                }
+                None => {
+                    return Err(Error::InvalidConfig(format!(
+                        "Expected string in {}, not {:?}",
+                        name, k
+                    )))
+                }
+            }
+        }
+        let domain =
+            domain.ok_or_else(|| Error::InvalidConfig(format!("{} is missing a name", name)))?;
+        let rrtype = rrtype.ok_or_else(|| {
+            Error::InvalidConfig(format!("{} {} is missing a type", name, domain))
+        })?;
+        let value = value.ok_or_else(|| {
+            Error::InvalidConfig(format!("{} {} is missing a value", name, domain))
+        })?;
+        let (rrtype, rdata) = str_rdata(&domain, &rrtype, &value)?;
+        Ok(Some(super::dnspkt::RR {
+            domain: str_domain(name, &domain)?,
+            class: super::dnspkt::CLASS_IN,
+            rrtype,
+            ttl: ttl.unwrap_or(DEFAULT_RECORD_TTL),
+            rdata,
+        }))
+    } else {
+        Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(fragment)
+        )))
+    }
+}
+
+/* Nameservers are an IP address, optionally with a port (eg [2001:db8::53]:8853) */
+fn parse_string_nameserver(
+    name: &str,
+    fragment: &yaml::Yaml,
+) -> Result<Option<(std::net::IpAddr, Option<u16>)>, Error> {
+    if let Some(addr) = fragment
+        .as_str()
+        .and_then(|s| s.parse::<std::net::SocketAddr>().ok())
+    {
+        Ok(Some((addr.ip(), Some(addr.port()))))
+    } else {
+        Ok(parse_string_ip(name, fragment)?.map(|ip| (ip, None)))
+    }
+}
+
+fn parse_spki_pin(name: &str, fragment: &yaml::Yaml) -> Result<Option<Vec<u8>>, Error> {
+    use base64::Engine as _;
+    match parse_string(name, fragment)? {
+        None => Ok(None),
+        Some(pin) => base64::engine::general_purpose::STANDARD
+            .decode(&pin)
+            .ok()
+            .filter(|digest| digest.len() == 32)
+            .map(Some)
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "{}: {:?} is not a base64 encoded SHA-256 digest",
+                    name, pin
+                ))
+            }),
+    }
+}
+
+fn parse_trust_anchor(
+    name: &str,
+    fragment: &yaml::Yaml,
+) -> Result<Option<super::dnssec::TrustAnchor>, Error> {
+    match parse_string(name, fragment)? {
+        None => Ok(None),
+        Some(anchor) => anchor
+            .parse()
+            .map(Some)
+            .map_err(|err| Error::InvalidConfig(format!("{}: {}", name, err))),
+    }
+}
+
+fn parse_https_url(name: &str, fragment: &yaml::Yaml) -> Result<Option<hyper::Uri>, Error> {
+    match parse_string(name, fragment)? {
+        None => Ok(None),
+        Some(url) => match url.parse::<hyper::Uri>() {
+            Ok(uri) if uri.scheme_str() == Some("https") && uri.host().is_some() => Ok(Some(uri)),
+            _ => Err(Error::InvalidConfig(format!(
+                "{} should be an https:// URL, not {}",
+                name, url
+            ))),
+        },
+    }
+}
+
 pub fn parse_dns_route(name: &str, fragment: &yaml::Yaml) -> Result<Option<Route>, Error> {
     if let Some(h) = fragment.as_hash() {
         let mut suffixes = None;
         let mut servers = None;
         let mut handler = None;
+        let mut records = None;
+        let mut strategy = None;
+        let mut transport = None;
+        let mut auth_name = None;
+        let mut spki_pins = None;
+        let mut doh_url = None;
+        let mut dnssec_validate = None;
+        let mut trust_anchors = None;
         for (k, v) in h {
             match k.as_str() {
                 Some("domain-suffixes") => {
                     suffixes = parse_array("domain-suffixes", v, parse_string)?
                 }
-                Some("dns-servers") => servers = parse_array("domain-servers", v, parse_string_ip)?,
+                Some("dns-servers") => {
+                    servers = parse_array("dns-servers", v, parse_string_nameserver)?
+                }
+                Some("records") => records = parse_array("records", v, parse_dns_record)?,
+                Some("strategy") => match parse_string("strategy", v)? {
+                    Some(s) if s == "failover" => strategy = Some(Strategy::Failover),
+                    Some(s) if s == "round-robin" => strategy = Some(Strategy::RoundRobin),
+                    Some(s) if s == "fastest" => strategy = Some(Strategy::Fastest),
+                    Some(s) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} strategy {} not supported",
+                            name, s
+                        )))
+                    }
+                    None => (),
+                },
+                Some("transport") => match parse_string("transport", v)? {
+                    Some(t) if t == "dns" => transport = Some(HandlerTransport::Dns),
+                    Some(t) if t == "dns-over-tls" => transport = Some(HandlerTransport::Tls),
+                    Some(t) if t == "dns-over-https" => transport = Some(HandlerTransport::Https),
+                    Some(t) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} transport {} not supported",
+                            name, t
+                        )))
+                    }
+                    None => (),
+                },
+                Some("tls-auth-name") => auth_name = parse_string("tls-auth-name", v)?,
+                Some("tls-spki-pins") => {
+                    spki_pins = parse_array("tls-spki-pins", v, parse_spki_pin)?
+                }
+                Some("doh-url") => doh_url = parse_https_url("doh-url", v)?,
+                Some("dnssec-validate") => dnssec_validate = parse_boolean("dnssec-validate", v)?,
+                Some("dnssec-trust-anchors") => {
+                    trust_anchors = parse_array("dnssec-trust-anchors", v, parse_trust_anchor)?
+                }
                 Some("type") => match parse_string("type", v)? {
                     Some(t) if t == "forward" => handler = Some(HandlerType::Forward),
                     Some(t) if t == "forge-nxdomain" => handler = Some(HandlerType::ForgeNxDomain),
+                    Some(t) if t == "local-zone" => handler = Some(HandlerType::LocalZone),
+                    #[cfg(feature = "dhcp")]
+                    Some(t) if t == "dhcp-leases" => handler = Some(HandlerType::DhcpLeases),
                     Some(kw) => {
                         return Err(Error::InvalidConfig(format!(
                             "{} type {} not supported",
@@ -76,25 +406,128 @@ pub fn parse_dns_route(name: &str, fragment: &yaml::Yaml) -> Result<Option<Route
         let suffix_domains: Vec<super::dnspkt::Domain> = suffixes
             .unwrap_or_default()
             .iter()
-            .map(|d| d.parse())
+            .map(|d| {
+                d.parse()
+                    .map(|d: super::dnspkt::Domain| d.to_ascii_lowercase())
+            })
             .collect::<Result<_, &'static str>>()
             .map_err(|m| Error::InvalidConfig(m.into()))?;
-        let servers = servers.unwrap_or_default();
-        if servers.len() > 1 {
-            return Err(Error::InvalidConfig(
-                "Multiple DNS servers for a prefix not yet implemented.".into(), // TODO
-            ));
+        if records.is_some() && !matches!(handler, Some(HandlerType::LocalZone)) {
+            return Err(Error::InvalidConfig(format!(
+                "{}: records are only used by type local-zone",
+                name
+            )));
+        }
+        if strategy.is_some() && !matches!(handler, Some(HandlerType::Forward) | None) {
+            return Err(Error::InvalidConfig(format!(
+                "{}: strategy is only used by type forward",
+                name
+            )));
+        }
+        if transport.is_some() && !matches!(handler, Some(HandlerType::Forward) | None) {
+            return Err(Error::InvalidConfig(format!(
+                "{}: transport is only used by type forward",
+                name
+            )));
+        }
+        if dnssec_validate.is_some() && !matches!(handler, Some(HandlerType::Forward) | None) {
+            return Err(Error::InvalidConfig(format!(
+                "{}: dnssec-validate is only used by type forward",
+                name
+            )));
+        }
+        let dnssec = match (dnssec_validate, trust_anchors) {
+            (Some(true), anchors) => {
+                Some(anchors.unwrap_or_else(super::dnssec::root_trust_anchors))
+            }
+            (_, None) => None,
+            (_, Some(_)) => {
+                return Err(Error::InvalidConfig(format!(
+                    "{}: dnssec-trust-anchors are only used with dnssec-validate",
+                    name
+                )))
+            }
+        };
+        let mut servers = servers.unwrap_or_default();
+        let (transport, default_port) = match transport {
+            Some(HandlerTransport::Tls) => {
+                let spki_pins = spki_pins.take().unwrap_or_default();
+                if auth_name.is_none() && spki_pins.is_empty() {
+                    return Err(Error::InvalidConfig(format!(
+                        "{}: dns-over-tls needs a tls-auth-name or tls-spki-pins to check the server's certificate",
+                        name
+                    )));
+                }
+                let tls = TlsAuth {
+                    auth_name: auth_name.take(),
+                    spki_pins,
+                };
+                (Transport::DnsOverTls(tls), 853)
+            }
+            Some(HandlerTransport::Https) => {
+                let url = doh_url.take().ok_or_else(|| {
+                    Error::InvalidConfig(format!("{}: dns-over-https needs a doh-url", name))
+                })?;
+                /* The URL's host is usually a name, which we can't look up without already having
+                 * a nameserver, so the addresses to connect to are given in dns-servers instead.
+                 */
+                let host = url
+                    .host()
+                    .unwrap()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']');
+                if servers.is_empty() {
+                    match host.parse() {
+                        Ok(ip) => servers.push((ip, None)),
+                        Err(_) => {
+                            return Err(Error::InvalidConfig(format!(
+                                "{}: dns-over-https needs dns-servers to connect to {}",
+                                name, host
+                            )))
+                        }
+                    }
+                }
+                let spki_pins = spki_pins.take().unwrap_or_default();
+                /* Unless only pins are given, the certificate must be valid for the URL's host */
+                let auth_name = auth_name
+                    .take()
+                    .or_else(|| Some(host.to_string()).filter(|_| spki_pins.is_empty()));
+                let port = url.port_u16().unwrap_or(443);
+                let tls = TlsAuth {
+                    auth_name,
+                    spki_pins,
+                };
+                (Transport::DnsOverHttps(HttpsEndpoint { url, tls }), port)
+            }
+            Some(HandlerTransport::Dns) | None => (Transport::Dns, 53),
+        };
+        if auth_name.is_some() || spki_pins.is_some() {
+            return Err(Error::InvalidConfig(format!(
+                "{}: tls-auth-name and tls-spki-pins are only used by transports dns-over-tls and dns-over-https",
+                name
+            )));
+        }
+        if doh_url.is_some() {
+            return Err(Error::InvalidConfig(format!(
+                "{}: doh-url is only used by transport dns-over-https",
+                name
+            )));
         }
         match handler {
             Some(HandlerType::Forward) | None => {
                 return Ok(Some(Route {
                     suffixes: suffix_domains,
-                    dest: Handler::Forward(
-                        servers
+                    dest: Handler::Forward(Forwarders {
+                        servers: servers
                             .iter()
-                            .map(|ip| std::net::SocketAddr::new(*ip, 53))
+                            .map(|(ip, port)| {
+                                std::net::SocketAddr::new(*ip, port.unwrap_or(default_port))
+                            })
                             .collect(),
-                    ),
+                        strategy: strategy.unwrap_or(Strategy::Failover),
+                        transport,
+                        dnssec,
+                    }),
                 }));
             }
             Some(HandlerType::ForgeNxDomain) => {
@@ -103,6 +536,47 @@ pub fn parse_dns_route(name: &str, fragment: &yaml::Yaml) -> Result<Option<Route
                     dest: Handler::ForgeNxDomain,
                 }))
             }
+            Some(HandlerType::LocalZone) => {
+                let records = records.unwrap_or_default();
+                for rr in &records {
+                    if !suffix_domains.iter().any(|d| rr.domain.ends_with(d)) {
+                        return Err(Error::InvalidConfig(format!(
+                            "{}: record {} is not inside any of the domain-suffixes",
+                            name, rr.domain
+                        )));
+                    }
+                    if rr.rrtype == super::dnspkt::RR_CNAME
+                        && records
+                            .iter()
+                            .any(|other| other.domain == rr.domain && other != rr)
+                    {
+                        return Err(Error::InvalidConfig(format!(
+                            "{}: {} has a CNAME record, so cannot have any other records",
+                            name, rr.domain
+                        )));
+                    }
+                }
+                return Ok(Some(Route {
+                    suffixes: suffix_domains,
+                    dest: Handler::LocalZone(records),
+                }));
+            }
+            #[cfg(feature = "dhcp")]
+            Some(HandlerType::DhcpLeases) => {
+                if !suffix_domains
+                    .iter()
+                    .any(|d| !d.ends_with(&super::leases::REVERSE_ZONE))
+                {
+                    return Err(Error::InvalidConfig(format!(
+                        "{} of type dhcp-leases needs at least one domain suffix that isn't in-addr.arpa",
+                        name
+                    )));
+                }
+                return Ok(Some(Route {
+                    suffixes: suffix_domains,
+                    dest: Handler::DhcpLeases,
+                }));
+            }
         }
     }
     Ok(None)
@@ -112,6 +586,92 @@ pub fn parse_dns_routes(name: &str, fragment: &yaml::Yaml) -> Result<Option<Vec<
     parse_array(name, fragment, parse_dns_route)
 }
 
+pub fn parse_dns_cache(name: &str, fragment: &yaml::Yaml) -> Result<Option<CacheLimits>, Error> {
+    match fragment {
+        yaml::Yaml::Null => Ok(None),
+        yaml::Yaml::Hash(h) => {
+            let mut limits = CacheLimits::default();
+            for (k, v) in h {
+                match k.as_str() {
+                    Some("max-entries") => {
+                        limits.max_entries =
+                            parse_num("max-entries", v)?.unwrap_or(limits.max_entries)
+                    }
+                    Some("max-bytes") => {
+                        limits.max_bytes = parse_num("max-bytes", v)?.unwrap_or(limits.max_bytes)
+                    }
+                    Some(opt) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "Unknown {} keyword {}",
+                            name, opt
+                        )))
+                    }
+                    None => {
+                        return Err(Error::InvalidConfig(format!(
+                            "Expected string in {}, not {:?}",
+                            name, k
+                        )))
+                    }
+                }
+            }
+            Ok(Some(limits))
+        }
+        e => Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
+
+pub fn parse_dns_tls(name: &str, fragment: &yaml::Yaml) -> Result<Option<TlsListeners>, Error> {
+    match fragment {
+        yaml::Yaml::Null => Ok(None),
+        yaml::Yaml::Hash(h) => {
+            let mut certificate = None;
+            let mut key = None;
+            let mut listeners = None;
+            let mut https_listeners = None;
+            for (k, v) in h {
+                match k.as_str() {
+                    Some("certificate") => certificate = parse_string("certificate", v)?,
+                    Some("key") => key = parse_string("key", v)?,
+                    Some("listeners") => {
+                        listeners = parse_array("listeners", v, parse_string_sockaddr)?
+                    }
+                    Some("https-listeners") => {
+                        https_listeners = parse_array("https-listeners", v, parse_string_sockaddr)?
+                    }
+                    Some(opt) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "Unknown {} keyword {}",
+                            name, opt
+                        )))
+                    }
+                    None => {
+                        return Err(Error::InvalidConfig(format!(
+                            "Expected string in {}, not {:?}",
+                            name, k
+                        )))
+                    }
+                }
+            }
+            let missing = |what| Error::InvalidConfig(format!("{} needs a {}", name, what));
+            Ok(Some(TlsListeners {
+                certificate: certificate.ok_or_else(|| missing("certificate"))?.into(),
+                key: key.ok_or_else(|| missing("key"))?.into(),
+                listeners,
+                https_listeners: https_listeners.unwrap_or_default(),
+            }))
+        }
+        e => Err(Error::InvalidConfig(format!(
+            "{} should be a hash, not {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
+
 #[test]
 fn test_dns_config() -> Result<(), Error> {
     use crate::config;
@@ -127,3 +687,304 @@ dns-routes:
     )?;
     Ok(())
 }
+
+#[test]
+fn test_forward_strategy_config() {
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "
+domain-suffixes: ['']
+dns-servers: [192.0.2.53, 2001:db8::53]
+strategy: fastest
+",
+    )
+    .unwrap();
+    match parse_dns_route("dns-routes", &y[0]).unwrap().unwrap().dest {
+        Handler::Forward(fwd) => {
+            assert_eq!(
+                fwd.servers,
+                vec![
+                    "192.0.2.53:53".parse().unwrap(),
+                    "[2001:db8::53]:53".parse().unwrap()
+                ]
+            );
+            assert_eq!(fwd.strategy, Strategy::Fastest);
+        }
+        _ => panic!("Expected a forward route"),
+    }
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], strategy: random }",
+    )
+    .unwrap();
+    assert!(parse_dns_route("dns-routes", &y[0]).is_err());
+}
+
+#[test]
+fn test_forward_transport_config() {
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "
+domain-suffixes: ['']
+dns-servers: [192.0.2.53, '[2001:db8::53]:8853']
+transport: dns-over-tls
+tls-auth-name: dns.example
+tls-spki-pins: ['AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=']
+",
+    )
+    .unwrap();
+    match parse_dns_route("dns-routes", &y[0]).unwrap().unwrap().dest {
+        Handler::Forward(fwd) => {
+            assert_eq!(
+                fwd.servers,
+                vec![
+                    "192.0.2.53:853".parse().unwrap(),
+                    "[2001:db8::53]:8853".parse().unwrap()
+                ]
+            );
+            assert_eq!(
+                fwd.transport,
+                Transport::DnsOverTls(TlsAuth {
+                    auth_name: Some("dns.example".into()),
+                    spki_pins: vec![vec![0; 32]],
+                })
+            );
+        }
+        _ => panic!("Expected a forward route"),
+    }
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "
+domain-suffixes: ['']
+dns-servers: [192.0.2.53]
+transport: dns-over-https
+doh-url: https://dns.example/dns-query
+",
+    )
+    .unwrap();
+    match parse_dns_route("dns-routes", &y[0]).unwrap().unwrap().dest {
+        Handler::Forward(fwd) => {
+            assert_eq!(fwd.servers, vec!["192.0.2.53:443".parse().unwrap()]);
+            assert_eq!(
+                fwd.transport,
+                Transport::DnsOverHttps(HttpsEndpoint {
+                    url: "https://dns.example/dns-query".parse().unwrap(),
+                    tls: TlsAuth {
+                        auth_name: Some("dns.example".into()),
+                        spki_pins: vec![],
+                    }
+                })
+            );
+        }
+        _ => panic!("Expected a forward route"),
+    }
+    /* An address in the URL is used if there are no dns-servers */
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "{ domain-suffixes: [''], transport: dns-over-https, doh-url: 'https://[2001:db8::53]:8443/dns-query' }",
+    )
+    .unwrap();
+    match parse_dns_route("dns-routes", &y[0]).unwrap().unwrap().dest {
+        Handler::Forward(fwd) => {
+            assert_eq!(fwd.servers, vec!["[2001:db8::53]:8443".parse().unwrap()])
+        }
+        _ => panic!("Expected a forward route"),
+    }
+    for bad in [
+        /* Nothing to check the certificate against */
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], transport: dns-over-tls }",
+        /* Not a SHA-256 digest */
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], transport: dns-over-tls, tls-spki-pins: [AAAA] }",
+        /* TLS options without TLS */
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], tls-auth-name: dns.example }",
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], transport: carrier-pigeon }",
+        "{ domain-suffixes: [''], type: forge-nxdomain, transport: dns }",
+        /* Nowhere to send the queries */
+        "{ domain-suffixes: [''], transport: dns-over-https }",
+        "{ domain-suffixes: [''], transport: dns-over-https, doh-url: 'https://dns.example/dns-query' }",
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], transport: dns-over-https, doh-url: 'http://dns.example/' }",
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], doh-url: 'https://dns.example/' }",
+    ]
+    .iter()
+    {
+        let y = yaml_rust::YamlLoader::load_from_str(bad).unwrap();
+        assert!(parse_dns_route("dns-routes", &y[0]).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_dnssec_config() {
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], dnssec-validate: true }",
+    )
+    .unwrap();
+    match parse_dns_route("dns-routes", &y[0]).unwrap().unwrap().dest {
+        Handler::Forward(fwd) => {
+            assert_eq!(fwd.dnssec, Some(super::dnssec::root_trust_anchors()))
+        }
+        _ => panic!("Expected a forward route"),
+    }
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "
+domain-suffixes: [example]
+dns-servers: [192.0.2.53]
+dnssec-validate: true
+dnssec-trust-anchors: ['example. 12345 13 2 0123456789abcdef0123456789ABCDEF0123456789abcdef0123456789ABCDEF']
+",
+    )
+    .unwrap();
+    match parse_dns_route("dns-routes", &y[0]).unwrap().unwrap().dest {
+        Handler::Forward(fwd) => {
+            let anchors = fwd.dnssec.unwrap();
+            assert_eq!(anchors.len(), 1);
+            assert_eq!(anchors[0].zone, "example".parse().unwrap());
+            assert_eq!(anchors[0].ds.key_tag, 12345);
+            assert_eq!(anchors[0].ds.algorithm, 13);
+            assert_eq!(anchors[0].ds.digest.len(), 32);
+        }
+        _ => panic!("Expected a forward route"),
+    }
+    for bad in [
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], dnssec-validate: yes please }",
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], dnssec-validate: true, dnssec-trust-anchors: ['. 20326 8 2'] }",
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], dnssec-validate: true, dnssec-trust-anchors: ['. 20326 8 2 XYZ'] }",
+        /* Trust anchors without validation */
+        "{ domain-suffixes: [''], dns-servers: [192.0.2.53], dnssec-trust-anchors: ['. 20326 8 2 00'] }",
+        "{ domain-suffixes: [''], type: forge-nxdomain, dnssec-validate: true }",
+    ]
+    .iter()
+    {
+        let y = yaml_rust::YamlLoader::load_from_str(bad).unwrap();
+        assert!(parse_dns_route("dns-routes", &y[0]).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_dns_cache_config() {
+    let y = yaml_rust::YamlLoader::load_from_str("{ max-entries: 500 }").unwrap();
+    assert_eq!(
+        parse_dns_cache("dns-cache", &y[0]).unwrap(),
+        Some(CacheLimits {
+            max_entries: 500,
+            ..Default::default()
+        })
+    );
+    for bad in [
+        "{ max-entries: -1 }",
+        "{ max-bytes: lots }",
+        "{ max-ttl: 60 }",
+        "[1]",
+    ]
+    .iter()
+    {
+        let y = yaml_rust::YamlLoader::load_from_str(bad).unwrap();
+        assert!(parse_dns_cache("dns-cache", &y[0]).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_dns_tls_config() {
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "
+certificate: /etc/erbium/dns.pem
+key: /etc/erbium/dns.key
+listeners: ['[::]:853']
+https-listeners: ['[::]:443', '0.0.0.0:443']
+",
+    )
+    .unwrap();
+    let tls = parse_dns_tls("dns-tls", &y[0]).unwrap().unwrap();
+    assert_eq!(tls.certificate, std::path::Path::new("/etc/erbium/dns.pem"));
+    assert_eq!(tls.key, std::path::Path::new("/etc/erbium/dns.key"));
+    assert_eq!(tls.listeners.unwrap().len(), 1);
+    assert_eq!(tls.https_listeners.len(), 2);
+
+    let y = yaml_rust::YamlLoader::load_from_str("{ certificate: /etc/erbium/dns.pem }").unwrap();
+    assert!(parse_dns_tls("dns-tls", &y[0]).is_err());
+}
+
+#[test]
+fn test_local_zone_config() {
+    use super::dnspkt::*;
+    let y = yaml_rust::YamlLoader::load_from_str(
+        "
+domain-suffixes: [Internal.Example]
+type: local-zone
+records:
+  - { name: www.internal.example, type: A, value: 192.0.2.10 }
+  - { name: www.internal.example, type: aaaa, value: '2001:db8::10', ttl: 60 }
+  - { name: internal.example, type: MX, value: 10 mail.internal.example }
+  - { name: _sip._udp.internal.example, type: SRV, value: 1 2 5060 sip.internal.example }
+  - { name: internal.example, type: TXT, value: v=spf1 -all }
+",
+    )
+    .unwrap();
+    let route = parse_dns_route("dns-routes", &y[0]).unwrap().unwrap();
+    assert_eq!(route.suffixes, vec!["internal.example".parse().unwrap()]);
+    let records = match route.dest {
+        Handler::LocalZone(records) => records,
+        _ => panic!("Expected a local-zone"),
+    };
+    assert_eq!(records.len(), 5);
+    assert_eq!(records[0].rdata, RData::A("192.0.2.10".parse().unwrap()));
+    assert_eq!(records[0].ttl, DEFAULT_RECORD_TTL);
+    assert_eq!(records[1].rrtype, RR_AAAA);
+    assert_eq!(records[1].ttl, 60);
+    assert_eq!(
+        records[2].rdata,
+        RData::Mx(PrefDomainData {
+            pref: 10,
+            domain: "mail.internal.example".parse().unwrap()
+        })
+    );
+    assert_eq!(
+        records[3].rdata,
+        RData::Srv(SrvData {
+            priority: 1,
+            weight: 2,
+            port: 5060,
+            target: "sip.internal.example".parse().unwrap()
+        })
+    );
+    assert_eq!(records[4].rdata, RData::Txt(vec![b"v=spf1 -all".to_vec()]));
+
+    for bad in [
+        /* Outside the zone */
+        "{ name: www.example.com, type: A, value: 192.0.2.1 }",
+        /* CNAME and other data */
+        "[{ name: a.internal.example, type: CNAME, value: b.internal.example },
+          { name: a.internal.example, type: A, value: 192.0.2.1 }]",
+        "{ name: a.internal.example, type: A, value: 2001:db8::1 }",
+        "{ name: a.internal.example, type: MX, value: mail.internal.example }",
+        "{ name: a.internal.example, type: HINFO, value: x }",
+    ] {
+        let records = if bad.starts_with('[') {
+            bad.to_string()
+        } else {
+            format!("[{}]", bad)
+        };
+        let y = yaml_rust::YamlLoader::load_from_str(&format!(
+            "{{ domain-suffixes: [internal.example], type: local-zone, records: {} }}",
+            records
+        ))
+        .unwrap();
+        assert!(parse_dns_route("dns-routes", &y[0]).is_err(), "{}", bad);
+    }
+}
+
+#[cfg(feature = "dhcp")]
+#[test]
+fn test_dhcp_leases_config() {
+    use crate::config;
+    config::load_config_from_string_for_test(
+        "---
+dns-routes:
+  - domain-suffixes: ['home.example', '2.0.192.in-addr.arpa']
+    type: dhcp-leases
+",
+    )
+    .expect("dhcp-leases route should be accepted");
+    assert!(config::load_config_from_string_for_test(
+        "---
+dns-routes:
+  - domain-suffixes: ['2.0.192.in-addr.arpa']
+    type: dhcp-leases
+",
+    )
+    .is_err());
+}
@@ -54,14 +54,23 @@ pub const RR_CNAME: Type = Type(5);
 pub const RR_SOA: Type = Type(6);
 pub const RR_PTR: Type = Type(12);
 pub const RR_MX: Type = Type(15);
+pub const RR_TXT: Type = Type(16);
 pub const RR_RP: Type = Type(17);
 pub const RR_AFSDB: Type = Type(18);
 pub const RR_RT: Type = Type(21);
+pub const RR_AAAA: Type = Type(28);
+pub const RR_SRV: Type = Type(33);
 pub const RR_NAPTR: Type = Type(35);
 pub const RR_OPT: Type = Type(41);
+pub const RR_DS: Type = Type(43);
+pub const RR_RRSIG: Type = Type(46);
 pub const RR_NSEC: Type = Type(47);
+pub const RR_DNSKEY: Type = Type(48);
 pub const RR_NSEC3: Type = Type(50);
+pub const RR_SVCB: Type = Type(64);
+pub const RR_HTTPS: Type = Type(65);
 pub const RR_ANY: Type = Type(255);
+pub const RR_CAA: Type = Type(257);
 
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -71,10 +80,20 @@ impl fmt::Display for Type {
             &RR_CNAME => write!(f, "CNAME"),
             &RR_SOA => write!(f, "SOA"),
             &RR_PTR => write!(f, "PTR"),
+            &RR_MX => write!(f, "MX"),
+            &RR_TXT => write!(f, "TXT"),
+            &RR_AAAA => write!(f, "AAAA"),
+            &RR_SRV => write!(f, "SRV"),
             &RR_NAPTR => write!(f, "NAPTR"),
             &RR_OPT => write!(f, "OPT"),
+            &RR_DS => write!(f, "DS"),
+            &RR_RRSIG => write!(f, "RRSIG"),
             &RR_NSEC => write!(f, "NSEC"),
+            &RR_DNSKEY => write!(f, "DNSKEY"),
             &RR_NSEC3 => write!(f, "NSEC3"),
+            &RR_SVCB => write!(f, "SVCB"),
+            &RR_HTTPS => write!(f, "HTTPS"),
+            &RR_CAA => write!(f, "CAA"),
             Type(x) => write!(f, "Type#{}", x),
         }
     }
@@ -155,6 +174,12 @@ fn display_byte(b: u8) -> String {
 #[derive(Ord, Clone, PartialEq, Eq, PartialOrd, Hash, Debug)]
 pub struct Label(Vec<u8>);
 
+impl Label {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl From<Vec<u8>> for Label {
     fn from(mut v: Vec<u8>) -> Self {
         assert!(!v.is_empty());
@@ -193,6 +218,39 @@ impl Domain {
     pub fn ends_with(&self, other: &Self) -> bool {
         self.0.ends_with(&other.0)
     }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.0
+    }
+
+    pub fn to_ascii_lowercase(&self) -> Self {
+        Domain(
+            self.0
+                .iter()
+                .map(|l| Label(l.0.to_ascii_lowercase()))
+                .collect(),
+        )
+    }
+
+    /// Returns a new domain with `label` prepended, eg "host" + "example.com".
+    pub fn with_prefix(&self, label: Label) -> Self {
+        let mut v = Vec::with_capacity(self.0.len() + 1);
+        v.push(label);
+        v.extend(self.0.iter().cloned());
+        Domain(v)
+    }
+
+    /// Returns the domain with the first `count` labels removed, eg "com" from "example.com".
+    pub fn strip_labels(&self, count: usize) -> Self {
+        Domain(self.0[count.min(self.0.len())..].to_vec())
+    }
+
+    /// The uncompressed, lowercased wire format of the domain (RFC4034 Section 6.2).
+    pub fn canonical_wire(&self) -> Vec<u8> {
+        let mut v = vec![];
+        push_domain(&mut v, &self.to_ascii_lowercase());
+        v
+    }
 }
 
 impl From<Vec<Label>> for Domain {
@@ -478,25 +536,262 @@ impl Arbitrary for NAPTRData {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[cfg_attr(fuzzing, derive(Arbitrary))]
+pub struct SrvData {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: Domain,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CaaData {
+    pub flags: u8,
+    pub tag: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+#[cfg(fuzzing)]
+impl Arbitrary for CaaData {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'_>) -> arbitrary::Result<Self> {
+        let flags = <_>::arbitrary(u)?;
+        let mut tag: Vec<u8> = <_>::arbitrary(u)?;
+        let value = <_>::arbitrary(u)?;
+        tag.truncate(255);
+        Ok(CaaData { flags, tag, value })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(fuzzing, derive(Arbitrary))]
+pub struct SvcParamKey(pub u16);
+
+pub const SVCPARAM_MANDATORY: SvcParamKey = SvcParamKey(0);
+pub const SVCPARAM_ALPN: SvcParamKey = SvcParamKey(1);
+pub const SVCPARAM_NO_DEFAULT_ALPN: SvcParamKey = SvcParamKey(2);
+pub const SVCPARAM_PORT: SvcParamKey = SvcParamKey(3);
+pub const SVCPARAM_IPV4HINT: SvcParamKey = SvcParamKey(4);
+pub const SVCPARAM_ECH: SvcParamKey = SvcParamKey(5);
+pub const SVCPARAM_IPV6HINT: SvcParamKey = SvcParamKey(6);
+
+impl fmt::Display for SvcParamKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SVCPARAM_MANDATORY => write!(f, "mandatory"),
+            SVCPARAM_ALPN => write!(f, "alpn"),
+            SVCPARAM_NO_DEFAULT_ALPN => write!(f, "no-default-alpn"),
+            SVCPARAM_PORT => write!(f, "port"),
+            SVCPARAM_IPV4HINT => write!(f, "ipv4hint"),
+            SVCPARAM_ECH => write!(f, "ech"),
+            SVCPARAM_IPV6HINT => write!(f, "ipv6hint"),
+            SvcParamKey(x) => write!(f, "key{}", x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(fuzzing, derive(Arbitrary))]
+pub struct SvcParam {
+    pub key: SvcParamKey,
+    pub value: Vec<u8>,
+}
+
+impl SvcParam {
+    /// Formats the value in presentation format, if this is a key we understand.
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>) -> Option<fmt::Result> {
+        let v = &self.value[..];
+        match self.key {
+            SVCPARAM_NO_DEFAULT_ALPN if v.is_empty() => Some(Ok(())),
+            SVCPARAM_PORT if v.len() == 2 => {
+                Some(write!(f, "={}", u16::from_be_bytes([v[0], v[1]])))
+            }
+            SVCPARAM_MANDATORY if v.chunks_exact(2).remainder().is_empty() => Some(write!(
+                f,
+                "={}",
+                v.chunks(2)
+                    .map(|k| SvcParamKey(u16::from_be_bytes([k[0], k[1]])).to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )),
+            SVCPARAM_IPV4HINT if !v.is_empty() && v.chunks_exact(4).remainder().is_empty() => {
+                Some(write!(
+                    f,
+                    "={}",
+                    v.chunks(4)
+                        .map(|a| std::net::Ipv4Addr::new(a[0], a[1], a[2], a[3]).to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ))
+            }
+            SVCPARAM_IPV6HINT if !v.is_empty() && v.chunks_exact(16).remainder().is_empty() => {
+                Some(write!(
+                    f,
+                    "={}",
+                    v.chunks(16)
+                        .map(|a| {
+                            let mut octets = [0_u8; 16];
+                            octets.copy_from_slice(a);
+                            std::net::Ipv6Addr::from(octets).to_string()
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ))
+            }
+            SVCPARAM_ALPN => {
+                let mut ids = vec![];
+                let mut rest = v;
+                while let Some((&len, tail)) = rest.split_first() {
+                    let id = tail.get(..usize::from(len))?;
+                    ids.push(id.iter().map(|&b| display_byte(b)).collect::<String>());
+                    rest = &tail[usize::from(len)..];
+                }
+                Some(write!(f, "=\"{}\"", ids.join(",")))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key)?;
+        match self.fmt_value(f) {
+            Some(r) => r,
+            None if self.value.is_empty() => Ok(()),
+            None => write!(f, "={}", display_string(&self.value)),
+        }
+    }
+}
+
+/// Used for both SVCB and HTTPS records (RFC9460).
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(fuzzing, derive(Arbitrary))]
+pub struct SvcbData {
+    pub priority: u16,
+    pub target: Domain,
+    pub params: Vec<SvcParam>,
+}
+
+impl fmt::Display for SvcbData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.priority, display_name(&self.target))?;
+        for param in &self.params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum RData {
+    A(std::net::Ipv4Addr),
+    Aaaa(std::net::Ipv6Addr),
     CName(Domain),
     Mx(PrefDomainData),
     Ns(Domain),
     Ptr(Domain),
     Soa(SoaData),
+    Txt(Vec<Vec<u8>>),
     Opt(EdnsData),
     AfsDb(AFSDBData),
     Rp(RPData),
     Rt(PrefDomainData),
     NaPtr(NAPTRData),
+    Srv(SrvData),
+    Caa(CaaData),
+    Svcb(SvcbData),
+    Https(SvcbData),
     Other(Vec<u8>),
 }
 
+#[cfg(fuzzing)]
+impl Arbitrary for RData {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'_>) -> arbitrary::Result<Self> {
+        /* std::net addresses don't implement Arbitrary, so this can't be derived. */
+        Ok(match u.int_in_range(0..=17)? {
+            0 => RData::A(<[u8; 4]>::arbitrary(u)?.into()),
+            1 => RData::Aaaa(<[u8; 16]>::arbitrary(u)?.into()),
+            2 => RData::CName(<_>::arbitrary(u)?),
+            3 => RData::Mx(<_>::arbitrary(u)?),
+            4 => RData::Ns(<_>::arbitrary(u)?),
+            5 => RData::Ptr(<_>::arbitrary(u)?),
+            6 => RData::Soa(<_>::arbitrary(u)?),
+            7 => RData::Txt(
+                Vec::<Vec<u8>>::arbitrary(u)?
+                    .into_iter()
+                    .map(|mut s| {
+                        s.truncate(255);
+                        s
+                    })
+                    .collect(),
+            ),
+            8 => RData::Opt(<_>::arbitrary(u)?),
+            9 => RData::AfsDb(<_>::arbitrary(u)?),
+            10 => RData::Rp(<_>::arbitrary(u)?),
+            11 => RData::Rt(<_>::arbitrary(u)?),
+            12 => RData::NaPtr(<_>::arbitrary(u)?),
+            13 => RData::Srv(<_>::arbitrary(u)?),
+            14 => RData::Caa(<_>::arbitrary(u)?),
+            15 => RData::Svcb(<_>::arbitrary(u)?),
+            16 => RData::Https(<_>::arbitrary(u)?),
+            _ => RData::Other(<_>::arbitrary(u)?),
+        })
+    }
+}
+
+/// Formats a <character-string> in presentation format, quoted and escaped.
+fn display_string(s: &[u8]) -> String {
+    format!(
+        "\"{}\"",
+        s.iter()
+            .map(|&b| match b {
+                b'"' | b'\\' => format!("\\{}", char::from(b)),
+                b => display_byte(b),
+            })
+            .collect::<String>()
+    )
+}
+
+/// The root domain is written as "." rather than the empty string.
+fn display_name(d: &Domain) -> String {
+    if d.0.is_empty() {
+        ".".into()
+    } else {
+        d.to_string()
+    }
+}
+
 impl std::fmt::Display for RData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use RData::*;
         match self {
+            A(ip) => write!(f, "{}", ip),
+            Aaaa(ip) => write!(f, "{}", ip),
             CName(d) | Ns(d) | Ptr(d) => write!(f, "\"{}\"", d),
+            Txt(strings) => write!(
+                f,
+                "{}",
+                strings
+                    .iter()
+                    .map(|s| display_string(s))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Srv(srv) => write!(
+                f,
+                "{} {} {} {}",
+                srv.priority,
+                srv.weight,
+                srv.port,
+                display_name(&srv.target)
+            ),
+            Caa(caa) => write!(
+                f,
+                "{} {} {}",
+                caa.flags,
+                caa.tag.iter().map(|&b| display_byte(b)).collect::<String>(),
+                display_string(&caa.value)
+            ),
+            Svcb(svcb) | Https(svcb) => write!(f, "{}", svcb),
             Mx(pd) | Rt(pd) => write!(f, "{} {}", pd.pref, pd.domain),
             AfsDb(afs) => write!(f, "{} {}", afs.subtype, afs.hostname),
             Rp(rp) => write!(f, "{} {}", rp.mbox, rp.txt),
@@ -535,6 +830,59 @@ impl std::fmt::Display for RR {
     }
 }
 
+impl RR {
+    /// The RDATA in canonical form (RFC4034 Section 6.2), as covered by DNSSEC signatures: names
+    /// are uncompressed, and lowercased for the types that RFC lists.
+    pub fn canonical_rdata(&self) -> Vec<u8> {
+        use RData::*;
+        let lower = |d: &Domain| d.to_ascii_lowercase();
+        let lower_pd = |pd: &PrefDomainData| PrefDomainData {
+            pref: pd.pref,
+            domain: lower(&pd.domain),
+        };
+        let rdata = match &self.rdata {
+            CName(d) => CName(lower(d)),
+            Ns(d) => Ns(lower(d)),
+            Ptr(d) => Ptr(lower(d)),
+            Mx(pd) => Mx(lower_pd(pd)),
+            Rt(pd) => Rt(lower_pd(pd)),
+            Soa(soa) => Soa(SoaData {
+                mname: lower(&soa.mname),
+                rname: lower(&soa.rname),
+                ..soa.clone()
+            }),
+            Srv(srv) => Srv(SrvData {
+                target: lower(&srv.target),
+                ..srv.clone()
+            }),
+            AfsDb(afs) => AfsDb(AFSDBData {
+                subtype: afs.subtype,
+                hostname: lower(&afs.hostname),
+            }),
+            Rp(rp) => Rp(RPData {
+                mbox: lower(&rp.mbox),
+                txt: lower(&rp.txt),
+            }),
+            NaPtr(na) => NaPtr(NAPTRData {
+                replacement: lower(&na.replacement),
+                ..na.clone()
+            }),
+            other => other.clone(),
+        };
+        let mut v = vec![];
+        push_rdata(
+            &mut v,
+            &RR {
+                rdata,
+                ..self.clone()
+            },
+            None,
+        );
+        /* Skip the rdlength */
+        v.split_off(2)
+    }
+}
+
 impl fmt::Debug for RR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "RR({})", self)
@@ -549,6 +897,13 @@ impl Arbitrary for RR {
         let ttl = <_>::arbitrary(u)?;
         let rdata = <_>::arbitrary(u)?;
         let rrtype = match &rdata {
+            RData::A(_) => RR_A,
+            RData::Aaaa(_) => RR_AAAA,
+            RData::Txt(_) => RR_TXT,
+            RData::Srv(_) => RR_SRV,
+            RData::Caa(_) => RR_CAA,
+            RData::Svcb(_) => RR_SVCB,
+            RData::Https(_) => RR_HTTPS,
             RData::Ns(_) => RR_NS,
             RData::CName(_) => RR_CNAME,
             RData::Soa(_) => RR_SOA,
@@ -572,6 +927,13 @@ impl Arbitrary for RR {
                     && rrtype != RR_RT
                     && rrtype != RR_NAPTR
                     && rrtype != RR_OPT
+                    && rrtype != RR_A
+                    && rrtype != RR_AAAA
+                    && rrtype != RR_TXT
+                    && rrtype != RR_SRV
+                    && rrtype != RR_CAA
+                    && rrtype != RR_SVCB
+                    && rrtype != RR_HTTPS
                 {
                     break rrtype;
                 }
@@ -840,6 +1202,14 @@ fn push_compressed_domain(
     }
 }
 
+/* Some record types (eg SRV) are not allowed to use compression */
+fn push_domain(v: &mut Vec<u8>, d: &Domain) {
+    for l in &d.0 {
+        push_label(v, l);
+    }
+    v.push(0u8);
+}
+
 fn push_str(v: &mut Vec<u8>, s: &[u8]) {
     assert!(s.len() < 256);
     v.push(s.len() as u8);
@@ -922,17 +1292,70 @@ fn push_rr(v: &mut Vec<u8>, rr: &RR, offsets: &mut DomainOffsets) {
     push_u16(v, rr.rrtype.0);
     push_u16(v, rr.class.0);
     push_u32(v, rr.ttl);
+    push_rdata(v, rr, Some(offsets));
+}
+
+/* Pushes the rdlength and rdata.  Names are only compressed if offsets are given. */
+fn push_rdata(v: &mut Vec<u8>, rr: &RR, mut offsets: Option<&mut DomainOffsets>) {
+    let mut push_name = |vs: &mut Vec<u8>, d: &Domain, base_offset: usize| match &mut offsets {
+        Some(offsets) => push_compressed_domain(vs, d, offsets, base_offset),
+        None => push_domain(vs, d),
+    };
     match &rr.rdata {
+        RData::A(ip) => {
+            push_u16(v, 4);
+            v.extend_from_slice(&ip.octets());
+        }
+        RData::Aaaa(ip) => {
+            push_u16(v, 16);
+            v.extend_from_slice(&ip.octets());
+        }
+        RData::Txt(strings) => {
+            let mut vs = vec![];
+            for s in strings {
+                push_str(&mut vs, s);
+            }
+            push_u16(v, vs.len() as u16);
+            v.extend_from_slice(vs.as_slice());
+        }
+        RData::Srv(srv) => {
+            let mut vs = vec![];
+            push_u16(&mut vs, srv.priority);
+            push_u16(&mut vs, srv.weight);
+            push_u16(&mut vs, srv.port);
+            push_domain(&mut vs, &srv.target);
+            push_u16(v, vs.len() as u16);
+            v.extend_from_slice(vs.as_slice());
+        }
+        RData::Caa(caa) => {
+            let mut vs = vec![caa.flags];
+            push_str(&mut vs, &caa.tag);
+            vs.extend_from_slice(&caa.value);
+            push_u16(v, vs.len() as u16);
+            v.extend_from_slice(vs.as_slice());
+        }
+        RData::Svcb(svcb) | RData::Https(svcb) => {
+            let mut vs = vec![];
+            push_u16(&mut vs, svcb.priority);
+            push_domain(&mut vs, &svcb.target);
+            for param in &svcb.params {
+                push_u16(&mut vs, param.key.0);
+                push_u16(&mut vs, param.value.len() as u16);
+                vs.extend_from_slice(&param.value);
+            }
+            push_u16(v, vs.len() as u16);
+            v.extend_from_slice(vs.as_slice());
+        }
         RData::CName(d) | RData::Ptr(d) | RData::Ns(d) => {
             let mut vs = vec![];
-            push_compressed_domain(&mut vs, d, offsets, v.len() + 2);
+            push_name(&mut vs, d, v.len() + 2);
             push_u16(v, vs.len() as u16);
             v.extend_from_slice(vs.as_slice());
         }
         RData::Mx(pd) | RData::Rt(pd) => {
             let mut vs = vec![];
             push_u16(&mut vs, pd.pref);
-            push_compressed_domain(&mut vs, &pd.domain, offsets, v.len() + 2);
+            push_name(&mut vs, &pd.domain, v.len() + 2);
             push_u16(v, vs.len() as u16);
             v.extend_from_slice(vs.as_slice());
         }
@@ -943,22 +1366,22 @@ fn push_rr(v: &mut Vec<u8>, rr: &RR, offsets: &mut DomainOffsets) {
             push_str(&mut vs, &na.flags);
             push_str(&mut vs, &na.services);
             push_str(&mut vs, &na.regexp);
-            push_compressed_domain(&mut vs, &na.replacement, offsets, v.len() + 2);
+            push_name(&mut vs, &na.replacement, v.len() + 2);
             push_u16(v, vs.len() as u16);
             v.extend_from_slice(vs.as_slice());
         }
         RData::Rp(rp) => {
             let mut vs = vec![];
-            push_compressed_domain(&mut vs, &rp.mbox, offsets, v.len() + 2);
-            push_compressed_domain(&mut vs, &rp.txt, offsets, v.len() + 2);
+            push_name(&mut vs, &rp.mbox, v.len() + 2);
+            push_name(&mut vs, &rp.txt, v.len() + 2);
             push_u16(v, vs.len() as u16);
             v.extend_from_slice(vs.as_slice());
         }
         RData::Soa(s) => {
             assert!(rr.rrtype == RR_SOA);
             let mut vs = vec![];
-            push_compressed_domain(&mut vs, &s.mname, offsets, v.len() + 2);
-            push_compressed_domain(&mut vs, &s.rname, offsets, v.len() + 2);
+            push_name(&mut vs, &s.mname, v.len() + 2);
+            push_name(&mut vs, &s.rname, v.len() + 2);
             push_u32(&mut vs, s.serial);
             push_u32(&mut vs, s.refresh);
             push_u32(&mut vs, s.retry);
@@ -971,7 +1394,7 @@ fn push_rr(v: &mut Vec<u8>, rr: &RR, offsets: &mut DomainOffsets) {
         RData::AfsDb(afs) => {
             let mut vs = vec![];
             push_u16(&mut vs, afs.subtype);
-            push_compressed_domain(&mut vs, &afs.hostname, offsets, v.len() + 2);
+            push_name(&mut vs, &afs.hostname, v.len() + 2);
             push_u16(v, vs.len() as u16);
             v.extend_from_slice(vs.as_slice());
         }
@@ -1328,3 +1751,102 @@ fn domain_from_str() {
         ]))
     );
 }
+
+#[test]
+fn test_typed_rdata() {
+    let rr = |rrtype, rdata| RR {
+        domain: "test.example.com".parse().unwrap(),
+        class: CLASS_IN,
+        rrtype,
+        ttl: 300,
+        rdata,
+    };
+    let records = [
+        (
+            rr(RR_A, RData::A("192.0.2.1".parse().unwrap())),
+            "192.0.2.1",
+        ),
+        (
+            rr(RR_AAAA, RData::Aaaa("2001:db8::1".parse().unwrap())),
+            "2001:db8::1",
+        ),
+        (
+            rr(
+                RR_TXT,
+                RData::Txt(vec![b"v=spf1 -all".to_vec(), b"say \"hi\"".to_vec()]),
+            ),
+            "\"v=spf1 -all\" \"say \\\"hi\\\"\"",
+        ),
+        (
+            rr(
+                RR_SRV,
+                RData::Srv(SrvData {
+                    priority: 10,
+                    weight: 60,
+                    port: 5060,
+                    target: "sip.example.com".parse().unwrap(),
+                }),
+            ),
+            "10 60 5060 sip.example.com",
+        ),
+        (
+            rr(
+                RR_CAA,
+                RData::Caa(CaaData {
+                    flags: 0,
+                    tag: b"issue".to_vec(),
+                    value: b"ca.example.net".to_vec(),
+                }),
+            ),
+            "0 issue \"ca.example.net\"",
+        ),
+        (
+            rr(
+                RR_HTTPS,
+                RData::Https(SvcbData {
+                    priority: 1,
+                    target: Domain::from(vec![]),
+                    params: vec![
+                        SvcParam {
+                            key: SVCPARAM_ALPN,
+                            value: b"\x02h2\x02h3".to_vec(),
+                        },
+                        SvcParam {
+                            key: SVCPARAM_PORT,
+                            value: vec![1, 187],
+                        },
+                        SvcParam {
+                            key: SVCPARAM_IPV4HINT,
+                            value: vec![192, 0, 2, 1, 192, 0, 2, 2],
+                        },
+                    ],
+                }),
+            ),
+            "1 . alpn=\"h2,h3\" port=443 ipv4hint=192.0.2.1,192.0.2.2",
+        ),
+        (
+            rr(
+                RR_SVCB,
+                RData::Svcb(SvcbData {
+                    priority: 0,
+                    target: "svc.example.com".parse().unwrap(),
+                    params: vec![SvcParam {
+                        key: SvcParamKey(667),
+                        value: b"hello".to_vec(),
+                    }],
+                }),
+            ),
+            "0 svc.example.com key667=\"hello\"",
+        ),
+    ];
+    let mut v = vec![];
+    let mut offsets = DomainOffsets::new();
+    for (orig, _) in &records {
+        push_rr(&mut v, orig, &mut offsets);
+    }
+    let mut p = super::parse::PktParser::new(&v);
+    for (orig, presentation) in &records {
+        assert_eq!(*orig, p.get_rr().unwrap());
+        assert_eq!(orig.rdata.to_string(), *presentation);
+    }
+}
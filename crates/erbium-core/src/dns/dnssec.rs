@@ -0,0 +1,1473 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  DNSSEC validation of replies from upstream nameservers (RFC4033, RFC4034, RFC4035, RFC5155).
+ *
+ *  Erbium forwards queries rather than resolving them itself, so instead of following referrals
+ *  down from the root, it asks the same upstream nameservers for the DNSKEY records of each zone
+ *  that signed part of a reply, then the DS records for those keys from the parent zone, and so on
+ *  until it reaches a trust anchor.  These lookups are validated and cached like any other query,
+ *  so each zone's keys are normally only fetched and checked once.
+ */
+
+use super::dnspkt;
+use super::parse::PktParser;
+use super::Error;
+use dnspkt::{Domain, Type, RR};
+
+const ALG_RSASHA1: u8 = 5;
+const ALG_RSASHA1_NSEC3_SHA1: u8 = 7;
+const ALG_RSASHA256: u8 = 8;
+const ALG_RSASHA512: u8 = 10;
+const ALG_ECDSAP256SHA256: u8 = 13;
+const ALG_ECDSAP384SHA384: u8 = 14;
+const ALG_ED25519: u8 = 15;
+
+const DIGEST_SHA1: u8 = 1;
+const DIGEST_SHA256: u8 = 2;
+const DIGEST_SHA384: u8 = 4;
+
+const DNSKEY_FLAG_ZONE: u16 = 0x0100;
+const NSEC3_HASH_SHA1: u8 = 1;
+const NSEC3_FLAG_OPT_OUT: u8 = 0x01;
+
+/* RFC9276 Section 3.2: checking NSEC3 records with more iterations than this is too expensive, so
+ * answers relying on them are treated as insecure.
+ */
+const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+/* Stop following CNAMEs after this many, in case they loop. */
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// The digest of a DNSKEY, as published in the parent zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ds {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+}
+
+impl Ds {
+    fn parse(rdata: &[u8]) -> Result<Self, String> {
+        let mut p = PktParser::new(rdata);
+        Ok(Ds {
+            key_tag: p.get_u16()?,
+            algorithm: p.get_u8()?,
+            digest_type: p.get_u8()?,
+            digest: p.get_rest(),
+        })
+    }
+}
+
+/// A DS record for a zone that is trusted without checking its parent, normally the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustAnchor {
+    pub zone: Domain,
+    pub ds: Ds,
+}
+
+/// Parses a trust anchor in DS presentation format, eg ". 20326 8 2 E06D...".
+impl std::str::FromStr for TrustAnchor {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        match fields[..] {
+            [zone, key_tag, algorithm, digest_type, ref digest @ ..] if !digest.is_empty() => {
+                let zone = match zone.trim_end_matches('.') {
+                    "" => Domain::from(vec![]),
+                    zone => zone
+                        .parse::<Domain>()
+                        .map_err(|e| format!("Invalid zone {}: {}", zone, e))?
+                        .to_ascii_lowercase(),
+                };
+                let digest = digest.concat();
+                let digest = digest
+                    .as_bytes()
+                    .chunks(2)
+                    .map(|pair| match pair {
+                        [_, _] => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| format!("Invalid digest {}", digest))?;
+                Ok(TrustAnchor {
+                    zone,
+                    ds: Ds {
+                        key_tag: key_tag
+                            .parse()
+                            .map_err(|_| format!("Invalid key tag {}", key_tag))?,
+                        algorithm: algorithm
+                            .parse()
+                            .map_err(|_| format!("Invalid algorithm {}", algorithm))?,
+                        digest_type: digest_type
+                            .parse()
+                            .map_err(|_| format!("Invalid digest type {}", digest_type))?,
+                        digest,
+                    },
+                })
+            }
+            _ => Err(format!(
+                "Expected \"zone key-tag algorithm digest-type digest\", not \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+/// The root zone's key signing keys, as published by IANA.
+pub fn root_trust_anchors() -> Vec<TrustAnchor> {
+    [
+        ". 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D",
+        ". 38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16",
+    ]
+    .iter()
+    .map(|anchor| anchor.parse().unwrap())
+    .collect()
+}
+
+fn rdata(rr: &RR) -> &[u8] {
+    match &rr.rdata {
+        dnspkt::RData::Other(rdata) => rdata,
+        _ => &[],
+    }
+}
+
+/* RFC4034 Appendix B */
+fn key_tag(rdata: &[u8]) -> u16 {
+    let mut ac: u32 = 0;
+    for (i, b) in rdata.iter().enumerate() {
+        ac += if i % 2 == 0 {
+            u32::from(*b) << 8
+        } else {
+            u32::from(*b)
+        };
+    }
+    ac += (ac >> 16) & 0xffff;
+    (ac & 0xffff) as u16
+}
+
+struct Dnskey {
+    flags: u16,
+    algorithm: u8,
+    public_key: Vec<u8>,
+    key_tag: u16,
+    rdata: Vec<u8>,
+}
+
+impl Dnskey {
+    fn parse(rdata: &[u8]) -> Result<Self, String> {
+        let mut p = PktParser::new(rdata);
+        let flags = p.get_u16()?;
+        let _protocol = p.get_u8()?;
+        let algorithm = p.get_u8()?;
+        Ok(Dnskey {
+            flags,
+            algorithm,
+            public_key: p.get_rest(),
+            key_tag: key_tag(rdata),
+            rdata: rdata.to_vec(),
+        })
+    }
+
+    fn matches(&self, zone: &Domain, ds: &Ds) -> bool {
+        ds.key_tag == self.key_tag
+            && ds.algorithm == self.algorithm
+            && digest(ds.digest_type, &[&zone.canonical_wire(), &self.rdata])
+                .map(|digest| digest == ds.digest)
+                .unwrap_or(false)
+    }
+}
+
+struct Rrsig {
+    type_covered: Type,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer: Domain,
+    signature: Vec<u8>,
+}
+
+impl Rrsig {
+    fn parse(rdata: &[u8]) -> Result<Self, String> {
+        let mut p = PktParser::new(rdata);
+        Ok(Rrsig {
+            type_covered: Type(p.get_u16()?),
+            algorithm: p.get_u8()?,
+            labels: p.get_u8()?,
+            original_ttl: p.get_u32()?,
+            expiration: p.get_u32()?,
+            inception: p.get_u32()?,
+            key_tag: p.get_u16()?,
+            signer: p.get_domain()?.to_ascii_lowercase(),
+            signature: p.get_rest(),
+        })
+    }
+
+    /* The signature covers all of the RRSIG's own RDATA except the signature. */
+    fn signed_fields(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.extend(self.type_covered.0.to_be_bytes().iter());
+        v.push(self.algorithm);
+        v.push(self.labels);
+        v.extend(self.original_ttl.to_be_bytes().iter());
+        v.extend(self.expiration.to_be_bytes().iter());
+        v.extend(self.inception.to_be_bytes().iter());
+        v.extend(self.key_tag.to_be_bytes().iter());
+        v.extend(self.signer.canonical_wire());
+        v
+    }
+}
+
+struct Nsec {
+    owner: Domain,
+    next: Domain,
+    types: Vec<u8>,
+}
+
+impl Nsec {
+    fn parse(owner: &Domain, rdata: &[u8]) -> Result<Self, String> {
+        let mut p = PktParser::new(rdata);
+        Ok(Nsec {
+            owner: owner.to_ascii_lowercase(),
+            next: p.get_domain()?.to_ascii_lowercase(),
+            types: p.get_rest(),
+        })
+    }
+
+    fn covers(&self, name: &Domain) -> bool {
+        covers(
+            &canonical_key(&self.owner),
+            &canonical_key(&self.next),
+            &canonical_key(name),
+        )
+    }
+}
+
+struct Nsec3 {
+    zone: Domain,
+    owner_hash: Vec<u8>,
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+    next_hash: Vec<u8>,
+    types: Vec<u8>,
+}
+
+impl Nsec3 {
+    fn parse(owner: &Domain, rdata: &[u8]) -> Result<Self, String> {
+        let owner_hash = owner
+            .labels()
+            .first()
+            .and_then(|label| base32hex_decode(label.as_bytes()))
+            .ok_or_else(|| format!("Invalid NSEC3 owner name {}", owner))?;
+        let mut p = PktParser::new(rdata);
+        let hash_algorithm = p.get_u8()?;
+        let flags = p.get_u8()?;
+        let iterations = p.get_u16()?;
+        let salt_len = p.get_u8()?;
+        let salt = p.get_bytes(usize::from(salt_len))?;
+        let hash_len = p.get_u8()?;
+        let next_hash = p.get_bytes(usize::from(hash_len))?;
+        Ok(Nsec3 {
+            zone: owner.strip_labels(1).to_ascii_lowercase(),
+            owner_hash,
+            hash_algorithm,
+            flags,
+            iterations,
+            salt,
+            next_hash,
+            types: p.get_rest(),
+        })
+    }
+
+    fn hash(&self, name: &Domain) -> Vec<u8> {
+        nsec3_hash(name, &self.salt, self.iterations)
+    }
+}
+
+/* RFC5155 Section 5 */
+fn nsec3_hash(name: &Domain, salt: &[u8], iterations: u16) -> Vec<u8> {
+    use ring::digest;
+    let mut hash = name.canonical_wire();
+    for _ in 0..=iterations {
+        let mut ctx = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+        ctx.update(&hash);
+        ctx.update(salt);
+        hash = ctx.finish().as_ref().to_vec();
+    }
+    hash
+}
+
+/* NSEC3 owner names are the hash in base32 with the extended hex alphabet (RFC4648 Section 7) */
+fn base32hex_decode(s: &[u8]) -> Option<Vec<u8>> {
+    let mut out = vec![];
+    let mut bits: u32 = 0;
+    let mut nbits = 0;
+    for c in s {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'0'..=b'9' => c - b'0',
+            c @ b'A'..=b'V' => c - b'A' + 10,
+            _ => return None,
+        };
+        bits = (bits << 5) | u32::from(value);
+        nbits += 5;
+        if nbits >= 8 {
+            nbits -= 8;
+            out.push((bits >> nbits) as u8);
+            bits &= (1 << nbits) - 1;
+        }
+    }
+    Some(out)
+}
+
+/* Checks the type bitmap of an NSEC or NSEC3 record (RFC4034 Section 4.1.2) */
+fn has_type(bitmap: &[u8], rrtype: Type) -> bool {
+    let window = (rrtype.0 >> 8) as u8;
+    let bit = usize::from(rrtype.0 & 0xff);
+    let mut rest = bitmap;
+    while let [block, len, tail @ ..] = rest {
+        let len = usize::from(*len);
+        if tail.len() < len {
+            return false;
+        }
+        if *block == window {
+            return tail[..len]
+                .get(bit / 8)
+                .map(|b| b & (0x80 >> (bit % 8)) != 0)
+                .unwrap_or(false);
+        }
+        rest = &tail[len..];
+    }
+    false
+}
+
+/* Names sort by their labels from the right, ignoring case (RFC4034 Section 6.1) */
+fn canonical_key(name: &Domain) -> Vec<Vec<u8>> {
+    name.labels()
+        .iter()
+        .rev()
+        .map(|label| label.as_bytes().to_ascii_lowercase())
+        .collect()
+}
+
+/* Whether an NSEC(3) record from owner to next covers name.  The last record in the zone wraps
+ * around to the first.
+ */
+fn covers<T: Ord>(owner: &T, next: &T, name: &T) -> bool {
+    if owner < next {
+        owner < name && name < next
+    } else {
+        name > owner || name < next
+    }
+}
+
+fn digest(digest_type: u8, data: &[&[u8]]) -> Option<Vec<u8>> {
+    use ring::digest;
+    let algorithm = match digest_type {
+        DIGEST_SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        DIGEST_SHA256 => &digest::SHA256,
+        DIGEST_SHA384 => &digest::SHA384,
+        _ => return None,
+    };
+    let mut ctx = digest::Context::new(algorithm);
+    data.iter().for_each(|d| ctx.update(d));
+    Some(ctx.finish().as_ref().to_vec())
+}
+
+fn supported_algorithm(algorithm: u8) -> bool {
+    matches!(
+        algorithm,
+        ALG_RSASHA1
+            | ALG_RSASHA1_NSEC3_SHA1
+            | ALG_RSASHA256
+            | ALG_RSASHA512
+            | ALG_ECDSAP256SHA256
+            | ALG_ECDSAP384SHA384
+            | ALG_ED25519
+    )
+}
+
+fn supported_digest(digest_type: u8) -> bool {
+    matches!(digest_type, DIGEST_SHA1 | DIGEST_SHA256 | DIGEST_SHA384)
+}
+
+fn verify_signature(key: &Dnskey, data: &[u8], signature: &[u8]) -> bool {
+    use ring::signature;
+    match key.algorithm {
+        ALG_RSASHA1 | ALG_RSASHA1_NSEC3_SHA1 | ALG_RSASHA256 | ALG_RSASHA512 => {
+            let params = match key.algorithm {
+                ALG_RSASHA256 => &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                ALG_RSASHA512 => &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY,
+                _ => &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY,
+            };
+            /* RFC3110 Section 2: the exponent length, the exponent, then the modulus */
+            let (exponent_len, rest) = match &key.public_key[..] {
+                [0, hi, lo, rest @ ..] => (usize::from(u16::from_be_bytes([*hi, *lo])), rest),
+                [len, rest @ ..] => (usize::from(*len), rest),
+                [] => return false,
+            };
+            if exponent_len > rest.len() {
+                return false;
+            }
+            let (e, n) = rest.split_at(exponent_len);
+            signature::RsaPublicKeyComponents { n, e }
+                .verify(params, data, signature)
+                .is_ok()
+        }
+        ALG_ECDSAP256SHA256 | ALG_ECDSAP384SHA384 => {
+            let algorithm = if key.algorithm == ALG_ECDSAP256SHA256 {
+                &signature::ECDSA_P256_SHA256_FIXED
+            } else {
+                &signature::ECDSA_P384_SHA384_FIXED
+            };
+            /* DNSSEC leaves out the leading byte that marks the point as uncompressed */
+            let mut point = vec![4];
+            point.extend(&key.public_key);
+            signature::UnparsedPublicKey::new(algorithm, point)
+                .verify(data, signature)
+                .is_ok()
+        }
+        ALG_ED25519 => signature::UnparsedPublicKey::new(&signature::ED25519, &key.public_key)
+            .verify(data, signature)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/* Builds the data an RRSIG signs (RFC4034 Section 3.1.8.1), and whether the RRset was expanded from
+ * a wildcard.
+ */
+fn signed_data(rrset: &[&RR], sig: &Rrsig) -> Option<(Vec<u8>, bool)> {
+    let owner = rrset[0].domain.to_ascii_lowercase();
+    let mut labels = owner.labels().len();
+    if owner.labels().first().map(|l| l.as_bytes()) == Some(b"*") {
+        labels -= 1;
+    }
+    let sig_labels = usize::from(sig.labels);
+    if sig_labels > labels {
+        return None;
+    }
+    let wildcard = sig_labels < labels;
+    let name = if wildcard {
+        owner
+            .strip_labels(owner.labels().len() - sig_labels)
+            .with_prefix(dnspkt::Label::from(b"*".to_vec()))
+    } else {
+        owner
+    };
+    let name = name.canonical_wire();
+    let mut rdatas = rrset
+        .iter()
+        .map(|rr| rr.canonical_rdata())
+        .collect::<Vec<_>>();
+    rdatas.sort();
+    rdatas.dedup();
+
+    let mut data = sig.signed_fields();
+    for rdata in rdatas {
+        data.extend(&name);
+        data.extend(rrset[0].rrtype.0.to_be_bytes().iter());
+        data.extend(rrset[0].class.0.to_be_bytes().iter());
+        data.extend(sig.original_ttl.to_be_bytes().iter());
+        data.extend((rdata.len() as u16).to_be_bytes().iter());
+        data.extend(rdata);
+    }
+    Some((data, wildcard))
+}
+
+struct RRset<'a> {
+    records: Vec<&'a RR>,
+    sigs: Vec<Rrsig>,
+}
+
+impl RRset<'_> {
+    fn owner(&self) -> Domain {
+        self.records[0].domain.to_ascii_lowercase()
+    }
+
+    fn rrtype(&self) -> Type {
+        self.records[0].rrtype
+    }
+}
+
+/* Groups the records in a section into RRsets, each with the RRSIGs that cover it. */
+fn rrsets(section: &[RR]) -> Vec<RRset<'_>> {
+    let mut rrsets: Vec<RRset> = vec![];
+    for rr in section.iter().filter(|rr| rr.rrtype != dnspkt::RR_RRSIG) {
+        let owner = rr.domain.to_ascii_lowercase();
+        match rrsets
+            .iter_mut()
+            .find(|set| set.rrtype() == rr.rrtype && set.owner() == owner)
+        {
+            Some(set) => set.records.push(rr),
+            None => rrsets.push(RRset {
+                records: vec![rr],
+                sigs: vec![],
+            }),
+        }
+    }
+    for rr in section.iter().filter(|rr| rr.rrtype == dnspkt::RR_RRSIG) {
+        let owner = rr.domain.to_ascii_lowercase();
+        if let Ok(sig) = Rrsig::parse(rdata(rr)) {
+            if let Some(set) = rrsets
+                .iter_mut()
+                .find(|set| set.rrtype() == sig.type_covered && set.owner() == owner)
+            {
+                set.sigs.push(sig);
+            }
+        }
+    }
+    rrsets
+}
+
+enum Denial {
+    Proven,
+    /* The records that would prove it are opted out, or too expensive to check. */
+    Insecure,
+    Unproven,
+}
+
+/* Looks for NSEC or NSEC3 records proving that there are no records of qtype at name, or no name
+ * at all if nxdomain is set (RFC4035 Section 5.4, RFC5155 Section 8).
+ */
+fn prove_denial(authority: &[RR], name: &Domain, qtype: Type, nxdomain: bool) -> Denial {
+    let nsecs = authority
+        .iter()
+        .filter(|rr| rr.rrtype == dnspkt::RR_NSEC)
+        .filter_map(|rr| Nsec::parse(&rr.domain, rdata(rr)).ok())
+        .collect::<Vec<_>>();
+    if !nsecs.is_empty() {
+        if !nxdomain {
+            return match nsecs.iter().find(|nsec| nsec.owner == *name) {
+                Some(nsec)
+                    if !has_type(&nsec.types, qtype)
+                        && !has_type(&nsec.types, dnspkt::RR_CNAME) =>
+                {
+                    Denial::Proven
+                }
+                _ => Denial::Unproven,
+            };
+        }
+        /* The name doesn't exist, and nor does a wildcard that could have matched it. */
+        let common = |other: &Domain| {
+            name.labels()
+                .iter()
+                .rev()
+                .zip(other.labels().iter().rev())
+                .take_while(|(a, b)| a.as_bytes().eq_ignore_ascii_case(b.as_bytes()))
+                .count()
+        };
+        for nsec in nsecs.iter().filter(|nsec| nsec.covers(name)) {
+            let encloser = std::cmp::max(common(&nsec.owner), common(&nsec.next));
+            let wildcard = name
+                .strip_labels(name.labels().len() - encloser)
+                .with_prefix(dnspkt::Label::from(b"*".to_vec()));
+            if nsecs.iter().any(|nsec| nsec.covers(&wildcard)) {
+                return Denial::Proven;
+            }
+        }
+        return Denial::Unproven;
+    }
+
+    let nsec3s = authority
+        .iter()
+        .filter(|rr| rr.rrtype == dnspkt::RR_NSEC3)
+        .filter_map(|rr| Nsec3::parse(&rr.domain, rdata(rr)).ok())
+        .filter(|nsec3| nsec3.hash_algorithm == NSEC3_HASH_SHA1 && name.ends_with(&nsec3.zone))
+        .collect::<Vec<_>>();
+    if nsec3s.is_empty() {
+        return Denial::Unproven;
+    }
+    if nsec3s
+        .iter()
+        .any(|nsec3| nsec3.iterations > MAX_NSEC3_ITERATIONS)
+    {
+        return Denial::Insecure;
+    }
+    let matching = |name: &Domain| {
+        nsec3s
+            .iter()
+            .find(|nsec3| nsec3.owner_hash == nsec3.hash(name))
+    };
+    let covering = |name: &Domain| {
+        nsec3s
+            .iter()
+            .find(|nsec3| covers(&nsec3.owner_hash, &nsec3.next_hash, &nsec3.hash(name)))
+    };
+
+    if !nxdomain {
+        if let Some(nsec3) = matching(name) {
+            return if !has_type(&nsec3.types, qtype) && !has_type(&nsec3.types, dnspkt::RR_CNAME) {
+                Denial::Proven
+            } else {
+                Denial::Unproven
+            };
+        }
+        /* Only DS queries can be answered by an opted out NSEC3 */
+        if qtype != dnspkt::RR_DS {
+            return Denial::Unproven;
+        }
+    }
+
+    /* The closest encloser proof (RFC5155 Section 8.3) */
+    for encloser_len in (0..name.labels().len()).rev() {
+        let encloser = name.strip_labels(name.labels().len() - encloser_len);
+        if matching(&encloser).is_none() {
+            continue;
+        }
+        let next_closer = name.strip_labels(name.labels().len() - encloser_len - 1);
+        return match covering(&next_closer) {
+            Some(nsec3) if nsec3.flags & NSEC3_FLAG_OPT_OUT != 0 => Denial::Insecure,
+            Some(_) if !nxdomain => Denial::Unproven,
+            Some(_) => {
+                let wildcard = encloser.with_prefix(dnspkt::Label::from(b"*".to_vec()));
+                if covering(&wildcard).is_some() {
+                    Denial::Proven
+                } else {
+                    Denial::Unproven
+                }
+            }
+            None => Denial::Unproven,
+        };
+    }
+    Denial::Unproven
+}
+
+/* A wildcard answer is only valid if the name that was asked for doesn't exist itself. */
+fn prove_wildcard(authority: &[RR], name: &Domain, labels: usize) -> bool {
+    let next_closer = name.strip_labels(name.labels().len().saturating_sub(labels + 1));
+    authority.iter().any(|rr| match rr.rrtype {
+        dnspkt::RR_NSEC => Nsec::parse(&rr.domain, rdata(rr))
+            .map(|nsec| nsec.covers(name))
+            .unwrap_or(false),
+        dnspkt::RR_NSEC3 => Nsec3::parse(&rr.domain, rdata(rr))
+            .map(|nsec3| {
+                nsec3.iterations <= MAX_NSEC3_ITERATIONS
+                    && covers(
+                        &nsec3.owner_hash,
+                        &nsec3.next_hash,
+                        &nsec3.hash(&next_closer),
+                    )
+            })
+            .unwrap_or(false),
+        _ => false,
+    })
+}
+
+/* Whether the records in a (validated) reply show that there is a delegation to name, without a DS
+ * record, which means everything below name is unsigned.
+ */
+fn insecure_delegation(authority: &[RR], name: &Domain) -> bool {
+    let delegation = |types: &[u8]| {
+        has_type(types, dnspkt::RR_NS)
+            && !has_type(types, dnspkt::RR_DS)
+            && !has_type(types, dnspkt::RR_SOA)
+    };
+    for rr in authority {
+        match rr.rrtype {
+            dnspkt::RR_NSEC => {
+                if let Ok(nsec) = Nsec::parse(&rr.domain, rdata(rr)) {
+                    if nsec.owner == *name {
+                        return delegation(&nsec.types);
+                    }
+                }
+            }
+            dnspkt::RR_NSEC3 => {
+                if let Ok(nsec3) = Nsec3::parse(&rr.domain, rdata(rr)) {
+                    let hash = nsec3.hash(name);
+                    if nsec3.owner_hash == hash {
+                        return delegation(&nsec3.types);
+                    }
+                    /* Unsigned delegations may be left out of an opt out span */
+                    if nsec3.flags & NSEC3_FLAG_OPT_OUT != 0
+                        && covers(&nsec3.owner_hash, &nsec3.next_hash, &hash)
+                    {
+                        return true;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    Secure,
+    Insecure,
+    Bogus(dnspkt::EdeCode, String),
+}
+
+impl Status {
+    /* A reply is only as secure as the least secure part of it. */
+    fn and(self, other: Status) -> Status {
+        match (self, other) {
+            (bogus @ Status::Bogus(..), _) | (_, bogus @ Status::Bogus(..)) => bogus,
+            (Status::Insecure, _) | (_, Status::Insecure) => Status::Insecure,
+            (Status::Secure, Status::Secure) => Status::Secure,
+        }
+    }
+}
+
+/// How the validator fetches the DNSKEY and DS records it needs.  The replies must already have
+/// been validated themselves: secure replies have the AD bit set, and bogus ones are
+/// Error::Dnssec.
+#[async_trait::async_trait]
+pub trait Lookup: Sync {
+    async fn lookup(&self, qname: &Domain, qtype: Type) -> Result<dnspkt::DNSPkt, Error>;
+}
+
+struct Validator<'a> {
+    lookup: &'a dyn Lookup,
+    anchors: &'a [TrustAnchor],
+    now: u32,
+}
+
+impl Validator<'_> {
+    async fn fetch(&self, qname: &Domain, qtype: Type) -> Result<dnspkt::DNSPkt, Status> {
+        match self.lookup.lookup(qname, qtype).await {
+            Ok(reply) => Ok(reply),
+            Err(Error::Dnssec(code, why)) => Err(Status::Bogus(code, why)),
+            Err(err) => Err(Status::Bogus(
+                dnspkt::EDE_DNSSEC_INDETERMINATE,
+                format!("Failed to look up {} {}: {}", qname, qtype, err),
+            )),
+        }
+    }
+
+    /* The length of the closest trust anchor above name, if there is one. */
+    fn anchor_labels(&self, name: &Domain) -> Option<usize> {
+        self.anchors
+            .iter()
+            .filter(|anchor| name.ends_with(&anchor.zone))
+            .map(|anchor| anchor.zone.labels().len())
+            .max()
+    }
+
+    /* The DS records vouching for a zone's keys, or why there aren't any. */
+    async fn zone_ds(&self, zone: &Domain) -> Result<Vec<Ds>, Status> {
+        let anchored = self
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.zone == *zone)
+            .map(|anchor| anchor.ds.clone())
+            .collect::<Vec<_>>();
+        if !anchored.is_empty() {
+            return Ok(anchored);
+        }
+        if self.anchor_labels(zone).is_none() {
+            return Err(Status::Insecure);
+        }
+        let reply = self.fetch(zone, dnspkt::RR_DS).await?;
+        if !reply.ad {
+            return Err(Status::Insecure);
+        }
+        Ok(reply
+            .answer
+            .iter()
+            .filter(|rr| rr.rrtype == dnspkt::RR_DS && rr.domain.to_ascii_lowercase() == *zone)
+            .filter_map(|rr| Ds::parse(rdata(rr)).ok())
+            .collect())
+    }
+
+    /* The trusted keys for a zone, or why there aren't any. */
+    async fn zone_keys(&self, zone: &Domain) -> Result<Vec<Dnskey>, Status> {
+        let reply = self.fetch(zone, dnspkt::RR_DNSKEY).await?;
+        if !reply.ad {
+            return Err(Status::Insecure);
+        }
+        Ok(reply
+            .answer
+            .iter()
+            .filter(|rr| rr.rrtype == dnspkt::RR_DNSKEY && rr.domain.to_ascii_lowercase() == *zone)
+            .filter_map(|rr| Dnskey::parse(rdata(rr)).ok())
+            .collect())
+    }
+
+    /* A zone's DNSKEYs are signed by one of themselves, so they can be trusted if a DS record
+     * vouches for the key that signed them.
+     */
+    async fn apex_keys(&self, zone: &Domain, rrset: &RRset<'_>) -> Result<Vec<Dnskey>, Status> {
+        let ds = self
+            .zone_ds(zone)
+            .await?
+            .into_iter()
+            .filter(|ds| supported_algorithm(ds.algorithm) && supported_digest(ds.digest_type))
+            .collect::<Vec<_>>();
+        /* RFC4035 Section 5.2: Zones we can't check are treated as unsigned */
+        if ds.is_empty() {
+            return Err(Status::Insecure);
+        }
+        let keys = rrset
+            .records
+            .iter()
+            .filter_map(|rr| Dnskey::parse(rdata(rr)).ok())
+            .filter(|key| ds.iter().any(|ds| key.matches(zone, ds)))
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            Err(Status::Bogus(
+                dnspkt::EDE_DNSKEY_MISSING,
+                format!("No DNSKEY for {} matches its DS records", zone),
+            ))
+        } else if keys.iter().all(|key| key.flags & DNSKEY_FLAG_ZONE == 0) {
+            Err(Status::Bogus(
+                dnspkt::EDE_NO_ZONE_KEY_BIT_SET,
+                format!("The DNSKEYs for {} are not zone keys", zone),
+            ))
+        } else {
+            Ok(keys)
+        }
+    }
+
+    /* Unsigned records are fine, as long as there is a delegation without a DS record above them.
+     * This looks for one by asking for the DS record at each name down from the trust anchor.
+     */
+    async fn unsigned(&self, owner: &Domain, rrtype: Type) -> Status {
+        /* DS records are in the parent zone */
+        let name = if rrtype == dnspkt::RR_DS {
+            owner.strip_labels(1)
+        } else {
+            owner.clone()
+        };
+        let anchor_labels = match self.anchor_labels(&name) {
+            Some(labels) => labels,
+            None => return Status::Insecure,
+        };
+        for labels in (anchor_labels + 1)..=name.labels().len() {
+            let zone = name.strip_labels(name.labels().len() - labels);
+            let reply = match self.fetch(&zone, dnspkt::RR_DS).await {
+                Ok(reply) => reply,
+                Err(status) => return status,
+            };
+            if !reply.ad {
+                return Status::Insecure;
+            }
+            if reply.answer.iter().any(|rr| rr.rrtype == dnspkt::RR_DS) {
+                continue;
+            }
+            if insecure_delegation(&reply.nameserver, &zone) {
+                return Status::Insecure;
+            }
+        }
+        Status::Bogus(
+            dnspkt::EDE_RRSIG_MISSING,
+            format!(
+                "{} {} is in a signed zone, but is not signed",
+                owner, rrtype
+            ),
+        )
+    }
+
+    /* Checks one of the RRset's signatures is valid, and made by one of the keys, returning
+     * whether the RRset was expanded from a wildcard.
+     */
+    fn verify(&self, rrset: &RRset<'_>, sigs: &[&Rrsig], keys: &[Dnskey]) -> Result<bool, Status> {
+        let mut failure = Status::Bogus(
+            dnspkt::EDE_DNSKEY_MISSING,
+            format!(
+                "No DNSKEY matches the signatures for {} {}",
+                rrset.owner(),
+                rrset.rrtype()
+            ),
+        );
+        for sig in sigs {
+            /* These use serial number arithmetic (RFC1982), as they wrap in 2106. */
+            if (self.now.wrapping_sub(sig.inception) as i32) < 0 {
+                failure = Status::Bogus(
+                    dnspkt::EDE_SIGNATURE_NOT_YET_VALID,
+                    format!(
+                        "Signature for {} {} is not yet valid",
+                        rrset.owner(),
+                        rrset.rrtype()
+                    ),
+                );
+                continue;
+            }
+            if (sig.expiration.wrapping_sub(self.now) as i32) < 0 {
+                failure = Status::Bogus(
+                    dnspkt::EDE_SIGNATURE_EXPIRED,
+                    format!(
+                        "Signature for {} {} has expired",
+                        rrset.owner(),
+                        rrset.rrtype()
+                    ),
+                );
+                continue;
+            }
+            let (data, wildcard) = match signed_data(&rrset.records, sig) {
+                Some(signed) => signed,
+                None => continue,
+            };
+            for key in keys.iter().filter(|key| {
+                key.key_tag == sig.key_tag
+                    && key.algorithm == sig.algorithm
+                    && key.flags & DNSKEY_FLAG_ZONE != 0
+            }) {
+                if verify_signature(key, &data, &sig.signature) {
+                    return Ok(wildcard);
+                }
+                failure = Status::Bogus(
+                    dnspkt::EDE_DNSSEC_BOGUS,
+                    format!("Invalid signature for {} {}", rrset.owner(), rrset.rrtype()),
+                );
+            }
+        }
+        Err(failure)
+    }
+
+    /* Validates an RRset, returning whether it was expanded from a wildcard if it's secure. */
+    async fn validate_rrset(&self, rrset: &RRset<'_>) -> Result<bool, Status> {
+        let owner = rrset.owner();
+        let signer = match rrset.sigs.first() {
+            Some(sig) => sig.signer.clone(),
+            None => return Err(self.unsigned(&owner, rrset.rrtype()).await),
+        };
+        if !owner.ends_with(&signer) {
+            return Err(Status::Bogus(
+                dnspkt::EDE_DNSSEC_BOGUS,
+                format!("{} is signed by {}, which is not a parent", owner, signer),
+            ));
+        }
+        let sigs = rrset
+            .sigs
+            .iter()
+            .filter(|sig| sig.signer == signer)
+            .collect::<Vec<_>>();
+        let keys = if rrset.rrtype() == dnspkt::RR_DNSKEY && owner == signer {
+            self.apex_keys(&signer, rrset).await?
+        } else {
+            self.zone_keys(&signer).await?
+        };
+        self.verify(rrset, &sigs, &keys)
+    }
+
+    async fn validate(&self, reply: &dnspkt::DNSPkt) -> Status {
+        let question = &reply.question;
+        let mut status = Status::Secure;
+        let mut wildcards = vec![];
+        for rrset in rrsets(&reply.answer) {
+            match self.validate_rrset(&rrset).await {
+                Ok(true) => wildcards.push((rrset.owner(), usize::from(rrset.sigs[0].labels))),
+                Ok(false) => (),
+                Err(err) => status = status.and(err),
+            }
+        }
+        /* Only the records that prove a negative answer matter in the authority section.  For
+         * instance, the NS records in referrals are never signed.
+         */
+        let mut authority = None;
+        for rrset in rrsets(&reply.nameserver) {
+            if !matches!(
+                rrset.rrtype(),
+                dnspkt::RR_SOA | dnspkt::RR_NSEC | dnspkt::RR_NSEC3
+            ) {
+                continue;
+            }
+            let rrset_status = match self.validate_rrset(&rrset).await {
+                Ok(_) => Status::Secure,
+                Err(err) => err,
+            };
+            authority = Some(authority.unwrap_or(Status::Secure).and(rrset_status));
+        }
+        if let Some(bogus @ Status::Bogus(..)) = authority {
+            return bogus;
+        }
+
+        /* Find which name the answer is about, following any CNAMEs */
+        let mut sname = question.qdomain.to_ascii_lowercase();
+        if question.qtype != dnspkt::RR_CNAME {
+            for _ in 0..MAX_CNAME_CHAIN {
+                match reply.answer.iter().find(|rr| {
+                    rr.rrtype == dnspkt::RR_CNAME && rr.domain.to_ascii_lowercase() == sname
+                }) {
+                    Some(RR {
+                        rdata: dnspkt::RData::CName(target),
+                        ..
+                    }) => sname = target.to_ascii_lowercase(),
+                    _ => break,
+                }
+            }
+        }
+        let answered = reply
+            .answer
+            .iter()
+            .any(|rr| rr.rrtype == question.qtype && rr.domain.to_ascii_lowercase() == sname);
+
+        if answered && reply.rcode != dnspkt::NXDOMAIN {
+            if status == Status::Secure {
+                for (owner, labels) in wildcards {
+                    if !prove_wildcard(&reply.nameserver, &owner, labels) {
+                        return Status::Bogus(
+                            dnspkt::EDE_NSEC_MISSING,
+                            format!("No proof that {} does not exist, for a wildcard", owner),
+                        );
+                    }
+                }
+            }
+            return status;
+        }
+
+        let nxdomain = reply.rcode == dnspkt::NXDOMAIN;
+        match authority {
+            /* Without an SOA or NSEC records, we can't tell which zone this came from. */
+            None => status.and(self.unsigned(&sname, question.qtype).await),
+            Some(Status::Secure) => {
+                match prove_denial(&reply.nameserver, &sname, question.qtype, nxdomain) {
+                    Denial::Proven => status,
+                    Denial::Insecure => status.and(Status::Insecure),
+                    Denial::Unproven => Status::Bogus(
+                        dnspkt::EDE_NSEC_MISSING,
+                        format!("No proof that {} {} does not exist", sname, question.qtype),
+                    ),
+                }
+            }
+            Some(authority) => status.and(authority),
+        }
+    }
+}
+
+/// Validates a reply, returning it with the AD bit set if it is secure, or Error::Dnssec if it is
+/// bogus.  now is the current time in seconds since the UNIX epoch.
+pub async fn validate(
+    lookup: &dyn Lookup,
+    anchors: &[TrustAnchor],
+    mut reply: dnspkt::DNSPkt,
+    now: u32,
+) -> Result<dnspkt::DNSPkt, Error> {
+    let validator = Validator {
+        lookup,
+        anchors,
+        now,
+    };
+    let status = if reply.rcode == dnspkt::NOERROR || reply.rcode == dnspkt::NXDOMAIN {
+        validator.validate(&reply).await
+    } else {
+        Status::Insecure
+    };
+    match status {
+        Status::Bogus(code, why) => Err(Error::Dnssec(code, why)),
+        status => {
+            reply.ad = status == Status::Secure;
+            Ok(reply)
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_rr(domain: &Domain, rrtype: Type, rdata: dnspkt::RData) -> RR {
+    RR {
+        domain: domain.clone(),
+        class: dnspkt::CLASS_IN,
+        rrtype,
+        ttl: 300,
+        rdata,
+    }
+}
+
+#[cfg(test)]
+fn test_a(domain: &str, addr: &str) -> RR {
+    test_rr(
+        &domain.parse().unwrap(),
+        dnspkt::RR_A,
+        dnspkt::RData::A(addr.parse().unwrap()),
+    )
+}
+
+#[cfg(test)]
+fn test_nsec(owner: &str, next: &str, types: &[Type]) -> RR {
+    let mut bitmap = [0u8; 32];
+    for rrtype in types {
+        bitmap[usize::from(rrtype.0 / 8)] |= 0x80 >> (rrtype.0 % 8);
+    }
+    let len = bitmap.iter().rposition(|b| *b != 0).unwrap() + 1;
+    let mut rdata = next.parse::<Domain>().unwrap().canonical_wire();
+    rdata.extend(&[0, len as u8]);
+    rdata.extend(&bitmap[..len]);
+    test_rr(
+        &owner.parse().unwrap(),
+        dnspkt::RR_NSEC,
+        dnspkt::RData::Other(rdata),
+    )
+}
+
+#[cfg(test)]
+struct TestKey {
+    zone: Domain,
+    pair: ring::signature::Ed25519KeyPair,
+    dnskey: RR,
+}
+
+#[cfg(test)]
+impl TestKey {
+    fn new(zone: Domain) -> Self {
+        use ring::signature::KeyPair as _;
+        let pkcs8 =
+            ring::signature::Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+                .unwrap();
+        let pair = ring::signature::Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        /* A zone key, and a secure entry point */
+        let mut rdata = vec![0x01, 0x01, 3, ALG_ED25519];
+        rdata.extend(pair.public_key().as_ref());
+        TestKey {
+            dnskey: test_rr(&zone, dnspkt::RR_DNSKEY, dnspkt::RData::Other(rdata)),
+            zone,
+            pair,
+        }
+    }
+
+    fn ds(&self) -> Ds {
+        let key = Dnskey::parse(rdata(&self.dnskey)).unwrap();
+        Ds {
+            key_tag: key.key_tag,
+            algorithm: key.algorithm,
+            digest_type: DIGEST_SHA256,
+            digest: digest(DIGEST_SHA256, &[&self.zone.canonical_wire(), &key.rdata]).unwrap(),
+        }
+    }
+
+    fn ds_rr(&self) -> RR {
+        let ds = self.ds();
+        let mut rdata = ds.key_tag.to_be_bytes().to_vec();
+        rdata.extend(&[ds.algorithm, ds.digest_type]);
+        rdata.extend(ds.digest);
+        test_rr(&self.zone, dnspkt::RR_DS, dnspkt::RData::Other(rdata))
+    }
+
+    /* Returns the RRset followed by its signature */
+    fn sign(&self, rrset: Vec<RR>, now: u32) -> Vec<RR> {
+        let owner = rrset[0].domain.clone();
+        let sig = Rrsig {
+            type_covered: rrset[0].rrtype,
+            algorithm: ALG_ED25519,
+            labels: owner.labels().len() as u8,
+            original_ttl: rrset[0].ttl,
+            expiration: now + 3600,
+            inception: now - 3600,
+            key_tag: key_tag(rdata(&self.dnskey)),
+            signer: self.zone.clone(),
+            signature: vec![],
+        };
+        let (data, _) = signed_data(&rrset.iter().collect::<Vec<_>>(), &sig).unwrap();
+        let mut rrsig = sig.signed_fields();
+        rrsig.extend(self.pair.sign(&data).as_ref());
+        let rrsig = test_rr(&owner, dnspkt::RR_RRSIG, dnspkt::RData::Other(rrsig));
+        rrset.into_iter().chain(std::iter::once(rrsig)).collect()
+    }
+}
+
+/* Pretends to be an upstream nameserver with a signed root, a signed "example" zone, and an unsigned
+ * "insecure.example" zone.  Like the cache, it validates the replies it returns.
+ */
+#[cfg(test)]
+struct TestZones {
+    anchors: Vec<TrustAnchor>,
+    replies: Vec<dnspkt::DNSPkt>,
+    now: u32,
+}
+
+#[cfg(test)]
+impl TestZones {
+    fn new() -> Self {
+        let now = super::local::now();
+        let root = TestKey::new(Domain::from(vec![]));
+        let example = TestKey::new("example".parse().unwrap());
+        let soa = example.sign(vec![super::local::soa(&example.zone, 1)], now);
+        let reply = |qname: &Domain, qtype, rcode, answer: Vec<RR>, nameserver: Vec<RR>| {
+            let mut reply = super::local::mk_query("example", qtype);
+            reply.question.qdomain = qname.clone();
+            reply.qr = true;
+            reply.rcode = rcode;
+            reply.answer = answer;
+            reply.nameserver = nameserver;
+            reply
+        };
+        let name = |name: &str| name.parse::<Domain>().unwrap();
+        let mut bad = example.sign(vec![test_a("bad.example", "192.0.2.4")], now);
+        bad[0] = test_a("bad.example", "192.0.2.5");
+        let nsec_apex = example.sign(
+            vec![test_nsec(
+                "example",
+                "insecure.example",
+                &[
+                    dnspkt::RR_NS,
+                    dnspkt::RR_SOA,
+                    dnspkt::RR_RRSIG,
+                    dnspkt::RR_NSEC,
+                    dnspkt::RR_DNSKEY,
+                ],
+            )],
+            now,
+        );
+        let nsec_insecure = example.sign(
+            vec![test_nsec(
+                "insecure.example",
+                "unsigned.example",
+                &[dnspkt::RR_NS, dnspkt::RR_RRSIG, dnspkt::RR_NSEC],
+            )],
+            now,
+        );
+        let nsec_unsigned = example.sign(
+            vec![test_nsec(
+                "unsigned.example",
+                "www.example",
+                &[dnspkt::RR_A, dnspkt::RR_RRSIG, dnspkt::RR_NSEC],
+            )],
+            now,
+        );
+        TestZones {
+            anchors: vec![TrustAnchor {
+                zone: root.zone.clone(),
+                ds: root.ds(),
+            }],
+            replies: vec![
+                reply(
+                    &root.zone,
+                    dnspkt::RR_DNSKEY,
+                    dnspkt::NOERROR,
+                    root.sign(vec![root.dnskey.clone()], now),
+                    vec![],
+                ),
+                reply(
+                    &example.zone,
+                    dnspkt::RR_DS,
+                    dnspkt::NOERROR,
+                    root.sign(vec![example.ds_rr()], now),
+                    vec![],
+                ),
+                reply(
+                    &example.zone,
+                    dnspkt::RR_DNSKEY,
+                    dnspkt::NOERROR,
+                    example.sign(vec![example.dnskey.clone()], now),
+                    vec![],
+                ),
+                reply(
+                    &name("www.example"),
+                    dnspkt::RR_A,
+                    dnspkt::NOERROR,
+                    example.sign(vec![test_a("www.example", "192.0.2.1")], now),
+                    vec![],
+                ),
+                reply(
+                    &name("bad.example"),
+                    dnspkt::RR_A,
+                    dnspkt::NOERROR,
+                    bad,
+                    vec![],
+                ),
+                reply(
+                    &name("missing.example"),
+                    dnspkt::RR_A,
+                    dnspkt::NXDOMAIN,
+                    vec![],
+                    [soa.clone(), nsec_apex, nsec_insecure.clone()].concat(),
+                ),
+                reply(
+                    &name("gone.example"),
+                    dnspkt::RR_A,
+                    dnspkt::NXDOMAIN,
+                    vec![],
+                    soa.clone(),
+                ),
+                reply(
+                    &name("insecure.example"),
+                    dnspkt::RR_DS,
+                    dnspkt::NOERROR,
+                    vec![],
+                    [soa.clone(), nsec_insecure].concat(),
+                ),
+                reply(
+                    &name("host.insecure.example"),
+                    dnspkt::RR_A,
+                    dnspkt::NOERROR,
+                    vec![test_a("host.insecure.example", "192.0.2.2")],
+                    vec![],
+                ),
+                reply(
+                    &name("unsigned.example"),
+                    dnspkt::RR_DS,
+                    dnspkt::NOERROR,
+                    vec![],
+                    [soa, nsec_unsigned].concat(),
+                ),
+                reply(
+                    &name("unsigned.example"),
+                    dnspkt::RR_A,
+                    dnspkt::NOERROR,
+                    vec![test_a("unsigned.example", "192.0.2.3")],
+                    vec![],
+                ),
+            ],
+            now,
+        }
+    }
+
+    async fn query(&self, qname: &str, qtype: Type) -> Result<dnspkt::DNSPkt, Error> {
+        self.lookup(&qname.parse().unwrap(), qtype).await
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Lookup for TestZones {
+    async fn lookup(&self, qname: &Domain, qtype: Type) -> Result<dnspkt::DNSPkt, Error> {
+        let reply = self
+            .replies
+            .iter()
+            .find(|reply| reply.question.qdomain == *qname && reply.question.qtype == qtype)
+            .unwrap_or_else(|| panic!("Unexpected lookup for {} {}", qname, qtype))
+            .clone();
+        validate(self, &self.anchors, reply, self.now).await
+    }
+}
+
+#[cfg(test)]
+fn ede(result: Result<dnspkt::DNSPkt, Error>) -> Option<dnspkt::EdeCode> {
+    match result {
+        Err(Error::Dnssec(code, _)) => Some(code),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn test_validate() {
+    let zones = TestZones::new();
+
+    let reply = zones.query("www.example", dnspkt::RR_A).await.unwrap();
+    assert!(reply.ad);
+    let reply = zones.query("missing.example", dnspkt::RR_A).await.unwrap();
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+    assert!(reply.ad);
+    /* Unsigned, but below a delegation without a DS record */
+    let reply = zones
+        .query("host.insecure.example", dnspkt::RR_A)
+        .await
+        .unwrap();
+    assert!(!reply.ad);
+
+    assert_eq!(
+        ede(zones.query("bad.example", dnspkt::RR_A).await),
+        Some(dnspkt::EDE_DNSSEC_BOGUS)
+    );
+    assert_eq!(
+        ede(zones.query("unsigned.example", dnspkt::RR_A).await),
+        Some(dnspkt::EDE_RRSIG_MISSING)
+    );
+    assert_eq!(
+        ede(zones.query("gone.example", dnspkt::RR_A).await),
+        Some(dnspkt::EDE_NSEC_MISSING)
+    );
+}
+
+#[tokio::test]
+async fn test_validate_trust() {
+    let mut zones = TestZones::new();
+    zones.now += 7200;
+    assert_eq!(
+        ede(zones.query("www.example", dnspkt::RR_A).await),
+        Some(dnspkt::EDE_SIGNATURE_EXPIRED)
+    );
+
+    let mut zones = TestZones::new();
+    zones.anchors = vec![TrustAnchor {
+        zone: Domain::from(vec![]),
+        ds: TestKey::new(Domain::from(vec![])).ds(),
+    }];
+    assert_eq!(
+        ede(zones.query("www.example", dnspkt::RR_A).await),
+        Some(dnspkt::EDE_DNSKEY_MISSING)
+    );
+
+    /* Without a trust anchor, nothing can be proven either way */
+    let mut zones = TestZones::new();
+    zones.anchors = vec![];
+    assert!(!zones.query("www.example", dnspkt::RR_A).await.unwrap().ad);
+}
+
+#[test]
+fn test_ecdsa() {
+    use ring::signature::{EcdsaKeyPair, KeyPair as _, ECDSA_P256_SHA256_FIXED_SIGNING};
+    let rng = ring::rand::SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+    let pair =
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+    let mut rdata = vec![0x01, 0x00, 3, ALG_ECDSAP256SHA256];
+    rdata.extend(&pair.public_key().as_ref()[1..]);
+    let key = Dnskey::parse(&rdata).unwrap();
+    let signature = pair.sign(&rng, b"signed data").unwrap();
+    assert!(verify_signature(&key, b"signed data", signature.as_ref()));
+    assert!(!verify_signature(&key, b"other data", signature.as_ref()));
+}
+
+#[test]
+fn test_nsec3_hash() {
+    /* From RFC5155 Appendix A */
+    let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+    for (name, hash) in [
+        ("example", "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom"),
+        ("a.example", "35mthgpgcu1qg68fab165klnsnk3dpvl"),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            nsec3_hash(&name.parse().unwrap(), &salt, 12),
+            base32hex_decode(hash.as_bytes()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_canonical_order() {
+    /* From RFC4034 Section 6.1 */
+    let z: Domain = "z.example".parse().unwrap();
+    let names = vec![
+        "example".parse().unwrap(),
+        "a.example".parse().unwrap(),
+        "yljkjljk.a.example".parse().unwrap(),
+        "Z.a.example".parse().unwrap(),
+        "zABC.a.EXAMPLE".parse().unwrap(),
+        z.clone(),
+        z.with_prefix(dnspkt::Label::from(vec![1])),
+        z.with_prefix(dnspkt::Label::from(b"*".to_vec())),
+        z.with_prefix(dnspkt::Label::from(vec![200])),
+    ];
+    for pair in names.windows(2) {
+        assert!(
+            canonical_key(&pair[0]) < canonical_key(&pair[1]),
+            "{} < {}",
+            pair[0],
+            pair[1]
+        );
+    }
+}
+
+#[test]
+fn test_trust_anchor() {
+    let anchors = root_trust_anchors();
+    assert_eq!(anchors[0].zone, Domain::from(vec![]));
+    assert_eq!(anchors[0].ds.key_tag, 20326);
+    assert_eq!(anchors[0].ds.algorithm, ALG_RSASHA256);
+    assert_eq!(anchors[0].ds.digest_type, DIGEST_SHA256);
+    assert_eq!(anchors[0].ds.digest.len(), 32);
+    assert!(". 20326 8 2 E06".parse::<TrustAnchor>().is_err());
+    assert!(". 20326 8".parse::<TrustAnchor>().is_err());
+}
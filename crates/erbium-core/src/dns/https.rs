@@ -0,0 +1,204 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Serving DNS over HTTPS (RFC8484) to clients.
+ *
+ *  TLS is terminated here rather than by a reverse proxy in front of the API server, so that the
+ *  ACLs see the real client address.
+ */
+
+use erbium_net::addr::NetAddr;
+use hyper::{Body, Request, Response};
+
+/* DNS messages are at most 64k, as they're length prefixed with a u16 over TCP. */
+const MAX_DNS_MESSAGE: usize = 65535;
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// The ALPN protocols offered to DNS over HTTPS clients.
+pub fn alpn_protocols() -> Vec<Vec<u8>> {
+    vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+}
+
+fn error_response(status: hyper::StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "text/plain")
+        .body(format!("{}\n", message).into())
+        .unwrap()
+}
+
+fn decode_dns_param(query: Option<&str>) -> Option<Vec<u8>> {
+    use base64::Engine as _;
+    let param = query?
+        .split('&')
+        .find_map(|param| param.strip_prefix("dns="))?;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(param)
+        .ok()
+        .filter(|pkt| pkt.len() <= MAX_DNS_MESSAGE)
+}
+
+async fn read_dns_body(body: Body) -> Result<Option<Vec<u8>>, hyper::Error> {
+    use hyper::body::HttpBody as _;
+    let mut body = body;
+    let mut pkt = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if pkt.len() + chunk.len() > MAX_DNS_MESSAGE {
+            return Ok(None);
+        }
+        pkt.extend_from_slice(&chunk);
+    }
+    Ok(Some(pkt))
+}
+
+/* Whether the client can make queries is decided by the dns-recursion permission, the same as any
+ * other DNS query.
+ */
+pub async fn serve_dns_query(
+    req: Request<Body>,
+    local_ip: std::net::IpAddr,
+    addr: NetAddr,
+    dns: &super::DnsService,
+) -> Result<Response<Body>, std::convert::Infallible> {
+    use hyper::{Method, StatusCode};
+
+    if req.uri().path() != "/dns-query" {
+        return Ok(error_response(StatusCode::NOT_FOUND, "Not found"));
+    }
+    let pkt = match *req.method() {
+        Method::GET => decode_dns_param(req.uri().query()),
+        Method::POST => {
+            if req
+                .headers()
+                .get(hyper::header::CONTENT_TYPE)
+                .map(|ct| ct != DNS_MESSAGE)
+                .unwrap_or(true)
+            {
+                return Ok(error_response(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    &format!("Expected {}", DNS_MESSAGE),
+                ));
+            }
+            match read_dns_body(req.into_body()).await {
+                Ok(pkt) => pkt,
+                Err(err) => {
+                    log::warn!("Failed to read DNS query from {}: {}", addr, err);
+                    return Ok(error_response(StatusCode::BAD_REQUEST, &err.to_string()));
+                }
+            }
+        }
+        _ => {
+            return Ok(error_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed",
+            ))
+        }
+    };
+    let pkt = match pkt {
+        Some(pkt) => pkt,
+        None => {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                "Missing or invalid DNS query",
+            ))
+        }
+    };
+
+    match dns
+        .handle_query(&pkt, local_ip, addr, super::Protocol::Https)
+        .await
+    {
+        Ok(reply) => Ok(Response::builder()
+            .header(hyper::header::CONTENT_TYPE, DNS_MESSAGE)
+            .body(reply.into())
+            .unwrap()),
+        Err(err) => Ok(error_response(StatusCode::BAD_REQUEST, &err.to_string())),
+    }
+}
+
+#[tokio::test]
+async fn test_dns_query() {
+    use super::{dnspkt, local, parse};
+    use base64::Engine as _;
+    use erbium_net::addr::WithPort as _;
+
+    let conf = crate::config::load_config_from_string_for_test(
+        "---
+dns-routes:
+  - domain-suffixes: ['']
+    type: forge-nxdomain
+",
+    )
+    .unwrap();
+    let dns = super::DnsService::new_for_test(conf).await;
+    let local_ip = std::net::Ipv4Addr::LOCALHOST;
+    let query = local::mk_query("www.example", dnspkt::RR_A).serialise();
+    let check_reply = |resp: Response<Body>| async {
+        assert_eq!(resp.status(), hyper::StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Type"], "application/dns-message");
+        let reply = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let reply = parse::PktParser::new(&reply).get_dns().unwrap();
+        assert!(reply.qr);
+        assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+    };
+
+    let get = Request::get(format!(
+        "/dns-query?dns={}",
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&query)
+    ))
+    .body(Body::empty())
+    .unwrap();
+    let resp = serve_dns_query(get, local_ip.into(), local_ip.with_port(1234), &dns)
+        .await
+        .unwrap();
+    check_reply(resp).await;
+
+    let post = Request::post("/dns-query")
+        .header("Content-Type", "application/dns-message")
+        .body(query.clone().into())
+        .unwrap();
+    let resp = serve_dns_query(post, local_ip.into(), local_ip.with_port(1234), &dns)
+        .await
+        .unwrap();
+    check_reply(resp).await;
+
+    let post = Request::post("/dns-query")
+        .header("Content-Type", "text/plain")
+        .body(query.clone().into())
+        .unwrap();
+    let resp = serve_dns_query(post, local_ip.into(), local_ip.with_port(1234), &dns)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let get = Request::get("/dns-query?dns=not*base64")
+        .body(Body::empty())
+        .unwrap();
+    let resp = serve_dns_query(get, local_ip.into(), local_ip.with_port(1234), &dns)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::BAD_REQUEST);
+
+    let post = Request::post("/metrics")
+        .header("Content-Type", "application/dns-message")
+        .body(query.into())
+        .unwrap();
+    let resp = serve_dns_query(post, local_ip.into(), local_ip.with_port(1234), &dns)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), hyper::StatusCode::NOT_FOUND);
+}
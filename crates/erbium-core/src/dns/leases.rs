@@ -0,0 +1,536 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Answers DNS queries for the names of clients that have active DHCP leases.
+ */
+
+use super::dnspkt;
+use super::Error;
+use crate::dhcp::{dhcppkt, pool, worker::PoolWorker};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/* Leases can be released or handed to another client at any time, so don't let people cache the
+ * answers for too long.
+ */
+const MAX_TTL: u32 = 300;
+
+/* Changes made by another process (such as a separate erbium-dhcp) aren't seen by the pool's change
+ * counter, so reread the leases after this long anyway.
+ */
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/* Flags from the client FQDN option (RFC4702) */
+const FQDN_FLAG_E: u8 = 0x04;
+const FQDN_FLAG_N: u8 = 0x08;
+
+lazy_static::lazy_static! {
+    pub static ref REVERSE_ZONE: dnspkt::Domain = "in-addr.arpa".parse().unwrap();
+}
+
+/// Turns a client provided name into a single DNS label, or None if nothing usable is left.
+fn sanitise_name(name: &[u8]) -> Option<String> {
+    let name = name
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_alphanumeric() {
+                char::from(b.to_ascii_lowercase())
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim_matches('-');
+    if name.is_empty() || name.len() > 63 {
+        None
+    } else {
+        Some(name.into())
+    }
+}
+
+/// Extracts the name a client wants to be known by from the options stored with its lease.
+///
+/// The client FQDN option takes precedence over the host name option.  A client that sets the N
+/// flag has asked for no DNS records to be published on its behalf.
+fn lease_name(raw_options: &[u8]) -> Option<String> {
+    let options = dhcppkt::parse_options(crate::pktparser::Buffer::new(raw_options)).ok()?;
+    if let Some(fqdn) = options.get_raw_option(&dhcppkt::OPTION_FQDN) {
+        if fqdn.len() > 3 {
+            let flags = fqdn[0];
+            if flags & FQDN_FLAG_N != 0 {
+                return None;
+            }
+            let name = &fqdn[3..];
+            let host = if flags & FQDN_FLAG_E != 0 {
+                /* Canonical wire format, we only care about the first label */
+                name.get(1..=usize::from(name[0]))
+            } else {
+                name.split(|&b| b == b'.').next()
+            };
+            if let Some(host) = host.and_then(sanitise_name) {
+                return Some(host);
+            }
+        }
+    }
+    options.get_hostname().and_then(|h| {
+        h.split('.')
+            .next()
+            .and_then(|h| sanitise_name(h.as_bytes()))
+    })
+}
+
+#[derive(Default, Debug)]
+struct Names {
+    by_name: HashMap<String, Vec<(Ipv4Addr, u32)>>,
+    by_addr: HashMap<Ipv4Addr, (String, u32)>,
+}
+
+/// Builds the name to address mappings from the active leases.
+///
+/// If more than one client claims the same name, then whichever client currently owns the name
+/// keeps it for as long as it holds an active lease.  Otherwise the name goes to the client whose
+/// lease started first.  Clients that lose out don't get any records at all.
+fn build_names(
+    owners: &mut HashMap<String, Vec<u8>>,
+    leases: &[pool::LeaseInfo],
+    now: u32,
+) -> Names {
+    let mut claims: HashMap<String, Vec<&pool::LeaseInfo>> = HashMap::new();
+    for lease in leases
+        .iter()
+        .filter(|li| li.expire > now && !li.client_id.is_empty())
+    {
+        if let Some(name) = lease_name(&lease.options) {
+            claims.entry(name).or_default().push(lease);
+        }
+    }
+
+    owners.retain(|name, _| claims.contains_key(name));
+
+    let mut names = Names::default();
+    for (name, claimants) in claims {
+        let owner = match owners.get(&name) {
+            Some(owner) if claimants.iter().any(|li| &li.client_id == owner) => owner.clone(),
+            _ => claimants
+                .iter()
+                .min_by_key(|li| (li.start, &li.client_id))
+                .map(|li| li.client_id.clone())
+                .unwrap(),
+        };
+        for lease in claimants.iter().filter(|li| li.client_id == owner) {
+            names
+                .by_name
+                .entry(name.clone())
+                .or_default()
+                .push((lease.ip, lease.expire));
+            names.by_addr.insert(lease.ip, (name.clone(), lease.expire));
+        }
+        owners.insert(name, owner);
+    }
+    names
+}
+
+/// Returns the address from a name like 1.2.0.192.in-addr.arpa.
+fn parse_reverse(domain: &dnspkt::Domain) -> Option<Ipv4Addr> {
+    let labels = domain.labels();
+    if labels.len() != 6 || !domain.ends_with(&REVERSE_ZONE) {
+        return None;
+    }
+    let mut octets = [0_u8; 4];
+    for (octet, label) in octets.iter_mut().rev().zip(&labels[..4]) {
+        *octet = std::str::from_utf8(label.as_bytes()).ok()?.parse().ok()?;
+    }
+    Some(octets.into())
+}
+
+fn ttl(expire: u32, now: u32) -> u32 {
+    std::cmp::min(expire.saturating_sub(now), MAX_TTL)
+}
+
+struct Cached {
+    names: Arc<Names>,
+    changes: u64,
+    refreshed: Instant,
+}
+
+struct State {
+    pool: Option<PoolWorker>,
+    owners: HashMap<String, Vec<u8>>,
+    cached: Option<Cached>,
+}
+
+impl State {
+    /* Returns the names for the current leases, only rebuilding them when the leases change. */
+    async fn get_names(
+        &mut self,
+        conf: &crate::config::Config,
+        now: u32,
+    ) -> Result<Arc<Names>, pool::Error> {
+        if self.pool.is_none() {
+            self.pool = Some(PoolWorker::new(pool::Pool::open(conf)?));
+        }
+        let pool = self.pool.as_ref().unwrap();
+        let changes = pool.changes();
+        match &self.cached {
+            Some(cached)
+                if cached.changes == changes && cached.refreshed.elapsed() < REFRESH_INTERVAL =>
+            {
+                return Ok(cached.names.clone())
+            }
+            _ => (),
+        }
+        let leases = pool.call(|pool| pool.get_leases()).await?;
+        let names = Arc::new(build_names(&mut self.owners, &leases, now));
+        self.cached = Some(Cached {
+            names: names.clone(),
+            changes,
+            refreshed: Instant::now(),
+        });
+        Ok(names)
+    }
+}
+
+pub struct LeaseHandler {
+    state: tokio::sync::Mutex<State>,
+}
+
+impl LeaseHandler {
+    pub fn new() -> Self {
+        /* The lease database is opened on first use, so that configurations that don't use
+         * dhcp-leases don't need access to it.
+         */
+        Self::new_with_pool(None)
+    }
+
+    /// Answers from the leases of a DHCP server running in this process, rather than opening the
+    /// lease database separately (which would be empty if the leases are only kept in memory).
+    pub async fn set_pool(&self, pool: PoolWorker) {
+        let mut state = self.state.lock().await;
+        state.pool = Some(pool);
+        state.cached = None;
+    }
+
+    fn new_with_pool(pool: Option<PoolWorker>) -> Self {
+        LeaseHandler {
+            state: tokio::sync::Mutex::new(State {
+                pool,
+                owners: HashMap::new(),
+                cached: None,
+            }),
+        }
+    }
+
+    /// Answers a query that matched `suffix` from a dhcp-leases route.
+    pub async fn handle_query(
+        &self,
+        conf: &crate::config::Config,
+        route: &super::config::Route,
+        suffix: &dnspkt::Domain,
+        query: &dnspkt::DNSPkt,
+    ) -> Result<dnspkt::DNSPkt, Error> {
+        let now = super::local::now();
+        let names = self
+            .state
+            .lock()
+            .await
+            .get_names(conf, now)
+            .await
+            .map_err(Error::LeaseError)?;
+
+        let question = &query.question;
+        let qlabels = question.qdomain.labels().len();
+        let slabels = suffix.labels().len();
+        let mut answer = vec![];
+        let exists;
+        if qlabels == slabels {
+            /* The apex of the zone always exists */
+            if question.qtype == dnspkt::RR_SOA || question.qtype == dnspkt::RR_ANY {
+                answer.push(super::local::soa(suffix, now));
+            }
+            exists = true;
+        } else if suffix.ends_with(&REVERSE_ZONE) {
+            if qlabels < 6 {
+                /* Parent of an address, so an empty non-terminal */
+                exists = true;
+            } else if let Some((name, expire)) = parse_reverse(&question.qdomain)
+                .and_then(|ip| names.by_addr.get(&ip))
+                /* The names may have been built before the lease expired */
+                .filter(|(_, expire)| *expire > now)
+            {
+                /* Configuration ensures there is at least one forward suffix */
+                let zone = route
+                    .suffixes
+                    .iter()
+                    .find(|d| !d.ends_with(&REVERSE_ZONE))
+                    .unwrap();
+                if question.qtype == dnspkt::RR_PTR || question.qtype == dnspkt::RR_ANY {
+                    answer.push(dnspkt::RR {
+                        domain: question.qdomain.clone(),
+                        class: dnspkt::CLASS_IN,
+                        rrtype: dnspkt::RR_PTR,
+                        ttl: ttl(*expire, now),
+                        rdata: dnspkt::RData::Ptr(
+                            zone.with_prefix(name.as_bytes().to_vec().into()),
+                        ),
+                    });
+                }
+                exists = true;
+            } else {
+                exists = false;
+            }
+        } else if qlabels == slabels + 1 {
+            let host = String::from_utf8_lossy(question.qdomain.labels()[0].as_bytes())
+                .to_ascii_lowercase();
+            let addresses = names
+                .by_name
+                .get(&host)
+                .map(|addresses| {
+                    addresses
+                        .iter()
+                        .filter(|(_, expire)| *expire > now)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            if !addresses.is_empty() {
+                if question.qtype == dnspkt::RR_A || question.qtype == dnspkt::RR_ANY {
+                    answer.extend(addresses.iter().map(|(ip, expire)| dnspkt::RR {
+                        domain: question.qdomain.clone(),
+                        class: dnspkt::CLASS_IN,
+                        rrtype: dnspkt::RR_A,
+                        ttl: ttl(*expire, now),
+                        rdata: dnspkt::RData::A(*ip),
+                    }));
+                }
+                exists = true;
+            } else {
+                exists = false;
+            }
+        } else {
+            exists = false;
+        }
+
+        Ok(super::local::authoritative_reply(
+            query, suffix, exists, answer,
+        ))
+    }
+}
+
+impl Default for LeaseHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_lease_name() {
+    /* Host name only */
+    assert_eq!(lease_name(b"\x0c\x06Laptop\xff"), Some("laptop".into()));
+    /* FQDN in ASCII overrides the host name */
+    assert_eq!(
+        lease_name(b"\x0c\x06laptop\x51\x13\x00\x00\x00desk.example.com\xff"),
+        Some("desk".into())
+    );
+    /* FQDN in canonical wire format */
+    assert_eq!(
+        lease_name(b"\x51\x0d\x04\x00\x00\x04desk\x03com\x00\xff"),
+        Some("desk".into())
+    );
+    /* N flag means the client doesn't want any records published */
+    assert_eq!(
+        lease_name(b"\x0c\x06laptop\x51\x08\x08\x00\x00desk.\xff"),
+        None
+    );
+    assert_eq!(
+        lease_name(b"\x0c\x09Bob's PC!\xff"),
+        Some("bob-s-pc".into())
+    );
+    assert_eq!(lease_name(b"\x0c\x03___\xff"), None);
+}
+
+#[test]
+fn test_parse_reverse() {
+    assert_eq!(
+        parse_reverse(&"1.2.0.192.in-addr.arpa".parse().unwrap()),
+        Some("192.0.2.1".parse().unwrap())
+    );
+    assert_eq!(
+        parse_reverse(&"2.0.192.in-addr.arpa".parse().unwrap()),
+        None
+    );
+    assert_eq!(
+        parse_reverse(&"256.2.0.192.in-addr.arpa".parse().unwrap()),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_lease_queries() {
+    let mut p = pool::Pool::new_in_memory().unwrap();
+    for (client, ip, options) in [
+        (&b"laptop"[..], "192.0.2.1", &b"\x0c\x06laptop\xff"[..]),
+        (b"printer", "192.0.2.2", b"\x0c\x07printer\xff"),
+        /* Conflicts with the first laptop */
+        (b"laptop2", "192.0.2.3", b"\x0c\x06LAPTOP\xff"),
+    ] {
+        let mut addresses = pool::PoolAddresses::default();
+        addresses.insert(ip.parse().unwrap());
+        p.allocate_address(
+            client,
+            None,
+            &addresses,
+            pool::DEFAULT_MIN_LEASE,
+            pool::DEFAULT_MAX_LEASE,
+            options,
+        )
+        .unwrap();
+    }
+    let worker = PoolWorker::new(p);
+    let handler = LeaseHandler::new_with_pool(Some(worker.clone()));
+    let conf = crate::config::Config::default();
+    let route = super::config::Route {
+        suffixes: vec![
+            "home.example".parse().unwrap(),
+            "2.0.192.in-addr.arpa".parse().unwrap(),
+        ],
+        dest: super::config::Handler::DhcpLeases,
+    };
+    let forward = &route.suffixes[0];
+    let reverse = &route.suffixes[1];
+
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            forward,
+            &super::local::mk_query("Printer.home.example", dnspkt::RR_A),
+        )
+        .await
+        .unwrap();
+    assert!(reply.aa);
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert_eq!(reply.answer.len(), 1);
+    assert_eq!(
+        reply.answer[0].rdata,
+        dnspkt::RData::A("192.0.2.2".parse().unwrap())
+    );
+    assert!(reply.answer[0].ttl <= MAX_TTL);
+
+    /* Only one of the two clients that asked for "laptop" gets it */
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            forward,
+            &super::local::mk_query("laptop.home.example", dnspkt::RR_A),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.answer.len(), 1);
+    let owner = reply.answer[0].rdata.clone();
+    let loser = if owner == dnspkt::RData::A("192.0.2.1".parse().unwrap()) {
+        "3.2.0.192.in-addr.arpa"
+    } else {
+        "1.2.0.192.in-addr.arpa"
+    };
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            reverse,
+            &super::local::mk_query(loser, dnspkt::RR_PTR),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+    /* ... and the owner stays stable */
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            forward,
+            &super::local::mk_query("laptop.home.example", dnspkt::RR_A),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.answer[0].rdata, owner);
+
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            reverse,
+            &super::local::mk_query("2.2.0.192.in-addr.arpa", dnspkt::RR_PTR),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        reply.answer[0].rdata,
+        dnspkt::RData::Ptr("printer.home.example".parse().unwrap())
+    );
+
+    /* Existing name, but wrong type is NODATA */
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            forward,
+            &super::local::mk_query("printer.home.example", dnspkt::RR_MX),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert!(reply.answer.is_empty());
+    assert_eq!(reply.nameserver[0].rrtype, dnspkt::RR_SOA);
+
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            forward,
+            &super::local::mk_query("nothere.home.example", dnspkt::RR_A),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            reverse,
+            &super::local::mk_query("200.2.0.192.in-addr.arpa", dnspkt::RR_PTR),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+
+    /* Changes to the leases are seen straight away */
+    worker
+        .call(|p| p.release_address(b"printer", "192.0.2.2".parse().unwrap()))
+        .await
+        .unwrap();
+    let reply = handler
+        .handle_query(
+            &conf,
+            &route,
+            forward,
+            &super::local::mk_query("printer.home.example", dnspkt::RR_A),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+}
@@ -0,0 +1,268 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Authoritative answers for zones that are configured locally.
+ */
+
+use super::dnspkt;
+
+const SOA_TTL: u32 = 300;
+const NEGATIVE_TTL: u32 = 60;
+
+/* Stop following CNAMEs after this many, in case they loop. */
+const MAX_CNAME_CHAIN: usize = 8;
+
+pub fn now() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .expect("clock failure")
+        .as_secs() as u32
+}
+
+/// Synthesises an SOA record for a locally served zone.
+pub fn soa(zone: &dnspkt::Domain, serial: u32) -> dnspkt::RR {
+    dnspkt::RR {
+        domain: zone.clone(),
+        class: dnspkt::CLASS_IN,
+        rrtype: dnspkt::RR_SOA,
+        ttl: SOA_TTL,
+        rdata: dnspkt::RData::Soa(dnspkt::SoaData {
+            mname: zone.clone(),
+            rname: zone.with_prefix(b"hostmaster".to_vec().into()),
+            serial,
+            refresh: SOA_TTL,
+            retry: NEGATIVE_TTL,
+            expire: SOA_TTL,
+            minimum: NEGATIVE_TTL,
+        }),
+    }
+}
+
+/// Builds an authoritative reply to `query`.
+///
+/// If there are no answers, then the SOA for `zone` is put in the authority section so the
+/// negative answer can be cached.
+pub fn authoritative_reply(
+    query: &dnspkt::DNSPkt,
+    zone: &dnspkt::Domain,
+    exists: bool,
+    answer: Vec<dnspkt::RR>,
+) -> dnspkt::DNSPkt {
+    let nameserver = if answer.is_empty() {
+        vec![soa(zone, now())]
+    } else {
+        vec![]
+    };
+    dnspkt::DNSPkt {
+        qid: query.qid,
+        rd: query.rd,
+        tc: false,
+        aa: true,
+        qr: true,
+        opcode: dnspkt::OPCODE_QUERY,
+        cd: false,
+        ad: false,
+        ra: false,
+        rcode: if exists {
+            dnspkt::NOERROR
+        } else {
+            dnspkt::NXDOMAIN
+        },
+        bufsize: 4096,
+        edns_ver: query.edns_ver.map(|_| 0),
+        edns_do: false,
+        question: query.question.clone(),
+        answer,
+        nameserver,
+        additional: vec![],
+        edns: None,
+    }
+}
+
+fn matches_type(rr: &dnspkt::RR, qtype: dnspkt::Type) -> bool {
+    qtype == dnspkt::RR_ANY || rr.rrtype == qtype
+}
+
+/// Answers a query that matched `zone` from the records configured for a local-zone route.
+pub fn handle_query(
+    records: &[dnspkt::RR],
+    zone: &dnspkt::Domain,
+    query: &dnspkt::DNSPkt,
+) -> dnspkt::DNSPkt {
+    let qdomain = query.question.qdomain.to_ascii_lowercase();
+    let qtype = query.question.qtype;
+    let mut answer = vec![];
+
+    if qdomain == *zone && (qtype == dnspkt::RR_SOA || qtype == dnspkt::RR_ANY) {
+        answer.push(soa(zone, now()));
+    }
+
+    /* Follow CNAMEs as long as they stay inside this zone */
+    let mut name = qdomain.clone();
+    for _ in 0..MAX_CNAME_CHAIN {
+        let cname = records
+            .iter()
+            .find(|rr| rr.domain == name && rr.rrtype == dnspkt::RR_CNAME);
+        match cname {
+            Some(rr) if qtype != dnspkt::RR_CNAME && qtype != dnspkt::RR_ANY => {
+                answer.push(rr.clone());
+                match &rr.rdata {
+                    dnspkt::RData::CName(target) if target.ends_with(zone) => name = target.clone(),
+                    _ => break,
+                }
+            }
+            _ => {
+                answer.extend(
+                    records
+                        .iter()
+                        .filter(|rr| rr.domain == name && matches_type(rr, qtype))
+                        .cloned(),
+                );
+                break;
+            }
+        }
+    }
+
+    /* Names that have no records, but have records below them still exist. */
+    let exists = qdomain == *zone || records.iter().any(|rr| rr.domain.ends_with(&qdomain));
+
+    authoritative_reply(query, zone, exists, answer)
+}
+
+#[cfg(test)]
+pub fn mk_query(qdomain: &str, qtype: dnspkt::Type) -> dnspkt::DNSPkt {
+    dnspkt::DNSPkt {
+        qid: 1,
+        rd: true,
+        tc: false,
+        aa: false,
+        qr: false,
+        opcode: dnspkt::OPCODE_QUERY,
+        cd: false,
+        ad: false,
+        ra: false,
+        rcode: dnspkt::NOERROR,
+        bufsize: 512,
+        edns_ver: None,
+        edns_do: false,
+        question: dnspkt::Question {
+            qdomain: qdomain.parse().unwrap(),
+            qtype,
+            qclass: dnspkt::CLASS_IN,
+        },
+        answer: vec![],
+        nameserver: vec![],
+        additional: vec![],
+        edns: None,
+    }
+}
+
+#[test]
+fn test_local_zone() {
+    let rr = |domain: &str, rrtype, rdata| dnspkt::RR {
+        domain: domain.parse().unwrap(),
+        class: dnspkt::CLASS_IN,
+        rrtype,
+        ttl: 300,
+        rdata,
+    };
+    let zone: dnspkt::Domain = "internal.example".parse().unwrap();
+    let www = rr(
+        "www.internal.example",
+        dnspkt::RR_A,
+        dnspkt::RData::A("192.0.2.10".parse().unwrap()),
+    );
+    let alias = rr(
+        "alias.internal.example",
+        dnspkt::RR_CNAME,
+        dnspkt::RData::CName("www.internal.example".parse().unwrap()),
+    );
+    let records = vec![
+        www.clone(),
+        alias.clone(),
+        rr(
+            "_sip._udp.internal.example",
+            dnspkt::RR_TXT,
+            dnspkt::RData::Txt(vec![b"x".to_vec()]),
+        ),
+    ];
+
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("WWW.internal.example", dnspkt::RR_A),
+    );
+    assert!(reply.aa);
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert_eq!(reply.answer, vec![www.clone()]);
+    assert!(reply.nameserver.is_empty());
+    /* RD is copied from the query (RFC1035 4.1.1) */
+    assert!(reply.rd);
+    let mut query = mk_query("www.internal.example", dnspkt::RR_A);
+    query.rd = false;
+    assert!(!handle_query(&records, &zone, &query).rd);
+
+    /* CNAMEs are followed */
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("alias.internal.example", dnspkt::RR_A),
+    );
+    assert_eq!(reply.answer, vec![alias.clone(), www.clone()]);
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("alias.internal.example", dnspkt::RR_CNAME),
+    );
+    assert_eq!(reply.answer, vec![alias]);
+
+    /* NODATA */
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("www.internal.example", dnspkt::RR_AAAA),
+    );
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert!(reply.answer.is_empty());
+    assert_eq!(reply.nameserver[0].rrtype, dnspkt::RR_SOA);
+    assert_eq!(reply.nameserver[0].domain, zone);
+
+    /* Empty non-terminal */
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("_udp.internal.example", dnspkt::RR_A),
+    );
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+
+    /* NXDOMAIN */
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("nope.internal.example", dnspkt::RR_A),
+    );
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+    assert_eq!(reply.nameserver[0].rrtype, dnspkt::RR_SOA);
+
+    /* SOA at the apex */
+    let reply = handle_query(
+        &records,
+        &zone,
+        &mk_query("internal.example", dnspkt::RR_SOA),
+    );
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert_eq!(reply.answer[0].rrtype, dnspkt::RR_SOA);
+}
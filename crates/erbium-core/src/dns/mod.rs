@@ -30,18 +30,31 @@ mod bucket;
 mod cache;
 pub(crate) mod config;
 pub mod dnspkt;
+mod dnssec;
+mod https;
+#[cfg(feature = "dhcp")]
+mod leases;
+pub(crate) mod local;
 mod outquery;
 #[cfg(fuzzing)]
 pub mod parse;
 #[cfg(not(fuzzing))]
-mod parse;
+pub(crate) mod parse;
 mod router;
+mod tls;
+mod upstream;
 
 use bytes::BytesMut;
 use tokio_util::codec::Decoder;
 
 type Key = [u8; 8];
 
+/* Clients keep TLS connections open between queries, but we don't want to hold on to them
+ * forever.
+ */
+const TLS_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const TLS_PORT: u16 = 853;
+
 struct CookieKeys {
     next_refresh: tokio::time::Instant,
     current: Key,
@@ -132,6 +145,7 @@ lazy_static::lazy_static! {
 #[cfg_attr(test, derive(Debug))]
 pub enum Error {
     ListenError(std::io::Error, Box<erbium_net::addr::NetAddr>),
+    TlsConfig(String),
     AcceptError(std::io::Error),
     RecvError(std::io::Error),
     ParseError(String),
@@ -141,6 +155,9 @@ pub enum Error {
     NoRouteConfigured,
     NotAuthoritative,
     OutReply(outquery::Error),
+    Dnssec(dnspkt::EdeCode, String),
+    #[cfg(feature = "dhcp")]
+    LeaseError(crate::dhcp::pool::Error),
 }
 
 impl std::fmt::Display for Error {
@@ -148,6 +165,7 @@ impl std::fmt::Display for Error {
         use Error::*;
         match self {
             ListenError(io, addr) => write!(f, "Failed to listen for DNS on {}: {}", addr, io),
+            TlsConfig(msg) => write!(f, "Failed to set up DNS over TLS: {}", msg),
             AcceptError(io) => write!(f, "Failed to accept new TCP connection for DNS: {}", io),
             RecvError(io) => write!(f, "Failed to receive DNS in query: {}", io),
             ParseError(msg) => write!(f, "Failed to parse DNS in query: {}", msg),
@@ -157,6 +175,9 @@ impl std::fmt::Display for Error {
             NoRouteConfigured => write!(f, "No route configured"),
             Denied(msg) => write!(f, "Denied: {}", msg),
             OutReply(err) => write!(f, "{}", err),
+            Dnssec(_, msg) => write!(f, "DNSSEC validation failed: {}", msg),
+            #[cfg(feature = "dhcp")]
+            LeaseError(err) => write!(f, "Failed to read DHCP leases: {}", err),
         }
     }
 }
@@ -505,9 +526,15 @@ impl Decoder for DnsCodec {
     }
 }
 
+/// How a query reached us.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Protocol {
     Udp,
     Tcp,
+    /// DNS over TLS (RFC7858).
+    Tls,
+    /// DNS over HTTPS (RFC8484).
+    Https,
 }
 
 impl std::fmt::Display for Protocol {
@@ -515,6 +542,24 @@ impl std::fmt::Display for Protocol {
         match &self {
             Protocol::Udp => write!(f, "UDP"),
             Protocol::Tcp => write!(f, "TCP"),
+            Protocol::Tls => write!(f, "TLS"),
+            Protocol::Https => write!(f, "HTTPS"),
+        }
+    }
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "udp" => Ok(Protocol::Udp),
+            "tcp" => Ok(Protocol::Tcp),
+            "tls" => Ok(Protocol::Tls),
+            "https" => Ok(Protocol::Https),
+            _ => Err(format!(
+                "Unknown DNS protocol {}, expected udp, tcp, tls or https",
+                s
+            )),
         }
     }
 }
@@ -599,6 +644,10 @@ struct DnsListenerHandler {
     next: acl::DnsAclHandler,
     udp_listeners: Vec<UdpSocket>,
     tcp_listeners: Vec<tokio::net::TcpListener>,
+    tls_listeners: Vec<tokio::net::TcpListener>,
+    tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
+    https_listeners: Vec<tokio::net::TcpListener>,
+    https_acceptor: Option<tokio_rustls::TlsAcceptor>,
     rate_limiter: std::sync::Arc<IpRateLimiter>,
 }
 
@@ -657,6 +706,7 @@ impl DnsListenerHandler {
     async fn listen_tcp(
         _conf: &crate::config::SharedConfig,
         addr: &erbium_net::addr::NetAddr,
+        protocol: Protocol,
     ) -> Result<tokio::net::TcpListener, Error> {
         use erbium_net::addr::NetAddrExt as _;
         let tcp = tokio::net::TcpListener::bind(addr.to_std_socket_addr().ok_or_else(|| {
@@ -666,7 +716,8 @@ impl DnsListenerHandler {
         .map_err(|e| Error::ListenError(e, Box::new(*addr)))?;
 
         log::info!(
-            "Listening for DNS on TCP {}",
+            "Listening for DNS on {} {}",
+            protocol,
             tcp.local_addr()
                 .map(|name| format!("{}", name))
                 .unwrap_or_else(|_| "Unknown".into())
@@ -681,6 +732,10 @@ impl DnsListenerHandler {
     ) -> Result<Self, Error> {
         let mut udp_listeners = vec![];
         let mut tcp_listeners = vec![];
+        let mut tls_listeners = vec![];
+        let mut tls_acceptor = None;
+        let mut https_listeners = vec![];
+        let mut https_acceptor = None;
         {
             let roconf = conf.read().await;
             for addr in &roconf
@@ -689,7 +744,36 @@ impl DnsListenerHandler {
                 .await
             {
                 udp_listeners.push(Self::listen_udp(&conf, addr).await?);
-                tcp_listeners.push(Self::listen_tcp(&conf, addr).await?);
+                tcp_listeners.push(Self::listen_tcp(&conf, addr, Protocol::Tcp).await?);
+            }
+            if let Some(tls) = &roconf.dns_tls {
+                use erbium_net::addr::{NetAddrExt as _, WithPort as _};
+                tls_acceptor = Some(
+                    tls::acceptor(&tls.certificate, &tls.key, vec![]).map_err(Error::TlsConfig)?,
+                );
+                let addrs = match &tls.listeners {
+                    Some(addrs) => addrs.clone(),
+                    None => roconf
+                        .dns_listeners
+                        .as_sockaddrs(&roconf.addresses, netinfo, TLS_PORT)
+                        .await
+                        .iter()
+                        .filter_map(|addr| addr.ip())
+                        .map(|ip| ip.with_port(TLS_PORT))
+                        .collect(),
+                };
+                for addr in &addrs {
+                    tls_listeners.push(Self::listen_tcp(&conf, addr, Protocol::Tls).await?);
+                }
+                if !tls.https_listeners.is_empty() {
+                    https_acceptor = Some(
+                        tls::acceptor(&tls.certificate, &tls.key, https::alpn_protocols())
+                            .map_err(Error::TlsConfig)?,
+                    );
+                }
+                for addr in &tls.https_listeners {
+                    https_listeners.push(Self::listen_tcp(&conf, addr, Protocol::Https).await?);
+                }
             }
         }
         let rate_limiter = IpRateLimiter::new().into();
@@ -698,6 +782,10 @@ impl DnsListenerHandler {
             next: acl::DnsAclHandler::new(conf).await,
             udp_listeners,
             tcp_listeners,
+            tls_listeners,
+            tls_acceptor,
+            https_listeners,
+            https_acceptor,
             rate_limiter,
         })
     }
@@ -732,6 +820,18 @@ impl DnsListenerHandler {
     async fn create_in_reply(msg: &DnsMessage, outr: &dnspkt::DNSPkt) -> dnspkt::DNSPkt {
         let mut edns: dnspkt::EdnsData = Default::default();
         Self::add_edns(&mut edns, msg).await;
+        /* RFC4035 Section 3.2.1: DNSSEC records are only included if the client asked for them,
+         * either with the DO bit, or by querying for them directly.
+         */
+        let qtype = msg.in_query.question.qtype;
+        let wanted = |rr: &&dnspkt::RR| {
+            msg.in_query.edns_do
+                || rr.rrtype == qtype
+                || !matches!(
+                    rr.rrtype,
+                    dnspkt::RR_RRSIG | dnspkt::RR_NSEC | dnspkt::RR_NSEC3
+                )
+        };
         dnspkt::DNSPkt {
             qid: msg.in_query.qid,
             rd: false,
@@ -740,8 +840,11 @@ impl DnsListenerHandler {
             qr: true,
             opcode: dnspkt::OPCODE_QUERY,
 
-            cd: outr.cd,
-            ad: outr.ad,
+            cd: msg.in_query.cd,
+            /* RFC6840 Section 5.8: Only set AD for clients that show they understand it, and never
+             * for clients that asked us not to validate (CD).
+             */
+            ad: outr.ad && (msg.in_query.edns_do || msg.in_query.ad) && !msg.in_query.cd,
             ra: outr.ra,
 
             rcode: outr.rcode,
@@ -749,12 +852,12 @@ impl DnsListenerHandler {
             bufsize: 4096,
 
             edns_ver: msg.in_query.edns_ver.map(|_| 0),
-            edns_do: false,
+            edns_do: msg.in_query.edns_do,
 
             question: msg.in_query.question.clone(),
-            answer: outr.answer.clone(),
-            nameserver: outr.answer.clone(),
-            additional: outr.additional.clone(),
+            answer: outr.answer.iter().filter(wanted).cloned().collect(),
+            nameserver: outr.nameserver.iter().filter(wanted).cloned().collect(),
+            additional: outr.additional.iter().filter(wanted).cloned().collect(),
             edns: Some(edns),
         }
     }
@@ -768,6 +871,7 @@ impl DnsListenerHandler {
         match err {
             /* These errors mean we never get a packet to reply to. */
             ListenError(..) => unreachable!(),
+            TlsConfig(_) => unreachable!(),
             AcceptError(..) => unreachable!(),
             RecvError(_) => unreachable!(),
             ParseError(_) => unreachable!(),
@@ -829,6 +933,15 @@ impl DnsListenerHandler {
                 rcode = SERVFAIL;
                 edns.set_extended_dns_error(EDE_OTHER, "Internal Error");
             }
+            Dnssec(code, why) => {
+                rcode = SERVFAIL;
+                edns.set_extended_dns_error(code, &why);
+            }
+            #[cfg(feature = "dhcp")]
+            LeaseError(err) => {
+                rcode = SERVFAIL;
+                edns.set_extended_dns_error(EDE_OTHER, &err.to_string());
+            }
         }
         dnspkt::DNSPkt {
             qid: msg.in_query.qid,
@@ -1022,6 +1135,16 @@ impl DnsListenerHandler {
         pkt.serialise_with_size(size)
     }
 
+    /* Replies over TCP and TLS are prefixed with their length. */
+    fn prepare_to_send_stream(pkt: &dnspkt::DNSPkt, size: usize) -> Vec<u8> {
+        let serialised = Self::prepare_to_send(pkt, size);
+        let mut in_reply_bytes = vec![];
+        in_reply_bytes.reserve(2 + serialised.len());
+        in_reply_bytes.extend((serialised.len() as u16).to_be_bytes().iter());
+        in_reply_bytes.extend(serialised);
+        in_reply_bytes
+    }
+
     async fn run_tcp(
         s: &std::sync::Arc<tokio::sync::RwLock<Self>>,
         mut sock: tokio::net::TcpStream,
@@ -1068,12 +1191,8 @@ impl DnsListenerHandler {
             ) {
                 Ok(msg) => {
                     let in_reply = Self::recv_in_query(&q, &msg).await.unwrap();
-                    let serialised =
-                        Self::prepare_to_send(&in_reply, msg.in_query.bufsize as usize);
-                    let mut in_reply_bytes = vec![];
-                    in_reply_bytes.reserve(2 + serialised.len());
-                    in_reply_bytes.extend((serialised.len() as u16).to_be_bytes().iter());
-                    in_reply_bytes.extend(serialised);
+                    let in_reply_bytes =
+                        Self::prepare_to_send_stream(&in_reply, msg.in_query.bufsize as usize);
                     if let Err(io) = sock.write(&in_reply_bytes).await {
                         log::warn!("[{:x}] Failed to send DNS reply: {}", msg.in_query.qid, io);
                         IN_QUERY_RESULT
@@ -1106,6 +1225,130 @@ impl DnsListenerHandler {
         Ok(())
     }
 
+    /* Unlike plain TCP, clients keep TLS connections open (as they're expensive to set up), and
+     * send more queries without waiting for the replies (RFC7858 Section 3.3), so each reply is
+     * sent as soon as it's ready, in whatever order that happens to be.
+     */
+    async fn run_tls(
+        s: &std::sync::Arc<tokio::sync::RwLock<Self>>,
+        acceptor: tokio_rustls::TlsAcceptor,
+        sock: tokio::net::TcpStream,
+        sock_addr: NetAddr,
+    ) -> Result<(), Error> {
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+        let timed_out = || std::io::Error::from(std::io::ErrorKind::TimedOut);
+
+        let local_ip = sock.local_addr().map_err(Error::AcceptError)?.ip();
+        let tls = tokio::time::timeout(TLS_IDLE_TIMEOUT, acceptor.accept(sock))
+            .await
+            .map_err(|_| Error::AcceptError(timed_out()))?
+            .map_err(Error::AcceptError)?;
+        log::trace!("Received TLS connection {:?} ⇒ {:?}", sock_addr, local_ip);
+        let (mut reader, writer) = tokio::io::split(tls);
+        let writer = std::sync::Arc::new(tokio::sync::Mutex::new(writer));
+
+        loop {
+            let mut lbytes = [0u8; 2];
+            match tokio::time::timeout(TLS_IDLE_TIMEOUT, reader.read_exact(&mut lbytes)).await {
+                /* The client has finished with the connection, or gone idle */
+                Err(_) => return Ok(()),
+                Ok(Err(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Ok(Err(err)) => return Err(Error::RecvError(err)),
+                Ok(Ok(_)) => (),
+            }
+            let mut buffer = vec![0u8; u16::from_be_bytes(lbytes) as usize];
+            tokio::time::timeout(TLS_IDLE_TIMEOUT, reader.read_exact(&mut buffer[..]))
+                .await
+                .map_err(|_| Error::RecvError(timed_out()))?
+                .map_err(Error::RecvError)?;
+            let timer = IN_QUERY_LATENCY.with_label_values(&["TLS"]).start_timer();
+
+            let q = s.clone();
+            let writer = writer.clone();
+            tokio::spawn(async move {
+                match Self::build_dns_message(&buffer, local_ip, sock_addr, Protocol::Tls) {
+                    Ok(msg) => {
+                        let in_reply = Self::recv_in_query(&q, &msg).await.unwrap();
+                        let in_reply_bytes =
+                            Self::prepare_to_send_stream(&in_reply, msg.in_query.bufsize as usize);
+                        if let Err(io) = writer.lock().await.write_all(&in_reply_bytes).await {
+                            log::warn!("[{:x}] Failed to send DNS reply: {}", msg.in_query.qid, io);
+                            IN_QUERY_RESULT
+                                .with_label_values(&["TLS", "send fail"])
+                                .inc();
+                        }
+                        drop(timer);
+                    }
+                    Err(err) => {
+                        IN_QUERY_RESULT
+                            .with_label_values(&["TLS", "parse fail"])
+                            .inc();
+                        log::warn!("Failed to handle request: {}", err);
+                    }
+                }
+            });
+        }
+    }
+
+    async fn run_tls_listener(
+        tcp: &tokio::net::TcpListener,
+        acceptor: &tokio_rustls::TlsAcceptor,
+        s: &std::sync::Arc<tokio::sync::RwLock<Self>>,
+    ) -> Result<(), Error> {
+        let (sock, sock_addr) = tcp.accept().await.map_err(Error::AcceptError)?;
+        let local_s = s.clone();
+        let acceptor = acceptor.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = Self::run_tls(&local_s, acceptor, sock, sock_addr.into()).await {
+                log::debug!("{}: {}", sock_addr, err);
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn run_https(
+        s: &std::sync::Arc<tokio::sync::RwLock<Self>>,
+        acceptor: tokio_rustls::TlsAcceptor,
+        sock: tokio::net::TcpStream,
+        sock_addr: NetAddr,
+    ) -> Result<(), Error> {
+        let local_ip = sock.local_addr().map_err(Error::AcceptError)?.ip();
+        let tls = tokio::time::timeout(TLS_IDLE_TIMEOUT, acceptor.accept(sock))
+            .await
+            .map_err(|_| Error::AcceptError(std::io::ErrorKind::TimedOut.into()))?
+            .map_err(Error::AcceptError)?;
+        log::trace!("Received HTTPS connection {:?} ⇒ {:?}", sock_addr, local_ip);
+        let dns = DnsService { next: s.clone() };
+        let service = hyper::service::service_fn(move |req| {
+            let dns = dns.clone();
+            async move { https::serve_dns_query(req, local_ip, sock_addr, &dns).await }
+        });
+        hyper::server::conn::Http::new()
+            .serve_connection(tls, service)
+            .await
+            .map_err(|err| Error::RecvError(std::io::Error::other(err)))
+    }
+
+    async fn run_https_listener(
+        tcp: &tokio::net::TcpListener,
+        acceptor: &tokio_rustls::TlsAcceptor,
+        s: &std::sync::Arc<tokio::sync::RwLock<Self>>,
+    ) -> Result<(), Error> {
+        let (sock, sock_addr) = tcp.accept().await.map_err(Error::AcceptError)?;
+        let local_s = s.clone();
+        let acceptor = acceptor.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = Self::run_https(&local_s, acceptor, sock, sock_addr.into()).await {
+                log::debug!("{}: {}", sock_addr, err);
+            }
+        });
+
+        Ok(())
+    }
+
     async fn run(s: &std::sync::Arc<tokio::sync::RwLock<Self>>) -> Result<(), Error> {
         use futures::StreamExt as _;
         let mut services = futures::stream::FuturesUnordered::new();
@@ -1152,17 +1395,101 @@ impl DnsListenerHandler {
             }));
         }
 
+        if let Some(acceptor) = my_self.tls_acceptor.clone() {
+            for listener in my_self.tls_listeners.drain(..) {
+                let s_clone = s.clone();
+                let acceptor = acceptor.clone();
+                services.push(tokio::spawn(async move {
+                    loop {
+                        match Self::run_tls_listener(&listener, &acceptor, &s_clone).await {
+                            Ok(()) => (),
+                            Err(err) => {
+                                log::warn!(
+                                    "{}: {}",
+                                    listener
+                                        .local_addr()
+                                        .map(|a| format!("{}", a))
+                                        .unwrap_or_else(|e| format!("<unknown: {}>", e)),
+                                    err
+                                )
+                            }
+                        }
+                    }
+                }));
+            }
+        }
+
+        if let Some(acceptor) = my_self.https_acceptor.clone() {
+            for listener in my_self.https_listeners.drain(..) {
+                let s_clone = s.clone();
+                let acceptor = acceptor.clone();
+                services.push(tokio::spawn(async move {
+                    loop {
+                        match Self::run_https_listener(&listener, &acceptor, &s_clone).await {
+                            Ok(()) => (),
+                            Err(err) => {
+                                log::warn!(
+                                    "{}: {}",
+                                    listener
+                                        .local_addr()
+                                        .map(|a| format!("{}", a))
+                                        .unwrap_or_else(|e| format!("<unknown: {}>", e)),
+                                    err
+                                )
+                            }
+                        }
+                    }
+                }));
+            }
+        }
+
         drop(my_self);
 
         services.next().await.unwrap().unwrap()
     }
 }
 
+#[derive(Clone)]
 pub struct DnsService {
     next: std::sync::Arc<tokio::sync::RwLock<DnsListenerHandler>>,
 }
 
 impl DnsService {
+    /// Answers a query that didn't arrive on a DNS stream, such as DNS over HTTPS, returning the
+    /// serialised reply.
+    async fn handle_query(
+        &self,
+        pkt: &[u8],
+        local_ip: std::net::IpAddr,
+        remote_addr: NetAddr,
+        protocol: Protocol,
+    ) -> Result<Vec<u8>, Error> {
+        let protocol_name = protocol.to_string();
+        let _timer = IN_QUERY_LATENCY
+            .with_label_values(&[&protocol_name])
+            .start_timer();
+        let msg = match DnsListenerHandler::build_dns_message(pkt, local_ip, remote_addr, protocol)
+        {
+            Ok(msg) => msg,
+            Err(err) => {
+                IN_QUERY_RESULT
+                    .with_label_values(&[&protocol_name, "parse fail"])
+                    .inc();
+                return Err(err);
+            }
+        };
+        let in_reply = DnsListenerHandler::recv_in_query(&self.next, &msg)
+            .await
+            .unwrap();
+        Ok(in_reply.serialise())
+    }
+
+    /// Answer dhcp-leases routes from the leases of the DHCP server in this process.
+    #[cfg(feature = "dhcp")]
+    pub async fn set_dhcp_pool(&self, pool: crate::dhcp::worker::PoolWorker) {
+        self.next.read().await.next.set_dhcp_pool(pool).await
+    }
+
     pub async fn run(self) -> Result<(), Error> {
         loop {
             DnsListenerHandler::run(&self.next).await?;
@@ -1177,4 +1504,201 @@ impl DnsService {
             next: tokio::sync::RwLock::new(DnsListenerHandler::new(conf, netinfo).await?).into(),
         })
     }
+
+    /* A service without any listeners, for feeding queries to directly. */
+    #[cfg(test)]
+    pub(crate) async fn new_for_test(conf: crate::config::SharedConfig) -> Self {
+        Self {
+            next: tokio::sync::RwLock::new(DnsListenerHandler {
+                next: acl::DnsAclHandler::new(conf).await,
+                udp_listeners: vec![],
+                tcp_listeners: vec![],
+                tls_listeners: vec![],
+                tls_acceptor: None,
+                https_listeners: vec![],
+                https_acceptor: None,
+                rate_limiter: IpRateLimiter::new().into(),
+            })
+            .into(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_dns_over_tls_listener() {
+    use crypto::digest::Digest as _;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    let cert = rcgen::generate_simple_self_signed(vec!["dns.example".into()]).unwrap();
+    let dir = std::env::temp_dir().join(format!("erbium-dns-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let certificate = dir.join("cert.pem");
+    let key = dir.join("key.pem");
+    std::fs::write(&certificate, cert.serialize_pem().unwrap()).unwrap();
+    std::fs::write(&key, cert.serialize_private_key_pem()).unwrap();
+    let acceptor = tls::acceptor(&certificate, &key, vec![]).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let conf = crate::config::load_config_from_string_for_test(
+        "---
+dns-routes:
+  - domain-suffixes: ['']
+    type: forge-nxdomain
+",
+    )
+    .unwrap();
+    let service = DnsService::new_for_test(conf).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            DnsListenerHandler::run_tls_listener(&listener, &acceptor, &service.next)
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut hasher = crypto::sha2::Sha256::new();
+    hasher.input(&cert.get_key_pair().public_key_der());
+    let mut pin = vec![0; hasher.output_bytes()];
+    hasher.result(&mut pin);
+    let auth = config::TlsAuth {
+        auth_name: None,
+        spki_pins: vec![pin],
+    };
+    let mut client = tls::connect(server, &auth, vec![]).await.unwrap();
+
+    /* Send two queries before reading any replies */
+    let query = local::mk_query("www.example", dnspkt::RR_A).serialise();
+    for _ in 0..2 {
+        client
+            .write_all(&(query.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        client.write_all(&query).await.unwrap();
+    }
+    for _ in 0..2 {
+        let mut len = [0u8; 2];
+        client.read_exact(&mut len).await.unwrap();
+        let mut reply = vec![0u8; u16::from_be_bytes(len) as usize];
+        client.read_exact(&mut reply).await.unwrap();
+        let reply = parse::PktParser::new(&reply).get_dns().unwrap();
+        assert!(reply.qr);
+        assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+    }
+}
+
+#[tokio::test]
+async fn test_dns_over_https_listener() {
+    use crypto::digest::Digest as _;
+
+    let cert = rcgen::generate_simple_self_signed(vec!["dns.example".into()]).unwrap();
+    let dir = std::env::temp_dir().join(format!("erbium-dns-https-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let certificate = dir.join("cert.pem");
+    let key = dir.join("key.pem");
+    std::fs::write(&certificate, cert.serialize_pem().unwrap()).unwrap();
+    std::fs::write(&key, cert.serialize_private_key_pem()).unwrap();
+    let acceptor = tls::acceptor(&certificate, &key, https::alpn_protocols()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let conf = crate::config::load_config_from_string_for_test(
+        "---
+dns-routes:
+  - domain-suffixes: ['']
+    type: forge-nxdomain
+",
+    )
+    .unwrap();
+    let service = DnsService::new_for_test(conf).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            DnsListenerHandler::run_https_listener(&listener, &acceptor, &service.next)
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut hasher = crypto::sha2::Sha256::new();
+    hasher.input(&cert.get_key_pair().public_key_der());
+    let mut pin = vec![0; hasher.output_bytes()];
+    hasher.result(&mut pin);
+    let auth = config::TlsAuth {
+        auth_name: None,
+        spki_pins: vec![pin],
+    };
+    let client = tls::connect(server, &auth, vec![b"h2".to_vec()])
+        .await
+        .unwrap();
+    let (mut sender, connection) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake(client)
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+
+    let query = local::mk_query("www.example", dnspkt::RR_A).serialise();
+    let request = hyper::Request::post("https://dns.example/dns-query")
+        .header(hyper::header::CONTENT_TYPE, "application/dns-message")
+        .body(hyper::Body::from(query))
+        .unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let reply = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let reply = parse::PktParser::new(&reply).get_dns().unwrap();
+    assert!(reply.qr);
+    assert_eq!(reply.rcode, dnspkt::NXDOMAIN);
+}
+
+#[cfg(feature = "dhcp")]
+#[tokio::test]
+async fn test_dhcp_leases_from_pool() {
+    use crate::dhcp::{pool, worker::PoolWorker};
+    use erbium_net::addr::WithPort as _;
+
+    let conf = crate::config::load_config_from_string_for_test(
+        "---
+lease-storage: memory
+dns-routes:
+  - domain-suffixes: [home.example]
+    type: dhcp-leases
+",
+    )
+    .unwrap();
+    let service = DnsService::new_for_test(conf).await;
+    let pool = PoolWorker::new(pool::Pool::new_in_memory().unwrap());
+    pool.call(|p| {
+        p.allocate_address(
+            b"laptop",
+            None,
+            &["192.0.2.1".parse().unwrap()].iter().copied().collect(),
+            pool::DEFAULT_MIN_LEASE,
+            pool::DEFAULT_MAX_LEASE,
+            b"\x0c\x06laptop\xff",
+        )
+    })
+    .await
+    .unwrap();
+    service.set_dhcp_pool(pool).await;
+
+    /* The lease is answered for, rather than looked for in a separate (empty) store */
+    let query = local::mk_query("laptop.home.example", dnspkt::RR_A).serialise();
+    let local_ip = std::net::Ipv4Addr::LOCALHOST;
+    let reply = service
+        .handle_query(
+            &query,
+            local_ip.into(),
+            local_ip.with_port(1234),
+            Protocol::Udp,
+        )
+        .await
+        .unwrap();
+    let reply = parse::PktParser::new(&reply).get_dns().unwrap();
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert_eq!(
+        reply.answer[0].rdata,
+        dnspkt::RData::A("192.0.2.1".parse().unwrap())
+    );
 }
@@ -21,7 +21,7 @@ use crate::dns::rand::RngCore;
 use std::cell::Cell;
 use std::sync::Arc;
 use tokio::net::UdpSocket;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::Mutex;
 use tokio::time::{Duration, Instant};
 
 use crate::dns::dnspkt;
@@ -42,13 +42,9 @@ use crate::dns::parse;
  * needed that DNS response still has some time to complete before the ~1s perceptual deadline is
  * hit.
  *
- * TODO:
- *  - This should be per nameserver, rather than for all nameservers.  We should construct a
- *  per nameserver object and pass that around instead of this global.
+ * This estimate is kept per nameserver (see upstream::Health), as different nameservers can have
+ * wildly different response times.
  */
-lazy_static::lazy_static! {
-    static ref DNS_TIMEOUT: RwLock<Duration> = RwLock::new(Duration::from_millis(800));
-}
 
 /* Since the DNS timeout is dynamic, we want to make sure it doesn't somehow get crazily out of
  * bounds due to some weird effects.
@@ -64,11 +60,32 @@ const MAX_DNS_TIMEOUT: Duration = Duration::from_millis(2000);
  * So we spawn a separate task per nameserver, with a channel to send queries
  * (TcpNameserverMessage) on.  This message contains a oneshot reply channel that gets the reply
  * (and possibly any errors that occurred).
+ *
+ * DNS over TLS uses exactly the same framing as TCP, so it shares this code, it just wraps the
+ * connection in TLS first.  Different routes may authenticate the same server differently, so
+ * connections are kept per address and authentication.
  */
 type TcpNameserverChannel = tokio::sync::mpsc::Sender<TcpNameserverMessage>;
+type TcpNameserverKey = (std::net::SocketAddr, Option<super::config::TlsAuth>);
+
+trait Stream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send {}
+impl<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send> Stream for T {}
+
+/* DNS over HTTPS is much simpler, as HTTP/2 already multiplexes requests over one connection and
+ * hyper matches up the replies for us.  So all we keep per nameserver is the connection, which is
+ * reopened if the server has closed it.
+ */
+type HttpsNameserverKey = (std::net::SocketAddr, super::config::HttpsEndpoint);
+type HttpsConnection = Arc<Mutex<Option<hyper::client::conn::SendRequest<hyper::Body>>>>;
+
+/* Unlike UDP, we don't retry HTTPS queries ourselves, so just give up on very slow replies. */
+const HTTPS_TIMEOUT: Duration = Duration::from_secs(10);
+/* Connecting holds up every other query to the same server, so don't wait long for it. */
+const HTTPS_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
 lazy_static::lazy_static! {
-    static ref NAMESERVER_INFO: tokio::sync::Mutex<std::collections::HashMap<std::net::SocketAddr,TcpNameserverChannel>> = Default::default();
+    static ref NAMESERVER_INFO: tokio::sync::Mutex<std::collections::HashMap<TcpNameserverKey,TcpNameserverChannel>> = Default::default();
+    static ref HTTPS_NAMESERVERS: tokio::sync::Mutex<std::collections::HashMap<HttpsNameserverKey,HttpsConnection>> = Default::default();
 
     static ref DNS_SENT_QUERIES: prometheus::IntCounterVec =
         prometheus::register_int_counter_vec!("dns_out_query_packets_sent",
@@ -86,7 +103,7 @@ lazy_static::lazy_static! {
     static ref OUT_QUERY_RESULT: prometheus::IntCounterVec =
         prometheus::register_int_counter_vec!("dns_out_query_result",
             "DNS out query results",
-            &["dns_server", "result"])
+            &["dns_server", "protocol", "result"])
         .unwrap();
 
     static ref OUT_QUERY_RETRY: prometheus::IntCounterVec =
@@ -101,10 +118,11 @@ lazy_static::lazy_static! {
             &["dns_server"])
         .unwrap();
 
-    // TODO: This should be per nameserver.
-    static ref OUT_QUERY_TIMEOUT: prometheus::IntGauge =
-        prometheus::register_int_gauge!("dns_out_query_timeout_ms",
-            "The current dynamic timeout for out queries").unwrap();
+    static ref OUT_QUERY_TIMEOUT: prometheus::IntGaugeVec =
+        prometheus::register_int_gauge_vec!("dns_out_query_timeout_ms",
+            "The current dynamic timeout for out queries",
+            &["dns_server"])
+        .unwrap();
 }
 
 #[derive(Debug)]
@@ -139,10 +157,11 @@ impl std::fmt::Display for Error {
 
 type Protocol = super::Protocol;
 
-fn increment_result(dns_server: &str, result: &Result<dnspkt::DNSPkt, Error>) {
+fn increment_result(dns_server: &str, protocol: Protocol, result: &Result<dnspkt::DNSPkt, Error>) {
     OUT_QUERY_RESULT
         .with_label_values(&[
             dns_server,
+            &protocol.to_string(),
             &match result {
                 Ok(pkt) => match pkt
                     .edns
@@ -174,17 +193,19 @@ struct TcpNameserverMessage {
 
 struct TcpNameserver {
     addr: std::net::SocketAddr,
-    tcp: Option<tokio::net::TcpStream>,
+    tls: Option<super::config::TlsAuth>,
+    tcp: Option<Box<dyn Stream>>,
     tcp_last_send_activity: Instant,
     tcp_last_recv_activity: Instant,
     qid2reply: std::collections::HashMap<u16, Responder<super::dnspkt::DNSPkt>>,
 }
 
 impl TcpNameserver {
-    fn start(addr: std::net::SocketAddr) -> TcpNameserverChannel {
+    fn start((addr, tls): TcpNameserverKey) -> TcpNameserverChannel {
         let (tx, rx) = tokio::sync::mpsc::channel(2);
         let ret = Box::new(Self {
             addr,
+            tls,
             tcp: None,
             tcp_last_send_activity: Instant::now(),
             tcp_last_recv_activity: Instant::now(),
@@ -196,19 +217,30 @@ impl TcpNameserver {
         tx
     }
 
+    fn protocol(tls: &Option<super::config::TlsAuth>) -> &'static str {
+        if tls.is_some() {
+            "TLS"
+        } else {
+            "TCP"
+        }
+    }
+
     async fn send_query_to(
         addr: &std::net::SocketAddr,
+        tls: Option<&super::config::TlsAuth>,
         out_query: super::dnspkt::DNSPkt,
     ) -> Result<super::dnspkt::DNSPkt, Error> {
+        let key = (*addr, tls.cloned());
+        let protocol = Self::protocol(&key.1);
         let chan = NAMESERVER_INFO
             .lock()
             .await
-            .entry(*addr)
-            .or_insert_with(|| TcpNameserver::start(*addr))
+            .entry(key.clone())
+            .or_insert_with(|| TcpNameserver::start(key))
             .clone();
         let (tx, rx) = tokio::sync::oneshot::channel();
         let _timer = OUT_QUERY_LATENCY
-            .with_label_values(&[&addr.to_string(), "TCP"])
+            .with_label_values(&[&addr.to_string(), protocol])
             .start_timer();
         chan.send(TcpNameserverMessage {
             out_query,
@@ -246,7 +278,7 @@ impl TcpNameserver {
             buf.extend((bytes.len() as u16).to_be_bytes().iter());
             buf.extend(bytes);
             DNS_SENT_QUERIES
-                .with_label_values(&[&self.addr.to_string(), "TCP"])
+                .with_label_values(&[&self.addr.to_string(), Self::protocol(&self.tls)])
                 .inc();
             let ret = tcp_sock.write_all(&buf).await.map_err(Error::FailedToSend);
             self.tcp_last_send_activity = Instant::now();
@@ -302,6 +334,17 @@ impl TcpNameserver {
         }
     }
 
+    async fn connect(
+        addr: std::net::SocketAddr,
+        tls: &Option<super::config::TlsAuth>,
+    ) -> Result<Box<dyn Stream>, std::io::Error> {
+        let tcp: Box<dyn Stream> = match tls {
+            Some(auth) => Box::new(super::tls::connect(addr, auth, vec![]).await?),
+            None => Box::new(tokio::net::TcpStream::connect(addr).await?),
+        };
+        Ok(tcp)
+    }
+
     async fn run(mut self, mut chan: tokio::sync::mpsc::Receiver<TcpNameserverMessage>) {
         loop {
             let last_send_activity = self.tcp_last_send_activity;
@@ -348,8 +391,12 @@ impl TcpNameserver {
                 }
             } else if let Some(msg) = chan.recv().await {
                 /* We've not already opened the tcp connection, so open it now. */
-                log::trace!("Opening new TCP channel to {}", self.addr);
-                match tokio::net::TcpStream::connect(self.addr).await {
+                log::trace!(
+                    "Opening new {} channel to {}",
+                    Self::protocol(&self.tls),
+                    self.addr
+                );
+                match Self::connect(self.addr, &self.tls).await {
                     Ok(sock) => self.tcp = Some(sock),
                     /* If we can't open the channel, report the error, and give up. */
                     Err(err) => {
@@ -372,7 +419,98 @@ impl TcpNameserver {
     }
 }
 
-fn create_outquery(id: u16, q: &dnspkt::Question) -> dnspkt::DNSPkt {
+async fn https_connect(
+    addr: std::net::SocketAddr,
+    endpoint: &super::config::HttpsEndpoint,
+) -> Result<hyper::client::conn::SendRequest<hyper::Body>, Error> {
+    log::trace!("Opening new HTTPS connection to {}", addr);
+    let tls = super::tls::connect(addr, &endpoint.tls, vec![b"h2".to_vec()])
+        .await
+        .map_err(Error::FailedToSend)?;
+    let (mut sender, connection) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake(tls)
+        .await
+        .map_err(|err| Error::FailedToSendMsg(err.to_string()))?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            log::trace!("HTTPS connection to {} closed: {}", addr, err);
+        }
+    });
+    futures::future::poll_fn(|cx| sender.poll_ready(cx))
+        .await
+        .map_err(|err| Error::FailedToSendMsg(err.to_string()))?;
+    Ok(sender)
+}
+
+async fn send_https_query(
+    addr: std::net::SocketAddr,
+    endpoint: &super::config::HttpsEndpoint,
+    out_query: dnspkt::DNSPkt,
+) -> Result<dnspkt::DNSPkt, Error> {
+    let conn = HTTPS_NAMESERVERS
+        .lock()
+        .await
+        .entry((addr, endpoint.clone()))
+        .or_default()
+        .clone();
+    let _timer = OUT_QUERY_LATENCY
+        .with_label_values(&[&addr.to_string(), "HTTPS"])
+        .start_timer();
+    let request = hyper::Request::post(endpoint.url.clone())
+        .header(hyper::header::CONTENT_TYPE, "application/dns-message")
+        .header(hyper::header::ACCEPT, "application/dns-message")
+        .body(out_query.serialise().into())
+        .map_err(|err| Error::Internal(format!("Failed to build HTTPS request: {}", err)))?;
+
+    let response = {
+        let mut conn = conn.lock().await;
+        if let Some(sender) = conn.as_mut() {
+            if futures::future::poll_fn(|cx| sender.poll_ready(cx))
+                .await
+                .is_err()
+            {
+                *conn = None;
+            }
+        }
+        let sender = match conn.as_mut() {
+            Some(sender) => sender,
+            None => conn.insert(
+                tokio::time::timeout(HTTPS_CONNECT_TIMEOUT, https_connect(addr, endpoint))
+                    .await
+                    .map_err(|_| Error::Timeout)??,
+            ),
+        };
+        DNS_SENT_QUERIES
+            .with_label_values(&[&addr.to_string(), "HTTPS"])
+            .inc();
+        sender.send_request(request)
+    };
+
+    let reply = async {
+        let response = response
+            .await
+            .map_err(|err| Error::FailedToRecvMsg(err.to_string()))?;
+        if response.status() != hyper::StatusCode::OK {
+            return Err(Error::FailedToRecvMsg(format!(
+                "HTTP status {}",
+                response.status()
+            )));
+        }
+        hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|err| Error::FailedToRecvMsg(err.to_string()))
+    };
+    let body = tokio::time::timeout(HTTPS_TIMEOUT, reply)
+        .await
+        .map_err(|_| Error::Timeout)??;
+    parse::PktParser::new(&body).get_dns().map_err(Error::Parse)
+}
+
+/* When we're validating DNSSEC ourselves, we want the signatures (DO), and we want the upstream to
+ * give us the records even if it thinks they're bogus (CD) so we can report why.
+ */
+fn create_outquery(id: u16, q: &dnspkt::Question, dnssec: bool) -> dnspkt::DNSPkt {
     dnspkt::DNSPkt {
         qid: id,
         rd: true,
@@ -381,7 +519,7 @@ fn create_outquery(id: u16, q: &dnspkt::Question) -> dnspkt::DNSPkt {
         qr: false,
         opcode: dnspkt::OPCODE_QUERY,
 
-        cd: false,
+        cd: dnssec,
         ad: false,
         ra: false,
         rcode: dnspkt::NOERROR,
@@ -389,7 +527,7 @@ fn create_outquery(id: u16, q: &dnspkt::Question) -> dnspkt::DNSPkt {
         bufsize: 4096,
 
         edns_ver: Some(0),
-        edns_do: false,
+        edns_do: dnssec,
 
         question: q.clone(),
         answer: vec![],
@@ -402,12 +540,14 @@ fn create_outquery(id: u16, q: &dnspkt::Question) -> dnspkt::DNSPkt {
 #[derive(Clone)]
 pub struct OutQuery {
     rng: Arc<Mutex<Cell<rand::rngs::OsRng>>>,
+    upstreams: Arc<super::upstream::Upstreams>,
 }
 
 impl OutQuery {
     pub fn new() -> Self {
         OutQuery {
             rng: Arc::new(Mutex::new(Cell::new(rand::rngs::OsRng::default()))),
+            upstreams: Default::default(),
         }
     }
 
@@ -459,8 +599,10 @@ impl OutQuery {
         let mut attempts = futures::stream::FuturesUnordered::new();
         log::trace!("OutQuery: {:?}", oq);
 
-        let initial_timeout: Duration = *DNS_TIMEOUT.read().await;
-        OUT_QUERY_TIMEOUT.set(initial_timeout.as_millis() as i64);
+        let initial_timeout: Duration = self.upstreams.with_health(addr, |h| h.timeout);
+        OUT_QUERY_TIMEOUT
+            .with_label_values(&[&addr.to_string()])
+            .set(initial_timeout.as_millis() as i64);
         let mut timeout = initial_timeout;
         let _timer = OUT_QUERY_LATENCY
             .with_label_values(&[&addr.to_string(), "UDP"])
@@ -481,41 +623,43 @@ impl OutQuery {
                                 // If we made multiple attempts, then we should figure out what
                                 // caused us to make multiple attempts, and try and optimise for
                                 // that situation.
-                                let mut timeout = DNS_TIMEOUT.write().await;
-                                if dur < initial_timeout {
-                                    // If the reply was shorter than the initial timeout, then
-                                    // there's a good chance that this was due to the original
-                                    // packet being lost.  In that case, we should lower our
-                                    // estimate of the initial timeout, so we recover from packet
-                                    // loss faster.  We EWMA our estimate towards the time it took.
-                                    if dur <= *timeout {
-                                        // Alpha is how fast we adapt to changes (between 0..BASE)
-                                        const ALPHA : u32 = 10;
-                                        const BASE : u32 = 1000;
-                                        let new_timeout = (dur * ALPHA + *timeout * ( BASE - ALPHA)) / BASE;
+                                self.upstreams.with_health(addr, |h| {
+                                    let timeout = &mut h.timeout;
+                                    if dur < initial_timeout {
+                                        // If the reply was shorter than the initial timeout, then
+                                        // there's a good chance that this was due to the original
+                                        // packet being lost.  In that case, we should lower our
+                                        // estimate of the initial timeout, so we recover from packet
+                                        // loss faster.  We EWMA our estimate towards the time it took.
+                                        if dur <= *timeout {
+                                            // Alpha is how fast we adapt to changes (between 0..BASE)
+                                            const ALPHA : u32 = 10;
+                                            const BASE : u32 = 1000;
+                                            let new_timeout = (dur * ALPHA + *timeout * ( BASE - ALPHA)) / BASE;
+                                            *timeout = std::cmp::max(
+                                                std::cmp::min(new_timeout, MAX_DNS_TIMEOUT),
+                                                MIN_DNS_TIMEOUT);
+                                        }
+                                    } else {
+                                        // If the reply was not shorter than the initial timeout, then
+                                        // the initial timeout was too short (the recursive server just
+                                        // took a long time to reply) and we needlessly sent a
+                                        // retransmission.  In this case, we should increase our
+                                        // estimate of the initial timeout to be at least as long as
+                                        // the timeout.
+
+                                        // We don't want to set this directly to the time it took, we
+                                        // want to provide some headroom as this is unlikely to be the
+                                        // slowest that the server can be.  So increase duration by
+                                        // the ratio HEADROOM/BASE.
+                                        const HEADROOM : u32 = 10;
+                                        const BASE : u32 = 100;
+                                        let new_timeout = dur * (BASE + HEADROOM/BASE);
                                         *timeout = std::cmp::max(
-                                            std::cmp::min(new_timeout, MAX_DNS_TIMEOUT),
+                                            std::cmp::min(std::cmp::max(*timeout, new_timeout), MAX_DNS_TIMEOUT),
                                             MIN_DNS_TIMEOUT);
                                     }
-                                } else {
-                                    // If the reply was not shorter than the initial timeout, then
-                                    // the initial timeout was too short (the recursive server just
-                                    // took a long time to reply) and we needlessly sent a
-                                    // retransmission.  In this case, we should increase our
-                                    // estimate of the initial timeout to be at least as long as
-                                    // the timeout.
-
-                                    // We don't want to set this directly to the time it took, we
-                                    // want to provide some headroom as this is unlikely to be the
-                                    // slowest that the server can be.  So increase duration by
-                                    // the ratio HEADROOM/BASE.
-                                    const HEADROOM : u32 = 10;
-                                    const BASE : u32 = 100;
-                                    let new_timeout = dur * (BASE + HEADROOM/BASE);
-                                    *timeout = std::cmp::max(
-                                        std::cmp::min(std::cmp::max(*timeout, new_timeout), MAX_DNS_TIMEOUT),
-                                        MIN_DNS_TIMEOUT);
-                                }
+                                });
                             }
                             Ok(pkt)
                         }
@@ -553,48 +697,71 @@ impl OutQuery {
         &self,
         msg: &super::DnsMessage,
         addr: std::net::SocketAddr,
+        transport: &super::config::Transport,
+        dnssec: bool,
     ) -> Result<dnspkt::DNSPkt, Error> {
+        use super::config::Transport;
         let q = &msg.in_query.question;
         let id = self.rng.lock().await.get().next_u32() as u16;
-        let oq = create_outquery(id, q);
-
-        let out_reply;
-        match msg.protocol {
-            Protocol::Udp => {
+        let oq = create_outquery(id, q, dnssec);
+
+        let (protocol, out_reply) = match (transport, &msg.protocol) {
+            /* Everything goes over TLS, regardless of how the query reached us. */
+            (Transport::DnsOverTls(auth), _) => (
+                Protocol::Tls,
+                TcpNameserver::send_query_to(&addr, Some(auth), oq).await,
+            ),
+            (Transport::DnsOverHttps(endpoint), _) => {
+                (Protocol::Https, send_https_query(addr, endpoint, oq).await)
+            }
+            /* Queries that arrived encrypted are only protected as far as us, so there's no reason
+             * to prefer the slower TCP for them.
+             */
+            (Transport::Dns, Protocol::Udp | Protocol::Tls | Protocol::Https) => {
                 /* TODO: If we have a warm TCP connection already open, _and_ we have stats that
                  * say TCP is faster than UDP (which is likely if packet loss is high), then we
                  * should skip UDP and just use the existing TCP connection.
                  */
-                let reply = self.send_udp(addr, &oq).await?;
-                if reply.qid != id {
-                    /* This smells dangerously like a kaminisky attack.  Disregard the message, and immediately
-                     * retry over TCP.
-                     */
-                    OUT_QUERY_RETRY
-                        .with_label_values(&[&addr.to_string(), "KAMINSKY"])
-                        .inc();
-                    out_reply = TcpNameserver::send_query_to(&addr, oq).await?;
-                } else if reply.tc {
-                    /* If it's a truncated reply, then retry again over TCP, so we can get the full
-                     * reply.  Truncated replies are also used by servers that suspect that we are
-                     * spoofing to get us to prove that we can perform a 3 way handshake.
-                     */
-                    OUT_QUERY_RETRY
-                        .with_label_values(&[&addr.to_string(), "TRUNCATED"])
-                        .inc();
-                    out_reply = TcpNameserver::send_query_to(&addr, oq).await?;
-                } else {
-                    out_reply = reply;
+                match self.send_udp(addr, &oq).await {
+                    Ok(reply) if reply.qid != id => {
+                        /* This smells dangerously like a kaminisky attack.  Disregard the message, and immediately
+                         * retry over TCP.
+                         */
+                        OUT_QUERY_RETRY
+                            .with_label_values(&[&addr.to_string(), "KAMINSKY"])
+                            .inc();
+                        (
+                            Protocol::Tcp,
+                            TcpNameserver::send_query_to(&addr, None, oq).await,
+                        )
+                    }
+                    Ok(reply) if reply.tc => {
+                        /* If it's a truncated reply, then retry again over TCP, so we can get the full
+                         * reply.  Truncated replies are also used by servers that suspect that we are
+                         * spoofing to get us to prove that we can perform a 3 way handshake.
+                         */
+                        OUT_QUERY_RETRY
+                            .with_label_values(&[&addr.to_string(), "TRUNCATED"])
+                            .inc();
+                        (
+                            Protocol::Tcp,
+                            TcpNameserver::send_query_to(&addr, None, oq).await,
+                        )
+                    }
+                    reply => (Protocol::Udp, reply),
                 }
             }
             /* If the original request came in on TCP, then we're going to assume that they had a
              * good reason for it (eg, a previous reply was truncated, or due to kaminsky attacks
              * or whatever), so we're going to follow suit.
              */
-            Protocol::Tcp => {
-                out_reply = TcpNameserver::send_query_to(&addr, oq).await?;
-            }
-        }
+            (Transport::Dns, Protocol::Tcp) => (
+                Protocol::Tcp,
+                TcpNameserver::send_query_to(&addr, None, oq).await,
+            ),
+        };
+        increment_result(&addr.to_string(), protocol, &out_reply);
+        let out_reply = out_reply?;
 
         if out_reply.qid != id {
             log::warn!("Mismatched ID: {} != {}", out_reply.qid, id);
@@ -603,19 +770,221 @@ impl OutQuery {
         Ok(out_reply)
     }
 
-    pub async fn handle_query(
+    async fn handle_query_server(
         &self,
         msg: &super::DnsMessage,
         addr: std::net::SocketAddr,
-    ) -> Result<dnspkt::DNSPkt, super::Error> {
+        transport: &super::config::Transport,
+        dnssec: bool,
+    ) -> Result<dnspkt::DNSPkt, Error> {
         OUT_QUERY_OUTSTANDING
             .with_label_values(&[&addr.to_string()])
             .inc();
-        let ret = self.handle_query_internal(msg, addr).await;
+        let start = Instant::now();
+        let ret = self
+            .handle_query_internal(msg, addr, transport, dnssec)
+            .await;
         OUT_QUERY_OUTSTANDING
             .with_label_values(&[&addr.to_string()])
             .dec();
-        increment_result(&addr.to_string(), &ret);
+        match ret {
+            Ok(_) => self.upstreams.success(addr, Instant::now() - start),
+            Err(_) => self.upstreams.failure(addr, Instant::now()),
+        }
+        ret
+    }
+
+    pub async fn handle_query(
+        &self,
+        msg: &super::DnsMessage,
+        forwarders: &super::config::Forwarders,
+    ) -> Result<dnspkt::DNSPkt, super::Error> {
+        let mut ret = Err(Error::Internal("No nameservers configured".into()));
+        for addr in self
+            .upstreams
+            .order(&forwarders.servers, forwarders.strategy, Instant::now())
+        {
+            ret = self
+                .handle_query_server(
+                    msg,
+                    addr,
+                    &forwarders.transport,
+                    forwarders.dnssec.is_some(),
+                )
+                .await;
+            match &ret {
+                /* Other servers might be able to give us a better answer than these. */
+                Ok(reply) if reply.rcode == dnspkt::SERVFAIL || reply.rcode == dnspkt::REFUSED => {
+                    OUT_QUERY_RETRY
+                        .with_label_values(&[&addr.to_string(), &reply.rcode.to_string()])
+                        .inc();
+                }
+                Ok(_) => break,
+                Err(e) => {
+                    log::debug!("[{:x}] {} failed: {}", msg.in_query.qid, addr, e);
+                }
+            }
+        }
         ret.map_err(super::Error::OutReply)
     }
 }
+
+/* A self signed certificate for stand in nameservers, and the SPKI pin that matches it. */
+#[cfg(test)]
+fn stand_in_tls(alpn: Vec<Vec<u8>>) -> (tokio_rustls::TlsAcceptor, Vec<u8>) {
+    use crypto::digest::Digest as _;
+    use tokio_rustls::rustls;
+    let cert = rcgen::generate_simple_self_signed(vec!["dns.example".into()]).unwrap();
+    let mut hasher = crypto::sha2::Sha256::new();
+    hasher.input(&cert.get_key_pair().public_key_der());
+    let mut pin = vec![0; hasher.output_bytes()];
+    hasher.result(&mut pin);
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(cert.serialize_der().unwrap())],
+            rustls::PrivateKey(cert.serialize_private_key_der()),
+        )
+        .unwrap();
+    config.alpn_protocols = alpn;
+    (tokio_rustls::TlsAcceptor::from(Arc::new(config)), pin)
+}
+
+/* Stand in nameservers answer every query with an empty NOERROR reply. */
+#[cfg(test)]
+fn stand_in_reply(query: &[u8]) -> Vec<u8> {
+    let mut reply = parse::PktParser::new(query).get_dns().unwrap();
+    reply.qr = true;
+    reply.ra = true;
+    reply.serialise()
+}
+
+#[cfg(test)]
+fn test_message() -> super::DnsMessage {
+    use erbium_net::addr::WithPort as _;
+    super::DnsMessage {
+        in_query: super::local::mk_query("www.example", dnspkt::RR_A),
+        in_size: 0,
+        local_ip: std::net::Ipv4Addr::LOCALHOST.into(),
+        remote_addr: std::net::Ipv4Addr::LOCALHOST.with_port(5353),
+        protocol: Protocol::Udp,
+    }
+}
+
+#[tokio::test]
+async fn test_dns_over_tls() {
+    use crate::dns::config::{Forwarders, Strategy, TlsAuth, Transport};
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    let (acceptor, pin) = stand_in_tls(vec![]);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((sock, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let mut tls = acceptor.accept(sock).await?;
+                loop {
+                    let mut len = [0u8; 2];
+                    tls.read_exact(&mut len).await?;
+                    let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+                    tls.read_exact(&mut buf).await?;
+                    let reply = stand_in_reply(&buf);
+                    tls.write_all(&(reply.len() as u16).to_be_bytes()).await?;
+                    tls.write_all(&reply).await?;
+                }
+                #[allow(unreachable_code)]
+                Ok::<_, std::io::Error>(())
+            });
+        }
+    });
+
+    let outquery = OutQuery::new();
+    let msg = test_message();
+    let forwarders = |spki_pins| Forwarders {
+        servers: vec![server],
+        strategy: Strategy::Failover,
+        transport: Transport::DnsOverTls(TlsAuth {
+            auth_name: None,
+            spki_pins,
+        }),
+        dnssec: None,
+    };
+
+    let reply = outquery
+        .handle_query(&msg, &forwarders(vec![pin]))
+        .await
+        .unwrap();
+    assert!(reply.qr);
+    assert_eq!(reply.rcode, dnspkt::NOERROR);
+    assert_eq!(reply.question, msg.in_query.question);
+
+    /* A server that doesn't match the pin is never sent the query */
+    assert!(outquery
+        .handle_query(&msg, &forwarders(vec![vec![0; 32]]))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_dns_over_https() {
+    use crate::dns::config::{Forwarders, HttpsEndpoint, Strategy, TlsAuth, Transport};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let (acceptor, pin) = stand_in_tls(vec![b"h2".to_vec()]);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        while let Ok((sock, _)) = listener.accept().await {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let tls = acceptor.accept(sock).await.unwrap();
+                let service =
+                    hyper::service::service_fn(|req: hyper::Request<hyper::Body>| async {
+                        assert_eq!(req.method(), hyper::Method::POST);
+                        assert_eq!(req.uri().path(), "/dns-query");
+                        let query = hyper::body::to_bytes(req.into_body()).await?;
+                        Ok::<_, hyper::Error>(hyper::Response::new(hyper::Body::from(
+                            stand_in_reply(&query),
+                        )))
+                    });
+                hyper::server::conn::Http::new()
+                    .http2_only(true)
+                    .serve_connection(tls, service)
+                    .await
+            });
+        }
+    });
+
+    let outquery = OutQuery::new();
+    let msg = test_message();
+    let forwarders = Forwarders {
+        servers: vec![server],
+        strategy: Strategy::Failover,
+        transport: Transport::DnsOverHttps(HttpsEndpoint {
+            url: "https://dns.example/dns-query".parse().unwrap(),
+            tls: TlsAuth {
+                auth_name: None,
+                spki_pins: vec![pin],
+            },
+        }),
+        dnssec: None,
+    };
+
+    /* Both queries are sent over the same connection */
+    let (first, second) = futures::join!(
+        outquery.handle_query(&msg, &forwarders),
+        outquery.handle_query(&msg, &forwarders)
+    );
+    for reply in [first, second].iter() {
+        let reply = reply.as_ref().unwrap();
+        assert!(reply.qr);
+        assert_eq!(reply.rcode, dnspkt::NOERROR);
+        assert_eq!(reply.question, msg.in_query.question);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
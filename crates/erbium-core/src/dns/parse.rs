@@ -90,22 +90,22 @@ impl<'l> PktParser<'l> {
             Err("Truncated Packet (u8)".into())
         }
     }
-    fn get_u8(&mut self) -> Result<u8, String> {
+    pub(crate) fn get_u8(&mut self) -> Result<u8, String> {
         let ret = self.peek_u8()?;
         self.offset += 1;
         Ok(ret)
     }
-    fn get_u16(&mut self) -> Result<u16, String> {
+    pub(crate) fn get_u16(&mut self) -> Result<u16, String> {
         Ok((self.get_u8()? as u16) * 256 + (self.get_u8()? as u16))
     }
-    fn get_u32(&mut self) -> Result<u32, String> {
+    pub(crate) fn get_u32(&mut self) -> Result<u32, String> {
         Ok((self.get_u8()? as u32) * (256 * 256 * 256)
             + (self.get_u8()? as u32) * (256 * 256)
             + (self.get_u8()? as u32) * (256)
             + (self.get_u8()? as u32))
     }
 
-    fn get_bytes(&mut self, count: usize) -> Result<Vec<u8>, String> {
+    pub(crate) fn get_bytes(&mut self, count: usize) -> Result<Vec<u8>, String> {
         if self.offset + count <= self.buffer.len() {
             let ret = self.buffer[self.offset..self.offset + count].to_vec();
             self.offset += count;
@@ -119,6 +119,13 @@ impl<'l> PktParser<'l> {
         }
     }
 
+    /// Returns everything that hasn't been read yet.
+    pub(crate) fn get_rest(&mut self) -> Vec<u8> {
+        let ret = self.buffer[self.offset.min(self.buffer.len())..].to_vec();
+        self.offset = self.buffer.len();
+        ret
+    }
+
     fn get_string(&mut self) -> Result<Vec<u8>, String> {
         let size = self.get_u8()? as usize;
         self.get_bytes(size)
@@ -175,8 +182,72 @@ impl<'l> PktParser<'l> {
 
     fn get_rdata(&mut self, rtype: dnspkt::Type) -> Result<dnspkt::RData, String> {
         use dnspkt::RData::*;
+        use std::convert::TryInto as _;
         let rdlen = self.get_u16()? as usize;
+        let end = self.offset + rdlen;
         match rtype {
+            dnspkt::RR_A => {
+                let rdata = self.get_bytes(rdlen)?;
+                let octets: [u8; 4] = rdata[..]
+                    .try_into()
+                    .map_err(|_| format!("A record with incorrect length ({})", rdlen))?;
+                Ok(A(octets.into()))
+            }
+            dnspkt::RR_AAAA => {
+                let rdata = self.get_bytes(rdlen)?;
+                let octets: [u8; 16] = rdata[..]
+                    .try_into()
+                    .map_err(|_| format!("AAAA record with incorrect length ({})", rdlen))?;
+                Ok(Aaaa(octets.into()))
+            }
+            dnspkt::RR_TXT => {
+                let mut strings = vec![];
+                while self.offset < end {
+                    strings.push(self.get_string()?);
+                }
+                if self.offset != end {
+                    return Err(format!("TXT record overruns its length ({})", rdlen));
+                }
+                Ok(Txt(strings))
+            }
+            dnspkt::RR_SRV => Ok(Srv(dnspkt::SrvData {
+                priority: self.get_u16()?,
+                weight: self.get_u16()?,
+                port: self.get_u16()?,
+                target: self.get_domain()?,
+            })),
+            dnspkt::RR_CAA => {
+                let flags = self.get_u8()?;
+                let tag = self.get_string()?;
+                let value = self.get_bytes(end.checked_sub(self.offset).ok_or("Truncated CAA")?)?;
+                Ok(Caa(dnspkt::CaaData { flags, tag, value }))
+            }
+            dnspkt::RR_SVCB | dnspkt::RR_HTTPS => {
+                let priority = self.get_u16()?;
+                let target = self.get_domain()?;
+                let mut params = vec![];
+                while self.offset < end {
+                    let key = dnspkt::SvcParamKey(self.get_u16()?);
+                    let len = self.get_u16()? as usize;
+                    params.push(dnspkt::SvcParam {
+                        key,
+                        value: self.get_bytes(len)?,
+                    });
+                }
+                if self.offset != end {
+                    return Err(format!("{} record overruns its length ({})", rtype, rdlen));
+                }
+                let svcb = dnspkt::SvcbData {
+                    priority,
+                    target,
+                    params,
+                };
+                if rtype == dnspkt::RR_HTTPS {
+                    Ok(Https(svcb))
+                } else {
+                    Ok(Svcb(svcb))
+                }
+            }
             dnspkt::RR_CNAME => {
                 Ok(CName(self.get_domain()?))
                 // TODO: assert the domain == rdlen.
@@ -396,3 +467,20 @@ fn test_parse_empty_domain() {
     let mut pkt = PktParser::new(&[0x00]);
     assert_eq!(pkt.get_domain().unwrap(), dnspkt::Domain::from(vec![]));
 }
+
+#[test]
+fn test_parse_bad_address_length() {
+    /* Root domain, type A, class IN, ttl 0, rdlen 3 */
+    let mut pkt = PktParser::new(&[0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 3, 192, 0, 2]);
+    assert!(pkt.get_rr().is_err());
+}
+
+#[test]
+fn test_parse_overlong_final_element() {
+    /* Root domain, type TXT, class IN, ttl 0, rdlen 3, but the string claims 3 bytes of text */
+    let mut pkt = PktParser::new(&[0, 0, 16, 0, 1, 0, 0, 0, 0, 0, 3, 3, b'a', b'b', b'c']);
+    assert!(pkt.get_rr().is_err());
+    /* Root domain, type SVCB, class IN, ttl 0, rdlen 7, with a parameter value running past it */
+    let mut pkt = PktParser::new(&[0, 0, 64, 0, 1, 0, 0, 0, 0, 0, 7, 0, 1, 0, 0, 1, 0, 1, 0xaa]);
+    assert!(pkt.get_rr().is_err());
+}
@@ -25,16 +25,26 @@ use super::Error;
 pub struct DnsRouteHandler {
     conf: crate::config::SharedConfig,
     next: super::cache::CacheHandler,
+    #[cfg(feature = "dhcp")]
+    leases: super::leases::LeaseHandler,
 }
 
 impl DnsRouteHandler {
     pub async fn new(conf: crate::config::SharedConfig) -> Self {
+        let cache_limits = conf.read().await.dns_cache;
         DnsRouteHandler {
             conf,
-            next: super::cache::CacheHandler::new().await,
+            next: super::cache::CacheHandler::new(cache_limits).await,
+            #[cfg(feature = "dhcp")]
+            leases: super::leases::LeaseHandler::new(),
         }
     }
 
+    #[cfg(feature = "dhcp")]
+    pub async fn set_dhcp_pool(&self, pool: crate::dhcp::worker::PoolWorker) {
+        self.leases.set_pool(pool).await
+    }
+
     pub async fn handle_query(&self, msg: &super::DnsMessage) -> Result<dnspkt::DNSPkt, Error> {
         let conf = self.conf.clone();
         let locked_conf = conf.read().await;
@@ -74,10 +84,21 @@ impl DnsRouteHandler {
                         // We will only forward queries when requested to do so.
                         Err(Error::NotAuthoritative)
                     } else {
-                        self.next.handle_query(msg, dest[0]).await
+                        self.next.handle_query(msg, dest).await
                     }
                 }
                 Handler::ForgeNxDomain => Err(Error::Blocked),
+                Handler::LocalZone(ref records) => Ok(super::local::handle_query(
+                    records,
+                    best_suffix.unwrap(),
+                    &msg.in_query,
+                )),
+                #[cfg(feature = "dhcp")]
+                Handler::DhcpLeases => {
+                    self.leases
+                        .handle_query(&locked_conf, route, best_suffix.unwrap(), &msg.in_query)
+                        .await
+                }
             }
         } else {
             Err(Error::NoRouteConfigured)
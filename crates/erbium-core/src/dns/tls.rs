@@ -0,0 +1,253 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  TLS connections to upstream nameservers (DNS over TLS, RFC7858, and DNS over HTTPS, RFC8484),
+ *  and from clients.
+ *
+ *  RFC8310 describes two ways of authenticating the server: checking the certificate is valid for
+ *  an "authentication domain name", and checking that a known public key (an "SPKI pin") is in the
+ *  certificate chain.  We support either, or both together.
+ */
+
+use super::config::TlsAuth;
+use std::sync::Arc;
+use tokio_rustls::rustls;
+
+lazy_static::lazy_static! {
+    static ref SYSTEM_ROOTS: Arc<rustls::RootCertStore> = Arc::new(load_system_roots());
+}
+
+fn load_system_roots() -> rustls::RootCertStore {
+    let mut roots = rustls::RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            let certs = certs.into_iter().map(|cert| cert.0).collect::<Vec<_>>();
+            let (_added, ignored) = roots.add_parsable_certificates(&certs);
+            if ignored > 0 {
+                log::warn!("Ignored {} unparsable system CA certificates", ignored);
+            }
+        }
+        Err(err) => log::warn!("Failed to load system CA certificates: {}", err),
+    }
+    roots
+}
+
+/* Reads one DER element from the front of buf, returning the tag, the contents, and whatever
+ * follows it.
+ */
+fn der_element(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *buf.first()?;
+    let first = *buf.get(1)?;
+    let (header, len) = if first < 0x80 {
+        (2, first as usize)
+    } else {
+        let octets = (first & 0x7f) as usize;
+        if octets == 0 || octets > 4 {
+            return None;
+        }
+        let len = buf
+            .get(2..2 + octets)?
+            .iter()
+            .fold(0, |acc, x| (acc << 8) | *x as usize);
+        (2 + octets, len)
+    };
+    let end = header.checked_add(len)?;
+    Some((tag, buf.get(header..end)?, &buf[end..]))
+}
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_EXPLICIT_0: u8 = 0xa0;
+
+/* Finds the (DER encoded) SubjectPublicKeyInfo in a certificate, as per RFC5280 Section 4.1 */
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    let (tag, cert, _) = der_element(cert)?;
+    if tag != DER_SEQUENCE {
+        return None;
+    }
+    let (tag, mut tbs, _) = der_element(cert)?;
+    if tag != DER_SEQUENCE {
+        return None;
+    }
+    /* The version is optional */
+    if tbs.first() == Some(&DER_EXPLICIT_0) {
+        tbs = der_element(tbs)?.2;
+    }
+    /* Skip the serial number, signature algorithm, issuer, validity and subject */
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.2;
+    }
+    match der_element(tbs)? {
+        (DER_SEQUENCE, _, rest) => Some(&tbs[..tbs.len() - rest.len()]),
+        _ => None,
+    }
+}
+
+fn spki_pin(cert: &rustls::Certificate) -> Option<Vec<u8>> {
+    use crypto::digest::Digest as _;
+    let mut hasher = crypto::sha2::Sha256::new();
+    hasher.input(spki(&cert.0)?);
+    let mut digest = vec![0; hasher.output_bytes()];
+    hasher.result(&mut digest);
+    Some(digest)
+}
+
+struct Verifier {
+    webpki: Option<rustls::client::WebPkiVerifier>,
+    spki_pins: Vec<Vec<u8>>,
+}
+
+impl rustls::client::ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        if self.spki_pins.is_empty() {
+            /* Config parsing makes sure we have at least one way to check the server. */
+            let webpki = self.webpki.as_ref().expect("no way to verify server");
+            return webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            );
+        }
+        /* We can only trust the intermediates to be part of the chain if the chain has been
+         * verified, otherwise a server could send any certificate along with its own.  The TLS
+         * handshake proves the server holds the key for the end entity certificate.
+         */
+        let chain: &[rustls::Certificate] = if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+            intermediates
+        } else {
+            &[]
+        };
+        if std::iter::once(end_entity)
+            .chain(chain)
+            .filter_map(spki_pin)
+            .any(|pin| self.spki_pins.contains(&pin))
+        {
+            Ok(rustls::client::ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "Certificate chain does not match any configured SPKI pin".into(),
+            ))
+        }
+    }
+}
+
+/// Opens a TLS connection to a nameserver, checking its certificate as configured, and offering
+/// the given ALPN protocols.
+pub async fn connect(
+    addr: std::net::SocketAddr,
+    auth: &TlsAuth,
+    alpn: Vec<Vec<u8>>,
+) -> Result<tokio_rustls::client::TlsStream<tokio::net::TcpStream>, std::io::Error> {
+    use std::convert::TryFrom as _;
+    let server_name = match &auth.auth_name {
+        Some(name) => rustls::ServerName::try_from(name.as_str()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid TLS authentication name {:?}", name),
+            )
+        })?,
+        None => rustls::ServerName::IpAddress(addr.ip()),
+    };
+    let verifier = Verifier {
+        webpki: auth
+            .auth_name
+            .as_ref()
+            .map(|_| rustls::client::WebPkiVerifier::new(SYSTEM_ROOTS.clone(), None)),
+        spki_pins: auth.spki_pins.clone(),
+    };
+    let mut config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    config.alpn_protocols = alpn;
+    let tcp = tokio::net::TcpStream::connect(addr).await?;
+    tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(server_name, tcp)
+        .await
+}
+
+fn read_pem(path: &std::path::Path) -> Result<Vec<rustls_pemfile::Item>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    rustls_pemfile::read_all(&mut std::io::BufReader::new(file))
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+}
+
+/// Loads a certificate chain and private key (both PEM encoded) for serving DNS over TLS or
+/// HTTPS, offering the given ALPN protocols.
+pub fn acceptor(
+    certificate: &std::path::Path,
+    key: &std::path::Path,
+    alpn: Vec<Vec<u8>>,
+) -> Result<tokio_rustls::TlsAcceptor, String> {
+    use rustls_pemfile::Item;
+    let chain = read_pem(certificate)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(cert) => Some(rustls::Certificate(cert)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if chain.is_empty() {
+        return Err(format!(
+            "No certificates found in {}",
+            certificate.display()
+        ));
+    }
+    let key = read_pem(key)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => {
+                Some(rustls::PrivateKey(key))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("No private key found in {}", key.display()))?;
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(|err| format!("Invalid certificate {}: {}", certificate.display(), err))?;
+    config.alpn_protocols = alpn;
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
+
+#[test]
+fn test_spki() {
+    let cert = rcgen::generate_simple_self_signed(vec!["dns.example".into()]).unwrap();
+    let der = cert.serialize_der().unwrap();
+    assert_eq!(spki(&der), Some(&cert.get_key_pair().public_key_der()[..]));
+    assert_eq!(spki(&der[..der.len() - 1]), None);
+    assert_eq!(spki(b""), None);
+}
@@ -0,0 +1,233 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Tracks the health of upstream nameservers, and picks which ones to send queries to.
+ */
+
+use super::config::Strategy;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::time::{Duration, Instant};
+
+/* See the comment in outquery.rs for why we start with 800ms */
+pub const INITIAL_TIMEOUT: Duration = Duration::from_millis(800);
+
+/* After this many queries in a row fail, stop sending queries to the server for a while. */
+const FAILURES_BEFORE_DOWN: u32 = 3;
+
+/* How long to leave a failed server alone for before trying it again.  This doubles each time a
+ * server fails again, up to the maximum.
+ */
+const MIN_HOLDDOWN: Duration = Duration::from_secs(5);
+const MAX_HOLDDOWN: Duration = Duration::from_secs(300);
+
+lazy_static::lazy_static! {
+    static ref UPSTREAM_HEALTHY: prometheus::IntGaugeVec =
+        prometheus::register_int_gauge_vec!("dns_out_server_healthy",
+            "If the upstream nameserver is currently considered healthy",
+            &["dns_server"])
+        .unwrap();
+
+    static ref UPSTREAM_SRTT: prometheus::IntGaugeVec =
+        prometheus::register_int_gauge_vec!("dns_out_server_srtt_ms",
+            "Smoothed round trip time to the upstream nameserver",
+            &["dns_server"])
+        .unwrap();
+}
+
+#[derive(Debug)]
+pub struct Health {
+    /// The dynamic initial timeout for UDP queries to this server.
+    pub timeout: Duration,
+    srtt: Option<Duration>,
+    failures: u32,
+    holddown: Duration,
+    down_until: Option<Instant>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            timeout: INITIAL_TIMEOUT,
+            srtt: None,
+            failures: 0,
+            holddown: MIN_HOLDDOWN,
+            down_until: None,
+        }
+    }
+}
+
+impl Health {
+    pub fn is_up(&self, now: Instant) -> bool {
+        self.down_until.map(|until| until <= now).unwrap_or(true)
+    }
+
+    /// The server gave us a reply.
+    fn success(&mut self, rtt: Duration) {
+        /* EWMA with an alpha of 1/8, like TCP (RFC6298) */
+        self.srtt = Some(match self.srtt {
+            None => rtt,
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+        });
+        self.failures = 0;
+        self.holddown = MIN_HOLDDOWN;
+        self.down_until = None;
+    }
+
+    /// The server failed to give us a reply.  Returns true if this marked the server down.
+    fn failure(&mut self, now: Instant) -> bool {
+        self.failures += 1;
+        if self.failures < FAILURES_BEFORE_DOWN {
+            return false;
+        }
+        let was_down = self.down_until.is_some();
+        if was_down {
+            /* A retry after a holddown failed again, so back off further */
+            self.holddown = std::cmp::min(self.holddown * 2, MAX_HOLDDOWN);
+        }
+        self.down_until = Some(now + self.holddown);
+        !was_down
+    }
+}
+
+#[derive(Default)]
+pub struct Upstreams {
+    health: std::sync::Mutex<HashMap<SocketAddr, Health>>,
+    next: AtomicUsize,
+}
+
+impl Upstreams {
+    /// Runs `f` with the health of `addr`, creating it if this is the first time we've seen it.
+    pub fn with_health<R>(&self, addr: SocketAddr, f: impl FnOnce(&mut Health) -> R) -> R {
+        f(self.health.lock().unwrap().entry(addr).or_default())
+    }
+
+    pub fn success(&self, addr: SocketAddr, rtt: Duration) {
+        let srtt = self.with_health(addr, |h| {
+            h.success(rtt);
+            h.srtt.unwrap_or_default()
+        });
+        let server = addr.to_string();
+        UPSTREAM_HEALTHY.with_label_values(&[&server]).set(1);
+        UPSTREAM_SRTT
+            .with_label_values(&[&server])
+            .set(srtt.as_millis() as i64);
+    }
+
+    pub fn failure(&self, addr: SocketAddr, now: Instant) {
+        if self.with_health(addr, |h| h.failure(now)) {
+            log::warn!("Upstream nameserver {} is not responding", addr);
+            UPSTREAM_HEALTHY
+                .with_label_values(&[&addr.to_string()])
+                .set(0);
+        }
+    }
+
+    /// Returns the order in which servers should be tried for a query.
+    ///
+    /// Servers that are up are always tried before servers that are down, but servers that are
+    /// down are still included as a last resort.
+    pub fn order(
+        &self,
+        servers: &[SocketAddr],
+        strategy: Strategy,
+        now: Instant,
+    ) -> Vec<SocketAddr> {
+        let mut order = servers.to_vec();
+        match strategy {
+            Strategy::Failover => (),
+            Strategy::RoundRobin => {
+                if !order.is_empty() {
+                    let start = self.next.fetch_add(1, Ordering::Relaxed) % order.len();
+                    order.rotate_left(start);
+                }
+            }
+            Strategy::Fastest => {
+                /* Servers we haven't heard from yet sort first, so we find out how fast they are. */
+                let health = self.health.lock().unwrap();
+                order.sort_by_key(|addr| health.get(addr).and_then(|h| h.srtt));
+            }
+        }
+        let health = self.health.lock().unwrap();
+        /* This is a stable sort, so it keeps the order chosen above within up and down servers. */
+        order.sort_by_key(|addr| !health.get(addr).map(|h| h.is_up(now)).unwrap_or(true));
+        order
+    }
+}
+
+#[test]
+fn test_health() {
+    let now = Instant::now();
+    let mut h = Health::default();
+    assert!(h.is_up(now));
+    assert!(!h.failure(now));
+    assert!(!h.failure(now));
+    assert!(h.failure(now));
+    assert!(!h.is_up(now));
+    assert!(h.is_up(now + MIN_HOLDDOWN));
+    /* Failing again after the holddown backs off further */
+    assert!(!h.failure(now + MIN_HOLDDOWN));
+    assert!(!h.is_up(now + MIN_HOLDDOWN * 2));
+    assert!(h.is_up(now + MIN_HOLDDOWN * 3));
+    h.success(Duration::from_millis(10));
+    assert!(h.is_up(now));
+    assert_eq!(h.srtt, Some(Duration::from_millis(10)));
+    h.success(Duration::from_millis(90));
+    assert_eq!(h.srtt, Some(Duration::from_millis(20)));
+}
+
+#[test]
+fn test_order() {
+    let a: SocketAddr = "192.0.2.1:53".parse().unwrap();
+    let b: SocketAddr = "192.0.2.2:53".parse().unwrap();
+    let c: SocketAddr = "192.0.2.3:53".parse().unwrap();
+    let servers = [a, b, c];
+    let now = Instant::now();
+    let upstreams = Upstreams::default();
+
+    assert_eq!(
+        upstreams.order(&servers, Strategy::Failover, now),
+        [a, b, c]
+    );
+    assert_eq!(
+        upstreams.order(&servers, Strategy::RoundRobin, now),
+        [a, b, c]
+    );
+    assert_eq!(
+        upstreams.order(&servers, Strategy::RoundRobin, now),
+        [b, c, a]
+    );
+
+    upstreams.success(a, Duration::from_millis(50));
+    upstreams.success(b, Duration::from_millis(10));
+    /* c has never been tried, so it's tried first to find out how fast it is */
+    assert_eq!(upstreams.order(&servers, Strategy::Fastest, now), [c, b, a]);
+    upstreams.success(c, Duration::from_millis(30));
+    assert_eq!(upstreams.order(&servers, Strategy::Fastest, now), [b, c, a]);
+
+    for _ in 0..FAILURES_BEFORE_DOWN {
+        upstreams.failure(a, now);
+    }
+    assert_eq!(
+        upstreams.order(&servers, Strategy::Failover, now),
+        [b, c, a]
+    );
+    assert_eq!(
+        upstreams.order(&servers, Strategy::Failover, now + MIN_HOLDDOWN),
+        [a, b, c]
+    );
+}
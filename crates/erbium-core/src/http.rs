@@ -104,6 +104,26 @@ async fn serve_metrics(_req: Request<Body>) -> Result<Response<Body>, Infallible
         .unwrap())
 }
 
+fn format_lease(li: &crate::dhcp::pool::LeaseInfo) -> String {
+    format!(
+        "{{ \"ip\": \"{}\", \"client_id\": \"{}\", \"start\": {}, \"expire\": {}{} }}",
+        li.ip,
+        li.client_id
+            .iter()
+            .map(|b| format!("{:0>2x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+        li.start,
+        li.expire,
+        crate::dhcp::dhcppkt::parse_options(crate::pktparser::Buffer::new(&li.options))
+            .ok()
+            .and_then(|o| o.get_hostname())
+            .map(|h| format!(", \"host-name\": {:?}", h))
+            .or_else(|| Some("".to_string()))
+            .unwrap(),
+    )
+}
+
 async fn serve_leases(
     _req: Request<Body>,
     dhcp: &std::sync::Arc<crate::dhcp::DhcpService>,
@@ -114,23 +134,7 @@ async fn serve_leases(
         "{{ \"leases\" : [\n{}\n]}}\n",
         leases
             .iter()
-            .map(|li| format!(
-                " {{ \"ip\": \"{}\", \"client_id\": \"{}\", \"start\": {}, \"expire\": {}{} }}",
-                li.ip,
-                li.client_id
-                    .iter()
-                    .map(|b| format!("{:0>2x}", b))
-                    .collect::<Vec<_>>()
-                    .join(":"),
-                li.start,
-                li.expire,
-                crate::dhcp::dhcppkt::parse_options(crate::pktparser::Buffer::new(&li.options))
-                    .ok()
-                    .and_then(|o| o.get_hostname())
-                    .map(|h| format!(", \"host-name\": {:?}", h))
-                    .or_else(|| Some("".to_string()))
-                    .unwrap(),
-            ))
+            .map(|li| format!(" {}", format_lease(li)))
             .collect::<Vec<_>>()
             .join(",\n")
     );
@@ -142,6 +146,142 @@ async fn serve_leases(
         .unwrap())
 }
 
+/* Splits a query string into its parameters.  None of the values the API takes need to be
+ * percent-decoded.
+ */
+fn parse_query(query: Option<&str>) -> std::collections::HashMap<&str, &str> {
+    query
+        .unwrap_or("")
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key, value),
+            None => (param, ""),
+        })
+        .collect()
+}
+
+/* Parses hex bytes, optionally separated by colons (eg 00:11:22:33:44:55) */
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let digits = s.replace(':', "");
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [_, _] => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn error_response(status: hyper::StatusCode, message: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain")
+        .body(format!("{}\n", message).into())
+        .unwrap()
+}
+
+/* Handles changes to a single lease under /api/v1/leases/<address> */
+async fn serve_lease_update(
+    req: Request<Body>,
+    path: &str,
+    dhcp: &std::sync::Arc<crate::dhcp::DhcpService>,
+) -> Result<Response<Body>, Infallible> {
+    use crate::dhcp::pool;
+    use hyper::{Method, StatusCode};
+
+    let (addr, action) = match path.split_once('/') {
+        Some((addr, action)) => (addr, Some(action)),
+        None => (path, None),
+    };
+    let addr: std::net::Ipv4Addr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(_) => {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("{:?} is not an IPv4 address", addr),
+            ))
+        }
+    };
+    let query = parse_query(req.uri().query());
+    let duration = match query.get("duration").map(|d| d.parse()) {
+        Some(Ok(secs)) => Some(std::time::Duration::from_secs(secs)),
+        Some(Err(_)) => {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                "duration should be a number of seconds".into(),
+            ))
+        }
+        None => None,
+    };
+
+    let result = match (req.method(), action) {
+        (&Method::PUT, None) => {
+            /* Clients nearly always identify themselves by their hardware type (1 for ethernet)
+             * followed by their MAC address, so reserve the address for that.
+             */
+            let clientid = match (query.get("mac"), query.get("client-id")) {
+                (Some(mac), None) => parse_hex(mac)
+                    .filter(|mac| mac.len() == 6)
+                    .map(|mac| [&[1u8][..], &mac].concat()),
+                (None, Some(clientid)) => parse_hex(clientid).filter(|c| !c.is_empty()),
+                _ => None,
+            };
+            match clientid {
+                Some(clientid) => dhcp
+                    .reserve_lease(clientid, addr, duration.unwrap_or(pool::DEFAULT_MAX_LEASE))
+                    .await
+                    .map(Some),
+                None => {
+                    return Ok(error_response(
+                        StatusCode::BAD_REQUEST,
+                        "Expected either a mac or a client-id".into(),
+                    ))
+                }
+            }
+        }
+        (&Method::POST, Some("extend")) => match duration {
+            Some(duration) => dhcp.extend_lease(addr, duration).await.map(Some),
+            None => {
+                return Ok(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "Missing duration".into(),
+                ))
+            }
+        },
+        (&Method::POST, Some("expire")) => dhcp.expire_lease(addr).await.map(Some),
+        (&Method::DELETE, None) => dhcp.delete_lease(addr).await.map(|()| None),
+        _ => return Ok(error_response(StatusCode::NOT_FOUND, "Not found".into())),
+    };
+
+    match result {
+        Ok(Some(lease)) => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-type", "application/json")
+            .body(format!("{}\n", format_lease(&lease)).into())
+            .unwrap()),
+        Ok(None) => Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Body::empty())
+            .unwrap()),
+        Err(e) => {
+            let status = match e {
+                pool::Error::NoSuchLease => StatusCode::NOT_FOUND,
+                pool::Error::InvalidDuration => StatusCode::BAD_REQUEST,
+                pool::Error::RequestedAddressInUse | pool::Error::PeerAddress => {
+                    StatusCode::CONFLICT
+                }
+                _ => {
+                    log::warn!("Failed to update lease for {}: {}", addr, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            Ok(error_response(status, e.to_string()))
+        }
+    }
+}
+
 fn permission_denied() -> Response<Body> {
     use hyper::StatusCode;
     Response::builder()
@@ -183,7 +323,10 @@ async fn serve_request(
 ) -> Result<Response<Body>, Infallible> {
     use hyper::{Method, StatusCode};
 
-    let client = acl::Attributes { addr: *addr };
+    let client = acl::Attributes {
+        addr: *addr,
+        ..Default::default()
+    };
 
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/") => {
@@ -210,6 +353,18 @@ async fn serve_request(
             }
         }
         (&Method::GET, "/api/v1/leases.json") => serve_leases(req, &dhcp).await,
+        (_, path) if path.starts_with("/api/v1/leases/") => {
+            if let Some(ret) = require_http_permission(
+                &conf.read().await.acls,
+                &client,
+                acl::PermissionType::HttpRw,
+            ) {
+                Ok(ret)
+            } else {
+                let path = path["/api/v1/leases/".len()..].to_string();
+                serve_lease_update(req, &path, &dhcp).await
+            }
+        }
         _ => {
             if let Some(ret) = require_http_permission(
                 &conf.read().await.acls,
@@ -365,3 +520,20 @@ pub async fn run(
     }
     Ok(())
 }
+
+#[test]
+fn test_parse_query() {
+    let query = parse_query(Some("mac=00:11:22:33:44:55&duration=3600&flag"));
+    assert_eq!(query.get("mac"), Some(&"00:11:22:33:44:55"));
+    assert_eq!(query.get("duration"), Some(&"3600"));
+    assert_eq!(query.get("flag"), Some(&""));
+    assert!(parse_query(None).is_empty());
+
+    assert_eq!(
+        parse_hex("00:11:22:aa:BB:cc"),
+        Some(vec![0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc])
+    );
+    assert_eq!(parse_hex("01ab"), Some(vec![0x01, 0xab]));
+    assert_eq!(parse_hex("01a"), None);
+    assert_eq!(parse_hex("zz"), None);
+}
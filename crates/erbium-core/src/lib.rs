@@ -21,6 +21,8 @@ pub mod acl;
 pub mod config;
 #[cfg(feature = "dhcp")]
 pub mod dhcp;
+#[cfg(feature = "dhcp")]
+pub mod dhcp6;
 #[cfg(any(feature = "dns", fuzzing))]
 pub mod dns;
 #[cfg(feature = "http")]
@@ -28,6 +30,8 @@ pub mod http;
 pub mod lldp;
 pub mod pktparser;
 pub mod radv;
+#[cfg(feature = "tftp")]
+pub mod tftp;
 
 #[cfg(test)]
 mod test_man_configs;
@@ -109,6 +109,8 @@ impl NDOptions {
                 (&DNSSL, _) => false,
                 (&CAPTIVE_PORTAL, &NDOptionValue::CaptivePortal(_)) => true,
                 (&CAPTIVE_PORTAL, _) => false,
+                (&PREFIX_INFO, &NDOptionValue::Prefix(_)) => true,
+                (&PREFIX_INFO, _) => false,
                 (_, _) => unimplemented!(),
             })
             .cloned()
@@ -218,6 +218,16 @@ impl RaAdvService {
         }
 
         for prefix in &intf.prefixes {
+            /* Prefixes that are set aside for delegation to downstream routers are not on this
+             * link.
+             */
+            #[cfg(feature = "dhcp")]
+            if config
+                .dhcp6
+                .is_delegated(&crate::config::Prefix6::new(prefix.addr, prefix.prefixlen))
+            {
+                continue;
+            }
             options.add_option(icmppkt::NDOptionValue::Prefix(icmppkt::AdvPrefix {
                 prefixlen: prefix.prefixlen,
                 onlink: prefix.onlink,
@@ -597,6 +607,37 @@ fn test_build_announcement() {
     icmppkt::serialise(&icmppkt::Icmp6::RtrAdvert(msg));
 }
 
+#[cfg(feature = "dhcp")]
+#[test]
+fn test_delegated_prefixes_not_announced() {
+    let mut conf = crate::config::Config::default();
+    conf.dhcp6.delegations = vec![crate::dhcp6::config::Delegation {
+        prefix: crate::config::Prefix6::new("2001:db8:100::".parse().unwrap(), 48),
+        delegated_length: 56,
+    }];
+    let mk_prefix = |addr: &str| config::Prefix {
+        addr: addr.parse().unwrap(),
+        prefixlen: 64,
+        onlink: true,
+        autonomous: true,
+        valid: std::time::Duration::from_secs(3600),
+        preferred: std::time::Duration::from_secs(1800),
+    };
+    let msg = RaAdvService::build_announcement_pure(
+        &conf,
+        &config::Interface {
+            name: "eth0".into(),
+            prefixes: vec![mk_prefix("2001:db8::"), mk_prefix("2001:db8:100:1::")],
+            ..Default::default()
+        },
+        None,
+        None,
+        std::net::Ipv6Addr::UNSPECIFIED,
+        ADV_DEFAULT_LIFETIME,
+    );
+    assert_eq!(msg.options.find_option(icmppkt::PREFIX_INFO).len(), 1);
+}
+
 #[test]
 fn test_default_values() {
     let conf = crate::config::Config {
@@ -0,0 +1,70 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  TFTP Configuration parsing.
+ */
+
+pub use crate::config::*;
+use erbium_net::addr::{NetAddr, ToNetAddr as _, WithPort as _, UNSPECIFIED6};
+use yaml_rust::yaml;
+
+const TFTP_PORT: u16 = 69;
+
+#[derive(Debug)]
+pub struct Config {
+    /// The directory that files are served from.
+    pub root: std::path::PathBuf,
+    pub listeners: Vec<NetAddr>,
+}
+
+pub fn parse(name: &str, fragment: &yaml::Yaml) -> Result<Option<Config>, Error> {
+    match fragment {
+        yaml::Yaml::Null => Ok(None),
+        yaml::Yaml::Hash(h) => {
+            let mut root = None;
+            let mut listeners = None;
+            for (k, v) in h {
+                match (k.as_str(), v) {
+                    (Some("root"), r) => root = parse_string("root", r)?,
+                    (Some("listeners"), l) => {
+                        listeners = parse_array("listeners", l, parse_string_sockaddr)?
+                    }
+                    (Some(m), _) => {
+                        return Err(Error::InvalidConfig(format!("Unknown {} key {}", name, m)))
+                    }
+                    (None, _) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{} keys are expected to be strings",
+                            name
+                        )))
+                    }
+                }
+            }
+            Ok(Some(Config {
+                root: root
+                    .ok_or_else(|| Error::InvalidConfig(format!("{} requires a root", name)))?
+                    .into(),
+                listeners: listeners
+                    .unwrap_or_else(|| vec![UNSPECIFIED6.with_port(TFTP_PORT).to_net_addr()]),
+            }))
+        }
+        e => Err(Error::InvalidConfig(format!(
+            "Expected hash for {}, got {}",
+            name,
+            type_to_name(e)
+        ))),
+    }
+}
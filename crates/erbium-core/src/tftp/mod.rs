@@ -0,0 +1,706 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  Read only TFTP server, for serving network boot files.
+ */
+
+use crate::acl;
+use erbium_net::addr::{NetAddr, NetAddrExt as _};
+use erbium_net::udp;
+use std::collections::VecDeque;
+use tokio::io::AsyncReadExt as _;
+
+pub(crate) mod config;
+pub mod tftppkt;
+
+use tftppkt::Packet;
+
+/* RFC2348: The block size option */
+const DEFAULT_BLOCK_SIZE: usize = 512;
+const MIN_BLOCK_SIZE: usize = 8;
+const MAX_BLOCK_SIZE: usize = 65464;
+
+/* RFC7440 allows windows of up to 65535 blocks, but that's a lot of memory to hold on to for one
+ * client, so we offer clients a smaller window if they ask for more than this.
+ */
+const MAX_WINDOW_SIZE: u16 = 64;
+
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+const MAX_RETRIES: u32 = 5;
+
+lazy_static::lazy_static! {
+    static ref TFTP_REQUESTS: prometheus::IntCounterVec =
+        prometheus::register_int_counter_vec!("tftp_requests",
+            "Number of TFTP requests received, by result",
+            &["result"])
+        .unwrap();
+    static ref TFTP_ACTIVE: prometheus::IntGauge =
+        prometheus::register_int_gauge!("tftp_active_transfers",
+            "Number of TFTP transfers in progress")
+        .unwrap();
+    static ref TFTP_SENT_BYTES: prometheus::IntCounter =
+        prometheus::register_int_counter!("tftp_sent_bytes",
+            "Number of bytes of files sent over TFTP")
+        .unwrap();
+    static ref TFTP_RETRANSMITS: prometheus::IntCounter =
+        prometheus::register_int_counter!("tftp_retransmits",
+            "Number of times TFTP packets were sent again after a timeout")
+        .unwrap();
+}
+
+pub enum Error {
+    ListenError(std::io::Error, Box<NetAddr>),
+    RecvError(std::io::Error),
+    SendError(std::io::Error),
+    ParseError(String),
+    RefusedByAcl(acl::AclError),
+    ReadOnly,
+    UnsupportedMode,
+    BadPath(String),
+    FileError(std::io::Error),
+    Timeout,
+    Aborted(tftppkt::ErrorCode, String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Error::*;
+        match self {
+            ListenError(io, addr) => write!(f, "Failed to listen for TFTP on {}: {}", addr, io),
+            RecvError(io) => write!(f, "Failed to receive TFTP packet: {}", io),
+            SendError(io) => write!(f, "Failed to send TFTP packet: {}", io),
+            ParseError(msg) => write!(f, "Failed to parse TFTP request: {}", msg),
+            RefusedByAcl(why) => write!(f, "Request refused by policy: {}", why),
+            ReadOnly => write!(f, "Writes are not supported"),
+            UnsupportedMode => write!(f, "Unsupported transfer mode"),
+            BadPath(path) => write!(f, "Refusing to serve {:?}", path),
+            FileError(io) => write!(f, "{}", io),
+            Timeout => write!(f, "Timed out"),
+            Aborted(code, msg) => write!(f, "Aborted by client: {:?}: {}", code, msg),
+        }
+    }
+}
+
+impl Error {
+    /// The error, if any, to send back to the client.
+    fn to_packet(&self) -> Option<Packet> {
+        use Error::*;
+        let code = match self {
+            ParseError(_) | UnsupportedMode => tftppkt::ERR_ILLEGAL_OPERATION,
+            RefusedByAcl(_) | ReadOnly | BadPath(_) => tftppkt::ERR_ACCESS_VIOLATION,
+            FileError(io) if io.kind() == std::io::ErrorKind::NotFound => {
+                tftppkt::ERR_FILE_NOT_FOUND
+            }
+            FileError(io) if io.kind() == std::io::ErrorKind::PermissionDenied => {
+                tftppkt::ERR_ACCESS_VIOLATION
+            }
+            FileError(_) => tftppkt::ERR_NOT_DEFINED,
+            ListenError(_, _) | RecvError(_) | SendError(_) | Timeout | Aborted(_, _) => {
+                return None
+            }
+        };
+        /* Don't leak details about the server's filesystem to the client */
+        Some(Packet::Error {
+            code,
+            message: format!("{:?}", code),
+        })
+    }
+
+    fn metric_label(&self) -> &'static str {
+        use Error::*;
+        match self {
+            ListenError(_, _) | RecvError(_) | SendError(_) => "io error",
+            ParseError(_) | UnsupportedMode => "bad request",
+            RefusedByAcl(_) => "refused by acl",
+            ReadOnly | BadPath(_) => "access violation",
+            FileError(io) if io.kind() == std::io::ErrorKind::NotFound => "not found",
+            FileError(_) => "file error",
+            Timeout => "timeout",
+            Aborted(_, _) => "aborted",
+        }
+    }
+}
+
+/// Maps the filename a client asked for to a path relative to the TFTP root.
+///
+/// Leading slashes are ignored, and ".." is refused so that clients can't escape the root.  Some
+/// PXE clients use backslashes as a path separator, so those are accepted too.
+fn relative_path(filename: &str) -> Option<std::path::PathBuf> {
+    let mut path = std::path::PathBuf::new();
+    for component in filename.split(['/', '\\']) {
+        match component {
+            "" | "." => (),
+            ".." => return None,
+            c => path.push(c),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Finds the file for `filename` under `root`.
+///
+/// As well as refusing "..", this refuses symlinks that point outside of the root.
+async fn resolve_path(root: &std::path::Path, filename: &str) -> Result<std::path::PathBuf, Error> {
+    let relative = relative_path(filename).ok_or_else(|| Error::BadPath(filename.into()))?;
+    let root = tokio::fs::canonicalize(root)
+        .await
+        .map_err(Error::FileError)?;
+    let path = tokio::fs::canonicalize(root.join(relative))
+        .await
+        .map_err(Error::FileError)?;
+    if path.starts_with(&root) {
+        Ok(path)
+    } else {
+        Err(Error::BadPath(filename.into()))
+    }
+}
+
+/* RFC1350 Appendix: netascii uses CR LF for end of line, and CR NUL for a bare CR. */
+fn to_netascii(data: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(data.len());
+    for &b in data {
+        match b {
+            b'\n' => ret.extend(b"\r\n"),
+            b'\r' => ret.extend(b"\r\0"),
+            b => ret.push(b),
+        }
+    }
+    ret
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct TransferOptions {
+    blksize: usize,
+    windowsize: u16,
+}
+
+/// RFC2347: Works out which of the options the client asked for we will use, and the options to
+/// acknowledge.  Options we don't understand (or values we don't like) are ignored.
+fn negotiate_options(
+    req: &tftppkt::Request,
+    size: Option<u64>,
+) -> (TransferOptions, Vec<(String, String)>) {
+    let mut opts = TransferOptions {
+        blksize: DEFAULT_BLOCK_SIZE,
+        windowsize: 1,
+    };
+    let mut oack = vec![];
+    for (name, value) in &req.options {
+        match name.as_str() {
+            /* RFC2348 */
+            "blksize" => {
+                if let Some(blksize) = value.parse::<usize>().ok().filter(|&b| b >= MIN_BLOCK_SIZE)
+                {
+                    opts.blksize = std::cmp::min(blksize, MAX_BLOCK_SIZE);
+                    oack.push((name.clone(), opts.blksize.to_string()));
+                }
+            }
+            /* RFC2349: The client sends 0, and we reply with the size of the file. */
+            "tsize" => {
+                if let Some(size) = size {
+                    oack.push((name.clone(), size.to_string()));
+                }
+            }
+            /* RFC7440 */
+            "windowsize" => {
+                if let Some(windowsize) = value.parse::<u16>().ok().filter(|&w| w >= 1) {
+                    opts.windowsize = std::cmp::min(windowsize, MAX_WINDOW_SIZE);
+                    oack.push((name.clone(), opts.windowsize.to_string()));
+                }
+            }
+            _ => (),
+        }
+    }
+    (opts, oack)
+}
+
+/// Reads a file one block at a time, converting to netascii if required.
+struct BlockReader {
+    file: tokio::fs::File,
+    netascii: bool,
+    pending: Vec<u8>,
+    eof: bool,
+}
+
+impl BlockReader {
+    async fn next_block(&mut self, size: usize) -> Result<Vec<u8>, std::io::Error> {
+        let mut buf = vec![0; size];
+        while self.pending.len() < size && !self.eof {
+            let len = self.file.read(&mut buf).await?;
+            if len == 0 {
+                self.eof = true;
+            } else if self.netascii {
+                self.pending.extend(to_netascii(&buf[..len]));
+            } else {
+                self.pending.extend(&buf[..len]);
+            }
+        }
+        let len = std::cmp::min(size, self.pending.len());
+        Ok(self.pending.drain(..len).collect())
+    }
+}
+
+struct Transfer {
+    sock: tokio::net::UdpSocket,
+}
+
+impl Transfer {
+    async fn send(&self, pkt: &Packet) -> Result<(), Error> {
+        self.sock
+            .send(&pkt.serialise())
+            .await
+            .map(|_| ())
+            .map_err(Error::SendError)
+    }
+
+    /// Waits for an ACK until `deadline`, returning None if none arrived.
+    async fn recv_ack(&self, deadline: tokio::time::Instant) -> Result<Option<u16>, Error> {
+        let mut buf = vec![0; 1500];
+        loop {
+            let len = match tokio::time::timeout_at(deadline, self.sock.recv(&mut buf)).await {
+                Ok(len) => len.map_err(Error::RecvError)?,
+                Err(_) => return Ok(None),
+            };
+            match tftppkt::parse(&buf[..len]) {
+                Ok(Packet::Ack { block }) => return Ok(Some(block)),
+                Ok(Packet::Error { code, message }) => return Err(Error::Aborted(code, message)),
+                /* Anything else is ignored */
+                _ => (),
+            }
+        }
+    }
+
+    /// Sends an option acknowledgement, and waits for the client to acknowledge it.
+    async fn send_oack(&self, oack: Vec<(String, String)>) -> Result<(), Error> {
+        let pkt = Packet::OptionAck(oack);
+        for _ in 0..MAX_RETRIES {
+            self.send(&pkt).await?;
+            let deadline = tokio::time::Instant::now() + TIMEOUT;
+            while let Some(block) = self.recv_ack(deadline).await? {
+                if block == 0 {
+                    return Ok(());
+                }
+            }
+            TFTP_RETRANSMITS.inc();
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Sends the file, `windowsize` blocks at a time (RFC7440).  Returns the number of bytes sent.
+    async fn send_file(
+        &self,
+        reader: &mut BlockReader,
+        opts: &TransferOptions,
+    ) -> Result<u64, Error> {
+        /* The blocks that have been sent but not acknowledged, starting at block number `base` */
+        let mut window: VecDeque<Vec<u8>> = VecDeque::new();
+        let mut base: u64 = 1;
+        let mut finished_reading = false;
+        let mut sent: u64 = 0;
+        let mut retries = 0;
+        loop {
+            while window.len() < opts.windowsize.into() && !finished_reading {
+                let data = reader
+                    .next_block(opts.blksize)
+                    .await
+                    .map_err(Error::FileError)?;
+                /* A block shorter than the block size (even an empty one) ends the transfer */
+                finished_reading = data.len() < opts.blksize;
+                window.push_back(data);
+            }
+            if window.is_empty() {
+                return Ok(sent);
+            }
+            for (i, data) in window.iter().enumerate() {
+                self.send(&Packet::Data {
+                    /* Block numbers wrap around for large files */
+                    block: (base + i as u64) as u16,
+                    data: data.clone(),
+                })
+                .await?;
+            }
+            let deadline = tokio::time::Instant::now() + TIMEOUT;
+            let mut acked = 0;
+            while acked == 0 {
+                match self.recv_ack(deadline).await? {
+                    Some(block) => {
+                        /* Old and duplicate ACKs are ignored, otherwise we would send the window
+                         * again for each of them.
+                         */
+                        let count = block.wrapping_sub((base - 1) as u16) as usize;
+                        if count <= window.len() {
+                            acked = count;
+                        }
+                    }
+                    None => break,
+                }
+            }
+            if acked == 0 {
+                retries += 1;
+                if retries > MAX_RETRIES {
+                    return Err(Error::Timeout);
+                }
+                TFTP_RETRANSMITS.inc();
+                continue;
+            }
+            retries = 0;
+            for data in window.drain(..acked) {
+                sent += data.len() as u64;
+            }
+            base += acked as u64;
+        }
+    }
+}
+
+pub struct TftpService {
+    conf: crate::config::SharedConfig,
+    listeners: Vec<udp::UdpSocket>,
+}
+
+impl TftpService {
+    async fn listen(addr: &NetAddr) -> Result<udp::UdpSocket, Error> {
+        let udp = udp::UdpSocket::bind(&[*addr])
+            .await
+            .map_err(|e| Error::ListenError(e, Box::new(*addr)))?;
+        if addr.as_sockaddr_in6().is_some() {
+            udp.set_opt_ipv6_packet_info(true)
+        } else {
+            udp.set_opt_ipv4_packet_info(true)
+        }
+        .map_err(|e| Error::ListenError(e, Box::new(*addr)))?;
+        log::info!("Listening for TFTP on {}", addr);
+        Ok(udp)
+    }
+
+    /// Returns None if TFTP isn't configured.
+    pub async fn new(conf: crate::config::SharedConfig) -> Result<Option<Self>, Error> {
+        let mut listeners = vec![];
+        if let Some(tftp) = &conf.read().await.tftp {
+            for addr in &tftp.listeners {
+                listeners.push(Self::listen(addr).await?);
+            }
+        } else {
+            return Ok(None);
+        }
+        Ok(Some(Self { conf, listeners }))
+    }
+
+    async fn handle_request(
+        conf: &crate::config::SharedConfig,
+        transfer: &Transfer,
+        remote: NetAddr,
+        buffer: &[u8],
+    ) -> Result<(String, u64), Error> {
+        let req = match tftppkt::parse(buffer).map_err(|e| Error::ParseError(e.to_string()))? {
+            Packet::ReadRequest(req) => req,
+            Packet::WriteRequest(_) => return Err(Error::ReadOnly),
+            pkt => return Err(Error::ParseError(format!("Unexpected {:?}", pkt))),
+        };
+        let root = {
+            let conf = conf.read().await;
+            acl::require_permission(
+                &conf.acls,
+                &acl::Attributes {
+                    addr: remote,
+                    ..Default::default()
+                },
+                acl::PermissionType::Tftp,
+            )
+            .map_err(Error::RefusedByAcl)?;
+            match &conf.tftp {
+                Some(tftp) => tftp.root.clone(),
+                None => return Err(Error::BadPath(req.filename)),
+            }
+        };
+        let netascii = match req.mode {
+            tftppkt::Mode::Octet => false,
+            tftppkt::Mode::NetAscii => true,
+            tftppkt::Mode::Mail => return Err(Error::UnsupportedMode),
+        };
+
+        let path = resolve_path(&root, &req.filename).await?;
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(Error::FileError)?;
+        let metadata = file.metadata().await.map_err(Error::FileError)?;
+        if !metadata.is_file() {
+            return Err(Error::BadPath(req.filename));
+        }
+        /* We don't know how big a netascii file will be until we've converted it */
+        let (opts, oack) = negotiate_options(&req, Some(metadata.len()).filter(|_| !netascii));
+        if !oack.is_empty() {
+            transfer.send_oack(oack).await?;
+        }
+        let mut reader = BlockReader {
+            file,
+            netascii,
+            pending: vec![],
+            eof: false,
+        };
+        let sent = transfer.send_file(&mut reader, &opts).await?;
+        Ok((req.filename, sent))
+    }
+
+    async fn run_transfer(
+        conf: crate::config::SharedConfig,
+        local_ip: std::net::IpAddr,
+        remote: NetAddr,
+        buffer: Vec<u8>,
+    ) {
+        let remote_addr = match remote.to_std_socket_addr() {
+            Some(addr) => addr,
+            None => return,
+        };
+        /* RFC1350 Section 4: Each transfer uses a new port (transfer ID) */
+        let sock = match tokio::net::UdpSocket::bind((local_ip, 0)).await {
+            Ok(sock) => sock,
+            Err(e) => {
+                log::warn!("{}: Failed to create TFTP transfer socket: {}", remote, e);
+                TFTP_REQUESTS.with_label_values(&["io error"]).inc();
+                return;
+            }
+        };
+        if let Err(e) = sock.connect(remote_addr).await {
+            log::warn!("{}: Failed to connect TFTP transfer socket: {}", remote, e);
+            TFTP_REQUESTS.with_label_values(&["io error"]).inc();
+            return;
+        }
+        let transfer = Transfer { sock };
+
+        TFTP_ACTIVE.inc();
+        let result = Self::handle_request(&conf, &transfer, remote, &buffer).await;
+        TFTP_ACTIVE.dec();
+
+        match result {
+            Ok((filename, sent)) => {
+                log::info!("{}: Sent {} ({} bytes) over TFTP", remote, filename, sent);
+                TFTP_SENT_BYTES.inc_by(sent);
+                TFTP_REQUESTS.with_label_values(&["ok"]).inc();
+            }
+            Err(err) => {
+                log::warn!("{}: TFTP: {}", remote, err);
+                TFTP_REQUESTS.with_label_values(&[err.metric_label()]).inc();
+                if let Some(pkt) = err.to_packet() {
+                    let _ = transfer.send(&pkt).await;
+                }
+            }
+        }
+    }
+
+    async fn run_listener(
+        conf: crate::config::SharedConfig,
+        listener: udp::UdpSocket,
+    ) -> Result<(), Error> {
+        loop {
+            let rm = match listener.recv_msg(65536, udp::MsgFlags::empty()).await {
+                Ok(rm) => rm,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(Error::RecvError(err)),
+            };
+            if let (Some(local_ip), Some(remote)) = (rm.local_ip(), rm.address) {
+                tokio::spawn(Self::run_transfer(
+                    conf.clone(),
+                    local_ip,
+                    remote,
+                    rm.buffer,
+                ));
+            }
+        }
+    }
+
+    pub async fn run(self) -> Result<(), Error> {
+        use futures::StreamExt as _;
+        let mut services = futures::stream::FuturesUnordered::new();
+        for listener in self.listeners {
+            services.push(tokio::spawn(Self::run_listener(
+                self.conf.clone(),
+                listener,
+            )));
+        }
+        match services.next().await {
+            Some(Ok(ret)) => ret,
+            Some(Err(err)) => Err(Error::RecvError(err.into())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_relative_path() {
+    use std::path::PathBuf;
+    assert_eq!(
+        relative_path("pxelinux.0"),
+        Some(PathBuf::from("pxelinux.0"))
+    );
+    assert_eq!(
+        relative_path("/boot//grub/./grub.cfg"),
+        Some(PathBuf::from("boot/grub/grub.cfg"))
+    );
+    assert_eq!(
+        relative_path("boot\\x64\\wdsnbp.com"),
+        Some(PathBuf::from("boot/x64/wdsnbp.com"))
+    );
+    assert_eq!(relative_path("../etc/passwd"), None);
+    assert_eq!(relative_path("boot/../../etc/passwd"), None);
+    assert_eq!(relative_path("..\\etc\\passwd"), None);
+    assert_eq!(relative_path("/"), None);
+}
+
+#[tokio::test]
+async fn test_resolve_path() {
+    let root = std::env::temp_dir().join(format!("erbium-tftp-test-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("boot")).unwrap();
+    std::fs::write(root.join("boot/ipxe.efi"), b"ipxe").unwrap();
+    std::os::unix::fs::symlink("/etc", root.join("escape")).unwrap();
+
+    assert_eq!(
+        resolve_path(&root, "/boot/ipxe.efi").await.ok(),
+        Some(root.canonicalize().unwrap().join("boot/ipxe.efi"))
+    );
+    assert!(matches!(
+        resolve_path(&root, "escape/passwd").await,
+        Err(Error::BadPath(_))
+    ));
+    assert!(matches!(
+        resolve_path(&root, "missing").await,
+        Err(Error::FileError(_))
+    ));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_to_netascii() {
+    assert_eq!(to_netascii(b"a\nb\rc"), b"a\r\nb\r\0c");
+}
+
+#[test]
+fn test_negotiate_options() {
+    let req = tftppkt::Request {
+        filename: "ipxe.efi".into(),
+        mode: tftppkt::Mode::Octet,
+        options: vec![
+            ("blksize".into(), "100000".into()),
+            ("tsize".into(), "0".into()),
+            ("windowsize".into(), "4".into()),
+            ("unknown".into(), "1".into()),
+        ],
+    };
+    assert_eq!(
+        negotiate_options(&req, Some(1234)),
+        (
+            TransferOptions {
+                blksize: MAX_BLOCK_SIZE,
+                windowsize: 4,
+            },
+            vec![
+                ("blksize".into(), MAX_BLOCK_SIZE.to_string()),
+                ("tsize".into(), "1234".into()),
+                ("windowsize".into(), "4".into()),
+            ]
+        )
+    );
+    /* Invalid values are ignored */
+    let req = tftppkt::Request {
+        options: vec![("blksize".into(), "4".into()), ("tsize".into(), "0".into())],
+        ..req
+    };
+    assert_eq!(
+        negotiate_options(&req, None),
+        (
+            TransferOptions {
+                blksize: DEFAULT_BLOCK_SIZE,
+                windowsize: 1,
+            },
+            vec![]
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_transfer() {
+    let root = std::env::temp_dir().join(format!("erbium-tftp-xfer-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let contents: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+    std::fs::write(root.join("ipxe.efi"), &contents).unwrap();
+    let conf = crate::config::load_config_from_string_for_test(&format!(
+        "tftp: {{ root: {} }}",
+        root.display()
+    ))
+    .expect("Failed to parse test config");
+
+    let client = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    server.connect(client.local_addr().unwrap()).await.unwrap();
+    client.connect(server.local_addr().unwrap()).await.unwrap();
+    let transfer = Transfer { sock: server };
+    let request = Packet::ReadRequest(tftppkt::Request {
+        filename: "/ipxe.efi".into(),
+        mode: tftppkt::Mode::Octet,
+        options: vec![
+            ("tsize".into(), "0".into()),
+            ("windowsize".into(), "2".into()),
+        ],
+    })
+    .serialise();
+    let remote: NetAddr = client.local_addr().unwrap().into();
+    let server = TftpService::handle_request(&conf, &transfer, remote, &request);
+
+    let client = async {
+        let mut buf = vec![0; 1500];
+        let mut received: Vec<u8> = vec![];
+        let len = client.recv(&mut buf).await.unwrap();
+        assert_eq!(
+            tftppkt::parse(&buf[..len]).unwrap(),
+            Packet::OptionAck(vec![
+                ("tsize".into(), "2000".into()),
+                ("windowsize".into(), "2".into()),
+            ])
+        );
+        client
+            .send(&Packet::Ack { block: 0 }.serialise())
+            .await
+            .unwrap();
+        loop {
+            let len = client.recv(&mut buf).await.unwrap();
+            match tftppkt::parse(&buf[..len]).unwrap() {
+                Packet::Data { block, data } => {
+                    received.extend(&data);
+                    /* Only acknowledge the end of each window */
+                    if block % 2 == 0 || data.len() < DEFAULT_BLOCK_SIZE {
+                        client
+                            .send(&Packet::Ack { block }.serialise())
+                            .await
+                            .unwrap();
+                    }
+                    if data.len() < DEFAULT_BLOCK_SIZE {
+                        return received;
+                    }
+                }
+                pkt => panic!("Unexpected {:?}", pkt),
+            }
+        }
+    };
+    let (result, received) = futures::join!(server, client);
+    assert!(matches!(result, Ok((_, 2000))));
+    assert_eq!(received, contents);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
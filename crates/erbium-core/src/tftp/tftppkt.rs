@@ -0,0 +1,248 @@
+/*   Copyright 2021 Perry Lorier
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ *  SPDX-License-Identifier: Apache-2.0
+ *
+ *  TFTP packet parsing and serialisation (RFC1350, with options from RFC2347).
+ */
+
+use crate::pktparser::{Buffer, ParseError};
+
+/* RFC1350 Section 5: TFTP Packets */
+pub const OP_RRQ: u16 = 1;
+pub const OP_WRQ: u16 = 2;
+pub const OP_DATA: u16 = 3;
+pub const OP_ACK: u16 = 4;
+pub const OP_ERROR: u16 = 5;
+pub const OP_OACK: u16 = 6; /* RFC2347 */
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode(pub u16);
+pub const ERR_NOT_DEFINED: ErrorCode = ErrorCode(0);
+pub const ERR_FILE_NOT_FOUND: ErrorCode = ErrorCode(1);
+pub const ERR_ACCESS_VIOLATION: ErrorCode = ErrorCode(2);
+pub const ERR_ILLEGAL_OPERATION: ErrorCode = ErrorCode(4);
+pub const ERR_UNKNOWN_TID: ErrorCode = ErrorCode(5);
+pub const ERR_OPTION_REFUSED: ErrorCode = ErrorCode(8); /* RFC2347 */
+
+impl std::fmt::Debug for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ERR_NOT_DEFINED => write!(f, "Not defined"),
+            ERR_FILE_NOT_FOUND => write!(f, "File not found"),
+            ERR_ACCESS_VIOLATION => write!(f, "Access violation"),
+            ErrorCode(3) => write!(f, "Disk full"),
+            ERR_ILLEGAL_OPERATION => write!(f, "Illegal TFTP operation"),
+            ERR_UNKNOWN_TID => write!(f, "Unknown transfer ID"),
+            ErrorCode(6) => write!(f, "File already exists"),
+            ErrorCode(7) => write!(f, "No such user"),
+            ERR_OPTION_REFUSED => write!(f, "Option negotiation refused"),
+            ErrorCode(x) => write!(f, "Error #{}", x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    NetAscii,
+    Octet,
+    Mail,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub filename: String,
+    pub mode: Mode,
+    /* RFC2347: Option names are case insensitive, so they are lowercased when parsed. */
+    pub options: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn get_option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Packet {
+    ReadRequest(Request),
+    WriteRequest(Request),
+    Data { block: u16, data: Vec<u8> },
+    Ack { block: u16 },
+    Error { code: ErrorCode, message: String },
+    OptionAck(Vec<(String, String)>),
+}
+
+fn get_string(buf: &mut Buffer) -> Result<String, ParseError> {
+    let mut s = vec![];
+    loop {
+        match buf.get_u8().ok_or(ParseError::UnexpectedEndOfInput)? {
+            0 => break,
+            c => s.push(c),
+        }
+    }
+    String::from_utf8(s).map_err(|e| ParseError::InvalidArgument(e.to_string()))
+}
+
+fn get_options(buf: &mut Buffer) -> Result<Vec<(String, String)>, ParseError> {
+    let mut options = vec![];
+    while !buf.empty() {
+        let name = get_string(buf)?.to_ascii_lowercase();
+        let value = get_string(buf)?;
+        options.push((name, value));
+    }
+    Ok(options)
+}
+
+fn get_request(buf: &mut Buffer) -> Result<Request, ParseError> {
+    let filename = get_string(buf)?;
+    let mode = match get_string(buf)?.to_ascii_lowercase().as_str() {
+        "netascii" => Mode::NetAscii,
+        "octet" => Mode::Octet,
+        "mail" => Mode::Mail,
+        m => return Err(ParseError::InvalidArgument(format!("Unknown mode {}", m))),
+    };
+    let options = get_options(buf)?;
+    Ok(Request {
+        filename,
+        mode,
+        options,
+    })
+}
+
+pub fn parse(pkt: &[u8]) -> Result<Packet, ParseError> {
+    let mut buf = Buffer::new(pkt);
+    let opcode = buf.get_be16().ok_or(ParseError::UnexpectedEndOfInput)?;
+    match opcode {
+        OP_RRQ => Ok(Packet::ReadRequest(get_request(&mut buf)?)),
+        OP_WRQ => Ok(Packet::WriteRequest(get_request(&mut buf)?)),
+        OP_DATA => Ok(Packet::Data {
+            block: buf.get_be16().ok_or(ParseError::UnexpectedEndOfInput)?,
+            data: buf.get_vec(buf.remaining()).unwrap_or_default(),
+        }),
+        OP_ACK => Ok(Packet::Ack {
+            block: buf.get_be16().ok_or(ParseError::UnexpectedEndOfInput)?,
+        }),
+        OP_ERROR => Ok(Packet::Error {
+            code: ErrorCode(buf.get_be16().ok_or(ParseError::UnexpectedEndOfInput)?),
+            /* Some clients forget to null terminate the message */
+            message: get_string(&mut buf).unwrap_or_default(),
+        }),
+        OP_OACK => Ok(Packet::OptionAck(get_options(&mut buf)?)),
+        x => Err(ParseError::InvalidArgument(format!("Unknown opcode {}", x))),
+    }
+}
+
+fn put_string(v: &mut Vec<u8>, s: &str) {
+    v.extend(s.as_bytes());
+    v.push(0);
+}
+
+fn put_request(v: &mut Vec<u8>, opcode: u16, req: &Request) {
+    v.extend(opcode.to_be_bytes());
+    put_string(v, &req.filename);
+    put_string(
+        v,
+        match req.mode {
+            Mode::NetAscii => "netascii",
+            Mode::Octet => "octet",
+            Mode::Mail => "mail",
+        },
+    );
+    for (k, val) in &req.options {
+        put_string(v, k);
+        put_string(v, val);
+    }
+}
+
+impl Packet {
+    pub fn serialise(&self) -> Vec<u8> {
+        let mut v = vec![];
+        match self {
+            Packet::ReadRequest(req) => put_request(&mut v, OP_RRQ, req),
+            Packet::WriteRequest(req) => put_request(&mut v, OP_WRQ, req),
+            Packet::Data { block, data } => {
+                v.extend(OP_DATA.to_be_bytes());
+                v.extend(block.to_be_bytes());
+                v.extend(data);
+            }
+            Packet::Ack { block } => {
+                v.extend(OP_ACK.to_be_bytes());
+                v.extend(block.to_be_bytes());
+            }
+            Packet::Error { code, message } => {
+                v.extend(OP_ERROR.to_be_bytes());
+                v.extend(code.0.to_be_bytes());
+                put_string(&mut v, message);
+            }
+            Packet::OptionAck(options) => {
+                v.extend(OP_OACK.to_be_bytes());
+                for (k, val) in options {
+                    put_string(&mut v, k);
+                    put_string(&mut v, val);
+                }
+            }
+        }
+        v
+    }
+}
+
+#[test]
+fn test_parse_rrq() {
+    let pkt = parse(b"\x00\x01pxelinux.0\x00OCTET\x00BLKSIZE\x001428\x00tsize\x000\x00").unwrap();
+    assert_eq!(
+        pkt,
+        Packet::ReadRequest(Request {
+            filename: "pxelinux.0".into(),
+            mode: Mode::Octet,
+            options: vec![
+                ("blksize".into(), "1428".into()),
+                ("tsize".into(), "0".into())
+            ],
+        })
+    );
+    if let Packet::ReadRequest(req) = pkt {
+        assert_eq!(req.get_option("blksize"), Some("1428"));
+        assert_eq!(req.get_option("windowsize"), None);
+    }
+    /* Missing null terminator */
+    assert!(parse(b"\x00\x01pxelinux.0\x00octet").is_err());
+    assert!(parse(b"\x00\x01pxelinux.0\x00binary\x00").is_err());
+}
+
+#[test]
+fn test_roundtrip() {
+    for pkt in [
+        Packet::Data {
+            block: 65535,
+            data: vec![1, 2, 3],
+        },
+        Packet::Ack { block: 7 },
+        Packet::Error {
+            code: ERR_FILE_NOT_FOUND,
+            message: "File not found".into(),
+        },
+        Packet::OptionAck(vec![("blksize".into(), "1428".into())]),
+        Packet::WriteRequest(Request {
+            filename: "upload".into(),
+            mode: Mode::NetAscii,
+            options: vec![],
+        }),
+    ] {
+        assert_eq!(parse(&pkt.serialise()).unwrap(), pkt);
+    }
+}
@@ -417,6 +417,80 @@ impl NetLinkNetInfo {
         }
     }
 
+    /// Adds (or replaces) or deletes an IPv6 route, waiting for the kernel to acknowledge it.
+    async fn send_route6_change(
+        add: bool,
+        prefix: std::net::Ipv6Addr,
+        prefixlen: u8,
+        nexthop: std::net::Ipv6Addr,
+        ifidx: u32,
+    ) -> Result<(), std::io::Error> {
+        use netlink_packet_route::rtnl::nlas::route::Nla;
+        use netlink_packet_route::RouteHeader;
+        let mut socket = Socket::new(protocols::NETLINK_ROUTE)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+
+        let route = RouteMessage {
+            header: RouteHeader {
+                address_family: AF_INET6 as u8,
+                destination_prefix_length: prefixlen,
+                table: RT_TABLE_MAIN,
+                protocol: RTPROT_DHCP,
+                scope: RT_SCOPE_UNIVERSE,
+                kind: RTN_UNICAST,
+                ..Default::default()
+            },
+            nlas: vec![
+                Nla::Destination(prefix.octets().to_vec()),
+                Nla::Gateway(nexthop.octets().to_vec()),
+                Nla::Oif(ifidx),
+            ],
+        };
+        let (flags, payload) = if add {
+            (
+                NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_REPLACE,
+                NewRoute(route),
+            )
+        } else {
+            (NLM_F_REQUEST | NLM_F_ACK, DelRoute(route))
+        };
+        let mut packet = NetlinkMessage {
+            header: NetlinkHeader {
+                flags,
+                sequence_number: 1,
+                ..Default::default()
+            },
+            payload: NetlinkPayload::from(payload),
+        };
+        packet.finalize();
+        let mut buf = vec![0; packet.header.length as usize];
+        assert!(buf.len() == packet.buffer_len());
+        packet.serialize(&mut buf[..]);
+        socket.send(&buf[..]).await?;
+
+        let mut receive_buffer = vec![0; 4096];
+        let size = socket.recv(&mut receive_buffer[..]).await?;
+        match <NetlinkMessage<RtnlMessage>>::deserialize(&receive_buffer[..size]) {
+            Ok(NetlinkMessage {
+                payload: NetlinkPayload::Ack(_),
+                ..
+            }) => Ok(()),
+            Ok(NetlinkMessage {
+                payload: NetlinkPayload::Error(e),
+                ..
+            }) => Err(std::io::Error::from_raw_os_error(-e.code)),
+            Ok(m) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unexpected netlink reply: {:?}", m),
+            )),
+            Err(e) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e.to_string(),
+            )),
+        }
+    }
+
     async fn process_message(sni: &SharedNetInfo, rx_packet: &NetlinkMessage<RtnlMessage>) -> bool {
         match &rx_packet.payload {
             InnerMessage(NewLink(link)) => {
@@ -646,6 +720,27 @@ impl SharedNetInfo {
             .next()
     }
 
+    /// Installs a route to an IPv6 prefix via a nexthop, replacing any existing route.
+    pub async fn add_route6(
+        &self,
+        prefix: std::net::Ipv6Addr,
+        prefixlen: u8,
+        nexthop: std::net::Ipv6Addr,
+        ifidx: u32,
+    ) -> Result<(), std::io::Error> {
+        NetLinkNetInfo::send_route6_change(true, prefix, prefixlen, nexthop, ifidx).await
+    }
+
+    pub async fn del_route6(
+        &self,
+        prefix: std::net::Ipv6Addr,
+        prefixlen: u8,
+        nexthop: std::net::Ipv6Addr,
+        ifidx: u32,
+    ) -> Result<(), std::io::Error> {
+        NetLinkNetInfo::send_route6_change(false, prefix, prefixlen, nexthop, ifidx).await
+    }
+
     pub async fn get_ipv6_default_route(
         &self,
     ) -> Option<(Option<std::net::Ipv6Addr>, Option<u32>)> {
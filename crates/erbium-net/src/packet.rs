@@ -193,6 +193,36 @@ impl<'a> Fragment<'a> {
             t,
         )
     }
+
+    /// Builds a broadcast ARP probe (RFC5227 Section 2.1.1) asking who has `target`.  The sender
+    /// IP address is all zeros so that the probe doesn't pollute other hosts' ARP caches.
+    pub fn new_arp_probe(srcmac: &[u8; 6], target: &net::Ipv4Addr) -> Fragment<'static> {
+        let mut f = Self::from_tail(Tail::None);
+        f.push_be16(0x0001); /* Hardware type: Ethernet */
+        f.push_be16(0x0800); /* Protocol type: IPv4 */
+        f.push_u8(6); /* Hardware address length */
+        f.push_u8(4); /* Protocol address length */
+        f.push_be16(0x0001); /* Operation: Request */
+        f.push_bytes(srcmac);
+        f.push_bytes(&net::Ipv4Addr::UNSPECIFIED.octets());
+        f.push_bytes(&[0; 6]);
+        f.push_bytes(&target.octets());
+        Self::new_ethernet(&[0xff; 6], srcmac, 0x0806_u16, Tail::Fragment(Box::new(f)))
+    }
+
+    /// Builds an ICMP echo request, suitable for sending on a raw IPv4 socket.
+    pub fn new_icmp4_echo<'l>(id: u16, seq: u16, payload: Tail<'l>) -> Fragment<'l> {
+        let mut f = Self::from_tail(payload);
+        f.push_u8(8); /* Type: Echo Request */
+        f.push_u8(0); /* Code */
+        f.push_be16(0x0000); /* Checksum - filled in below */
+        f.push_be16(id);
+        f.push_be16(seq);
+        let netsum = f.netsum();
+        f.buffer[2] = (netsum >> 8) as u8;
+        f.buffer[3] = (netsum & 0xFF) as u8;
+        f
+    }
 }
 
 #[test]
@@ -207,6 +237,26 @@ fn test_udp_packet() {
     println!("u={:?}", u);
 }
 
+#[test]
+fn test_icmp4_echo() {
+    let echo = Fragment::new_icmp4_echo(0x1234, 1, Tail::Payload(b"erbium")).flatten();
+    assert_eq!(&echo[..2], &[8, 0]);
+    assert_eq!(&echo[4..8], &[0x12, 0x34, 0x00, 0x01]);
+    assert_eq!(finish_netsum(partial_netsum(0, &echo)), 0);
+}
+
+#[test]
+fn test_arp_probe() {
+    let probe =
+        Fragment::new_arp_probe(&[2, 0, 0, 0, 0, 1], &"192.0.2.1".parse().unwrap()).flatten();
+    assert_eq!(probe.len(), 14 + 28);
+    assert_eq!(&probe[..6], &[0xff; 6]);
+    assert_eq!(&probe[12..14], &[0x08, 0x06]);
+    /* Sender protocol address is unspecified */
+    assert_eq!(&probe[28..32], &[0, 0, 0, 0]);
+    assert_eq!(&probe[38..42], &[192, 0, 2, 1]);
+}
+
 #[test]
 fn test_checksum() {
     let data = vec![8, 0, 0, 0, 0x12, 0x34, 0x00, 0x01];
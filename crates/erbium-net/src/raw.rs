@@ -59,6 +59,7 @@ impl From<IpProto> for u16 {
 pub struct EthProto(u16);
 impl EthProto {
     pub const IP4: EthProto = EthProto(0x0800);
+    pub const ARP: EthProto = EthProto(0x0806);
     pub const ALL: EthProto = EthProto(0x0003);
     pub const LLDP: EthProto = EthProto(0x88cc);
 }
@@ -353,3 +353,25 @@ pub fn set_ipv6_multicast_hoplimit(fd: RawFd, val: i32) -> Result<(), nix::Error
         nix::errno::Errno::result(res).map(drop)
     }
 }
+
+pub fn set_ipv6_add_membership(
+    fd: RawFd,
+    group: std::net::Ipv6Addr,
+    ifindex: u32,
+) -> Result<(), nix::Error> {
+    /* nix's Ipv6MembershipRequest always uses interface 0, so do it ourselves. */
+    let mreq = libc::ipv6_mreq {
+        ipv6mr_multiaddr: std_to_libc_in6_addr(group),
+        ipv6mr_interface: ifindex,
+    };
+    unsafe {
+        let res = libc::setsockopt(
+            fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_ADD_MEMBERSHIP,
+            &mreq as *const libc::ipv6_mreq as *const libc::c_void,
+            std::mem::size_of::<libc::ipv6_mreq>() as libc::socklen_t,
+        );
+        nix::errno::Errno::result(res).map(drop)
+    }
+}
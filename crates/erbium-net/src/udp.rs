@@ -120,6 +120,15 @@ impl UdpSocket {
         .map_err(|e| e.into())
     }
 
+    pub fn set_opt_ipv6_add_membership(
+        &self,
+        group: net::Ipv6Addr,
+        ifindex: u32,
+    ) -> Result<(), io::Error> {
+        crate::socket::set_ipv6_add_membership(self.fd.get_ref().as_raw_fd(), group, ifindex)
+            .map_err(|e| e.into())
+    }
+
     pub fn set_opt_reuse_port(&self, b: bool) -> Result<(), io::Error> {
         nix::sys::socket::setsockopt(
             self.fd.get_ref().as_raw_fd(),
@@ -18,7 +18,7 @@
  */
 
 use futures::StreamExt as _;
-use log::{error, info};
+use log::{error, info, warn};
 
 use erbium::*;
 
@@ -48,17 +48,27 @@ async fn go() -> Result<(), Error> {
      * Currently we don't do anything smart with the command line.
      */
     let args: Vec<_> = std::env::args_os().collect();
-    if args.len() > 2 || (args.len() == 2 && args[1].to_string_lossy().starts_with('-')) {
-        return Err(Error::CommandLine(format!(
-            "Usage: {} <configfile>",
+    let usage = || {
+        Error::CommandLine(format!(
+            "Usage: {} [--state-dir <dir>] [<configfile>]",
             args[0].to_string_lossy()
-        )));
-    }
-    let config_file = if args.len() == 1 {
-        std::path::Path::new("erbium.conf")
-    } else {
-        std::path::Path::new(&args[1])
+        ))
     };
+    let mut state_dir = None;
+    let mut config_file = None;
+    let mut remaining = args.iter().skip(1);
+    while let Some(arg) = remaining.next() {
+        if arg == "--state-dir" {
+            state_dir = Some(std::path::PathBuf::from(
+                remaining.next().ok_or_else(usage)?,
+            ));
+        } else if arg.to_string_lossy().starts_with('-') || config_file.is_some() {
+            return Err(usage());
+        } else {
+            config_file = Some(std::path::Path::new(arg));
+        }
+    }
+    let config_file = config_file.unwrap_or_else(|| std::path::Path::new("erbium.conf"));
     /* Build the shared network information database that various systems depend on */
     let netinfo = erbium_net::netinfo::SharedNetInfo::new().await;
 
@@ -66,19 +76,13 @@ async fn go() -> Result<(), Error> {
     let conf = erbium::config::load_config_from_path(config_file)
         .await
         .map_err(|e| Error::Config(config_file.to_path_buf(), e))?;
+    /* The command line overrides the configuration file */
+    if let Some(state_dir) = state_dir {
+        conf.write().await.state_dir = state_dir;
+    }
 
     /* Initialise each of the services, and record them */
     let mut services = futures::stream::FuturesUnordered::new();
-    #[cfg(feature = "dns")]
-    {
-        let dns = dns::DnsService::new(conf.clone(), &netinfo)
-            .await
-            .map_err(|err| Error::Service(err.to_string()))?;
-        services.push(tokio::spawn(async move {
-            dns.run().await.map_err(|err| err.to_string())
-        }));
-    }
-
     #[cfg(feature = "dhcp")]
     let dhcp;
     #[cfg(feature = "dhcp")]
@@ -90,6 +94,28 @@ async fn go() -> Result<(), Error> {
         );
         let dhcp_copy = dhcp.clone();
         services.push(tokio::spawn(async move { dhcp_copy.run().await }));
+
+        /* DHCPv6 is an optional extra, so don't let it stop DHCPv4 from running */
+        match dhcp6::Dhcp6Service::new(netinfo.clone(), conf.clone()).await {
+            Ok(Some(dhcp6)) => {
+                let dhcp6 = std::sync::Arc::new(dhcp6);
+                services.push(tokio::spawn(async move { dhcp6.run().await }));
+            }
+            Ok(None) => (),
+            Err(err) => warn!("Not starting DHCPv6: {}", err),
+        }
+    }
+    #[cfg(feature = "dns")]
+    {
+        let dns = dns::DnsService::new(conf.clone(), &netinfo)
+            .await
+            .map_err(|err| Error::Service(err.to_string()))?;
+        /* Answer for DHCP clients from the same leases, even if they're only kept in memory */
+        #[cfg(feature = "dhcp")]
+        dns.set_dhcp_pool(dhcp.pool()).await;
+        services.push(tokio::spawn(async move {
+            dns.run().await.map_err(|err| err.to_string())
+        }));
     }
     #[cfg(feature = "radv")]
     {
@@ -100,6 +126,17 @@ async fn go() -> Result<(), Error> {
 
         services.push(tokio::spawn(async move { radv.run().await }));
     }
+    #[cfg(feature = "tftp")]
+    {
+        if let Some(tftp) = tftp::TftpService::new(conf.clone())
+            .await
+            .map_err(|err| Error::Service(err.to_string()))?
+        {
+            services.push(tokio::spawn(async move {
+                tftp.run().await.map_err(|err| err.to_string())
+            }));
+        }
+    }
     #[cfg(feature = "http")]
     http::run(dhcp, conf.clone())
         .await